@@ -0,0 +1,47 @@
+//! Fibonacci numbers, recursively, with a `SyncMemo` so that each one is
+//! only worked out once:
+//!
+//! ```text
+//! cargo run --example fibonacci -- 100
+//! ```
+//!
+//! The naive recursion calls itself about `fib(n)` times, which for 100
+//! would take over ten thousand years at a billion calls a second. With
+//! the memo, each of `fib(0)` to `fib(n)` is called once.
+
+use std::{
+    env,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        LazyLock,
+    },
+};
+
+use advanced_features::memo::SyncMemo;
+
+type Fib = SyncMemo<u32, u128, fn(&u32) -> u128>;
+
+static FIB: LazyLock<Fib> = LazyLock::new(|| SyncMemo::new(fib));
+static CALLS: AtomicU64 = AtomicU64::new(0);
+
+fn fib(&n: &u32) -> u128 {
+    CALLS.fetch_add(1, Ordering::Relaxed);
+    match n {
+        0 | 1 => u128::from(n),
+        _ => FIB.get(&(n - 1)) + FIB.get(&(n - 2)),
+    }
+}
+
+fn main() {
+    // fib(186) is the biggest that fits in a u128.
+    let n = match env::args().nth(1).map(|n| n.parse::<u32>()) {
+        None => 100,
+        Some(Ok(n)) if n <= 186 => n,
+        _ => {
+            eprintln!("usage: fibonacci [N], with N at most 186");
+            std::process::exit(2);
+        }
+    };
+    println!("fib({n}) = {}", FIB.get(&n));
+    println!("{} calls to fib", CALLS.load(Ordering::Relaxed));
+}
@@ -0,0 +1,172 @@
+//! Functions that take closures and return new ones, which is "Returning
+//! Closures" put to use.
+//!
+//! [`compose`] and [`pipe`] chain two functions, and return `impl Fn`: the
+//! closure they make has one concrete type, so nothing needs boxing, and
+//! calling it can be inlined like any other call.
+//!
+//! [`curry2`] and [`memoize`] return boxes. `curry2` has to: its closure
+//! returns another closure, and `impl Fn(A) -> impl Fn(B) -> C` isn't
+//! allowed, so the inner one is a `Box<dyn Fn>`. `memoize` boxes by choice,
+//! so that a memoized function has the same type whatever it wraps, and
+//! can be stored alongside others. `benches/combinators.rs` measures what
+//! the box costs.
+
+use std::{cell::RefCell, collections::HashMap, hash::Hash, rc::Rc};
+
+/// A boxed function from `A` to `B`.
+pub type BoxFn<'a, A, B> = Box<dyn Fn(A) -> B + 'a>;
+
+/// `f` after `g`: `compose(f, g)(x)` is `f(g(x))`, as in maths.
+///
+/// ```
+/// use advanced_features::combinators::compose;
+///
+/// let add_one_then_double = compose(|x| x * 2, |x: i32| x + 1);
+/// assert_eq!(add_one_then_double(3), 8);
+/// ```
+pub fn compose<A, B, C>(f: impl Fn(B) -> C, g: impl Fn(A) -> B) -> impl Fn(A) -> C {
+    move |x| f(g(x))
+}
+
+/// `f` then `g`: `pipe(f, g)(x)` is `g(f(x))`, which reads in the order
+/// things happen.
+///
+/// ```
+/// use advanced_features::combinators::pipe;
+///
+/// let parse_then_double = pipe(|s: &str| s.parse::<i32>().unwrap(), |x| x * 2);
+/// assert_eq!(parse_then_double("21"), 42);
+/// ```
+pub fn pipe<A, B, C>(f: impl Fn(A) -> B, g: impl Fn(B) -> C) -> impl Fn(A) -> C {
+    move |x| g(f(x))
+}
+
+/// `f`, taking its arguments one at a time: `curry2(f)(a)(b)` is `f(a, b)`.
+///
+/// `curry2(f)(a)` can be called any number of times, so each call gets a
+/// clone of `a`.
+///
+/// ```
+/// use advanced_features::combinators::curry2;
+///
+/// let add = curry2(|a: i32, b: i32| a + b);
+/// let add_five = add(5);
+/// assert_eq!((add_five(1), add_five(2)), (6, 7));
+/// ```
+pub fn curry2<'a, A, B, C>(f: impl Fn(A, B) -> C + 'a) -> impl Fn(A) -> BoxFn<'a, B, C>
+where
+    A: Clone + 'a,
+{
+    // Every partial application shares the one `f`.
+    let f = Rc::new(f);
+    move |a| {
+        let f = Rc::clone(&f);
+        Box::new(move |b| f(a.clone(), b))
+    }
+}
+
+/// `f`, remembering what it returned for each argument, so that it's only
+/// called once per argument.
+///
+/// The cache is a `RefCell`, so the memoized function is `Fn` and can be
+/// shared, but not between threads. `f` mustn't call the memoized function
+/// itself: that's a second borrow of the cache, which panics.
+///
+/// ```
+/// use std::cell::Cell;
+///
+/// use advanced_features::combinators::memoize;
+///
+/// let calls = Cell::new(0);
+/// let square = memoize(|x: u64| {
+///     calls.set(calls.get() + 1);
+///     x * x
+/// });
+/// assert_eq!((square(4), square(4), square(5)), (16, 16, 25));
+/// assert_eq!(calls.get(), 2);
+/// ```
+pub fn memoize<'a, A, B>(f: impl Fn(A) -> B + 'a) -> BoxFn<'a, A, B>
+where
+    A: Eq + Hash + Clone + 'a,
+    B: Clone + 'a,
+{
+    let cache = RefCell::new(HashMap::new());
+    Box::new(move |x: A| {
+        if let Some(y) = cache.borrow().get(&x) {
+            return B::clone(y);
+        }
+        let y = f(x.clone());
+        cache.borrow_mut().insert(x, y.clone());
+        y
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn inc(x: i64) -> i64 {
+        x + 1
+    }
+
+    fn double(x: i64) -> i64 {
+        x * 2
+    }
+
+    #[test]
+    fn compose_and_pipe_run_in_opposite_orders() {
+        for x in -10..10 {
+            assert_eq!(compose(inc, double)(x), inc(double(x)));
+            assert_eq!(pipe(inc, double)(x), double(inc(x)));
+            assert_eq!(compose(inc, double)(x), pipe(double, inc)(x));
+        }
+    }
+
+    #[test]
+    fn composition_is_associative() {
+        let square = |x: i64| x * x;
+        let left = compose(compose(inc, double), square);
+        let right = compose(inc, compose(double, square));
+        for x in -10..10 {
+            assert_eq!(left(x), right(x));
+        }
+    }
+
+    #[test]
+    fn boxed_functions_compose_too() {
+        // Different closures, one type, so they fit in one `Vec`.
+        let steps: Vec<BoxFn<i64, i64>> = vec![Box::new(inc), Box::new(double), Box::new(inc)];
+        let all = steps
+            .into_iter()
+            .fold(Box::new(|x| x) as BoxFn<i64, i64>, |done, step| {
+                Box::new(pipe(done, step))
+            });
+        assert_eq!(all(3), 9);
+    }
+
+    #[test]
+    fn curried_functions_apply_one_argument_at_a_time() {
+        let greet = curry2(|greeting: String, name: &str| format!("{greeting}, {name}!"));
+        let hello = greet(String::from("Hello"));
+        assert_eq!(hello("Ferris"), "Hello, Ferris!");
+        assert_eq!(hello("world"), "Hello, world!");
+        assert_eq!(greet(String::from("Hi"))("you"), "Hi, you!");
+    }
+
+    #[test]
+    fn memoized_functions_run_once_per_argument() {
+        let calls = Cell::new(0);
+        let len = memoize(|s: String| {
+            calls.set(calls.get() + 1);
+            s.len()
+        });
+        for _ in 0..3 {
+            assert_eq!(len(String::from("four")), 4);
+            assert_eq!(len(String::from("sixsix")), 6);
+        }
+        assert_eq!(calls.get(), 2);
+    }
+}
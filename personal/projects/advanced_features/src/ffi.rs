@@ -0,0 +1,428 @@
+//! Listing 19-8's `extern "C"` block, grown into safe wrappers for a few
+//! more of libc's functions.
+//!
+//! Declaring a C function is easy; calling it safely means handling what
+//! its man page says and its signature doesn't:
+//!
+//! - `abs` is undefined for `INT_MIN`, which has no positive `int`.
+//! - `gethostname` writes into a buffer we size, and may not nul-terminate
+//!   a name that doesn't fit.
+//! - `sysconf` returns -1 both for "no limit" and for errors, and only
+//!   `errno` tells them apart, so `errno` has to be cleared first.
+//! - `nanosleep` stops early when a signal arrives, and says how much of
+//!   the sleep was left.
+//! - `snprintf` is variadic, declared with `...`, so the compiler checks
+//!   nothing after the format: not how many arguments there are, nor their
+//!   types. A `%s` given an `int` reads memory at whatever address that is,
+//!   and `%n` writes to one. [`c_format`] checks each conversion against
+//!   its argument before C sees them.
+//!
+//! Each wrapper deals with those, and turns `errno` into an `io::Error`.
+
+use std::{
+    ffi::{CStr, CString},
+    io,
+    os::raw::{c_char, c_double, c_int, c_long, c_uint, c_ulong},
+    ptr,
+    time::Duration,
+};
+
+#[repr(C)]
+struct Timespec {
+    tv_sec: c_long,
+    tv_nsec: c_long,
+}
+
+extern "C" {
+    fn abs(input: c_int) -> c_int;
+    fn getpid() -> c_int;
+    fn gethostname(name: *mut c_char, len: usize) -> c_int;
+    fn sysconf(name: c_int) -> c_long;
+    fn nanosleep(req: *const Timespec, rem: *mut Timespec) -> c_int;
+    fn snprintf(buf: *mut c_char, size: usize, format: *const c_char, ...) -> c_int;
+    #[cfg_attr(target_os = "linux", link_name = "__errno_location")]
+    #[cfg_attr(target_os = "macos", link_name = "__error")]
+    fn errno_location() -> *mut c_int;
+}
+
+/// The absolute value of `input`, according to C, or `None` for
+/// `i32::MIN`, whose absolute value doesn't fit.
+pub fn c_abs(input: i32) -> Option<i32> {
+    if input == i32::MIN {
+        return None;
+    }
+    // SAFETY: `abs` is defined for every `int` but `INT_MIN`.
+    Some(unsafe { abs(input) })
+}
+
+/// This process's ID.
+pub fn pid() -> u32 {
+    // SAFETY: `getpid` takes nothing and can't fail.
+    unsafe { getpid() as u32 }
+}
+
+/// The machine's host name.
+pub fn hostname() -> io::Result<String> {
+    // Names are at most `HOST_NAME_MAX` bytes, plus the nul.
+    let max = sysconf_value(Sysconf::HostNameMax)?.unwrap_or(255);
+    let mut buf = vec![0u8; max as usize + 1];
+    loop {
+        // SAFETY: `buf` is valid for writes of `buf.len()` bytes.
+        if unsafe { gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+            let err = io::Error::last_os_error();
+            // Linux says the name didn't fit; try again with more room.
+            if err.raw_os_error() != Some(ENAMETOOLONG) {
+                return Err(err);
+            }
+        } else if let Ok(name) = CStr::from_bytes_until_nul(&buf) {
+            return name
+                .to_str()
+                .map(String::from)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err));
+        }
+        // No nul means the name was cut off to fit, on systems that don't
+        // report it.
+        buf.resize(buf.len() * 2, 0);
+    }
+}
+
+#[cfg(target_os = "linux")]
+const ENAMETOOLONG: c_int = 36;
+#[cfg(target_os = "macos")]
+const ENAMETOOLONG: c_int = 63;
+
+/// The settings [`sysconf_value`] can look up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sysconf {
+    /// Clock ticks per second.
+    ClockTicks,
+    /// The most files a process can have open.
+    OpenMax,
+    /// The size of a page of memory, in bytes.
+    PageSize,
+    /// The number of processors online.
+    ProcessorsOnline,
+    /// The longest a host name can be, in bytes.
+    HostNameMax,
+}
+
+impl Sysconf {
+    #[cfg(target_os = "linux")]
+    fn name(self) -> c_int {
+        match self {
+            Sysconf::ClockTicks => 2,
+            Sysconf::OpenMax => 4,
+            Sysconf::PageSize => 30,
+            Sysconf::ProcessorsOnline => 84,
+            Sysconf::HostNameMax => 180,
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    fn name(self) -> c_int {
+        match self {
+            Sysconf::ClockTicks => 3,
+            Sysconf::OpenMax => 5,
+            Sysconf::PageSize => 29,
+            Sysconf::ProcessorsOnline => 58,
+            Sysconf::HostNameMax => 72,
+        }
+    }
+}
+
+/// A system setting, or `None` if the system puts no limit on it.
+pub fn sysconf_value(setting: Sysconf) -> io::Result<Option<u64>> {
+    // SAFETY: `errno_location` returns this thread's `errno`, which is
+    // valid to write for as long as the thread runs, and `sysconf` takes
+    // any `int`.
+    let value = unsafe {
+        *errno_location() = 0;
+        sysconf(setting.name())
+    };
+    if value >= 0 {
+        return Ok(Some(value as u64));
+    }
+    match io::Error::last_os_error() {
+        err if err.raw_os_error() == Some(0) => Ok(None),
+        err => Err(err),
+    }
+}
+
+/// Sleep for `duration`, going back to sleep for the rest of it whenever
+/// a signal cuts it short.
+pub fn sleep(duration: Duration) -> io::Result<()> {
+    let mut request = Timespec {
+        tv_sec: c_long::try_from(duration.as_secs())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "duration too long"))?,
+        tv_nsec: duration.subsec_nanos() as c_long,
+    };
+    let mut remaining = Timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    loop {
+        // SAFETY: both point at `Timespec`s that live across the call, and
+        // `request` has nanoseconds below a billion, as `nanosleep` needs.
+        if unsafe { nanosleep(&request, &mut remaining) } == 0 {
+            return Ok(());
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+        request = Timespec {
+            tv_sec: remaining.tv_sec,
+            tv_nsec: remaining.tv_nsec,
+        };
+    }
+}
+
+/// An argument for [`c_format`], as the C type its conversion expects.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CArg<'a> {
+    /// For `%d` and `%i`.
+    Int(c_int),
+    /// For `%ld` and `%li`.
+    Long(c_long),
+    /// For `%u`, `%o`, `%x` and `%X`.
+    UInt(c_uint),
+    /// For those with `l`, like `%lu`.
+    ULong(c_ulong),
+    /// For `%f`, `%e`, `%g` and `%a`, in either case.
+    Double(c_double),
+    /// For `%c`.
+    Char(u8),
+    /// For `%s`.
+    Str(&'a CStr),
+}
+
+impl CArg<'_> {
+    fn kind(&self) -> &'static str {
+        match self {
+            CArg::Int(_) => "Int",
+            CArg::Long(_) => "Long",
+            CArg::UInt(_) => "UInt",
+            CArg::ULong(_) => "ULong",
+            CArg::Double(_) => "Double",
+            CArg::Char(_) => "Char",
+            CArg::Str(_) => "Str",
+        }
+    }
+}
+
+/// `format` filled in by C's `snprintf`, with one of `args` for each of
+/// its conversions.
+///
+/// The conversions understood are `d i u o x X f F e E g G a A c s`, with
+/// any flags, a width, a precision and an `l` where C allows one. Anything
+/// else, a `*` width or an argument of the wrong kind is an
+/// `InvalidInput` error, as are arguments left over or missing.
+///
+/// Each conversion is handed to `snprintf` alone, with its one argument,
+/// so every call has a count and types fixed at compile time; the text
+/// between them never goes to C at all.
+pub fn c_format(format: &str, args: &[CArg<'_>]) -> io::Result<String> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+
+    let mut out = Vec::new();
+    let mut args = args.iter();
+    let mut rest = format;
+    while let Some(percent) = rest.find('%') {
+        out.extend_from_slice(&rest.as_bytes()[..percent]);
+        rest = &rest[percent..];
+        if let Some(after) = rest.strip_prefix("%%") {
+            out.push(b'%');
+            rest = after;
+            continue;
+        }
+
+        // `%`, flags, width, precision, length, then the conversion.
+        let conversion_at = rest[1..]
+            .find(|c: char| !matches!(c, '-' | '+' | ' ' | '#' | '0'..='9' | '.' | 'l' | '*'))
+            .map(|at| at + 1)
+            .ok_or_else(|| invalid(format!("`{rest}` is an unfinished conversion")))?;
+        let conversion = rest[conversion_at..].chars().next().unwrap();
+        let spec = &rest[..conversion_at + conversion.len_utf8()];
+        let middle = &rest[1..conversion_at];
+        rest = &rest[spec.len()..];
+
+        if middle.contains('*') {
+            return Err(invalid(format!("`{spec}`: `*` isn't supported")));
+        }
+        let long = match middle.matches('l').count() {
+            0 => false,
+            1 if middle.ends_with('l') => true,
+            _ => return Err(invalid(format!("`{spec}` isn't a supported conversion"))),
+        };
+        let expected = match (conversion, long) {
+            ('d' | 'i', false) => "Int",
+            ('d' | 'i', true) => "Long",
+            ('u' | 'o' | 'x' | 'X', false) => "UInt",
+            ('u' | 'o' | 'x' | 'X', true) => "ULong",
+            // `l` means nothing to these, and C allows it.
+            ('f' | 'F' | 'e' | 'E' | 'g' | 'G' | 'a' | 'A', _) => "Double",
+            ('c', false) => "Char",
+            ('s', false) => "Str",
+            _ => return Err(invalid(format!("`{spec}` isn't a supported conversion"))),
+        };
+
+        let arg = args
+            .next()
+            .ok_or_else(|| invalid(format!("`{spec}` has no argument")))?;
+        if arg.kind() != expected {
+            return Err(invalid(format!("`{spec}` needs a {expected}, not {arg:?}")));
+        }
+        let spec = CString::new(spec).expect("a conversion has no nul");
+        out.extend(format_one(&spec, *arg)?);
+    }
+    out.extend_from_slice(rest.as_bytes());
+
+    if args.len() > 0 {
+        return Err(invalid(format!(
+            "{} more arguments than conversions",
+            args.len()
+        )));
+    }
+    // C only wrote what the format and arguments asked for, and every
+    // argument that could carry text was a checked `CStr`, but not
+    // necessarily UTF-8.
+    String::from_utf8(out).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// `spec`, a single conversion already checked to take `arg`, formatted
+/// by `snprintf`: once with no buffer, to learn the length, then again
+/// into one that size.
+fn format_one(spec: &CStr, arg: CArg<'_>) -> io::Result<Vec<u8>> {
+    let call = |buf: *mut c_char, size: usize| {
+        // SAFETY: `spec` is one conversion, and `c_format` has checked that
+        // `arg` is the type it reads, after C's promotions: `char` is
+        // passed as an `int`, and a string as a pointer to a nul-terminated
+        // one that outlives the call. `buf` is either null with `size` 0,
+        // or valid for writes of `size` bytes.
+        unsafe {
+            match arg {
+                CArg::Int(value) => snprintf(buf, size, spec.as_ptr(), value),
+                CArg::Long(value) => snprintf(buf, size, spec.as_ptr(), value),
+                CArg::UInt(value) => snprintf(buf, size, spec.as_ptr(), value),
+                CArg::ULong(value) => snprintf(buf, size, spec.as_ptr(), value),
+                CArg::Double(value) => snprintf(buf, size, spec.as_ptr(), value),
+                CArg::Char(value) => snprintf(buf, size, spec.as_ptr(), c_int::from(value)),
+                CArg::Str(value) => snprintf(buf, size, spec.as_ptr(), value.as_ptr()),
+            }
+        }
+    };
+
+    // A negative length is an error, such as output longer than `INT_MAX`.
+    let len = usize::try_from(call(ptr::null_mut(), 0)).map_err(|_| io::Error::last_os_error())?;
+    let mut buf = vec![0u8; len + 1];
+    call(buf.as_mut_ptr().cast(), buf.len());
+    buf.truncate(len);
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{process, time::Instant};
+
+    use super::*;
+
+    #[test]
+    fn abs_refuses_int_min() {
+        assert_eq!(c_abs(-3), Some(3));
+        assert_eq!(c_abs(i32::MAX), Some(i32::MAX));
+        assert_eq!(c_abs(i32::MIN), None);
+    }
+
+    #[test]
+    fn pid_is_this_process() {
+        assert_eq!(pid(), process::id());
+    }
+
+    #[test]
+    fn hostname_is_a_whole_name() {
+        let name = hostname().unwrap();
+        assert!(!name.is_empty());
+        assert!(!name.contains('\0'));
+        let max = sysconf_value(Sysconf::HostNameMax).unwrap().unwrap_or(255);
+        assert!(name.len() as u64 <= max);
+    }
+
+    #[test]
+    fn sysconf_reports_real_values() {
+        let page = sysconf_value(Sysconf::PageSize).unwrap().unwrap();
+        assert!(page.is_power_of_two());
+        assert!(sysconf_value(Sysconf::ProcessorsOnline).unwrap().unwrap() >= 1);
+        assert!(sysconf_value(Sysconf::ClockTicks).unwrap().unwrap() > 0);
+    }
+
+    #[test]
+    fn sleeps_at_least_as_long_as_asked() {
+        let start = Instant::now();
+        sleep(Duration::from_millis(20)).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        let err = sleep(Duration::from_secs(u64::MAX)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn formats_like_printf() {
+        let name = c"Ferris";
+        let text = c_format(
+            "%s has %d legs, %05.1f%% of a %c%s, %#x %lu",
+            &[
+                CArg::Str(name),
+                CArg::Int(10),
+                CArg::Double(12.25),
+                CArg::Char(b'c'),
+                CArg::Str(c"rab"),
+                CArg::UInt(255),
+                CArg::ULong(c_ulong::MAX),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            text,
+            format!(
+                "Ferris has 10 legs, 012.2% of a crab, 0xff {}",
+                c_ulong::MAX
+            )
+        );
+        assert_eq!(
+            c_format("%-4s|%.2s|", &[CArg::Str(c"a"), CArg::Str(c"xyz")]).unwrap(),
+            "a   |xy|"
+        );
+        assert_eq!(c_format("no conversions", &[]).unwrap(), "no conversions");
+    }
+
+    #[test]
+    fn checks_arguments_before_calling_c() {
+        let fails = |format: &str, args: &[CArg<'_>]| {
+            let err = c_format(format, args).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{format}");
+            err.to_string()
+        };
+
+        assert_eq!(fails("%s", &[CArg::Int(1)]), "`%s` needs a Str, not Int(1)");
+        assert_eq!(
+            fails("%ld", &[CArg::Int(1)]),
+            "`%ld` needs a Long, not Int(1)"
+        );
+        assert_eq!(fails("%d %d", &[CArg::Int(1)]), "`%d` has no argument");
+        assert_eq!(
+            fails("%d", &[CArg::Int(1), CArg::Int(2)]),
+            "1 more arguments than conversions"
+        );
+        assert_eq!(
+            fails("%n", &[CArg::Int(0)]),
+            "`%n` isn't a supported conversion"
+        );
+        assert_eq!(fails("%p", &[]), "`%p` isn't a supported conversion");
+        assert_eq!(
+            fails("%lld", &[CArg::Long(0)]),
+            "`%lld` isn't a supported conversion"
+        );
+        assert_eq!(fails("%*d", &[CArg::Int(3)]), "`%*d`: `*` isn't supported");
+        assert_eq!(fails("50%", &[]), "`%` is an unfinished conversion");
+    }
+}
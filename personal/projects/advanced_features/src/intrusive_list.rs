@@ -0,0 +1,517 @@
+//! An intrusive doubly linked list: each value carries its own `prev` and
+//! `next` pointers, instead of the list wrapping it in a node.
+//!
+//! A value joins the list by embedding a [`Links`] and implementing
+//! [`Linked`] to say where it is. The list takes ownership of values as
+//! `Box`es, turns them into raw pointers while they're linked, and hands
+//! the `Box` back when they're removed, so no allocation is ever made for
+//! a node beyond the value's own.
+//!
+//! # Invariants
+//!
+//! For a list with `len` values:
+//!
+//! 1. `head` and `tail` are both `None` if `len` is 0, and otherwise point
+//!    at the first and last of `len` values, each one a `Box` the list
+//!    leaked and owns.
+//! 2. Following `next` from `head` visits each value once and ends at
+//!    `tail`, whose `next` is `None`; `prev` is the same walk backwards.
+//! 3. A value's links are only written by the list that owns it, and are
+//!    both `None` whenever the list doesn't own it.
+//!
+//! The public API keeps these without any help: it only ever lends out `&T`,
+//! so safe code can't overwrite a linked value (links and all) or move it,
+//! and `Links` has no public way to change a link. The unsafe code below
+//! relies on them, and each `SAFETY` comment says which.
+//!
+//! Links are `Cell`s, so the list updates them through shared references
+//! and never makes a `&mut T` to a value someone might be looking at. That
+//! also keeps the tests within what Miri's aliasing model allows.
+
+use std::{cell::Cell, fmt, marker::PhantomData, ptr::NonNull};
+
+/// The `prev` and `next` pointers a value embeds to be [`Linked`].
+pub struct Links<T> {
+    prev: Cell<Option<NonNull<T>>>,
+    next: Cell<Option<NonNull<T>>>,
+}
+
+impl<T> Links<T> {
+    /// Links for a value that isn't in a list.
+    pub const fn new() -> Links<T> {
+        Links {
+            prev: Cell::new(None),
+            next: Cell::new(None),
+        }
+    }
+
+    fn clear(&self) {
+        self.prev.set(None);
+        self.next.set(None);
+    }
+}
+
+impl<T> Default for Links<T> {
+    fn default() -> Links<T> {
+        Links::new()
+    }
+}
+
+impl<T> fmt::Debug for Links<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Links").finish_non_exhaustive()
+    }
+}
+
+/// A value with [`Links`] of its own.
+///
+/// # Safety
+///
+/// `links` must return the same field of `self` every time, and no other
+/// value's. The list trusts it to find the one place the value's pointers
+/// live.
+pub unsafe trait Linked: Sized {
+    fn links(&self) -> &Links<Self>;
+}
+
+/// Reaches the links of a value the list owns.
+///
+/// # Safety
+///
+/// `ptr` must point at a value the list owns (invariant 1), which is alive
+/// for as long as the list is borrowed.
+unsafe fn links<'a, T: Linked>(ptr: NonNull<T>) -> &'a Links<T> {
+    // SAFETY: the caller promises the value is alive; the links are only
+    // ever borrowed shared.
+    unsafe { ptr.as_ref() }.links()
+}
+
+pub struct IntrusiveList<T: Linked> {
+    head: Option<NonNull<T>>,
+    tail: Option<NonNull<T>>,
+    len: usize,
+    /// The list owns its values, and drops them when it's dropped.
+    _owns: PhantomData<Box<T>>,
+}
+
+impl<T: Linked> IntrusiveList<T> {
+    pub const fn new() -> IntrusiveList<T> {
+        IntrusiveList {
+            head: None,
+            tail: None,
+            len: 0,
+            _owns: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        // SAFETY: invariant 1; borrowing the list keeps the value alive.
+        self.head.map(|head| unsafe { head.as_ref() })
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        // SAFETY: as in `front`.
+        self.tail.map(|tail| unsafe { tail.as_ref() })
+    }
+
+    pub fn push_front(&mut self, value: Box<T>) {
+        // SAFETY: `None` is the position before the head.
+        unsafe { self.link_after(None, value) };
+    }
+
+    pub fn push_back(&mut self, value: Box<T>) {
+        // SAFETY: the tail is owned by this list (invariant 1).
+        unsafe { self.link_after(self.tail, value) };
+    }
+
+    pub fn pop_front(&mut self) -> Option<Box<T>> {
+        // SAFETY: the head is owned by this list (invariant 1).
+        self.head.map(|head| unsafe { self.unlink(head) })
+    }
+
+    pub fn pop_back(&mut self) -> Option<Box<T>> {
+        // SAFETY: as in `pop_front`.
+        self.tail.map(|tail| unsafe { self.unlink(tail) })
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            front: self.head,
+            back: self.tail,
+            remaining: self.len,
+            _list: PhantomData,
+        }
+    }
+
+    /// A cursor on the first value, or on the "ghost" position between the
+    /// ends if the list is empty.
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            current: self.head,
+            index: (!self.is_empty()).then_some(0),
+            list: self,
+        }
+    }
+
+    /// A cursor on the last value, or on the ghost if the list is empty.
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            current: self.tail,
+            index: self.len.checked_sub(1),
+            list: self,
+        }
+    }
+
+    /// Link `value` in after `prev`, or at the front if `prev` is `None`.
+    ///
+    /// # Safety
+    ///
+    /// `prev`, if it's `Some`, must be owned by this list.
+    unsafe fn link_after(&mut self, prev: Option<NonNull<T>>, value: Box<T>) {
+        // A Box we're given isn't in any list (invariant 3), so its links
+        // are free to overwrite.
+        let node = NonNull::from(Box::leak(value));
+        let next = match prev {
+            // SAFETY: the caller promises `prev` is ours.
+            Some(prev) => unsafe { links(prev) }.next.get(),
+            None => self.head,
+        };
+
+        // SAFETY: `node` was just leaked, so it's alive and now ours.
+        let node_links = unsafe { links(node) };
+        node_links.prev.set(prev);
+        node_links.next.set(next);
+
+        match prev {
+            // SAFETY: the caller promises `prev` is ours.
+            Some(prev) => unsafe { links(prev) }.next.set(Some(node)),
+            None => self.head = Some(node),
+        }
+        match next {
+            // SAFETY: `next` came from our own links, so it's ours
+            // (invariant 2).
+            Some(next) => unsafe { links(next) }.prev.set(Some(node)),
+            None => self.tail = Some(node),
+        }
+        self.len += 1;
+    }
+
+    /// Take `node` out of the list and hand it back.
+    ///
+    /// # Safety
+    ///
+    /// `node` must be owned by this list.
+    unsafe fn unlink(&mut self, node: NonNull<T>) -> Box<T> {
+        // SAFETY: the caller promises `node` is ours.
+        let node_links = unsafe { links(node) };
+        let (prev, next) = (node_links.prev.get(), node_links.next.get());
+
+        match prev {
+            // SAFETY: neighbours of our nodes are ours (invariant 2).
+            Some(prev) => unsafe { links(prev) }.next.set(next),
+            None => self.head = next,
+        }
+        match next {
+            // SAFETY: as above.
+            Some(next) => unsafe { links(next) }.prev.set(prev),
+            None => self.tail = prev,
+        }
+        node_links.clear();
+        self.len -= 1;
+
+        // SAFETY: `node` came from `Box::leak` in `link_after`, and nothing
+        // in the list points at it any more, so the Box is unique again.
+        unsafe { Box::from_raw(node.as_ptr()) }
+    }
+}
+
+impl<T: Linked> Default for IntrusiveList<T> {
+    fn default() -> IntrusiveList<T> {
+        IntrusiveList::new()
+    }
+}
+
+impl<T: Linked> Drop for IntrusiveList<T> {
+    fn drop(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+}
+
+impl<T: Linked> FromIterator<Box<T>> for IntrusiveList<T> {
+    fn from_iter<I: IntoIterator<Item = Box<T>>>(iter: I) -> IntrusiveList<T> {
+        let mut list = IntrusiveList::new();
+        for value in iter {
+            list.push_back(value);
+        }
+        list
+    }
+}
+
+impl<T: Linked + fmt::Debug> fmt::Debug for IntrusiveList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+pub struct Iter<'a, T: Linked> {
+    front: Option<NonNull<T>>,
+    back: Option<NonNull<T>>,
+    remaining: usize,
+    _list: PhantomData<&'a IntrusiveList<T>>,
+}
+
+impl<'a, T: Linked> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = self.front?;
+        self.remaining -= 1;
+        // SAFETY: `node` is one of the list's (invariant 2), and the list is
+        // borrowed for 'a, so it can't change or drop it in that time.
+        let node = unsafe { node.as_ref() };
+        self.front = node.links().next.get();
+        Some(node)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T: Linked> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = self.back?;
+        self.remaining -= 1;
+        // SAFETY: as in `next`.
+        let node = unsafe { node.as_ref() };
+        self.back = node.links().prev.get();
+        Some(node)
+    }
+}
+
+impl<T: Linked> ExactSizeIterator for Iter<'_, T> {}
+
+/// A position in an [`IntrusiveList`] that can move both ways and change
+/// the list around it. Past either end is the "ghost" position, from which
+/// moving on wraps round to the other end.
+pub struct CursorMut<'a, T: Linked> {
+    /// Always one of `list`'s values, or `None` at the ghost.
+    current: Option<NonNull<T>>,
+    index: Option<usize>,
+    list: &'a mut IntrusiveList<T>,
+}
+
+impl<T: Linked> CursorMut<'_, T> {
+    /// How far the cursor is from the front, or `None` at the ghost.
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    pub fn current(&self) -> Option<&T> {
+        // SAFETY: `current` is one of the list's values, which can't be
+        // removed while the returned borrow of the cursor lasts.
+        self.current.map(|node| unsafe { node.as_ref() })
+    }
+
+    pub fn move_next(&mut self) {
+        match self.current {
+            Some(node) => {
+                // SAFETY: `current` is one of the list's values.
+                self.current = unsafe { links(node) }.next.get();
+                self.index = self.current.and(self.index.map(|index| index + 1));
+            }
+            None => {
+                self.current = self.list.head;
+                self.index = self.current.map(|_| 0);
+            }
+        }
+    }
+
+    pub fn move_prev(&mut self) {
+        match self.current {
+            Some(node) => {
+                // SAFETY: `current` is one of the list's values.
+                self.current = unsafe { links(node) }.prev.get();
+                self.index = self.current.and(self.index.map(|index| index - 1));
+            }
+            None => {
+                self.current = self.list.tail;
+                self.index = self.list.len.checked_sub(1);
+            }
+        }
+    }
+
+    /// Add `value` after the cursor, or at the front if it's at the ghost.
+    pub fn insert_after(&mut self, value: Box<T>) {
+        // SAFETY: `current` is one of the list's values, and `None` means
+        // the front.
+        unsafe { self.list.link_after(self.current, value) };
+    }
+
+    /// Add `value` before the cursor, or at the back if it's at the ghost.
+    pub fn insert_before(&mut self, value: Box<T>) {
+        let prev = match self.current {
+            // SAFETY: `current` is one of the list's values.
+            Some(node) => unsafe { links(node) }.prev.get(),
+            None => self.list.tail,
+        };
+        // SAFETY: `prev` is a neighbour of one of the list's values, or its
+        // tail, so it's the list's too.
+        unsafe { self.list.link_after(prev, value) };
+        if let Some(index) = &mut self.index {
+            *index += 1;
+        }
+    }
+
+    /// Take out the value at the cursor, which moves on to the next one.
+    pub fn remove_current(&mut self) -> Option<Box<T>> {
+        let node = self.current?;
+        // SAFETY: `current` is one of the list's values.
+        self.current = unsafe { links(node) }.next.get();
+        if self.current.is_none() {
+            self.index = None;
+        }
+        // SAFETY: as above.
+        Some(unsafe { self.list.unlink(node) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct Task {
+        id: u32,
+        links: Links<Task>,
+    }
+
+    // SAFETY: always the same field of the same task.
+    unsafe impl Linked for Task {
+        fn links(&self) -> &Links<Task> {
+            &self.links
+        }
+    }
+
+    fn task(id: u32) -> Box<Task> {
+        Box::new(Task {
+            id,
+            links: Links::new(),
+        })
+    }
+
+    fn ids(list: &IntrusiveList<Task>) -> Vec<u32> {
+        list.iter().map(|task| task.id).collect()
+    }
+
+    #[test]
+    fn pushes_and_pops_at_both_ends() {
+        let mut list = IntrusiveList::new();
+        list.push_back(task(2));
+        list.push_front(task(1));
+        list.push_back(task(3));
+        assert_eq!(ids(&list), [1, 2, 3]);
+        assert_eq!(
+            list.iter().rev().map(|task| task.id).collect::<Vec<_>>(),
+            [3, 2, 1]
+        );
+        assert_eq!((list.front().unwrap().id, list.back().unwrap().id), (1, 3));
+
+        assert_eq!(list.pop_front().unwrap().id, 1);
+        assert_eq!(list.pop_back().unwrap().id, 3);
+        assert_eq!(list.pop_back().unwrap().id, 2);
+        assert!(list.pop_front().is_none());
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn a_popped_value_can_join_another_list() {
+        let mut first: IntrusiveList<_> = (1..=3).map(task).collect();
+        let mut second = IntrusiveList::new();
+        while let Some(task) = first.pop_back() {
+            second.push_back(task);
+        }
+        assert!(first.is_empty());
+        assert_eq!(ids(&second), [3, 2, 1]);
+    }
+
+    #[test]
+    fn the_cursor_edits_the_middle() {
+        let mut list: IntrusiveList<_> = [1, 3, 5].into_iter().map(task).collect();
+        let mut cursor = list.cursor_front_mut();
+        cursor.insert_after(task(2));
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!((cursor.index(), cursor.current().unwrap().id), (Some(2), 3));
+
+        cursor.insert_before(task(4));
+        assert_eq!(cursor.index(), Some(3));
+        assert_eq!(cursor.remove_current().unwrap().id, 3);
+        assert_eq!(cursor.current().unwrap().id, 5);
+
+        cursor.move_next();
+        assert_eq!(cursor.index(), None);
+        cursor.insert_after(task(0));
+        cursor.insert_before(task(6));
+        cursor.move_prev();
+        assert_eq!(cursor.current().unwrap().id, 6);
+        assert_eq!(ids(&list), [0, 1, 2, 4, 5, 6]);
+        assert_eq!(list.len(), 6);
+    }
+
+    #[test]
+    fn removing_everything_with_the_cursor() {
+        let mut list: IntrusiveList<_> = (0..4).map(task).collect();
+        let mut cursor = list.cursor_front_mut();
+        let mut removed = Vec::new();
+        while let Some(task) = cursor.remove_current() {
+            removed.push(task.id);
+        }
+        assert_eq!(removed, [0, 1, 2, 3]);
+        assert!(list.is_empty() && list.front().is_none() && list.back().is_none());
+    }
+
+    #[test]
+    fn dropping_the_list_drops_its_values() {
+        struct Counted {
+            _count: Rc<()>,
+            links: Links<Counted>,
+        }
+
+        // SAFETY: always the same field.
+        unsafe impl Linked for Counted {
+            fn links(&self) -> &Links<Counted> {
+                &self.links
+            }
+        }
+
+        let count = Rc::new(());
+        let list: IntrusiveList<_> = (0..3)
+            .map(|_| {
+                Box::new(Counted {
+                    _count: Rc::clone(&count),
+                    links: Links::new(),
+                })
+            })
+            .collect();
+        assert_eq!(Rc::strong_count(&count), 4);
+        drop(list);
+        assert_eq!(Rc::strong_count(&count), 1);
+    }
+}
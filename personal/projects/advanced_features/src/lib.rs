@@ -0,0 +1,20 @@
+//! Code from the chapter that's grown big enough to want tests, kept out
+//! of the notes in `main.rs`.
+
+pub mod combinators;
+// Its libc constants and names are the Linux and macOS ones.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub mod ffi;
+pub mod intrusive_list;
+pub mod macros;
+pub mod memo;
+// The pool keeps its counters with these, so they live with it.
+pub use thread_pool::metrics;
+pub mod plugins;
+pub mod point;
+pub mod slices;
+pub mod thread_safe;
+pub mod uninit_buf;
+pub mod units;
+pub mod value;
+pub mod wrapper;
@@ -0,0 +1,526 @@
+//! Declarative macros from the "Macros" section, grown past the book's
+//! simplified versions.
+
+/// Listing 19-28's `vec!`, with the optimization the book leaves out: the
+/// vector is made with room for every element up front, so pushing them
+/// never reallocates.
+///
+/// ```
+/// use advanced_features::myvec;
+///
+/// let v: Vec<u32> = myvec![1, 2, 3];
+/// assert_eq!(v, [1, 2, 3]);
+/// assert_eq!(v.capacity(), 3);
+///
+/// let zeros = myvec![0; 4];
+/// assert_eq!(zeros, [0, 0, 0, 0]);
+/// ```
+#[macro_export]
+macro_rules! myvec {
+    () => {
+        ::std::vec::Vec::new()
+    };
+    ($elem:expr; $n:expr) => {
+        ::std::vec![$elem; $n]
+    };
+    ($($x:expr),+ $(,)?) => {{
+        let mut temp_vec = ::std::vec::Vec::with_capacity($crate::count!($($x)+));
+        $(
+            temp_vec.push($x);
+        )+
+        temp_vec
+    }};
+}
+
+/// The number of token trees it's given, as a constant.
+///
+/// An expression matched by a macro is passed on as a single token tree,
+/// so `count!($($x)*)` counts a repetition of `$x:expr`s however long each
+/// one is.
+///
+/// ```
+/// use advanced_features::count;
+///
+/// const N: usize = count!(a (b c) [d] "e");
+/// assert_eq!(N, 4);
+/// ```
+#[macro_export]
+macro_rules! count {
+    // Each token tree becomes a `()` in an array, whose length is known
+    // when compiling. Unlike counting by recursion, this doesn't hit the
+    // recursion limit on long lists.
+    ($($tt:tt)*) => {
+        <[()]>::len(&[$($crate::__replace!($tt ())),*])
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __replace {
+    ($_tt:tt $sub:expr) => {
+        $sub
+    };
+}
+
+/// The quiz's `manylet!`, which binds one value to several names:
+/// `manylet!(x, y = e)` declares both `x` and `y` as `e`.
+///
+/// The quiz's version pasted `e` into each `let`, so a `String` was moved
+/// into `x` and then again into `y`. This one evaluates `e` once, and says
+/// how each name gets its value:
+///
+/// - `manylet!(x, y = e)` copies it, so it only works for `Copy` values.
+/// - `manylet!(clone: x, y = e)` clones it for all but the last name, which
+///   gets the value itself.
+/// - `manylet!(ref: x, y = e)` makes them all shared references to it.
+///
+/// ```
+/// use advanced_features::manylet;
+///
+/// let s = String::from("A");
+/// manylet!(clone: x, y = s);
+/// x.push_str("B");
+/// assert_eq!(format!("{x}{y}"), "ABA");
+///
+/// let t = String::from("T");
+/// manylet!(ref: a, b = t);
+/// assert!(std::ptr::eq(a, b));
+/// ```
+///
+/// Without a mode, a `String` is moved twice, as it was in the quiz:
+///
+/// ```compile_fail
+/// use advanced_features::manylet;
+///
+/// let s = String::from("A");
+/// manylet!(x, y = s);
+/// x.push_str("B");
+/// println!("{x}{y}");
+/// ```
+///
+/// and a reference is read-only, so it can't be changed through:
+///
+/// ```compile_fail
+/// use advanced_features::manylet;
+///
+/// let s = String::from("A");
+/// manylet!(ref: x, y = s);
+/// x.push_str("B");
+/// ```
+#[macro_export]
+macro_rules! manylet {
+    (clone: $($i:ident),+ = $e:expr) => {
+        let value = $e;
+        $crate::manylet!(@clone value; $($i),+);
+    };
+    (ref: $($i:ident),+ = $e:expr) => {
+        let value = &$e;
+        $(
+            let $i = value;
+        )+
+    };
+    ($($i:ident),+ = $e:expr) => {
+        let value = $e;
+        $(
+            #[allow(unused_mut)]
+            let mut $i = value;
+        )+
+    };
+
+    (@clone $value:ident; $last:ident) => {
+        #[allow(unused_mut)]
+        let mut $last = $value;
+    };
+    (@clone $value:ident; $i:ident, $($rest:ident),+) => {
+        #[allow(unused_mut)]
+        let mut $i = ::std::clone::Clone::clone(&$value);
+        $crate::manylet!(@clone $value; $($rest),+);
+    };
+}
+
+/// Assert that a value matches a pattern, saying what it was if it doesn't.
+///
+/// `assert!(matches!(value, pattern))` fails with just "assertion failed";
+/// this prints the value, which needs `Debug`. A guard and a message can
+/// follow the pattern as they would in `matches!` and `assert!`.
+///
+/// ```
+/// use advanced_features::assert_matches;
+///
+/// assert_matches!("42".parse::<u8>(), Ok(n) if n > 40);
+/// ```
+///
+/// ```should_panic
+/// use advanced_features::assert_matches;
+///
+/// // Panics with: `Some(3)` does not match `None`
+/// assert_matches!(Some(3), None);
+/// ```
+#[macro_export]
+macro_rules! assert_matches {
+    ($value:expr, $pattern:pat $(if $guard:expr)? $(,)?) => {
+        match $value {
+            $pattern $(if $guard)? => {}
+            ref value => ::std::panic!(
+                "assertion failed: `{:?}` does not match `{}`",
+                value,
+                ::std::stringify!($pattern $(if $guard)?),
+            ),
+        }
+    };
+    ($value:expr, $pattern:pat $(if $guard:expr)?, $($arg:tt)+) => {
+        match $value {
+            $pattern $(if $guard)? => {}
+            ref value => ::std::panic!(
+                "assertion failed: `{:?}` does not match `{}`: {}",
+                value,
+                ::std::stringify!($pattern $(if $guard)?),
+                ::std::format_args!($($arg)+),
+            ),
+        }
+    };
+}
+
+/// Assert that a `Result` is an `Err`, and evaluate to the error.
+///
+/// With a pattern, the error has to match it too. Either way, a failure
+/// prints the `Ok` value or the error, so both need `Debug`.
+///
+/// ```
+/// use advanced_features::assert_err;
+///
+/// let err = assert_err!("x".parse::<u8>());
+/// assert_eq!(err.to_string(), "invalid digit found in string");
+///
+/// assert_err!("".parse::<bool>(), std::str::ParseBoolError { .. });
+/// ```
+#[macro_export]
+macro_rules! assert_err {
+    ($result:expr $(,)?) => {
+        match $result {
+            ::std::result::Result::Err(err) => err,
+            ::std::result::Result::Ok(value) => {
+                ::std::panic!("assertion failed: expected an error, got `Ok({:?})`", value)
+            }
+        }
+    };
+    ($result:expr, $pattern:pat $(if $guard:expr)? $(,)?) => {
+        match $crate::assert_err!($result) {
+            err @ $pattern $(if $guard)? => err,
+            err => ::std::panic!(
+                "assertion failed: the error `{:?}` does not match `{}`",
+                err,
+                ::std::stringify!($pattern $(if $guard)?),
+            ),
+        }
+    };
+}
+
+/// Assert that two floating-point numbers are within `tolerance` of each
+/// other, since rounding makes `assert_eq!` on them unreliable.
+///
+/// ```
+/// use advanced_features::assert_near;
+///
+/// assert_near!(0.1 + 0.2, 0.3, 1e-12);
+/// ```
+///
+/// ```should_panic
+/// use advanced_features::assert_near;
+///
+/// // Panics with: `1.0` and `1.5` differ by 0.5, more than 0.1
+/// assert_near!(1.0, 1.5, 0.1, "the ratios drifted");
+/// ```
+#[macro_export]
+macro_rules! assert_near {
+    ($left:expr, $right:expr, $tolerance:expr $(,)?) => {
+        match (&$left, &$right, &$tolerance) {
+            (left, right, tolerance) => {
+                // Operators rather than methods like `abs`, so that plain
+                // literals still default to `f64`. A NaN anywhere makes the
+                // difference NaN, which isn't within anything.
+                let difference = if *left > *right { *left - *right } else { *right - *left };
+                let within = difference <= *tolerance;
+                if !within {
+                    ::std::panic!(
+                        "assertion failed: `{:?}` and `{:?}` differ by {:?}, more than {:?}",
+                        left,
+                        right,
+                        difference,
+                        tolerance,
+                    );
+                }
+            }
+        }
+    };
+    ($left:expr, $right:expr, $tolerance:expr, $($arg:tt)+) => {
+        match (&$left, &$right, &$tolerance) {
+            (left, right, tolerance) => {
+                let difference = if *left > *right { *left - *right } else { *right - *left };
+                let within = difference <= *tolerance;
+                if !within {
+                    ::std::panic!(
+                        "assertion failed: `{:?}` and `{:?}` differ by {:?}, more than {:?}: {}",
+                        left,
+                        right,
+                        difference,
+                        tolerance,
+                        ::std::format_args!($($arg)+),
+                    );
+                }
+            }
+        }
+    };
+}
+
+/// A state machine from a table of its transitions.
+///
+/// It declares two fieldless enums, the states and the events, and a
+/// `transition(&mut self, event)` method on the states that moves along
+/// the table. An event with no transition from the current state is an
+/// [`InvalidTransition`], and leaves the state as it was.
+///
+/// ```
+/// use advanced_features::state_machine;
+///
+/// state_machine! {
+///     pub enum Light { Off, On }
+///     pub enum Switch { Flip, Unplug }
+///
+///     transitions {
+///         Off + Flip => On,
+///         On + Flip => Off,
+///         On + Unplug => Off,
+///     }
+/// }
+///
+/// let mut light = Light::Off;
+/// light.transition(Switch::Flip).unwrap();
+/// assert_eq!(light, Light::On);
+///
+/// let err = light.transition(Switch::Flip).and(light.transition(Switch::Unplug));
+/// assert_eq!(err.unwrap_err().to_string(), "can't Unplug when Off");
+/// ```
+///
+/// Every name in the table has to be one of the declared states or events:
+///
+/// ```compile_fail
+/// use advanced_features::state_machine;
+///
+/// state_machine! {
+///     enum Light { Off, On }
+///     enum Switch { Flip }
+///
+///     transitions {
+///         Off + Flip => Dimmed,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! state_machine {
+    (
+        $(#[$state_meta:meta])*
+        $state_vis:vis enum $state:ident { $($states:ident),+ $(,)? }
+        $(#[$event_meta:meta])*
+        $event_vis:vis enum $event:ident { $($events:ident),+ $(,)? }
+
+        transitions {
+            $($from:ident + $on:ident => $to:ident),* $(,)?
+        }
+    ) => {
+        $(#[$state_meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        $state_vis enum $state {
+            $($states),+
+        }
+
+        $(#[$event_meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        $event_vis enum $event {
+            $($events),+
+        }
+
+        impl $state {
+            /// Move to the state `event` leads to from this one, if there
+            /// is a transition for it.
+            $state_vis fn transition(
+                &mut self,
+                event: $event,
+            ) -> ::std::result::Result<(), $crate::macros::InvalidTransition<$state, $event>> {
+                // A table that covers every pair leaves the last arm
+                // unreachable.
+                #[allow(unreachable_patterns)]
+                let next = match (*self, event) {
+                    $(($state::$from, $event::$on) => $state::$to,)*
+                    (state, event) => {
+                        return ::std::result::Result::Err($crate::macros::InvalidTransition {
+                            state,
+                            event,
+                        })
+                    }
+                };
+                *self = next;
+                ::std::result::Result::Ok(())
+            }
+        }
+    };
+}
+
+/// An event [`state_machine!`] has no transition for in the current state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidTransition<S, E> {
+    pub state: S,
+    pub event: E,
+}
+
+impl<S: std::fmt::Debug, E: std::fmt::Debug> std::fmt::Display for InvalidTransition<S, E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "can't {:?} when {:?}", self.event, self.state)
+    }
+}
+
+impl<S: std::fmt::Debug, E: std::fmt::Debug> std::error::Error for InvalidTransition<S, E> {}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn allocates_exactly_once() {
+        let v = myvec![String::from("a"), "b".repeat(2), format!("{}", 3),];
+        assert_eq!(v, ["a", "bb", "3"]);
+        assert_eq!(v.capacity(), 3);
+
+        let empty: Vec<u8> = myvec![];
+        assert_eq!(empty.capacity(), 0);
+    }
+
+    #[test]
+    fn counts_whole_expressions() {
+        // Each expression is several tokens, but one token tree once it's
+        // been matched.
+        let v = myvec![1 + 2, [3, 4].len(), if true { 5 } else { 6 }];
+        assert_eq!((v.len(), v.capacity()), (3, 3));
+    }
+
+    #[test]
+    fn counts_long_lists() {
+        let v = myvec![
+            0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8,
+            9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7,
+            8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6,
+            7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5,
+            6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9,
+        ];
+        assert_eq!((v.len(), v.capacity()), (140, 140));
+    }
+
+    #[test]
+    fn manylet_copies_clones_or_borrows() {
+        manylet!(a, b = 1 + 1);
+        a += 1;
+        assert_eq!((a, b), (3, 2));
+
+        let calls = std::cell::Cell::new(0);
+        let next = || {
+            calls.set(calls.get() + 1);
+            vec![calls.get()]
+        };
+        manylet!(clone: x, y, z = next());
+        x.push(0);
+        assert_eq!((x, y, z), (vec![1, 0], vec![1], vec![1]));
+        assert_eq!(calls.get(), 1);
+
+        let value = String::from("shared");
+        manylet!(ref: r1, r2 = value);
+        assert_eq!((r1.as_str(), r2.len()), ("shared", 6));
+    }
+
+    #[test]
+    fn assert_matches_takes_guards_and_messages() {
+        assert_matches!(Some(5), Some(n) if n > 3);
+        assert_matches!([1, 2, 3], [1, ..], "starts with {}", 1);
+        let result = std::panic::catch_unwind(|| assert_matches!(Some(2), Some(n) if n > 3));
+        let message = *result.unwrap_err().downcast::<String>().unwrap();
+        assert_eq!(
+            message,
+            "assertion failed: `Some(2)` does not match `Some(n) if n > 3`"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "`Ok(7)` does not match `Err(_)`: for 7")]
+    fn assert_matches_prints_the_message() {
+        let value: Result<u8, ()> = Ok(7);
+        assert_matches!(value, Err(_), "for {}", 7);
+    }
+
+    #[test]
+    fn assert_err_returns_the_error() {
+        let err = assert_err!("300".parse::<u8>());
+        assert_eq!(*err.kind(), std::num::IntErrorKind::PosOverflow);
+        let err = assert_err!(Err::<(), _>(404), code if code >= 400);
+        assert_eq!(err, 404);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected an error, got `Ok(\"fine\")`")]
+    fn assert_err_prints_the_ok_value() {
+        assert_err!(Ok::<_, ()>("fine"));
+    }
+
+    #[test]
+    #[should_panic(expected = "the error `5` does not match `0..=3`")]
+    fn assert_err_prints_an_error_that_does_not_match() {
+        assert_err!(Err::<(), u8>(5), 0..=3);
+    }
+
+    #[test]
+    fn assert_near_allows_rounding() {
+        assert_near!(0.1_f64 + 0.2, 0.3, 1e-12);
+        assert_near!(1.0_f32, 1.05, 0.1, "close enough");
+    }
+
+    #[test]
+    #[should_panic(expected = "differ by NaN")]
+    fn assert_near_rejects_nan() {
+        assert_near!(f64::NAN, f64::NAN, 1.0);
+    }
+
+    #[test]
+    fn expands_the_count_to_a_constant() {
+        const EMPTY: usize = count!();
+        const THREE: usize = count!(x y z);
+        let array = [0u8; count!(a b)];
+        assert_eq!((EMPTY, THREE, array.len()), (0, 3, 2));
+    }
+
+    state_machine! {
+        enum Door { Open, Closed, Locked }
+        enum Action { Close, Open, Lock, Unlock }
+
+        transitions {
+            Open + Close => Closed,
+            Closed + Open => Open,
+            Closed + Lock => Locked,
+            Locked + Unlock => Closed,
+        }
+    }
+
+    #[test]
+    fn state_machine_follows_the_table() {
+        let mut door = Door::Open;
+        for action in [Action::Close, Action::Lock, Action::Unlock, Action::Open] {
+            door.transition(action).unwrap();
+        }
+        assert_eq!(door, Door::Open);
+
+        let err = assert_err!(door.transition(Action::Lock));
+        assert_eq!(
+            err,
+            super::InvalidTransition {
+                state: Door::Open,
+                event: Action::Lock
+            }
+        );
+        assert_eq!(door, Door::Open);
+    }
+}
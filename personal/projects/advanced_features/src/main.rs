@@ -1416,3 +1416,34 @@
 // Use this chapter as a reference to guide you to solutions.
 
 // Next, we’ll put everything we’ve discussed throughout the book into practice and do one more project!
+
+use std::env;
+
+use advanced_features::metrics::Counter;
+use smart_pointers::my_once_cell::MyOnceLock;
+
+// Listing 19-10 without `static mut`. A counter that changes is a metrics
+// `Counter`, an atomic as Chapter 16 would have it, and a global that's
+// set once and then only read is a MyOnceLock. Neither needs an unsafe
+// block to use.
+static COUNTER: Counter = Counter::new();
+static GREETING: MyOnceLock<String> = MyOnceLock::new();
+
+fn add_to_count(inc: u32) {
+    COUNTER.add(u64::from(inc));
+}
+
+fn greeting() -> &'static str {
+    GREETING.get_or_init(|| String::from("Hello"))
+}
+
+fn main() {
+    if let Some(greeting) = env::args().nth(1) {
+        GREETING.set(greeting).unwrap();
+    }
+
+    add_to_count(3);
+
+    println!("COUNTER: {}", COUNTER.get());
+    println!("{}, world!", greeting());
+}
@@ -0,0 +1,379 @@
+//! `Memo<K, V, F>`: a function with a cache of what it's returned, for
+//! functions that are slow and always give the same answer for the same
+//! argument.
+//!
+//! [`combinators::memoize`](crate::combinators::memoize) is the closure
+//! version of this. A type can do more with the cache: bound it, so that
+//! once it's full the least recently used answer makes room for the next
+//! one, and put it behind a `Mutex` ([`SyncMemo`]) for threads to share.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    hash::Hash,
+    sync::Mutex,
+};
+
+/// `f`, with a cache of its results by argument.
+pub struct Memo<K, V, F> {
+    f: F,
+    cache: Cache<K, V>,
+}
+
+impl<K, V, F> Memo<K, V, F>
+where
+    K: Eq + Hash + Clone,
+    F: Fn(&K) -> V,
+{
+    /// Remember every result, for as long as the `Memo` lives.
+    pub fn new(f: F) -> Memo<K, V, F> {
+        Memo {
+            f,
+            cache: Cache::new(None),
+        }
+    }
+
+    /// Remember at most `capacity` results, forgetting the least recently
+    /// used to make room.
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn with_capacity(capacity: usize, f: F) -> Memo<K, V, F> {
+        Memo {
+            f,
+            cache: Cache::new(Some(capacity)),
+        }
+    }
+
+    /// `f(key)`, calling `f` only if it isn't cached.
+    pub fn get(&mut self, key: &K) -> &V {
+        if !self.cache.touch(key) {
+            let value = (self.f)(key);
+            self.cache.insert(key.clone(), value);
+        }
+        self.cache.peek(key).unwrap()
+    }
+}
+
+impl<K, V, F> Memo<K, V, F> {
+    /// How many results are cached.
+    pub fn len(&self) -> usize {
+        self.cache.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.values.is_empty()
+    }
+
+    /// The most results it will keep, if it's bounded.
+    pub fn capacity(&self) -> Option<usize> {
+        self.cache.capacity
+    }
+
+    pub fn clear(&mut self) {
+        self.cache.values.clear();
+        self.cache.recency.clear();
+    }
+}
+
+impl<K, V, F> fmt::Debug for Memo<K, V, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Memo")
+            .field("capacity", &self.cache.capacity)
+            .field("len", &self.cache.values.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A [`Memo`] that threads can share.
+///
+/// The lock is only held to look up or store a result, never while `f`
+/// runs. So `f` can call the same `SyncMemo` (say, to compute Fibonacci
+/// numbers from smaller ones), and a slow call doesn't hold up threads
+/// asking for other keys. The price is that two threads missing on the
+/// same key at once both call `f`; the second result is the one kept.
+pub struct SyncMemo<K, V, F> {
+    f: F,
+    cache: Mutex<Cache<K, V>>,
+}
+
+impl<K, V, F> SyncMemo<K, V, F>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    F: Fn(&K) -> V,
+{
+    pub fn new(f: F) -> SyncMemo<K, V, F> {
+        SyncMemo {
+            f,
+            cache: Mutex::new(Cache::new(None)),
+        }
+    }
+
+    /// As [`Memo::with_capacity`].
+    pub fn with_capacity(capacity: usize, f: F) -> SyncMemo<K, V, F> {
+        SyncMemo {
+            f,
+            cache: Mutex::new(Cache::new(Some(capacity))),
+        }
+    }
+
+    /// `f(key)`, calling `f` only if it isn't cached. Results are cloned
+    /// out, since another thread could evict them once the lock's let go.
+    pub fn get(&self, key: &K) -> V {
+        if let Some(value) = self.cached(key) {
+            return value;
+        }
+        let value = (self.f)(key);
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(key.clone(), value.clone());
+        value
+    }
+
+    fn cached(&self, key: &K) -> Option<V> {
+        let mut cache = self.cache.lock().unwrap();
+        cache.touch(key).then(|| cache.peek(key).unwrap().clone())
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.lock().unwrap().values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.values.clear();
+        cache.recency.clear();
+    }
+}
+
+impl<K, T, E, F> SyncMemo<K, Result<T, E>, F>
+where
+    K: Eq + Hash + Clone,
+    T: Clone,
+    E: Clone,
+    F: Fn(&K) -> Result<T, E>,
+{
+    /// As [`get`](SyncMemo::get), but only `Ok` results are cached, so a
+    /// key whose call failed is tried again the next time it's asked for.
+    pub fn get_ok(&self, key: &K) -> Result<T, E> {
+        if let Some(value) = self.cached(key) {
+            return value;
+        }
+        let value = (self.f)(key);
+        if let Ok(ok) = &value {
+            self.cache
+                .lock()
+                .unwrap()
+                .insert(key.clone(), Ok(ok.clone()));
+        }
+        value
+    }
+}
+
+impl<K, V, F> fmt::Debug for SyncMemo<K, V, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cache = self.cache.lock().unwrap();
+        f.debug_struct("SyncMemo")
+            .field("capacity", &cache.capacity)
+            .field("len", &cache.values.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The results, and for a bounded cache, which was used longest ago.
+///
+/// Every use gets the next number from `clock`, and `recency` maps those
+/// numbers back to keys, so its first entry is the least recently used.
+/// An unbounded cache never evicts, so it doesn't keep `recency` at all.
+struct Cache<K, V> {
+    values: HashMap<K, (V, u64)>,
+    recency: BTreeMap<u64, K>,
+    clock: u64,
+    capacity: Option<usize>,
+}
+
+impl<K: Eq + Hash + Clone, V> Cache<K, V> {
+    fn new(capacity: Option<usize>) -> Cache<K, V> {
+        assert!(capacity != Some(0), "a memo needs room for one result");
+        Cache {
+            values: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+            capacity,
+        }
+    }
+
+    fn peek(&self, key: &K) -> Option<&V> {
+        self.values.get(key).map(|(value, _)| value)
+    }
+
+    /// Mark `key` as just used, returning whether it's cached.
+    fn touch(&mut self, key: &K) -> bool {
+        let Some((_, used)) = self.values.get_mut(key) else {
+            return false;
+        };
+        if self.capacity.is_some() {
+            self.clock += 1;
+            let key = self.recency.remove(used).unwrap();
+            *used = self.clock;
+            self.recency.insert(self.clock, key);
+        }
+        true
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        let Some(capacity) = self.capacity else {
+            self.values.insert(key, (value, 0));
+            return;
+        };
+        self.clock += 1;
+        if let Some((_, used)) = self.values.insert(key.clone(), (value, self.clock)) {
+            self.recency.remove(&used);
+        } else if self.values.len() > capacity {
+            let (_, oldest) = self.recency.pop_first().unwrap();
+            self.values.remove(&oldest);
+        }
+        self.recency.insert(self.clock, key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::Cell,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            LazyLock,
+        },
+        thread,
+    };
+
+    use super::*;
+
+    #[test]
+    fn calls_once_per_key() {
+        let calls = Cell::new(0);
+        let mut len = Memo::new(|s: &String| {
+            calls.set(calls.get() + 1);
+            s.len()
+        });
+        for _ in 0..3 {
+            assert_eq!(*len.get(&"abc".to_string()), 3);
+            assert_eq!(*len.get(&"abcd".to_string()), 4);
+        }
+        assert_eq!((calls.get(), len.len(), len.capacity()), (2, 2, None));
+
+        len.clear();
+        assert!(len.is_empty());
+        len.get(&"abc".to_string());
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn bounded_memos_forget_the_least_recently_used() {
+        let calls = Cell::new(0);
+        let mut square = Memo::with_capacity(2, |x: &u32| {
+            calls.set(calls.get() + 1);
+            x * x
+        });
+        square.get(&1);
+        square.get(&2);
+        // 1 is now more recent than 2, so 3 pushes 2 out.
+        square.get(&1);
+        square.get(&3);
+        assert_eq!(square.len(), 2);
+        assert_eq!(calls.get(), 3);
+
+        square.get(&1);
+        square.get(&3);
+        assert_eq!(calls.get(), 3);
+        assert_eq!(*square.get(&2), 4);
+        assert_eq!(calls.get(), 4);
+        assert_eq!(square.len(), 2);
+    }
+
+    #[test]
+    fn bounded_caches_stay_consistent() {
+        let mut cache = Cache::new(Some(3));
+        for i in 0..100u64 {
+            let key = i * 7 % 5;
+            if !cache.touch(&key) {
+                cache.insert(key, i);
+            }
+            assert!(cache.values.len() <= 3);
+            assert_eq!(cache.values.len(), cache.recency.len());
+            for (used, key) in &cache.recency {
+                assert_eq!(cache.values[key].1, *used);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "room for one result")]
+    fn refuses_zero_capacity() {
+        Memo::with_capacity(0, |x: &u8| *x);
+    }
+
+    type Fib = SyncMemo<u32, u128, fn(&u32) -> u128>;
+
+    static FIB: LazyLock<Fib> = LazyLock::new(|| SyncMemo::new(fib));
+
+    fn fib(&n: &u32) -> u128 {
+        if n < 2 {
+            return u128::from(n);
+        }
+        FIB.get(&(n - 1)) + FIB.get(&(n - 2))
+    }
+
+    #[test]
+    fn sync_memos_can_call_themselves() {
+        // Without the cache, this would take 2^150-odd calls.
+        assert_eq!(FIB.get(&150), 9_969_216_677_189_303_386_214_405_760_200);
+        assert!(FIB.len() > 150);
+    }
+
+    #[test]
+    fn sync_memos_are_shared_between_threads() {
+        let calls = AtomicUsize::new(0);
+        let double = SyncMemo::with_capacity(100, |x: &u64| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            x * 2
+        });
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for x in 0..50 {
+                        assert_eq!(double.get(&x), x * 2);
+                    }
+                });
+            }
+        });
+        assert_eq!(double.len(), 50);
+        // Threads can race to fill the same key, but not by much.
+        let calls = calls.load(Ordering::SeqCst);
+        assert!((50..=200).contains(&calls), "{calls} calls");
+    }
+
+    #[test]
+    fn failures_are_tried_again() {
+        let calls = AtomicUsize::new(0);
+        let flaky = SyncMemo::new(|x: &u32| {
+            let call = calls.fetch_add(1, Ordering::SeqCst);
+            if call == 0 {
+                Err("not yet")
+            } else {
+                Ok(x + 1)
+            }
+        });
+        assert_eq!(flaky.get_ok(&1), Err("not yet"));
+        assert!(flaky.is_empty());
+        assert_eq!(flaky.get_ok(&1), Ok(2));
+        assert_eq!(flaky.get_ok(&1), Ok(2));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
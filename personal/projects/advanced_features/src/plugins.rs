@@ -0,0 +1,190 @@
+//! A [`Registry`] of plugins by name, where a plugin is a function
+//! pointer: "Advanced Functions and Closures" put to work.
+//!
+//! A `fn(&A) -> R` is a plain value. It's `Copy`, `Send` and `Sync`, and
+//! takes no generics or boxing to store, so a map of names to them is all a
+//! plugin system needs when the plugins don't keep any state. Calling one
+//! by name is [`dispatch`](Registry::dispatch).
+//!
+//! [`plugins!`](crate::plugins!) defines a set of plugin functions and
+//! lists them in a table in one go, so that adding a plugin is one entry in
+//! one place, in the spirit of the `inventory` crate (which collects them
+//! from across a whole program, with linker tricks this doesn't need).
+
+use std::{collections::BTreeMap, fmt};
+
+/// Plugins taking an `&A` and returning an `R`, by name.
+pub struct Registry<A: ?Sized, R> {
+    plugins: BTreeMap<&'static str, fn(&A) -> R>,
+}
+
+impl<A: ?Sized, R> Registry<A, R> {
+    pub fn new() -> Registry<A, R> {
+        Registry {
+            plugins: BTreeMap::new(),
+        }
+    }
+
+    /// Register `plugin` as `name`, returning the plugin it replaces, if
+    /// there was one.
+    pub fn register(&mut self, name: &'static str, plugin: fn(&A) -> R) -> Option<fn(&A) -> R> {
+        self.plugins.insert(name, plugin)
+    }
+
+    pub fn get(&self, name: &str) -> Option<fn(&A) -> R> {
+        self.plugins.get(name).copied()
+    }
+
+    /// Call the plugin called `name` with `arg`, or return `None` if there
+    /// isn't one.
+    pub fn dispatch(&self, name: &str, arg: &A) -> Option<R> {
+        self.get(name).map(|plugin| plugin(arg))
+    }
+
+    /// The plugins' names, sorted.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.plugins.keys().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.plugins.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+}
+
+impl<A: ?Sized, R> Default for Registry<A, R> {
+    fn default() -> Registry<A, R> {
+        Registry::new()
+    }
+}
+
+// By hand, since deriving would need `A: Clone` and `R: Clone`, and
+// function pointers are `Copy` whatever they take and return.
+impl<A: ?Sized, R> Clone for Registry<A, R> {
+    fn clone(&self) -> Registry<A, R> {
+        Registry {
+            plugins: self.plugins.clone(),
+        }
+    }
+}
+
+impl<A: ?Sized, R> fmt::Debug for Registry<A, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.names()).finish()
+    }
+}
+
+/// A registry of every plugin in a table, such as one [`plugins!`](crate::plugins!)
+/// makes. A name that's in the table twice gets the later plugin.
+impl<A: ?Sized, R> From<&[(&'static str, fn(&A) -> R)]> for Registry<A, R> {
+    fn from(table: &[(&'static str, fn(&A) -> R)]) -> Registry<A, R> {
+        let mut registry = Registry::new();
+        for &(name, plugin) in table {
+            registry.register(name, plugin);
+        }
+        registry
+    }
+}
+
+/// Define plugin functions and a `static` table of them by name.
+///
+/// ```
+/// use advanced_features::plugins::Registry;
+///
+/// advanced_features::plugins! {
+///     static MATH: fn(&i32) -> i32 = {
+///         "double" => fn double(x) { x * 2 }
+///         /// Plugins are ordinary functions, and can have docs.
+///         "square" => fn square(x) { x * x }
+///     }
+/// }
+///
+/// let registry = Registry::from(MATH);
+/// assert_eq!(registry.dispatch("square", &3), Some(9));
+/// assert_eq!(registry.dispatch("cube", &3), None);
+/// assert_eq!(double(&4), 8);
+/// ```
+#[macro_export]
+macro_rules! plugins {
+    (
+        $vis:vis static $table:ident: fn(&$arg:ty) -> $ret:ty = {
+            $(
+                $(#[$attr:meta])*
+                $name:literal => fn $plugin:ident($param:ident) $body:block
+            )*
+        }
+    ) => {
+        $(
+            $(#[$attr])*
+            $vis fn $plugin($param: &$arg) -> $ret $body
+        )*
+
+        $vis static $table: &[(&str, fn(&$arg) -> $ret)] = &[$(($name, $plugin)),*];
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shout(s: &str) -> String {
+        s.to_uppercase()
+    }
+
+    fn whisper(s: &str) -> String {
+        s.to_lowercase()
+    }
+
+    crate::plugins! {
+        static GREETINGS: fn(&str) -> String = {
+            "hello" => fn hello(name) { format!("Hello, {name}!") }
+            "bye" => fn bye(name) { format!("Goodbye, {name}.") }
+            "hello" => fn hello_again(name) { format!("Hello again, {name}!") }
+        }
+    }
+
+    #[test]
+    fn dispatches_by_name() {
+        let mut registry: Registry<str, String> = Registry::new();
+        assert!(registry.is_empty());
+        assert!(registry.register("shout", shout).is_none());
+        registry.register("whisper", whisper);
+
+        assert_eq!(registry.dispatch("shout", "hi").as_deref(), Some("HI"));
+        assert_eq!(registry.dispatch("whisper", "HI").as_deref(), Some("hi"));
+        assert_eq!(registry.dispatch("sing", "hi"), None);
+        assert_eq!(registry.names().collect::<Vec<_>>(), ["shout", "whisper"]);
+    }
+
+    #[test]
+    fn registering_again_replaces() {
+        let mut registry: Registry<str, String> = Registry::new();
+        registry.register("say", shout);
+        let old = registry.register("say", whisper).unwrap();
+        assert_eq!(old("Hi"), "HI");
+        assert_eq!(registry.dispatch("say", "Hi").as_deref(), Some("hi"));
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[test]
+    fn builds_from_a_plugins_table() {
+        let registry = Registry::from(GREETINGS);
+        assert_eq!(format!("{registry:?}"), r#"{"bye", "hello"}"#);
+        assert_eq!(
+            registry.dispatch("hello", "Ferris").as_deref(),
+            Some("Hello again, Ferris!")
+        );
+        assert_eq!(hello("Ferris"), "Hello, Ferris!");
+        assert_eq!(bye("Ferris"), "Goodbye, Ferris.");
+        // Changing a clone leaves the original alone.
+        let mut clone = registry.clone();
+        clone.register("bye", hello);
+        assert_eq!(
+            registry.dispatch("bye", "x").as_deref(),
+            Some("Goodbye, x.")
+        );
+    }
+}
@@ -0,0 +1,257 @@
+//! Listing 19-14's `Point`, with the rest of the arithmetic operators.
+//!
+//! The listing only overloads `+`. A point is a vector from the origin, so
+//! the other operators follow from that: `-` and unary `-` component by
+//! component, `*` and `/` scale it by a number, and `+=` and `-=` update it
+//! in place. `p[0]` and `p[1]` are `x` and `y`.
+//!
+//! Points are compared by their distance from the origin, so `<` means
+//! "shorter". Two different points can be the same length, like `(3, 4)`
+//! and `(4, 3)`. Those aren't equal, and neither is shorter, so
+//! `partial_cmp` says they can't be ordered: the "partial" in `PartialOrd`.
+
+use std::{
+    cmp::Ordering,
+    fmt,
+    ops::{Add, AddAssign, Div, Index, Mul, Neg, Sub, SubAssign},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Point {
+    pub const ORIGIN: Point = Point { x: 0, y: 0 };
+
+    pub fn new(x: i32, y: i32) -> Point {
+        Point { x, y }
+    }
+
+    /// The square of the distance from the origin, which is exact, unlike
+    /// [`magnitude`](Point::magnitude), and orders points the same way.
+    pub fn magnitude_squared(self) -> i64 {
+        let (x, y) = (i64::from(self.x), i64::from(self.y));
+        x * x + y * y
+    }
+
+    /// The distance from the origin.
+    pub fn magnitude(self) -> f64 {
+        f64::from(self.x).hypot(f64::from(self.y))
+    }
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+impl Add for Point {
+    type Output = Point;
+
+    fn add(self, other: Point) -> Point {
+        Point {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
+
+impl Sub for Point {
+    type Output = Point;
+
+    fn sub(self, other: Point) -> Point {
+        Point {
+            x: self.x - other.x,
+            y: self.y - other.y,
+        }
+    }
+}
+
+impl Neg for Point {
+    type Output = Point;
+
+    fn neg(self) -> Point {
+        Point {
+            x: -self.x,
+            y: -self.y,
+        }
+    }
+}
+
+impl AddAssign for Point {
+    fn add_assign(&mut self, other: Point) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Point {
+    fn sub_assign(&mut self, other: Point) {
+        *self = *self - other;
+    }
+}
+
+/// `p * 2`: scaling, with a number as the `Rhs`.
+impl Mul<i32> for Point {
+    type Output = Point;
+
+    fn mul(self, factor: i32) -> Point {
+        Point {
+            x: self.x * factor,
+            y: self.y * factor,
+        }
+    }
+}
+
+/// `2 * p`, which is the same thing the other way round.
+impl Mul<Point> for i32 {
+    type Output = Point;
+
+    fn mul(self, point: Point) -> Point {
+        point * self
+    }
+}
+
+/// `p / 2`, rounding each component toward zero, as `i32` division does.
+///
+/// Panics if `divisor` is 0.
+impl Div<i32> for Point {
+    type Output = Point;
+
+    fn div(self, divisor: i32) -> Point {
+        Point {
+            x: self.x / divisor,
+            y: self.y / divisor,
+        }
+    }
+}
+
+/// `p[0]` is `x` and `p[1]` is `y`.
+///
+/// Panics for any other index, as an array of two would.
+impl Index<usize> for Point {
+    type Output = i32;
+
+    fn index(&self, axis: usize) -> &i32 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            _ => panic!("index {axis} is out of bounds for a Point, which has 2 components"),
+        }
+    }
+}
+
+/// Shorter points are less. Points the same length are only equal if
+/// they're the same point; otherwise they can't be ordered.
+impl PartialOrd for Point {
+    fn partial_cmp(&self, other: &Point) -> Option<Ordering> {
+        match self.magnitude_squared().cmp(&other.magnitude_squared()) {
+            Ordering::Equal if self != other => None,
+            ordering => Some(ordering),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every point with both components in -4..=4, which is enough to hit
+    /// each sign, zero, and points of the same length.
+    fn points() -> impl Iterator<Item = Point> + Clone {
+        (-4..=4).flat_map(|x| (-4..=4).map(move |y| Point::new(x, y)))
+    }
+
+    fn pairs() -> impl Iterator<Item = (Point, Point)> {
+        points().flat_map(|p| points().map(move |q| (p, q)))
+    }
+
+    #[test]
+    fn listing_19_14() {
+        assert_eq!(Point::new(1, 0) + Point::new(2, 3), Point::new(3, 3));
+        assert_eq!(Point::new(1, 3).to_string(), "(1, 3)");
+    }
+
+    #[test]
+    fn addition_commutes_and_subtraction_undoes_it() {
+        for (p, q) in pairs() {
+            assert_eq!(p + q, q + p);
+            assert_eq!(p + q - q, p);
+            assert_eq!(p - q, -(q - p));
+            assert_eq!(p - q, p + -q);
+        }
+    }
+
+    #[test]
+    fn identities() {
+        for p in points() {
+            assert_eq!(p + Point::ORIGIN, p);
+            assert_eq!(p - p, Point::ORIGIN);
+            assert_eq!(p * 1, p);
+            assert_eq!(p / 1, p);
+            assert_eq!(-(-p), p);
+            assert_eq!(p * -1, -p);
+        }
+    }
+
+    #[test]
+    fn scaling_commutes_and_distributes() {
+        for (p, q) in pairs() {
+            for k in -3..=3 {
+                assert_eq!(p * k, k * p);
+                assert_eq!((p + q) * k, p * k + q * k);
+                if k != 0 {
+                    assert_eq!(p * k / k, p);
+                }
+            }
+        }
+        // Division rounds toward zero, in both directions.
+        assert_eq!(Point::new(7, -7) / 2, Point::new(3, -3));
+    }
+
+    #[test]
+    fn assigning_matches_the_operators() {
+        for (p, q) in pairs() {
+            let mut r = p;
+            r += q;
+            assert_eq!(r, p + q);
+            r -= q;
+            assert_eq!(r, p);
+        }
+    }
+
+    #[test]
+    fn indexes_its_components() {
+        for p in points() {
+            assert_eq!((p[0], p[1]), (p.x, p.y));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "index 2 is out of bounds for a Point")]
+    fn has_only_two_components() {
+        let _ = Point::ORIGIN[2];
+    }
+
+    #[test]
+    fn orders_by_length() {
+        assert!(Point::new(1, 1) < Point::new(0, 2));
+        assert!(Point::new(-3, 0) > Point::new(2, 2));
+        assert_eq!(Point::new(3, 4).magnitude(), 5.0);
+        // Same length, different points: not equal, and neither is less.
+        let (p, q) = (Point::new(3, 4), Point::new(4, 3));
+        assert_eq!(p.partial_cmp(&q), None);
+        assert_ne!(p, q);
+
+        for (p, q) in pairs() {
+            // Consistent with `==`, as `PartialOrd` requires.
+            assert_eq!(p.partial_cmp(&q) == Some(Ordering::Equal), p == q);
+            assert_eq!(p.partial_cmp(&q), q.partial_cmp(&p).map(Ordering::reverse));
+            // Negating doesn't change a point's length.
+            assert_eq!(p.magnitude_squared(), (-p).magnitude_squared());
+            assert!(p >= Point::ORIGIN);
+        }
+    }
+}
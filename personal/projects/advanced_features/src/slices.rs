@@ -0,0 +1,176 @@
+//! Listing 19-6's `split_at_mut`, for slices of any type, and
+//! [`split_into_chunks_mut`], which splits a slice into as many pieces as
+//! it's asked for.
+//!
+//! Both hand out several `&mut` slices of one slice, which the borrow
+//! checker can't see are disjoint, so they build them from a raw pointer,
+//! with the [`raw_ptr`](smart_pointers::raw_ptr) helpers so that debug
+//! builds check every offset is in bounds. What makes that sound is
+//! arithmetic: every piece starts where the last one ended, and the last
+//! one ends at the end of the slice. The tests check exactly that, by
+//! writing through every piece at once.
+
+use smart_pointers::raw_ptr::{offset_checked, slice_from_parts_mut_checked};
+
+/// Split `values` into `values[..mid]` and `values[mid..]`, both mutable.
+///
+/// Panics if `mid > values.len()`.
+///
+/// ```
+/// use advanced_features::slices::split_at_mut;
+///
+/// let mut v = vec![1, 2, 3, 4, 5, 6];
+/// let (a, b) = split_at_mut(&mut v, 3);
+/// a[0] = 10;
+/// b[0] = 40;
+/// assert_eq!(v, [10, 2, 3, 40, 5, 6]);
+/// ```
+pub fn split_at_mut<T>(values: &mut [T], mid: usize) -> (&mut [T], &mut [T]) {
+    let len = values.len();
+    let ptr = values.as_mut_ptr();
+
+    assert!(mid <= len, "mid {mid} is past the end of a slice of {len}");
+
+    // SAFETY: `ptr` is valid for `len` values of `T` for as long as the
+    // `values` borrow, which both slices are tied to. `mid <= len`, so
+    // `ptr.add(mid)` is in bounds (or one past the end), and the two
+    // slices cover `0..mid` and `mid..len`: all of `values`, with no value
+    // in both.
+    unsafe {
+        (
+            slice_from_parts_mut_checked(ptr, mid),
+            slice_from_parts_mut_checked(offset_checked(ptr, len, mid), len - mid),
+        )
+    }
+}
+
+/// Split `values` into `n` mutable pieces, in order, as even as they can
+/// be: the first `values.len() % n` are one longer than the rest.
+///
+/// Every piece is there even if it's empty, so each of `n` workers can be
+/// handed one.
+///
+/// Panics if `n` is 0.
+///
+/// ```
+/// use advanced_features::slices::split_into_chunks_mut;
+///
+/// let mut v = [0; 7];
+/// for (i, chunk) in split_into_chunks_mut(&mut v, 3).into_iter().enumerate() {
+///     chunk.fill(i);
+/// }
+/// assert_eq!(v, [0, 0, 0, 1, 1, 2, 2]);
+/// ```
+pub fn split_into_chunks_mut<T>(values: &mut [T], n: usize) -> Vec<&mut [T]> {
+    assert!(n > 0, "can't split a slice into 0 chunks");
+
+    let len = values.len();
+    let ptr = values.as_mut_ptr();
+    let (size, longer) = (len / n, len % n);
+
+    let mut chunks = Vec::with_capacity(n);
+    let mut start = 0;
+    for i in 0..n {
+        let chunk_len = size + usize::from(i < longer);
+        // SAFETY: the chunks are laid end to end from 0: this one covers
+        // `start..start + chunk_len`, and the next starts where it ends, so
+        // no two overlap. Their lengths add up to `longer * (size + 1) +
+        // (n - longer) * size`, which is `len`, so `start + chunk_len <=
+        // len` and every chunk is inside `values`. They all borrow from
+        // `values`, as in `split_at_mut`.
+        chunks.push(unsafe {
+            slice_from_parts_mut_checked(offset_checked(ptr, len, start), chunk_len)
+        });
+        start += chunk_len;
+    }
+    debug_assert_eq!(start, len);
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    /// Give every value the index of the piece it's in, through all the
+    /// pieces at once, and return the pieces' lengths.
+    fn label(pieces: Vec<&mut [u8]>) -> Vec<usize> {
+        let lens = pieces.iter().map(|piece| piece.len()).collect();
+        for (i, piece) in pieces.into_iter().enumerate() {
+            piece.fill(i as u8);
+        }
+        lens
+    }
+
+    /// If two pieces overlapped, the later one's labels would overwrite
+    /// some of the earlier one's, and its count would come up short.
+    fn assert_disjoint(values: &[u8], lens: &[usize]) {
+        for (i, &len) in lens.iter().enumerate() {
+            let written = values.iter().filter(|&&v| v == i as u8).count();
+            assert_eq!(written, len, "piece {i} was overwritten");
+        }
+    }
+
+    #[test]
+    fn splits_anywhere_in_bounds() {
+        for mid in 0..=5 {
+            let mut v = vec![String::new(); 5];
+            let (a, b) = split_at_mut(&mut v, mid);
+            assert_eq!((a.len(), b.len()), (mid, 5 - mid));
+            a.iter_mut().for_each(|s| s.push('a'));
+            b.iter_mut().for_each(|s| s.push('b'));
+            assert!(v.iter().all(|s| s.len() == 1));
+            assert_eq!(v.iter().filter(|s| *s == "a").count(), mid);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "mid 4 is past the end of a slice of 3")]
+    fn refuses_to_split_past_the_end() {
+        split_at_mut(&mut [1, 2, 3], 4);
+    }
+
+    #[test]
+    fn chunks_cover_the_slice_once() {
+        for len in 0..20 {
+            for n in 1..8 {
+                let mut v = vec![u8::MAX; len];
+                let lens = label(split_into_chunks_mut(&mut v, n));
+                assert_disjoint(&v, &lens);
+                assert_eq!(lens.len(), n);
+                assert_eq!(lens.iter().sum::<usize>(), len);
+                assert!(lens.windows(2).all(|w| w[0] == w[1] || w[0] == w[1] + 1));
+                // In order: each piece's label comes after the last one's.
+                assert!(v.windows(2).all(|w| w[0] <= w[1]));
+            }
+        }
+    }
+
+    #[test]
+    fn works_for_zero_sized_values() {
+        let mut v = [(); 10];
+        let chunks = split_into_chunks_mut(&mut v, 4);
+        assert_eq!(
+            chunks.iter().map(|c| c.len()).collect::<Vec<_>>(),
+            [3, 3, 2, 2]
+        );
+    }
+
+    #[test]
+    fn chunks_can_go_to_different_threads() {
+        let mut v: Vec<u64> = (1..=100).collect();
+        thread::scope(|s| {
+            for chunk in split_into_chunks_mut(&mut v, 6) {
+                s.spawn(move || chunk.iter_mut().for_each(|x| *x *= 2));
+            }
+        });
+        assert_eq!(v.iter().sum::<u64>(), 2 * 5050);
+    }
+
+    #[test]
+    #[should_panic(expected = "0 chunks")]
+    fn refuses_zero_chunks() {
+        split_into_chunks_mut(&mut [1], 0);
+    }
+}
@@ -0,0 +1,193 @@
+//! `AssertThreadSafe<T>`: a wrapper that `unsafe impl`s `Send` and `Sync`
+//! for any `T`, and checks the promise that makes that sound.
+//!
+//! The chapter's warning about implementing `Send` and `Sync` by hand is
+//! that the compiler takes our word for it. Usually the word is "this is
+//! only ever *used* on the thread that made it": an `Rc` or a raw pointer
+//! can be carried through a channel or a job and back, as long as nothing
+//! touches it on the way. This wrapper writes that promise down. It
+//! remembers which thread made it, and in debug builds every access from
+//! any other thread panics, so breaking the promise shows up in tests
+//! rather than as a data race. Release builds trust it, like any `unsafe`.
+
+use std::{
+    fmt,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    thread::{self, ThreadId},
+};
+
+pub struct AssertThreadSafe<T> {
+    value: ManuallyDrop<T>,
+    owner: ThreadId,
+}
+
+// SAFETY: `AssertThreadSafe::new`'s caller promises the value is only used
+// on the thread that made it, so moving or sharing the wrapper never lets
+// two threads at `T` at once.
+unsafe impl<T> Send for AssertThreadSafe<T> {}
+unsafe impl<T> Sync for AssertThreadSafe<T> {}
+
+impl<T> AssertThreadSafe<T> {
+    /// Wrap `value`, owned by the current thread.
+    ///
+    /// # Safety
+    ///
+    /// The value must only be used, and dropped, on this thread: getting at
+    /// it, dropping the wrapper or calling [`into_inner`](Self::into_inner)
+    /// anywhere else is undefined behavior. Debug builds panic instead.
+    pub unsafe fn new(value: T) -> AssertThreadSafe<T> {
+        AssertThreadSafe {
+            value: ManuallyDrop::new(value),
+            owner: thread::current().id(),
+        }
+    }
+
+    /// The thread that made this, and is the only one allowed to use it.
+    pub fn owner(&self) -> ThreadId {
+        self.owner
+    }
+
+    /// Whether the current thread is the owner. Checking doesn't touch the
+    /// value, so this is fine from any thread.
+    pub fn is_owner(&self) -> bool {
+        thread::current().id() == self.owner
+    }
+
+    pub fn get(&self) -> &T {
+        self.check("used");
+        &self.value
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.check("used");
+        &mut self.value
+    }
+
+    pub fn into_inner(self) -> T {
+        self.check("unwrapped");
+        let mut this = ManuallyDrop::new(self);
+        // SAFETY: `this` is never used or dropped again, so the value is
+        // taken out exactly once.
+        unsafe { ManuallyDrop::take(&mut this.value) }
+    }
+
+    #[track_caller]
+    fn check(&self, action: &str) {
+        if cfg!(debug_assertions) && !self.is_owner() {
+            panic!(
+                "value owned by {:?} {action} on {:?}",
+                self.owner,
+                thread::current().id()
+            );
+        }
+    }
+}
+
+impl<T> Deref for AssertThreadSafe<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.get()
+    }
+}
+
+impl<T> DerefMut for AssertThreadSafe<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.get_mut()
+    }
+}
+
+impl<T> Drop for AssertThreadSafe<T> {
+    fn drop(&mut self) {
+        if cfg!(debug_assertions) && !self.is_owner() {
+            // Leak the value rather than drop it on the wrong thread, and
+            // don't panic while already unwinding, which would abort.
+            if !thread::panicking() {
+                self.check("dropped");
+            }
+            return;
+        }
+        // SAFETY: this is the last use of the value.
+        unsafe { ManuallyDrop::drop(&mut self.value) }
+    }
+}
+
+/// Shows the owner, and the value only to its owner.
+impl<T: fmt::Debug> fmt::Debug for AssertThreadSafe<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("AssertThreadSafe");
+        if self.is_owner() {
+            debug.field("value", &*self.value);
+        }
+        debug.field("owner", &self.owner).finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{rc::Rc, sync::mpsc};
+
+    use super::*;
+
+    #[test]
+    fn the_owner_can_use_it() {
+        // SAFETY: only this thread uses it.
+        let mut shared = unsafe { AssertThreadSafe::new(Rc::new(5)) };
+        assert!(shared.is_owner());
+        assert_eq!(**shared, 5);
+        *Rc::get_mut(&mut shared).unwrap() += 1;
+        assert_eq!(*shared.into_inner(), 6);
+    }
+
+    /// An `Rc` can't be sent, but it can go through another thread and
+    /// back as long as that thread only passes it on.
+    #[test]
+    fn can_pass_through_other_threads_untouched() {
+        let (to_worker, from_main) = mpsc::channel();
+        let (to_main, from_worker) = mpsc::channel();
+        let worker = thread::spawn(move || {
+            let value: AssertThreadSafe<Rc<i32>> = from_main.recv().unwrap();
+            assert!(!value.is_owner());
+            to_main.send(value).unwrap();
+        });
+
+        // SAFETY: the worker only hands it back.
+        to_worker
+            .send(unsafe { AssertThreadSafe::new(Rc::new(7)) })
+            .unwrap();
+        let value = from_worker.recv().unwrap();
+        worker.join().unwrap();
+        assert_eq!(*value.into_inner(), 7);
+    }
+
+    #[test]
+    fn debug_shows_the_value_only_to_its_owner() {
+        // SAFETY: only formatted elsewhere, which doesn't touch the value.
+        let value = unsafe { AssertThreadSafe::new(3) };
+        assert!(format!("{value:?}").starts_with("AssertThreadSafe { value: 3, owner: "));
+        thread::scope(|s| {
+            s.spawn(|| assert!(!format!("{value:?}").contains("value:")));
+        });
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn using_it_elsewhere_panics_in_debug_builds() {
+        // SAFETY: the other thread's access is caught before it happens.
+        let value = unsafe { AssertThreadSafe::new(Rc::new(1)) };
+        let panicked = thread::scope(|s| s.spawn(|| **value).join().is_err());
+        assert!(panicked);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn dropping_it_elsewhere_panics_in_debug_builds() {
+        // SAFETY: the drop on the other thread is caught, and leaks.
+        let value = unsafe { AssertThreadSafe::new(Rc::new(1)) };
+        let weak = Rc::downgrade(value.get());
+        assert!(thread::spawn(move || drop(value)).join().is_err());
+        // Leaked, not dropped on the wrong thread.
+        assert!(weak.upgrade().is_some());
+    }
+}
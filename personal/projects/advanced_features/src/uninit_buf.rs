@@ -0,0 +1,178 @@
+//! `UninitBuf<T>`: a fixed-size buffer that starts out uninitialized and
+//! knows how much of itself has been written.
+//!
+//! `[0u8; 1024]` is zeroed every time it's made, only for the zeroes to be
+//! overwritten by whatever's read into it. A `MaybeUninit<u8>` array skips
+//! that, but then nothing says which bytes are safe to look at. This keeps
+//! the count: the first `len` values are initialized and the rest aren't,
+//! so reading the initialized part is safe, and only code that writes to
+//! the rest has to be trusted to say how much it wrote.
+
+use std::{fmt, mem::MaybeUninit, ptr};
+
+pub struct UninitBuf<T> {
+    /// The first `len` are initialized, and are dropped with the buffer.
+    buf: Box<[MaybeUninit<T>]>,
+    len: usize,
+}
+
+impl<T> UninitBuf<T> {
+    /// A buffer with room for `capacity` values, none of them initialized.
+    pub fn with_capacity(capacity: usize) -> UninitBuf<T> {
+        UninitBuf {
+            buf: Box::new_uninit_slice(capacity),
+            len: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// How many values are initialized.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == self.capacity()
+    }
+
+    /// Initialize the next value, or give it back if the buffer is full.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        match self.buf.get_mut(self.len) {
+            Some(slot) => {
+                slot.write(value);
+                self.len += 1;
+                Ok(())
+            }
+            None => Err(value),
+        }
+    }
+
+    /// The values that are initialized.
+    pub fn assume_init_slice(&self) -> &[T] {
+        // SAFETY: the first `len` values are initialized, and
+        // `MaybeUninit<T>` has the same layout as `T`.
+        unsafe { &*(&self.buf[..self.len] as *const [MaybeUninit<T>] as *const [T]) }
+    }
+
+    pub fn assume_init_slice_mut(&mut self) -> &mut [T] {
+        // SAFETY: as in `assume_init_slice`, through `&mut self`.
+        unsafe { &mut *(&mut self.buf[..self.len] as *mut [MaybeUninit<T>] as *mut [T]) }
+    }
+
+    /// The part after the initialized values, to be written to before
+    /// [`advance`] counts it.
+    ///
+    /// [`advance`]: UninitBuf::advance
+    pub fn spare_capacity_mut(&mut self) -> &mut [MaybeUninit<T>] {
+        &mut self.buf[self.len..]
+    }
+
+    /// Count the next `n` values as initialized.
+    ///
+    /// Panics if that's more than the spare capacity.
+    ///
+    /// # Safety
+    ///
+    /// The first `n` values of [`spare_capacity_mut`] must have been
+    /// initialized.
+    ///
+    /// [`spare_capacity_mut`]: UninitBuf::spare_capacity_mut
+    pub unsafe fn advance(&mut self, n: usize) {
+        assert!(
+            n <= self.capacity() - self.len,
+            "advanced {n} past a spare capacity of {}",
+            self.capacity() - self.len
+        );
+        self.len += n;
+    }
+
+    /// Drop the initialized values, leaving the whole buffer spare.
+    pub fn clear(&mut self) {
+        let values: *mut [T] = self.assume_init_slice_mut();
+        // Forget them first, so that if dropping one panics the rest are
+        // leaked instead of dropped twice.
+        self.len = 0;
+        // SAFETY: these were the initialized values, and with `len` at 0
+        // nothing will read or drop them again.
+        unsafe { ptr::drop_in_place(values) }
+    }
+}
+
+impl<T> Drop for UninitBuf<T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for UninitBuf<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UninitBuf")
+            .field("init", &self.assume_init_slice())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[test]
+    fn only_written_values_are_visible() {
+        let mut buf = UninitBuf::with_capacity(4);
+        assert!(buf.is_empty());
+        buf.push(1u8).unwrap();
+
+        let spare = buf.spare_capacity_mut();
+        assert_eq!(spare.len(), 3);
+        spare[0].write(2);
+        spare[1].write(3);
+        // SAFETY: both were just written.
+        unsafe { buf.advance(2) };
+
+        assert_eq!(buf.assume_init_slice(), [1, 2, 3]);
+        buf.assume_init_slice_mut()[0] = 9;
+        buf.push(4).unwrap();
+        assert!(buf.is_full());
+        assert_eq!(buf.push(5), Err(5));
+        assert_eq!(
+            format!("{buf:?}"),
+            "UninitBuf { init: [9, 2, 3, 4], capacity: 4 }"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "advanced 3 past a spare capacity of 2")]
+    fn refuses_to_advance_past_the_end() {
+        let mut buf = UninitBuf::<u8>::with_capacity(2);
+        // SAFETY: panics before anything is counted.
+        unsafe { buf.advance(3) };
+    }
+
+    #[test]
+    fn drops_exactly_the_initialized_values() {
+        let value = Rc::new(());
+        let mut buf = UninitBuf::with_capacity(8);
+        for _ in 0..3 {
+            buf.push(Rc::clone(&value)).unwrap();
+        }
+        assert_eq!(Rc::strong_count(&value), 4);
+
+        buf.clear();
+        assert_eq!(Rc::strong_count(&value), 1);
+        assert_eq!(buf.spare_capacity_mut().len(), 8);
+
+        buf.push(Rc::clone(&value)).unwrap();
+        drop(buf);
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+}
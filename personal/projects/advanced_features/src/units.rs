@@ -0,0 +1,214 @@
+//! Listing 19-15's `Millimeters` and `Meters`, grown into units of length,
+//! time and data size.
+//!
+//! Each unit is a newtype around a `u64`, so the compiler keeps them
+//! apart: a function taking `Meters` can't be given `Seconds`, or a bare
+//! number. Units of the same kind convert with `From`, from the bigger unit
+//! to the smaller one, which never loses anything. Adding or subtracting
+//! two of them uses the `Rhs` type parameter as the listing does, and
+//! gives the smaller unit:
+//!
+//! ```
+//! use advanced_features::units::{Kibibytes, Meters, Millimeters};
+//!
+//! assert_eq!(Millimeters(1) + Meters(2), Millimeters(2001));
+//! assert_eq!(Meters(2) - Millimeters(1), Millimeters(1999));
+//! assert_eq!(Kibibytes(2).to_string(), "2 KiB");
+//! ```
+//!
+//! Units of different kinds have no `Add` between them, so mixing them up
+//! doesn't compile:
+//!
+//! ```compile_fail
+//! use advanced_features::units::{Meters, Seconds};
+//!
+//! let _ = Meters(100) + Seconds(10);
+//! ```
+//!
+//! ```compile_fail
+//! use advanced_features::units::{Bytes, Milliseconds};
+//!
+//! let _ = Milliseconds::from(Bytes(512));
+//! ```
+//!
+//! Like `u64`, arithmetic that overflows, or subtracts more than there is,
+//! panics in debug builds.
+
+use std::{
+    fmt,
+    ops::{Add, Sub},
+};
+
+/// A unit: a `u64` newtype with `+` and `-` for itself, shown with
+/// `suffix` after the number.
+macro_rules! unit {
+    ($(#[$attr:meta])* $name:ident, $suffix:literal) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+        pub struct $name(pub u64);
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{} {}", self.0, $suffix)
+            }
+        }
+
+        impl Add for $name {
+            type Output = $name;
+
+            fn add(self, other: $name) -> $name {
+                $name(self.0 + other.0)
+            }
+        }
+
+        impl Sub for $name {
+            type Output = $name;
+
+            fn sub(self, other: $name) -> $name {
+                $name(self.0 - other.0)
+            }
+        }
+    };
+}
+
+/// One `$big` is `$factor` `$small`s: `From` one to the other, and `+`
+/// and `-` between them either way round, giving `$small`s.
+macro_rules! conversion {
+    ($big:ident => $factor:literal $small:ident) => {
+        impl From<$big> for $small {
+            fn from(big: $big) -> $small {
+                $small(big.0 * $factor)
+            }
+        }
+
+        impl Add<$big> for $small {
+            type Output = $small;
+
+            fn add(self, other: $big) -> $small {
+                self + $small::from(other)
+            }
+        }
+
+        impl Add<$small> for $big {
+            type Output = $small;
+
+            fn add(self, other: $small) -> $small {
+                $small::from(self) + other
+            }
+        }
+
+        impl Sub<$big> for $small {
+            type Output = $small;
+
+            fn sub(self, other: $big) -> $small {
+                self - $small::from(other)
+            }
+        }
+
+        impl Sub<$small> for $big {
+            type Output = $small;
+
+            fn sub(self, other: $small) -> $small {
+                $small::from(self) - other
+            }
+        }
+    };
+}
+
+unit!(Millimeters, "mm");
+unit!(Meters, "m");
+unit!(Kilometers, "km");
+conversion!(Meters => 1000 Millimeters);
+conversion!(Kilometers => 1_000_000 Millimeters);
+conversion!(Kilometers => 1000 Meters);
+
+unit!(Milliseconds, "ms");
+unit!(Seconds, "s");
+unit!(Minutes, "min");
+conversion!(Seconds => 1000 Milliseconds);
+conversion!(Minutes => 60_000 Milliseconds);
+conversion!(Minutes => 60 Seconds);
+
+unit!(Bytes, "B");
+unit!(
+    /// 1024 bytes.
+    Kibibytes,
+    "KiB"
+);
+unit!(
+    /// 1024 kibibytes.
+    Mebibytes,
+    "MiB"
+);
+conversion!(Kibibytes => 1024 Bytes);
+conversion!(Mebibytes => 1_048_576 Bytes);
+conversion!(Mebibytes => 1024 Kibibytes);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listing_19_15() {
+        assert_eq!(Millimeters(1) + Meters(2), Millimeters(2001));
+    }
+
+    #[test]
+    fn converts_down_without_loss() {
+        assert_eq!(Millimeters::from(Kilometers(3)), Millimeters(3_000_000));
+        assert_eq!(Meters::from(Kilometers(3)), Meters(3000));
+        assert_eq!(Seconds::from(Minutes(2)), Seconds(120));
+        assert_eq!(Milliseconds::from(Minutes(2)), Milliseconds(120_000));
+        assert_eq!(Bytes::from(Mebibytes(1)), Bytes(1 << 20));
+        // Going through the unit in between gets the same answer.
+        let km = Kilometers(7);
+        assert_eq!(Millimeters::from(Meters::from(km)), Millimeters::from(km));
+        let mib = Mebibytes(7);
+        assert_eq!(Bytes::from(Kibibytes::from(mib)), Bytes::from(mib));
+    }
+
+    #[test]
+    fn mixed_arithmetic_gives_the_smaller_unit() {
+        assert_eq!(Seconds(30) + Minutes(1), Seconds(90));
+        assert_eq!(Minutes(1) + Seconds(30), Seconds(90));
+        assert_eq!(Minutes(1) - Seconds(30), Seconds(30));
+        assert_eq!(Kibibytes(1) - Bytes(24), Bytes(1000));
+        assert_eq!(Bytes(24) + Kibibytes(1), Kibibytes(1) + Bytes(24));
+        assert_eq!(
+            Kilometers(1) + Meters(1) + Millimeters(1),
+            Millimeters(1_001_001)
+        );
+    }
+
+    #[test]
+    fn displays_with_a_suffix() {
+        let shown = [
+            Millimeters(5).to_string(),
+            Meters(5).to_string(),
+            Kilometers(5).to_string(),
+            Milliseconds(5).to_string(),
+            Seconds(5).to_string(),
+            Minutes(5).to_string(),
+            Bytes(5).to_string(),
+            Kibibytes(5).to_string(),
+            Mebibytes(5).to_string(),
+        ];
+        assert_eq!(
+            shown,
+            ["5 mm", "5 m", "5 km", "5 ms", "5 s", "5 min", "5 B", "5 KiB", "5 MiB"]
+        );
+    }
+
+    #[test]
+    fn compares_within_a_unit() {
+        assert!(Seconds(59) < Seconds(60));
+        assert_eq!(Seconds(60), Seconds::from(Minutes(1)));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "overflow")]
+    fn cannot_go_below_zero() {
+        let _ = Seconds(1) - Minutes(1);
+    }
+}
@@ -0,0 +1,216 @@
+//! A tagged value: a `union` that holds an integer, a float or a pointer,
+//! next to a tag saying which.
+//!
+//! Reading a union's field is the fifth unsafe superpower, because nothing
+//! stops code from reading a field other than the one last written. Here
+//! the fields are private and the tag is always set with them, so each
+//! accessor checks the tag and only reads the field it names. Code using
+//! [`Value`] can't get it wrong.
+//!
+//! `Value` is `#[repr(C)]`, so it's laid out like this C type and can be
+//! passed across FFI as one:
+//!
+//! ```c
+//! struct value {
+//!     uint32_t tag;          /* 0: int, 1: float, 2: ptr */
+//!     union {
+//!         int64_t int_;
+//!         double float_;
+//!         const void *ptr;
+//!     } payload;
+//! };
+//! ```
+//!
+//! C code handing one to Rust has to keep to the same rule: a tag of 0, 1
+//! or 2, and the field it names.
+
+use std::{ffi::c_void, fmt};
+
+/// Which field of a [`Value`] is set.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Int = 0,
+    Float = 1,
+    Ptr = 2,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+union Payload {
+    int: i64,
+    float: f64,
+    ptr: *const c_void,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Value {
+    kind: Kind,
+    payload: Payload,
+}
+
+impl Value {
+    pub fn int(int: i64) -> Value {
+        Value {
+            kind: Kind::Int,
+            payload: Payload { int },
+        }
+    }
+
+    pub fn float(float: f64) -> Value {
+        Value {
+            kind: Kind::Float,
+            payload: Payload { float },
+        }
+    }
+
+    /// A value holding `ptr`. It's only ever handed back, never
+    /// dereferenced, so any pointer will do.
+    pub fn ptr(ptr: *const c_void) -> Value {
+        Value {
+            kind: Kind::Ptr,
+            payload: Payload { ptr },
+        }
+    }
+
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self.kind {
+            // SAFETY: the tag says `int` is the field that was written.
+            Kind::Int => Some(unsafe { self.payload.int }),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        match self.kind {
+            // SAFETY: the tag says `float` is the field that was written.
+            Kind::Float => Some(unsafe { self.payload.float }),
+            _ => None,
+        }
+    }
+
+    pub fn as_ptr(&self) -> Option<*const c_void> {
+        match self.kind {
+            // SAFETY: the tag says `ptr` is the field that was written.
+            Kind::Ptr => Some(unsafe { self.payload.ptr }),
+            _ => None,
+        }
+    }
+}
+
+impl From<i64> for Value {
+    fn from(int: i64) -> Value {
+        Value::int(int)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(float: f64) -> Value {
+        Value::float(float)
+    }
+}
+
+/// Values are equal if they're the same kind with equal contents, so as
+/// with `f64`, a NaN isn't equal to itself.
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match self.kind {
+            Kind::Int => self.as_int() == other.as_int(),
+            Kind::Float => self.as_float() == other.as_float(),
+            Kind::Ptr => self.as_ptr() == other.as_ptr(),
+        }
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            Kind::Int => f.debug_tuple("Int").field(&self.as_int().unwrap()).finish(),
+            Kind::Float => f
+                .debug_tuple("Float")
+                .field(&self.as_float().unwrap())
+                .finish(),
+            Kind::Ptr => f.debug_tuple("Ptr").field(&self.as_ptr().unwrap()).finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::{align_of, offset_of, size_of};
+
+    use super::*;
+
+    fn every_kind() -> [Value; 3] {
+        static TARGET: u8 = 0;
+        [
+            Value::int(-7),
+            Value::float(2.5),
+            Value::ptr(&TARGET as *const u8 as *const c_void),
+        ]
+    }
+
+    #[test]
+    fn each_accessor_reads_only_its_own_kind() {
+        for value in every_kind() {
+            let kind = value.kind();
+            assert_eq!(value.as_int().is_some(), kind == Kind::Int);
+            assert_eq!(value.as_float().is_some(), kind == Kind::Float);
+            assert_eq!(value.as_ptr().is_some(), kind == Kind::Ptr);
+        }
+    }
+
+    #[test]
+    fn gives_back_what_went_in() {
+        for int in [0, -1, i64::MIN, i64::MAX] {
+            assert_eq!(Value::from(int).as_int(), Some(int));
+        }
+        for float in [0.0, -0.0, 1.5, f64::INFINITY, f64::MIN_POSITIVE] {
+            let value = Value::from(float).as_float().unwrap();
+            assert_eq!(value.to_bits(), float.to_bits());
+        }
+        assert!(Value::float(f64::NAN).as_float().unwrap().is_nan());
+        assert_eq!(
+            Value::ptr(std::ptr::null()).as_ptr(),
+            Some(std::ptr::null())
+        );
+    }
+
+    #[test]
+    fn compares_kind_and_contents() {
+        let [int, float, ptr] = every_kind();
+        assert_eq!(int, Value::int(-7));
+        assert_ne!(int, Value::int(7));
+        // The same bits as a different kind aren't the same value.
+        assert_ne!(Value::int(0), Value::float(0.0));
+        assert_ne!(float, Value::float(f64::NAN));
+        assert_ne!(ptr, Value::ptr(std::ptr::null()));
+        assert_eq!(format!("{int:?} {float:?}"), "Int(-7) Float(2.5)");
+    }
+
+    #[test]
+    fn is_laid_out_like_the_c_struct() {
+        assert_eq!(size_of::<Kind>(), 4);
+        assert_eq!(offset_of!(Value, kind), 0);
+        assert_eq!(offset_of!(Value, payload), 8);
+        assert_eq!(size_of::<Value>(), 16);
+        assert_eq!(align_of::<Value>(), 8);
+    }
+
+    /// Passing it by value through the C ABI and back keeps it intact.
+    #[test]
+    fn crosses_an_extern_c_call() {
+        extern "C" fn echo(value: Value) -> Value {
+            value
+        }
+
+        for value in every_kind() {
+            assert_eq!(echo(value), value);
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! Listing 19-23's `Wrapper`, with the traits that make a newtype as easy
+//! to use as what it wraps.
+//!
+//! The listing wraps a `Vec<String>` to get around the orphan rule and
+//! implement `Display` on it, and then points out the catch: `Wrapper`
+//! doesn't have any of `Vec`'s methods. There are two ways out, and this
+//! shows both:
+//!
+//! - Implement `Deref` and `DerefMut`, and every `Vec` (and slice) method
+//!   works on a `Wrapper` through deref coercion.
+//! - Write the methods you want yourself, delegating to `self.0`. Here
+//!   that's [`push`](Wrapper::push) and [`len`](Wrapper::len), plus the
+//!   traits deref coercion can't stand in for: `Index`, `IntoIterator` for
+//!   `for` loops, and `FromIterator` for `collect`. Without `Deref`, these
+//!   would be all a `Wrapper` could do, which is the way to go when it
+//!   shouldn't be able to do everything a `Vec` can.
+
+use std::{
+    fmt,
+    ops::{Deref, DerefMut, Index, IndexMut},
+    slice::{self, SliceIndex},
+    vec,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Wrapper(pub Vec<String>);
+
+impl Wrapper {
+    pub fn new() -> Wrapper {
+        Wrapper(Vec::new())
+    }
+
+    pub fn push(&mut self, value: impl Into<String>) {
+        self.0.push(value.into());
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// `[a, b, c]`, as in the listing.
+impl fmt::Display for Wrapper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}]", self.0.join(", "))
+    }
+}
+
+impl Deref for Wrapper {
+    type Target = Vec<String>;
+
+    fn deref(&self) -> &Vec<String> {
+        &self.0
+    }
+}
+
+impl DerefMut for Wrapper {
+    fn deref_mut(&mut self) -> &mut Vec<String> {
+        &mut self.0
+    }
+}
+
+/// Anything a slice can be indexed with: `w[0]`, `w[1..]`.
+impl<I: SliceIndex<[String]>> Index<I> for Wrapper {
+    type Output = I::Output;
+
+    fn index(&self, index: I) -> &I::Output {
+        &self.0[index]
+    }
+}
+
+impl<I: SliceIndex<[String]>> IndexMut<I> for Wrapper {
+    fn index_mut(&mut self, index: I) -> &mut I::Output {
+        &mut self.0[index]
+    }
+}
+
+impl IntoIterator for Wrapper {
+    type Item = String;
+    type IntoIter = vec::IntoIter<String>;
+
+    fn into_iter(self) -> vec::IntoIter<String> {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Wrapper {
+    type Item = &'a String;
+    type IntoIter = slice::Iter<'a, String>;
+
+    fn into_iter(self) -> slice::Iter<'a, String> {
+        self.0.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Wrapper {
+    type Item = &'a mut String;
+    type IntoIter = slice::IterMut<'a, String>;
+
+    fn into_iter(self) -> slice::IterMut<'a, String> {
+        self.0.iter_mut()
+    }
+}
+
+impl<S: Into<String>> FromIterator<S> for Wrapper {
+    fn from_iter<T: IntoIterator<Item = S>>(iter: T) -> Wrapper {
+        Wrapper(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<S: Into<String>> Extend<S> for Wrapper {
+    fn extend<T: IntoIterator<Item = S>>(&mut self, iter: T) {
+        self.0.extend(iter.into_iter().map(Into::into));
+    }
+}
+
+impl From<Vec<String>> for Wrapper {
+    fn from(values: Vec<String>) -> Wrapper {
+        Wrapper(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hello_world() -> Wrapper {
+        ["hello", "world"].into_iter().collect()
+    }
+
+    #[test]
+    fn listing_19_23() {
+        let w = Wrapper(vec![String::from("hello"), String::from("world")]);
+        assert_eq!(w.to_string(), "[hello, world]");
+        assert_eq!(hello_world(), w);
+    }
+
+    #[test]
+    fn has_vec_and_slice_methods_through_deref() {
+        let mut w = hello_world();
+        w.insert(0, String::from("oh"));
+        w.sort();
+        assert!(w.contains(&String::from("oh")));
+        assert_eq!(w.first().map(String::as_str), Some("hello"));
+        assert_eq!(w.join(" "), "hello oh world");
+        w.retain(|s| s.len() > 2);
+        assert_eq!(w.to_string(), "[hello, world]");
+    }
+
+    #[test]
+    fn pushes_anything_stringlike() {
+        let mut w = Wrapper::new();
+        assert!(w.is_empty());
+        w.push("a");
+        w.push(String::from("b"));
+        w.push('c');
+        w.extend(["d", "e"]);
+        assert_eq!(w.len(), 5);
+        assert_eq!(w.to_string(), "[a, b, c, d, e]");
+    }
+
+    #[test]
+    fn indexes_like_a_slice() {
+        let mut w = hello_world();
+        assert_eq!(w[1], "world");
+        assert_eq!(w[..1], ["hello"]);
+        w[0].make_ascii_uppercase();
+        assert_eq!(w.to_string(), "[HELLO, world]");
+    }
+
+    #[test]
+    fn iterates_in_a_for_loop() {
+        let mut w = hello_world();
+        for s in &mut w {
+            s.push('!');
+        }
+        let mut lens = Vec::new();
+        for s in &w {
+            lens.push(s.len());
+        }
+        assert_eq!(lens, [6, 6]);
+
+        let owned: Vec<String> = w.into_iter().collect();
+        assert_eq!(owned, ["hello!", "world!"]);
+    }
+}
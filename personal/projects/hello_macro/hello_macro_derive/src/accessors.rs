@@ -0,0 +1,154 @@
+//! `#[derive(Getters)]` and `#[derive(Setters)]`.
+//!
+//! Each named field gets a getter, `field(&self) -> &T`, and a setter,
+//! `set_field(&mut self, value: T) -> &mut Self`. A field can ask for a
+//! getter that copies with `#[get(copy)]`, and a setter that takes
+//! anything `Into` its type with `#[set(into)]`.
+
+use quote::{format_ident, quote};
+use syn::{Attribute, Data, Field, Fields};
+
+pub(crate) fn impl_getters(ast: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let getters = named_fields(ast, "Getters")?
+        .iter()
+        .map(|field| {
+            let ident = field.ident.as_ref().unwrap();
+            let ty = &field.ty;
+            Ok(if flag(&field.attrs, "get", "copy")? {
+                quote! {
+                    pub fn #ident(&self) -> #ty {
+                        self.#ident
+                    }
+                }
+            } else {
+                quote! {
+                    pub fn #ident(&self) -> &#ty {
+                        &self.#ident
+                    }
+                }
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            #(#getters)*
+        }
+    })
+}
+
+pub(crate) fn impl_setters(ast: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let setters = named_fields(ast, "Setters")?
+        .iter()
+        .map(|field| {
+            let ident = field.ident.as_ref().unwrap();
+            let setter = format_ident!("set_{}", ident);
+            let ty = &field.ty;
+            Ok(if flag(&field.attrs, "set", "into")? {
+                quote! {
+                    pub fn #setter(&mut self, value: impl ::std::convert::Into<#ty>) -> &mut Self {
+                        self.#ident = value.into();
+                        self
+                    }
+                }
+            } else {
+                quote! {
+                    pub fn #setter(&mut self, value: #ty) -> &mut Self {
+                        self.#ident = value;
+                        self
+                    }
+                }
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            #(#setters)*
+        }
+    })
+}
+
+/// The fields of a struct with named fields, which are the only ones with
+/// names to give accessors.
+fn named_fields<'a>(ast: &'a syn::DeriveInput, derive: &str) -> syn::Result<Vec<&'a Field>> {
+    match &ast.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => Ok(fields.named.iter().collect()),
+            _ => Err(syn::Error::new_spanned(
+                &data.fields,
+                format!("{derive} needs a struct with named fields"),
+            )),
+        },
+        _ => Err(syn::Error::new_spanned(
+            &ast.ident,
+            format!("{derive} can only be derived for structs"),
+        )),
+    }
+}
+
+/// Whether `#[name(option)]` is on the field. `option` is the only thing
+/// `name` can say, so anything else is an error.
+fn flag(attrs: &[Attribute], name: &str, option: &str) -> syn::Result<bool> {
+    let mut found = false;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident(name)) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(option) {
+                found = true;
+                Ok(())
+            } else {
+                Err(meta.error(format_args!("expected `{option}`")))
+            }
+        })?;
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+    use crate::expand_test::{assert_expands, assert_fails_at, derive};
+
+    #[test]
+    fn copies_or_borrows() {
+        let result = derive(
+            impl_getters,
+            "struct Point { #[get(copy)] x: i32, label: String }",
+        );
+        assert_expands(
+            result,
+            quote! {
+                impl Point {
+                    pub fn x(&self) -> i32 {
+                        self.x
+                    }
+                    pub fn label(&self) -> &String {
+                        &self.label
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn points_at_what_is_wrong() {
+        assert_fails_at(
+            derive(impl_getters, "struct Point(i32, i32);"),
+            "Getters needs a struct with named fields",
+            "1:13 (i32, i32)",
+        );
+        assert_fails_at(
+            derive(impl_setters, "struct Point { #[set(onto)] x: i32 }"),
+            "expected `into`",
+            "1:22 onto",
+        );
+    }
+}
@@ -0,0 +1,158 @@
+//! `config_env!("VAR", default)`: the value of an environment variable at
+//! compile time, as a literal of the default's type.
+//!
+//! `env!` and `option_env!` only give strings. This parses the variable's
+//! value like the default literal, so `config_env!("PORT", 7878u16)` is a
+//! `u16` constant, and a value that doesn't parse is a compile error
+//! pointing at the default. Unsuffixed integers and floats are only
+//! checked for being numbers; the compiler checks that they fit wherever
+//! they're used.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    parse::{Parse, ParseStream},
+    Lit, LitBool, LitFloat, LitInt, LitStr, Token,
+};
+
+struct ConfigEnv {
+    var: LitStr,
+    default: Lit,
+}
+
+impl Parse for ConfigEnv {
+    fn parse(input: ParseStream) -> syn::Result<ConfigEnv> {
+        let var = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let default = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(ConfigEnv { var, default })
+    }
+}
+
+pub(crate) fn impl_config_env(input: TokenStream) -> syn::Result<TokenStream> {
+    let ConfigEnv { var, default } = syn::parse2(input)?;
+    let name = var.value();
+    if !matches!(
+        default,
+        Lit::Str(_) | Lit::Bool(_) | Lit::Int(_) | Lit::Float(_)
+    ) {
+        return Err(syn::Error::new(
+            default.span(),
+            "the default has to be a string, integer, float or bool literal",
+        ));
+    }
+
+    let value = match std::env::var(&name) {
+        Ok(value) => parse_like(&default, &value).map_err(|expected| {
+            syn::Error::new(
+                default.span(),
+                format!("`{name}` is set to {value:?}, which isn't {expected}"),
+            )
+        })?,
+        Err(std::env::VarError::NotPresent) => default,
+        Err(std::env::VarError::NotUnicode(_)) => {
+            return Err(syn::Error::new(
+                var.span(),
+                format!("`{name}` isn't valid UTF-8"),
+            ))
+        }
+    };
+
+    // Cargo rebuilds a crate when a variable read with `option_env!`
+    // changes, but doesn't know what a proc macro reads, so ask for it.
+    Ok(quote! {
+        {
+            const _: ::std::option::Option<&str> = ::std::option_env!(#var);
+            #value
+        }
+    })
+}
+
+/// `value` as a literal of the same kind as `default`, or a description
+/// of what it should have been.
+fn parse_like(default: &Lit, value: &str) -> Result<Lit, String> {
+    let span = default.span();
+    let value = value.trim();
+    match default {
+        Lit::Str(_) => Ok(Lit::Str(LitStr::new(value, span))),
+        Lit::Bool(_) => match value {
+            "true" => Ok(Lit::Bool(LitBool::new(true, span))),
+            "false" => Ok(Lit::Bool(LitBool::new(false, span))),
+            _ => Err(String::from("`true` or `false`")),
+        },
+        Lit::Int(lit) => {
+            // Written back out from the parsed number, so `+5` or `007`
+            // become literals the compiler takes.
+            let n = value
+                .parse::<i128>()
+                .map(|n| n.to_string())
+                .or_else(|_| value.parse::<u128>().map(|n| n.to_string()));
+            let suffix = lit.suffix();
+            let fits = |n: &str| match suffix {
+                "" => true,
+                "u8" => n.parse::<u8>().is_ok(),
+                "u16" => n.parse::<u16>().is_ok(),
+                "u32" => n.parse::<u32>().is_ok(),
+                "u64" => n.parse::<u64>().is_ok(),
+                "u128" => n.parse::<u128>().is_ok(),
+                "usize" => n.parse::<usize>().is_ok(),
+                "i8" => n.parse::<i8>().is_ok(),
+                "i16" => n.parse::<i16>().is_ok(),
+                "i32" => n.parse::<i32>().is_ok(),
+                "i64" => n.parse::<i64>().is_ok(),
+                "i128" => n.parse::<i128>().is_ok(),
+                "isize" => n.parse::<isize>().is_ok(),
+                _ => false,
+            };
+            match n {
+                Ok(n) if fits(&n) => Ok(Lit::Int(LitInt::new(&format!("{n}{suffix}"), span))),
+                _ if suffix.is_empty() => Err(String::from("an integer")),
+                _ => Err(format!("a valid `{suffix}`")),
+            }
+        }
+        Lit::Float(lit) => match value.parse::<f64>() {
+            // `Debug` always writes a `.`, so the literal stays a float.
+            Ok(n) if n.is_finite() => Ok(Lit::Float(LitFloat::new(
+                &format!("{n:?}{}", lit.suffix()),
+                span,
+            ))),
+            _ => Err(String::from("a finite number")),
+        },
+        _ => unreachable!("checked in impl_config_env"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+    use crate::expand_test::{assert_expands, assert_fails_at, call};
+
+    #[test]
+    fn falls_back_to_the_default() {
+        assert_expands(
+            call(
+                impl_config_env,
+                r#""HELLO_MACRO_EXPAND_TEST_UNSET", 7878u16"#,
+            ),
+            quote! {
+                {
+                    const _: ::std::option::Option<&str> =
+                        ::std::option_env!("HELLO_MACRO_EXPAND_TEST_UNSET");
+                    7878u16
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn points_at_the_default() {
+        assert_fails_at(
+            call(impl_config_env, r#""HELLO_MACRO_EXPAND_TEST_UNSET", 'x'"#),
+            "the default has to be a string, integer, float or bool literal",
+            "1:34 'x'",
+        );
+    }
+}
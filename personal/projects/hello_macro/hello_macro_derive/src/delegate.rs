@@ -0,0 +1,307 @@
+//! `#[derive(Delegate)]`, for the newtype pattern's one drawback: a
+//! `Wrapper(Vec<String>)` has none of the `Vec`'s methods.
+//!
+//! `#[delegate(...)]` lists what to forward to the wrapped value:
+//!
+//! - Traits: `Deref`, `DerefMut`, `AsRef`, `AsMut`, `IntoIterator` (for the
+//!   wrapper and references to it), `Display` and `Debug`.
+//! - Collection methods by name: `len`, `is_empty`, `clear`, `push`, `pop`,
+//!   `contains`, `iter` and `iter_mut`. Their item type is the wrapped
+//!   value's `IntoIterator::Item`.
+//! - Any other method by its signature, like
+//!   `fn truncate(&mut self, len: usize)`.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_quote,
+    punctuated::Punctuated,
+    Data, Fields, FnArg, Ident, Pat, Signature, Token, Type,
+};
+
+pub(crate) fn impl_delegate(ast: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let inner = inner_type(ast)?;
+
+    let mut trait_impls = Vec::new();
+    let mut methods = Vec::new();
+    for attr in ast
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("delegate"))
+    {
+        let items = attr.parse_args_with(Punctuated::<Item, Token![,]>::parse_terminated)?;
+        for item in items {
+            match item {
+                Item::Name(ident) if starts_uppercase(&ident) => {
+                    trait_impls.push(delegate_trait(ast, inner, &ident)?)
+                }
+                Item::Name(ident) => {
+                    let signature = known_method(inner, &ident).ok_or_else(|| {
+                        syn::Error::new(
+                            ident.span(),
+                            format!(
+                                "no known signature for `{ident}`; \
+                                 write out its signature instead, like `fn {ident}(&self) -> ...`"
+                            ),
+                        )
+                    })?;
+                    methods.push(forward(&signature)?);
+                }
+                Item::Method(signature) => methods.push(forward(&signature)?),
+            }
+        }
+    }
+
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            #(#methods)*
+        }
+
+        #(#trait_impls)*
+    })
+}
+
+/// One thing in `#[delegate(...)]`: a trait or method name, or a whole
+/// method signature.
+enum Item {
+    Name(Ident),
+    Method(Box<Signature>),
+}
+
+impl Parse for Item {
+    fn parse(input: ParseStream) -> syn::Result<Item> {
+        if input.peek(Token![fn]) {
+            input
+                .parse()
+                .map(|signature| Item::Method(Box::new(signature)))
+        } else {
+            input.parse().map(Item::Name)
+        }
+    }
+}
+
+fn starts_uppercase(ident: &Ident) -> bool {
+    ident.to_string().starts_with(char::is_uppercase)
+}
+
+/// The type of the one field of a tuple struct.
+fn inner_type(ast: &syn::DeriveInput) -> syn::Result<&Type> {
+    if let Data::Struct(data) = &ast.data {
+        if let Fields::Unnamed(fields) = &data.fields {
+            if fields.unnamed.len() == 1 {
+                return Ok(&fields.unnamed[0].ty);
+            }
+        }
+    }
+    Err(syn::Error::new_spanned(
+        &ast.ident,
+        "Delegate needs a tuple struct with one field, like `struct Wrapper(Vec<String>);`",
+    ))
+}
+
+fn known_method(inner: &Type, name: &Ident) -> Option<Signature> {
+    let item = quote!(<#inner as ::std::iter::IntoIterator>::Item);
+    Some(match name.to_string().as_str() {
+        "len" => parse_quote!(fn len(&self) -> usize),
+        "is_empty" => parse_quote!(fn is_empty(&self) -> bool),
+        "clear" => parse_quote!(fn clear(&mut self)),
+        "push" => parse_quote!(fn push(&mut self, value: #item)),
+        "pop" => parse_quote!(fn pop(&mut self) -> ::std::option::Option<#item>),
+        "contains" => parse_quote! {
+            fn contains(&self, value: &#item) -> bool
+            where
+                #item: ::std::cmp::PartialEq
+        },
+        "iter" => parse_quote! {
+            fn iter(&self) -> <&'_ #inner as ::std::iter::IntoIterator>::IntoIter
+        },
+        "iter_mut" => parse_quote! {
+            fn iter_mut(&mut self) -> <&'_ mut #inner as ::std::iter::IntoIterator>::IntoIter
+        },
+        _ => return None,
+    })
+}
+
+/// A method that calls the one with the same signature on the wrapped
+/// value.
+fn forward(signature: &Signature) -> syn::Result<TokenStream> {
+    let name = &signature.ident;
+    let mut args = Vec::new();
+    for input in &signature.inputs {
+        match input {
+            FnArg::Receiver(_) => {}
+            FnArg::Typed(arg) => match &*arg.pat {
+                Pat::Ident(pat) => args.push(&pat.ident),
+                pat => {
+                    return Err(syn::Error::new_spanned(
+                        pat,
+                        "give each argument a plain name to pass it on by",
+                    ))
+                }
+            },
+        }
+    }
+    if signature.receiver().is_none() {
+        return Err(syn::Error::new_spanned(
+            signature,
+            "only methods that take `self` can be delegated",
+        ));
+    }
+    Ok(quote! {
+        pub #signature {
+            self.0.#name(#(#args),*)
+        }
+    })
+}
+
+fn delegate_trait(ast: &syn::DeriveInput, inner: &Type, name: &Ident) -> syn::Result<TokenStream> {
+    let ty = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    Ok(match name.to_string().as_str() {
+        "Deref" => quote! {
+            impl #impl_generics ::std::ops::Deref for #ty #ty_generics #where_clause {
+                type Target = #inner;
+
+                fn deref(&self) -> &#inner {
+                    &self.0
+                }
+            }
+        },
+        "DerefMut" => quote! {
+            impl #impl_generics ::std::ops::DerefMut for #ty #ty_generics #where_clause {
+                fn deref_mut(&mut self) -> &mut #inner {
+                    &mut self.0
+                }
+            }
+        },
+        "AsRef" => quote! {
+            impl #impl_generics ::std::convert::AsRef<#inner> for #ty #ty_generics #where_clause {
+                fn as_ref(&self) -> &#inner {
+                    &self.0
+                }
+            }
+        },
+        "AsMut" => quote! {
+            impl #impl_generics ::std::convert::AsMut<#inner> for #ty #ty_generics #where_clause {
+                fn as_mut(&mut self) -> &mut #inner {
+                    &mut self.0
+                }
+            }
+        },
+        "Display" | "Debug" => quote! {
+            impl #impl_generics ::std::fmt::#name for #ty #ty_generics #where_clause {
+                fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                    ::std::fmt::#name::fmt(&self.0, f)
+                }
+            }
+        },
+        "IntoIterator" => {
+            // The references' impls need a lifetime of their own, and to
+            // only exist when the wrapped value's references iterate too.
+            let mut generics = ast.generics.clone();
+            generics.params.insert(0, parse_quote!('__delegate));
+            let (ref_impl_generics, _, _) = generics.split_for_impl();
+            let mut by_ref = where_clause.cloned().unwrap_or_else(|| parse_quote!(where));
+            by_ref
+                .predicates
+                .push(parse_quote!(&'__delegate #inner: ::std::iter::IntoIterator));
+            let mut by_mut = where_clause.cloned().unwrap_or_else(|| parse_quote!(where));
+            by_mut
+                .predicates
+                .push(parse_quote!(&'__delegate mut #inner: ::std::iter::IntoIterator));
+
+            quote! {
+                impl #impl_generics ::std::iter::IntoIterator for #ty #ty_generics #where_clause {
+                    type Item = <#inner as ::std::iter::IntoIterator>::Item;
+                    type IntoIter = <#inner as ::std::iter::IntoIterator>::IntoIter;
+
+                    fn into_iter(self) -> Self::IntoIter {
+                        self.0.into_iter()
+                    }
+                }
+
+                impl #ref_impl_generics ::std::iter::IntoIterator
+                    for &'__delegate #ty #ty_generics #by_ref
+                {
+                    type Item = <&'__delegate #inner as ::std::iter::IntoIterator>::Item;
+                    type IntoIter = <&'__delegate #inner as ::std::iter::IntoIterator>::IntoIter;
+
+                    fn into_iter(self) -> Self::IntoIter {
+                        (&self.0).into_iter()
+                    }
+                }
+
+                impl #ref_impl_generics ::std::iter::IntoIterator
+                    for &'__delegate mut #ty #ty_generics #by_mut
+                {
+                    type Item = <&'__delegate mut #inner as ::std::iter::IntoIterator>::Item;
+                    type IntoIter = <&'__delegate mut #inner as ::std::iter::IntoIterator>::IntoIter;
+
+                    fn into_iter(self) -> Self::IntoIter {
+                        (&mut self.0).into_iter()
+                    }
+                }
+            }
+        }
+        _ => {
+            return Err(syn::Error::new(
+                name.span(),
+                format!(
+                    "can't delegate `{name}`; expected one of Deref, DerefMut, \
+                     AsRef, AsMut, IntoIterator, Display or Debug"
+                ),
+            ))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+    use crate::expand_test::{assert_expands, assert_fails_at, derive};
+
+    #[test]
+    fn forwards_written_out_methods() {
+        let result = derive(
+            impl_delegate,
+            "#[delegate(fn truncate(&mut self, len: usize))] struct Names(Vec<String>);",
+        );
+        assert_expands(
+            result,
+            quote! {
+                impl Names {
+                    pub fn truncate(&mut self, len: usize) {
+                        self.0.truncate(len)
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn points_at_what_it_cannot_forward() {
+        assert_fails_at(
+            derive(
+                impl_delegate,
+                "#[delegate(len, truncate)] struct Names(Vec<String>);",
+            ),
+            "no known signature for `truncate`; \
+             write out its signature instead, like `fn truncate(&self) -> ...`",
+            "1:17 truncate",
+        );
+        assert_fails_at(
+            derive(
+                impl_delegate,
+                "#[delegate(fn swap(&mut self, (a, b): (usize, usize)))] struct Names(Vec<String>);",
+            ),
+            "give each argument a plain name to pass it on by",
+            "1:31 (a, b)",
+        );
+    }
+}
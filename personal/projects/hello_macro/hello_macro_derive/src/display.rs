@@ -0,0 +1,210 @@
+//! `#[derive(Display)]`, formatting a struct with a template:
+//! `#[display("({x}, {y})")]`.
+//!
+//! The template is a format string whose arguments are the struct's fields,
+//! `{x}` for a named one and `{0}` for a tuple struct's. Everything
+//! `format!` allows after the name still works, like `{x:.2}`, and so does
+//! escaping braces as `{{` and `}}`. Each placeholder is checked against the
+//! fields here, so a typo is an error at the attribute rather than inside
+//! the generated code.
+
+use quote::{format_ident, quote};
+use syn::{Attribute, Data, Fields, LitStr};
+
+pub(crate) fn impl_display(ast: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let fields = match &ast.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "Display can only be derived for structs",
+            ))
+        }
+    };
+    let template = template(&ast.attrs, name)?;
+    let (format, used) = rewrite(&template, fields)?;
+
+    // Each field the template uses becomes a named argument: `x = self.x`,
+    // or `_0 = self.0` for a tuple field, since `{0}` has been rewritten to
+    // `{_0}`.
+    let args = used.iter().map(|field| {
+        let arg = match field {
+            FieldName::Named(name) => format_ident!("{}", name),
+            FieldName::Index(index) => format_ident!("_{}", index),
+        };
+        let member = match field {
+            FieldName::Named(name) => {
+                let ident = format_ident!("{}", name);
+                quote!(#ident)
+            }
+            FieldName::Index(index) => {
+                let index = syn::Index::from(*index);
+                quote!(#index)
+            }
+        };
+        quote!(#arg = self.#member)
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::std::fmt::Display for #name #ty_generics #where_clause {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                ::std::write!(f, #format, #(#args),*)
+            }
+        }
+    })
+}
+
+/// A field a template uses.
+#[derive(PartialEq)]
+pub(crate) enum FieldName {
+    Named(String),
+    Index(usize),
+}
+
+/// The string in the one `#[display("...")]` attribute.
+fn template(attrs: &[Attribute], name: &syn::Ident) -> syn::Result<LitStr> {
+    let mut found = attrs.iter().filter(|attr| attr.path().is_ident("display"));
+    let attr = found.next().ok_or_else(|| {
+        syn::Error::new_spanned(
+            name,
+            "Display needs a template, like #[display(\"({x}, {y})\")]",
+        )
+    })?;
+    if let Some(extra) = found.next() {
+        return Err(syn::Error::new_spanned(
+            extra,
+            "only one #[display] is allowed",
+        ));
+    }
+    attr.parse_args()
+}
+
+/// The template with tuple fields renamed so they can be named arguments,
+/// and the fields it uses, each once.
+pub(crate) fn rewrite(template: &LitStr, fields: &Fields) -> syn::Result<(LitStr, Vec<FieldName>)> {
+    let error = |message: String| syn::Error::new(template.span(), message);
+    let unclosed = || error("unclosed `{` in template; write `{{` for a brace".into());
+    let source = template.value();
+    let mut format = String::with_capacity(source.len());
+    let mut used = Vec::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        format.push(c);
+        match c {
+            '{' if chars.peek() == Some(&'{') => format.push(chars.next().unwrap()),
+            '}' if chars.peek() == Some(&'}') => format.push(chars.next().unwrap()),
+            '}' => {
+                return Err(error(
+                    "unmatched `}` in template; write `}}` for a brace".into(),
+                ))
+            }
+            '{' => {
+                let mut arg = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c == ':' || c == '}' {
+                        break;
+                    }
+                    arg.push(c);
+                    chars.next();
+                }
+                if chars.peek().is_none() {
+                    return Err(unclosed());
+                }
+                let arg = arg.trim();
+                let field = field(arg, fields).ok_or_else(|| {
+                    if arg.is_empty() {
+                        error("name the field in each `{}`, like `{x}` or `{0}`".into())
+                    } else {
+                        error(format!("no field `{arg}` to format"))
+                    }
+                })?;
+                match &field {
+                    FieldName::Named(name) => format.push_str(name),
+                    FieldName::Index(index) => format.push_str(&format!("_{index}")),
+                }
+                if !used.contains(&field) {
+                    used.push(field);
+                }
+                // The rest of the placeholder, format spec and all.
+                for c in chars.by_ref() {
+                    format.push(c);
+                    if c == '}' {
+                        break;
+                    }
+                }
+                if !format.ends_with('}') {
+                    return Err(unclosed());
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok((LitStr::new(&format, template.span()), used))
+}
+
+/// The field `arg` names, if there is one.
+fn field(arg: &str, fields: &Fields) -> Option<FieldName> {
+    match fields {
+        Fields::Named(_) => fields
+            .iter()
+            .filter_map(|field| field.ident.as_ref())
+            .find(|ident| *ident == arg)
+            .map(|ident| FieldName::Named(ident.to_string())),
+        Fields::Unnamed(_) => arg
+            .parse()
+            .ok()
+            .filter(|&index| index < fields.len())
+            .map(FieldName::Index),
+        Fields::Unit => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+    use crate::expand_test::{assert_expands, assert_fails_at, derive};
+
+    #[test]
+    fn passes_tuple_fields_by_name() {
+        let result = derive(
+            impl_display,
+            r#"#[display("{0}/{1:>3}")] struct Ratio(u8, u8);"#,
+        );
+        assert_expands(
+            result,
+            quote! {
+                impl ::std::fmt::Display for Ratio {
+                    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                        ::std::write!(f, "{_0}/{_1:>3}", _0 = self.0, _1 = self.1)
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn points_at_the_template() {
+        assert_fails_at(
+            derive(
+                impl_display,
+                r#"#[display("({x}, {z})")] struct Point { x: i32, y: i32 }"#,
+            ),
+            "no field `z` to format",
+            r#"1:11 "({x}, {z})""#,
+        );
+        assert_fails_at(
+            derive(
+                impl_display,
+                r#"#[display("{x}")] #[display("{y}")] struct Point { x: i32, y: i32 }"#,
+            ),
+            "only one #[display] is allowed",
+            r#"1:19 #[display("{y}")]"#,
+        );
+    }
+}
@@ -0,0 +1,387 @@
+//! `#[derive(ErrorKind)]`: `Display` and `std::error::Error` for an error
+//! type, the boilerplate every error enum in the book writes by hand.
+//!
+//! Each variant (or the struct) gives its message with `#[error("...")]`,
+//! a template like `#[derive(Display)]`'s that can use the variant's
+//! fields. A field marked `#[source]` is what `source()` returns, and one
+//! marked `#[from]` is the source too, and also gets a `From` impl, so `?`
+//! can convert it. A `#[from]` field has to be the variant's only one,
+//! since `From` has nothing to fill the others with.
+//!
+//! ```text
+//! #[derive(Debug, ErrorKind)]
+//! enum ConfigError {
+//!     #[error("couldn't read the config: {0}")]
+//!     Read(#[from] io::Error),
+//!     #[error("line {line}: {message}")]
+//!     Syntax { line: usize, message: String },
+//! }
+//! ```
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Attribute, Data, Field, Fields, Ident, LitStr};
+
+use crate::display::{rewrite, FieldName};
+
+pub(crate) fn impl_error_kind(ast: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let cases = match &ast.data {
+        Data::Struct(data) => vec![Case::new(quote!(Self), name, &ast.attrs, &data.fields)?],
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|variant| {
+                let ident = &variant.ident;
+                Case::new(quote!(Self::#ident), ident, &variant.attrs, &variant.fields)
+            })
+            .collect::<syn::Result<_>>()?,
+        Data::Union(data) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "ErrorKind can't be derived for unions",
+            ))
+        }
+    };
+
+    let messages = cases.iter().map(|case| {
+        let path = &case.path;
+        let format = &case.format;
+        // Only the fields the message uses are bound, by reference, under
+        // the names the rewritten template gives them. That's why the
+        // formatter is `__formatter`: a field named `f` would shadow it.
+        let bindings = case.used.iter().map(|field| {
+            let (member, binding) = match field {
+                FieldName::Named(name) => {
+                    let ident = format_ident!("{}", name);
+                    (quote!(#ident), ident)
+                }
+                FieldName::Index(index) => {
+                    let member = syn::Index::from(*index);
+                    (quote!(#member), format_ident!("_{}", index))
+                }
+            };
+            quote!(#member: ref #binding)
+        });
+        quote! {
+            #path { #(#bindings,)* .. } => ::std::write!(__formatter, #format),
+        }
+    });
+
+    // `source()` is only written out if something has one; otherwise the
+    // default, which returns `None`, is right.
+    let source = cases.iter().any(|case| case.source.is_some()).then(|| {
+        let arms = cases.iter().map(|case| {
+            let path = &case.path;
+            match &case.source {
+                Some(source) => {
+                    let member = &source.member;
+                    // `Box<dyn Error + Send + Sync>` isn't an `Error` itself,
+                    // so a boxed source is what's in the box.
+                    let value = match is_box(&source.ty) {
+                        true => quote!(&**source),
+                        false => quote!(source),
+                    };
+                    quote! {
+                        #path { #member: ref source, .. } => ::std::option::Option::Some(
+                            #value as &(dyn ::std::error::Error + 'static),
+                        ),
+                    }
+                }
+                None => quote!(#path { .. } => ::std::option::Option::None,),
+            }
+        });
+        quote! {
+            fn source(&self) -> ::std::option::Option<&(dyn ::std::error::Error + 'static)> {
+                match *self {
+                    #(#arms)*
+                }
+            }
+        }
+    });
+
+    let froms = cases.iter().filter_map(|case| {
+        let source = case.source.as_ref().filter(|source| source.from)?;
+        let path = &case.path;
+        let member = &source.member;
+        let ty = &source.ty;
+        Some(quote! {
+            impl #impl_generics ::std::convert::From<#ty> for #name #ty_generics #where_clause {
+                fn from(source: #ty) -> Self {
+                    #path { #member: source }
+                }
+            }
+        })
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::std::fmt::Display for #name #ty_generics #where_clause {
+            fn fmt(&self, __formatter: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                match *self {
+                    #(#messages)*
+                }
+            }
+        }
+
+        impl #impl_generics ::std::error::Error for #name #ty_generics #where_clause {
+            #source
+        }
+
+        #(#froms)*
+    })
+}
+
+/// A variant, or the struct: how to match it, its message, and its source.
+struct Case {
+    path: TokenStream,
+    format: LitStr,
+    used: Vec<FieldName>,
+    source: Option<Source>,
+}
+
+struct Source {
+    member: TokenStream,
+    ty: syn::Type,
+    /// Whether it's `#[from]` rather than just `#[source]`.
+    from: bool,
+}
+
+impl Case {
+    fn new(
+        path: TokenStream,
+        ident: &Ident,
+        attrs: &[Attribute],
+        fields: &Fields,
+    ) -> syn::Result<Case> {
+        let message = message(attrs, ident)?;
+        let (format, used) = rewrite(&message, fields)?;
+
+        let mut source = None;
+        for (index, field) in fields.iter().enumerate() {
+            let Some((attr, from)) = source_attr(field)? else {
+                continue;
+            };
+            if source.is_some() {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "only one field can be the source",
+                ));
+            }
+            if from && fields.len() > 1 {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "#[from] has to be on the only field, since `From` can't fill in the others",
+                ));
+            }
+            let member = match &field.ident {
+                Some(ident) => quote!(#ident),
+                None => {
+                    let index = syn::Index::from(index);
+                    quote!(#index)
+                }
+            };
+            source = Some(Source {
+                member,
+                ty: field.ty.clone(),
+                from,
+            });
+        }
+
+        Ok(Case {
+            path,
+            format,
+            used,
+            source,
+        })
+    }
+}
+
+/// The string in the one `#[error("...")]` attribute.
+fn message(attrs: &[Attribute], ident: &Ident) -> syn::Result<LitStr> {
+    let mut found = attrs.iter().filter(|attr| attr.path().is_ident("error"));
+    let attr = found.next().ok_or_else(|| {
+        syn::Error::new_spanned(
+            ident,
+            "ErrorKind needs a message for this, like #[error(\"it went wrong: {0}\")]",
+        )
+    })?;
+    if let Some(extra) = found.next() {
+        return Err(syn::Error::new_spanned(
+            extra,
+            "only one #[error] is allowed",
+        ));
+    }
+    attr.parse_args()
+}
+
+/// Whether `ty` is spelled as a `Box`. An alias for one isn't caught, and
+/// its source has to be unboxed by hand.
+fn is_box(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(path) if path.qself.is_none() => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Box"),
+        _ => false,
+    }
+}
+
+/// The field's `#[source]` or `#[from]`, and whether it's `#[from]`.
+fn source_attr(field: &Field) -> syn::Result<Option<(&Attribute, bool)>> {
+    let mut found = None;
+    for attr in &field.attrs {
+        let from = attr.path().is_ident("from");
+        if !from && !attr.path().is_ident("source") {
+            continue;
+        }
+        attr.meta.require_path_only()?;
+        if found.is_some() {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "a field is either #[source] or #[from], not both",
+            ));
+        }
+        found = Some((attr, from));
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+    use crate::expand_test::{assert_expands, assert_fails_at, derive};
+
+    #[test]
+    fn matches_each_variant() {
+        let result = derive(
+            impl_error_kind,
+            r#"
+                enum LoadError {
+                    #[error("couldn't read {path}: {source}")]
+                    Read { path: String, #[source] source: Io },
+                    #[error("bad number: {0}")]
+                    Number(#[from] Parse),
+                    #[error("empty")]
+                    Empty,
+                }
+            "#,
+        );
+        assert_expands(
+            result,
+            quote! {
+                impl ::std::fmt::Display for LoadError {
+                    fn fmt(&self, __formatter: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                        match *self {
+                            Self::Read { path: ref path, source: ref source, .. } =>
+                                ::std::write!(__formatter, "couldn't read {path}: {source}"),
+                            Self::Number { 0: ref _0, .. } => ::std::write!(__formatter, "bad number: {_0}"),
+                            Self::Empty { .. } => ::std::write!(__formatter, "empty"),
+                        }
+                    }
+                }
+
+                impl ::std::error::Error for LoadError {
+                    fn source(&self) -> ::std::option::Option<&(dyn ::std::error::Error + 'static)> {
+                        match *self {
+                            Self::Read { source: ref source, .. } => ::std::option::Option::Some(
+                                source as &(dyn ::std::error::Error + 'static),
+                            ),
+                            Self::Number { 0: ref source, .. } => ::std::option::Option::Some(
+                                source as &(dyn ::std::error::Error + 'static),
+                            ),
+                            Self::Empty { .. } => ::std::option::Option::None,
+                        }
+                    }
+                }
+
+                impl ::std::convert::From<Parse> for LoadError {
+                    fn from(source: Parse) -> Self {
+                        Self::Number { 0: source }
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn leaves_out_source_when_nothing_has_one() {
+        let result = derive(
+            impl_error_kind,
+            r#"#[error("{0} is too big")] struct TooBig(usize);"#,
+        );
+        assert_expands(
+            result,
+            quote! {
+                impl ::std::fmt::Display for TooBig {
+                    fn fmt(&self, __formatter: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                        match *self {
+                            Self { 0: ref _0, .. } => ::std::write!(__formatter, "{_0} is too big"),
+                        }
+                    }
+                }
+
+                impl ::std::error::Error for TooBig {}
+            },
+        );
+    }
+
+    #[test]
+    fn unboxes_a_boxed_source() {
+        let result = derive(
+            impl_error_kind,
+            r#"#[error("failed: {f}")] struct Failed { f: String, #[source] cause: Box<dyn Error + Send + Sync> }"#,
+        );
+        assert_expands(
+            result,
+            quote! {
+                impl ::std::fmt::Display for Failed {
+                    fn fmt(&self, __formatter: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                        match *self {
+                            Self { f: ref f, .. } => ::std::write!(__formatter, "failed: {f}"),
+                        }
+                    }
+                }
+
+                impl ::std::error::Error for Failed {
+                    fn source(&self) -> ::std::option::Option<&(dyn ::std::error::Error + 'static)> {
+                        match *self {
+                            Self { cause: ref source, .. } => ::std::option::Option::Some(
+                                &**source as &(dyn ::std::error::Error + 'static),
+                            ),
+                        }
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn points_at_mistakes() {
+        assert_fails_at(
+            derive(impl_error_kind, r#"enum E { #[error("a")] A, B }"#),
+            "ErrorKind needs a message for this, like #[error(\"it went wrong: {0}\")]",
+            "1:27 B",
+        );
+        assert_fails_at(
+            derive(
+                impl_error_kind,
+                r#"enum E { #[error("a")] A(#[from] Io, String) }"#,
+            ),
+            "#[from] has to be on the only field, since `From` can't fill in the others",
+            "1:26 #[from]",
+        );
+        assert_fails_at(
+            derive(
+                impl_error_kind,
+                r#"enum E { #[error("a")] A { #[source] a: Io, #[source] b: Io } }"#,
+            ),
+            "only one field can be the source",
+            "1:45 #[source]",
+        );
+    }
+}
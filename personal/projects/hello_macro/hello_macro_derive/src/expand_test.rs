@@ -0,0 +1,75 @@
+//! Helpers for testing the `impl_*` functions on source text, without a
+//! crate to expand them in.
+//!
+//! trybuild in `hello_macro` checks what a user of the macros sees. These
+//! check the same two things one level down, in a plain unit test:
+//! [`assert_expands`] that the code a macro builds is the code expected,
+//! and [`assert_fails_at`] that a misuse gets the right message, pointing
+//! at the right tokens. Spans only know where they are in tests, through
+//! `proc-macro2`'s `span-locations` feature.
+
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+
+/// Run a derive's `impl_*` function on the item in `source`.
+pub(crate) fn derive(
+    impl_derive: fn(&syn::DeriveInput) -> syn::Result<TokenStream>,
+    source: &str,
+) -> syn::Result<TokenStream> {
+    impl_derive(&syn::parse_str(source).expect("not a struct, enum or union"))
+}
+
+/// Run a function-like macro's `impl_*` function on the tokens in
+/// `source`.
+pub(crate) fn call(
+    impl_macro: fn(TokenStream) -> syn::Result<TokenStream>,
+    source: &str,
+) -> syn::Result<TokenStream> {
+    impl_macro(source.parse().expect("not valid tokens"))
+}
+
+/// Assert that a macro expanded to `expected`.
+///
+/// Both are parsed and printed again before they're compared, since the
+/// same code can be different tokens: `quote!` lexes the `0.` in
+/// `self.0.#method` as a float, but `self.0.len` as `0`, `.`, `len`.
+#[track_caller]
+pub(crate) fn assert_expands(result: syn::Result<TokenStream>, expected: TokenStream) {
+    match result {
+        Ok(tokens) => assert_eq!(normalize(tokens), normalize(expected)),
+        Err(err) => panic!("expected an expansion, got the error: {err}"),
+    }
+}
+
+#[track_caller]
+fn normalize(tokens: TokenStream) -> String {
+    let tokens = match syn::parse2::<syn::File>(tokens.clone()) {
+        Ok(file) => file.into_token_stream(),
+        // Not items: a function-like macro's expression.
+        Err(_) => syn::parse2::<syn::Expr>(tokens)
+            .expect("expanded to neither items nor an expression")
+            .into_token_stream(),
+    };
+    tokens.to_string()
+}
+
+/// Assert that a macro failed with `message`, and that its span starts at
+/// `at`: the line and column as rustc gives them, then the text it covers,
+/// like `"2:9 name"`.
+#[track_caller]
+pub(crate) fn assert_fails_at(result: syn::Result<TokenStream>, message: &str, at: &str) {
+    let err = match result {
+        Ok(tokens) => panic!("expected an error, got the expansion: {tokens}"),
+        Err(err) => err,
+    };
+    assert_eq!(err.to_string(), message);
+
+    let span = err.span();
+    let start = span.start();
+    let text = span.source_text().unwrap_or_default();
+    assert_eq!(
+        format!("{}:{} {text}", start.line, start.column + 1),
+        at,
+        "the error points at the wrong tokens"
+    );
+}
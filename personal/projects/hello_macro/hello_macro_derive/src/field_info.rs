@@ -0,0 +1,96 @@
+//! `#[derive(FieldInfo)]`.
+
+use quote::quote;
+use syn::{Data, Fields};
+
+pub(crate) fn impl_field_info(ast: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let fields = match &ast.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "FieldInfo can only be derived for structs",
+            ))
+        }
+    };
+    // Tuple struct fields are named by their index, as they're accessed.
+    let names: Vec<String> = match fields {
+        Fields::Named(_) => fields
+            .iter()
+            .map(|field| field.ident.as_ref().unwrap().to_string())
+            .collect(),
+        Fields::Unnamed(_) | Fields::Unit => {
+            (0..fields.len()).map(|index| index.to_string()).collect()
+        }
+    };
+    let types = fields.iter().map(|field| type_name(&field.ty));
+
+    Ok(quote! {
+        impl #impl_generics ::hello_macro::FieldInfo for #name #ty_generics #where_clause {
+            fn fields() -> &'static [(&'static str, &'static str)] {
+                &[#((#names, #types)),*]
+            }
+        }
+    })
+}
+
+/// The type as it would be written: `stringify!` would give back the
+/// tokens spaced out as `quote!` has them, like `Vec < String >`.
+fn type_name(ty: &syn::Type) -> String {
+    let mut name = quote!(#ty).to_string();
+    for (spaced, tight) in [
+        (" <", "<"),
+        ("< ", "<"),
+        (" >", ">"),
+        (" ,", ","),
+        (" ;", ";"),
+        (" ::", "::"),
+        (":: ", "::"),
+        ("& ", "&"),
+        ("* ", "*"),
+        ("( ", "("),
+        (" )", ")"),
+        ("[ ", "["),
+        (" ]", "]"),
+    ] {
+        name = name.replace(spaced, tight);
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+    use crate::expand_test::{assert_expands, assert_fails_at, derive};
+
+    #[test]
+    fn lists_tuple_fields_by_index() {
+        assert_expands(
+            derive(
+                impl_field_info,
+                "struct Pair(Vec<String>, &'static [u8; 4]);",
+            ),
+            quote! {
+                impl ::hello_macro::FieldInfo for Pair {
+                    fn fields() -> &'static [(&'static str, &'static str)] {
+                        &[("0", "Vec<String>"), ("1", "&'static [u8; 4]")]
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn points_at_an_enums_name() {
+        assert_fails_at(
+            derive(impl_field_info, "enum Shape { Circle }"),
+            "FieldInfo can only be derived for structs",
+            "1:6 Shape",
+        );
+    }
+}
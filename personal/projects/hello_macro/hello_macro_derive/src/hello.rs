@@ -0,0 +1,161 @@
+//! `#[derive(HelloMacro)]`.
+
+use quote::quote;
+use syn::{Attribute, Data, LitStr};
+
+pub(crate) fn impl_hello_macro(ast: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &ast.ident;
+    // `Wrapper<T>` needs `impl<T> HelloMacro for Wrapper<T>`, bounds and
+    // where clause included, not `impl HelloMacro for Wrapper`.
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let hello = HelloAttrs::parse(&ast.attrs)?;
+    let greeting = hello
+        .greeting
+        .map_or_else(|| String::from("Hello"), |lit| lit.value());
+    let display_name = hello
+        .name
+        .map_or_else(|| name.to_string(), |lit| lit.value());
+
+    let gen = match &ast.data {
+        Data::Struct(_) => quote! {
+            impl #impl_generics ::hello_macro::HelloMacro for #name #ty_generics #where_clause {
+                fn hello_macro() {
+                    println!("{}, Macro! My name is {}!", #greeting, #display_name);
+                }
+            }
+        },
+        Data::Enum(data) => {
+            let variants: Vec<_> = data.variants.iter().map(|variant| &variant.ident).collect();
+            let list = variants
+                .iter()
+                .map(|variant| variant.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let variants_are = if variants.is_empty() {
+                String::from("I have no variants")
+            } else {
+                format!("my variants are {list}")
+            };
+            quote! {
+                impl #impl_generics ::hello_macro::HelloMacro for #name #ty_generics #where_clause {
+                    fn hello_macro() {
+                        println!(
+                            "{}, Macro! My name is {}, and {}!",
+                            #greeting,
+                            #display_name,
+                            #variants_are,
+                        );
+                    }
+                }
+
+                impl #impl_generics #name #ty_generics #where_clause {
+                    /// The name of the variant `self` is.
+                    pub fn which_variant(&self) -> &'static str {
+                        match *self {
+                            #(Self::#variants { .. } => stringify!(#variants),)*
+                        }
+                    }
+                }
+            }
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "HelloMacro can't be derived for unions",
+            ))
+        }
+    };
+    Ok(gen)
+}
+
+/// The overrides in `#[hello(name = "...", greeting = "...")]`.
+#[derive(Default)]
+struct HelloAttrs {
+    name: Option<LitStr>,
+    greeting: Option<LitStr>,
+}
+
+impl HelloAttrs {
+    fn parse(attrs: &[Attribute]) -> syn::Result<HelloAttrs> {
+        let mut hello = HelloAttrs::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("hello")) {
+            attr.parse_nested_meta(|meta| {
+                let (key, slot) = if meta.path.is_ident("name") {
+                    ("name", &mut hello.name)
+                } else if meta.path.is_ident("greeting") {
+                    ("greeting", &mut hello.greeting)
+                } else {
+                    return Err(meta.error("expected `name` or `greeting`"));
+                };
+                if slot.is_some() {
+                    return Err(meta.error(format_args!("`{key}` is already set")));
+                }
+                *slot = Some(meta.value()?.parse()?);
+                Ok(())
+            })?;
+        }
+        Ok(hello)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+    use crate::expand_test::{assert_expands, assert_fails_at, derive};
+
+    #[test]
+    fn overrides_go_into_the_greeting() {
+        let result = derive(
+            impl_hello_macro,
+            r#"
+                #[hello(name = "Hotcakes", greeting = "Hi")]
+                struct Pancakes<T: Clone>(T);
+            "#,
+        );
+        assert_expands(
+            result,
+            quote! {
+                impl<T: Clone> ::hello_macro::HelloMacro for Pancakes<T> {
+                    fn hello_macro() {
+                        println!("{}, Macro! My name is {}!", "Hi", "Hotcakes");
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn points_at_the_bad_key() {
+        let result = derive(
+            impl_hello_macro,
+            r#"
+                #[hello(name = "A", nickname = "B")]
+                struct Pancakes;
+            "#,
+        );
+        assert_fails_at(result, "expected `name` or `greeting`", "2:37 nickname");
+
+        let result = derive(
+            impl_hello_macro,
+            r#"
+                #[hello(name = "A")]
+                #[hello(name = "B")]
+                struct Pancakes;
+            "#,
+        );
+        assert_fails_at(result, "`name` is already set", "3:25 name");
+    }
+
+    #[test]
+    fn refuses_unions_at_the_keyword() {
+        let result = derive(impl_hello_macro, "union Bits { int: u32, float: f32 }");
+        assert_fails_at(
+            result,
+            "HelloMacro can't be derived for unions",
+            "1:1 union",
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! The derive macros for `hello_macro`'s traits, from Listings 19-31 and
+//! 19-33, for boilerplate that isn't a trait, like accessors and error
+//! types, and the function-like `sql!`, `config_env!` and
+//! `impl_for_tuples!`.
+//!
+//! Each one is split in two: the `#[proc_macro_derive]` function here
+//! parses the input, and an `impl_*` function in the macro's own module
+//! builds the code from the syntax tree.
+//!
+//! Nothing here panics on bad input. A macro that panics fails with
+//! "proc-macro derive panicked", pointing at the whole derive. Instead,
+//! every problem is a `syn::Error` with the span of the code that caused
+//! it, which `parse_macro_input!` and `into_compile_error` turn into a
+//! `compile_error!` there, so the compiler reports it like one of its own.
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+mod accessors;
+mod config_env;
+mod delegate;
+mod display;
+mod error;
+#[cfg(test)]
+mod expand_test;
+mod field_info;
+mod hello;
+mod sql;
+mod to_json;
+mod tuples;
+
+#[proc_macro_derive(HelloMacro, attributes(hello))]
+pub fn hello_macro_derive(input: TokenStream) -> TokenStream {
+    // Construct a representation of Rust code as a syntax tree
+    // that we can manipulate, or return the compile error saying why not
+    let ast = parse_macro_input!(input as DeriveInput);
+
+    // Build the trait implementation
+    hello::impl_hello_macro(&ast)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(FieldInfo)]
+pub fn field_info_derive(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    field_info::impl_field_info(&ast)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(Getters, attributes(get))]
+pub fn getters_derive(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    accessors::impl_getters(&ast)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(Setters, attributes(set))]
+pub fn setters_derive(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    accessors::impl_setters(&ast)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(Display, attributes(display))]
+pub fn display_derive(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    display::impl_display(&ast)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(Delegate, attributes(delegate))]
+pub fn delegate_derive(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    delegate::impl_delegate(&ast)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(ErrorKind, attributes(error, source, from))]
+pub fn error_kind_derive(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    error::impl_error_kind(&ast)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(ToJson, attributes(json))]
+pub fn to_json_derive(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    to_json::impl_to_json(&ast)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro]
+pub fn sql(input: TokenStream) -> TokenStream {
+    sql::impl_sql(input.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro]
+pub fn config_env(input: TokenStream) -> TokenStream {
+    config_env::impl_config_env(input.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro]
+pub fn impl_for_tuples(input: TokenStream) -> TokenStream {
+    tuples::impl_impl_for_tuples(input.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,255 @@
+//! `sql!`, which parses a query when the code compiles and expands to a
+//! `hello_macro::sql::Query`. The grammar is in that module's docs.
+//!
+//! The query arrives as Rust tokens, which is what lets each error point
+//! at the word in the query that caused it: `sql!(SELECT * FORM posts)`
+//! fails with "expected `FROM`" under `FORM`.
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{
+    ext::IdentExt,
+    parse::{Parse, ParseStream},
+    Ident, LitInt, LitStr, Token,
+};
+
+pub(crate) fn impl_sql(input: TokenStream) -> syn::Result<TokenStream> {
+    let select: Select = syn::parse2(input)?;
+    Ok(select.into_token_stream())
+}
+
+const KEYWORDS: &[&str] = &[
+    "SELECT", "FROM", "WHERE", "AND", "ORDER", "BY", "ASC", "DESC", "LIMIT",
+];
+
+struct Select {
+    /// `None` for `*`.
+    columns: Option<Vec<Ident>>,
+    table: Ident,
+    filters: Vec<Condition>,
+    order_by: Option<(Ident, bool)>,
+    limit: Option<u64>,
+}
+
+struct Condition {
+    column: Ident,
+    op: TokenStream,
+    value: TokenStream,
+}
+
+impl Parse for Select {
+    fn parse(input: ParseStream) -> syn::Result<Select> {
+        keyword(input, "SELECT")?;
+        let columns = if input.peek(Token![*]) {
+            input.parse::<Token![*]>()?;
+            None
+        } else {
+            let mut columns = vec![name(input, "a column")?];
+            while input.peek(Token![,]) {
+                input.parse::<Token![,]>()?;
+                columns.push(name(input, "a column")?);
+            }
+            Some(columns)
+        };
+
+        keyword(input, "FROM")?;
+        let table = name(input, "a table")?;
+
+        let mut filters = Vec::new();
+        if peek_keyword(input, "WHERE") {
+            keyword(input, "WHERE")?;
+            filters.push(input.parse()?);
+            while peek_keyword(input, "AND") {
+                keyword(input, "AND")?;
+                filters.push(input.parse()?);
+            }
+        }
+
+        let mut order_by = None;
+        if peek_keyword(input, "ORDER") {
+            keyword(input, "ORDER")?;
+            keyword(input, "BY")?;
+            let column = name(input, "a column")?;
+            let descending = if peek_keyword(input, "DESC") {
+                keyword(input, "DESC")?;
+                true
+            } else {
+                if peek_keyword(input, "ASC") {
+                    keyword(input, "ASC")?;
+                }
+                false
+            };
+            order_by = Some((column, descending));
+        }
+
+        let mut limit = None;
+        if peek_keyword(input, "LIMIT") {
+            keyword(input, "LIMIT")?;
+            limit = Some(input.parse::<LitInt>()?.base10_parse()?);
+        }
+
+        if !input.is_empty() {
+            return Err(input.error("unexpected tokens after the query"));
+        }
+        Ok(Select {
+            columns,
+            table,
+            filters,
+            order_by,
+            limit,
+        })
+    }
+}
+
+impl Parse for Condition {
+    fn parse(input: ParseStream) -> syn::Result<Condition> {
+        let column = name(input, "a column")?;
+
+        // The two-character operators first, so `<=` isn't read as `<`.
+        let op = if input.peek(Token![<=]) {
+            input.parse::<Token![<=]>()?;
+            quote!(Le)
+        } else if input.peek(Token![>=]) {
+            input.parse::<Token![>=]>()?;
+            quote!(Ge)
+        } else if input.peek(Token![!=]) {
+            input.parse::<Token![!=]>()?;
+            quote!(Ne)
+        } else if input.peek(Token![==]) {
+            return Err(input.error("SQL compares with `=`, not `==`"));
+        } else if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            quote!(Eq)
+        } else if input.peek(Token![<]) {
+            input.parse::<Token![<]>()?;
+            quote!(Lt)
+        } else if input.peek(Token![>]) {
+            input.parse::<Token![>]>()?;
+            quote!(Gt)
+        } else {
+            return Err(input.error("expected one of `=`, `!=`, `<`, `<=`, `>`, `>=`"));
+        };
+
+        let value = if input.peek(LitStr) {
+            let s: LitStr = input.parse()?;
+            quote!(Str(#s))
+        } else if input.peek(LitInt) || input.peek(Token![-]) {
+            let negative = input.parse::<Option<Token![-]>>()?.is_some();
+            let lit: LitInt = input.parse()?;
+            let n: i64 = lit.base10_parse()?;
+            let n = if negative { -n } else { n };
+            quote!(Int(#n))
+        } else if peek_keyword(input, "TRUE") {
+            keyword(input, "TRUE")?;
+            quote!(Bool(true))
+        } else if peek_keyword(input, "FALSE") {
+            keyword(input, "FALSE")?;
+            quote!(Bool(false))
+        } else {
+            return Err(input.error("expected a number, a \"string\", TRUE or FALSE"));
+        };
+
+        Ok(Condition { column, op, value })
+    }
+}
+
+impl ToTokens for Select {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let sql = quote!(::hello_macro::sql);
+        let columns = match &self.columns {
+            None => quote!(#sql::Columns::All),
+            Some(columns) => {
+                let columns = columns.iter().map(Ident::unraw).map(|c| c.to_string());
+                quote!(#sql::Columns::Named(&[#(#columns),*]))
+            }
+        };
+        let table = self.table.unraw().to_string();
+        let filters = self.filters.iter().map(|condition| {
+            let column = condition.column.unraw().to_string();
+            let (op, value) = (&condition.op, &condition.value);
+            quote! {
+                #sql::Condition {
+                    column: #column,
+                    op: #sql::Op::#op,
+                    value: #sql::Value::#value,
+                }
+            }
+        });
+        let order_by = match &self.order_by {
+            None => quote!(::std::option::Option::None),
+            Some((column, descending)) => {
+                let column = column.unraw().to_string();
+                let order = if *descending {
+                    quote!(Desc)
+                } else {
+                    quote!(Asc)
+                };
+                quote!(::std::option::Option::Some((#column, #sql::Order::#order)))
+            }
+        };
+        let limit = match self.limit {
+            None => quote!(::std::option::Option::None),
+            Some(limit) => quote!(::std::option::Option::Some(#limit)),
+        };
+
+        tokens.extend(quote! {
+            #sql::Query {
+                columns: #columns,
+                table: #table,
+                filters: &[#(#filters),*],
+                order_by: #order_by,
+                limit: #limit,
+            }
+        });
+    }
+}
+
+/// Parse `word`, in any case.
+fn keyword(input: ParseStream, word: &str) -> syn::Result<()> {
+    input.step(|cursor| match cursor.ident() {
+        Some((ident, rest)) if ident.to_string().eq_ignore_ascii_case(word) => Ok(((), rest)),
+        _ => Err(cursor.error(format!("expected `{word}`"))),
+    })
+}
+
+fn peek_keyword(input: ParseStream, word: &str) -> bool {
+    input
+        .cursor()
+        .ident()
+        .is_some_and(|(ident, _)| ident.to_string().eq_ignore_ascii_case(word))
+}
+
+/// A table or column name, which can be anything but a keyword. Rust's
+/// own keywords are fine: `type` is a reasonable column.
+fn name(input: ParseStream, what: &str) -> syn::Result<Ident> {
+    let is_keyword = KEYWORDS.iter().any(|word| peek_keyword(input, word));
+    if is_keyword || !input.peek(Ident::peek_any) {
+        return Err(input.error(format!("expected {what} name")));
+    }
+    Ident::parse_any(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expand_test::{assert_fails_at, call};
+
+    #[test]
+    fn points_at_the_word_that_is_wrong() {
+        assert_fails_at(
+            call(impl_sql, "SELECT * FORM posts"),
+            "expected `FROM`",
+            "1:10 FORM",
+        );
+        assert_fails_at(
+            call(impl_sql, "SELECT title FROM posts WHERE id == 1"),
+            "SQL compares with `=`, not `==`",
+            "1:34 =",
+        );
+        assert_fails_at(
+            call(impl_sql, "SELECT from FROM posts"),
+            "expected a column name",
+            "1:8 from",
+        );
+    }
+}
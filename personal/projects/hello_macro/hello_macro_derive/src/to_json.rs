@@ -0,0 +1,232 @@
+//! `#[derive(ToJson)]`, writing a type with `hello_macro::json::Writer`.
+//!
+//! A struct with named fields is an object, a tuple struct is an array,
+//! unless it has one field, when it's just that field, and a unit struct is
+//! `null`. An enum whose variants have no fields is the variant's name as
+//! a string. A field or variant can be given another name in the JSON
+//! with `#[json(rename = "...")]`, and a field can be left out with
+//! `#[json(skip)]`.
+//!
+//! Every type parameter is required to be `ToJson` too, the way the
+//! standard library's derives bound theirs.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_quote, Attribute, Data, Fields, LitStr};
+
+pub(crate) fn impl_to_json(ast: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let name = &ast.ident;
+    let mut generics = ast.generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(::hello_macro::json::ToJson));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let body = match &ast.data {
+        Data::Struct(data) => write_struct(&data.fields)?,
+        Data::Enum(data) => {
+            let arms = data
+                .variants
+                .iter()
+                .map(|variant| {
+                    if !matches!(variant.fields, Fields::Unit) {
+                        return Err(syn::Error::new_spanned(
+                            &variant.fields,
+                            "ToJson can only be derived for enums whose variants have no fields",
+                        ));
+                    }
+                    let ident = &variant.ident;
+                    let json_name = JsonAttrs::parse(&variant.attrs, false)?
+                        .rename
+                        .map_or_else(|| ident.to_string(), |lit| lit.value());
+                    Ok(quote!(Self::#ident => json.string(#json_name),))
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+            quote! {
+                match *self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "ToJson can't be derived for unions",
+            ))
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::hello_macro::json::ToJson for #name #ty_generics #where_clause {
+            fn write_json(&self, json: ::hello_macro::json::Writer<'_>) {
+                #body
+            }
+        }
+    })
+}
+
+fn write_struct(fields: &Fields) -> syn::Result<TokenStream> {
+    let mut written = Vec::new();
+    for (index, field) in fields.iter().enumerate() {
+        let attrs = JsonAttrs::parse(&field.attrs, true)?;
+        if attrs.skip {
+            continue;
+        }
+        let member = match &field.ident {
+            Some(ident) => quote!(#ident),
+            None => {
+                let index = syn::Index::from(index);
+                quote!(#index)
+            }
+        };
+        let json_name = match (&attrs.rename, &field.ident) {
+            (Some(rename), _) => rename.value(),
+            (None, Some(ident)) => ident.to_string(),
+            (None, None) => String::new(),
+        };
+        written.push((member, json_name));
+    }
+
+    Ok(match fields {
+        Fields::Named(_) => {
+            let fields = written
+                .iter()
+                .map(|(member, json_name)| quote!(object.field(#json_name, &self.#member);));
+            quote! {
+                json.object(|object| {
+                    #(#fields)*
+                });
+            }
+        }
+        // A newtype is the value it wraps.
+        Fields::Unnamed(_) if fields.len() == 1 && written.len() == 1 => {
+            quote!(::hello_macro::json::ToJson::write_json(&self.0, json);)
+        }
+        Fields::Unnamed(_) => {
+            let members = written.iter().map(|(member, _)| member);
+            quote! {
+                json.array(|array| {
+                    #(array.value(&self.#members);)*
+                });
+            }
+        }
+        Fields::Unit => quote!(json.null();),
+    })
+}
+
+/// The options in `#[json(...)]`.
+#[derive(Default)]
+struct JsonAttrs {
+    rename: Option<LitStr>,
+    skip: bool,
+}
+
+impl JsonAttrs {
+    /// `field` is whether these are a field's, which can also be `skip`.
+    fn parse(attrs: &[Attribute], field: bool) -> syn::Result<JsonAttrs> {
+        let mut json = JsonAttrs::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("json")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    json.rename = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if field && meta.path.is_ident("skip") {
+                    json.skip = true;
+                    Ok(())
+                } else if field {
+                    Err(meta.error("expected `rename = \"...\"` or `skip`"))
+                } else {
+                    Err(meta.error("expected `rename = \"...\"`"))
+                }
+            })?;
+        }
+        Ok(json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+    use crate::expand_test::{assert_expands, assert_fails_at, derive};
+
+    #[test]
+    fn structs_are_objects() {
+        let result = derive(
+            impl_to_json,
+            r#"
+                struct Health<T> {
+                    status: &'static str,
+                    #[json(rename = "uptime_seconds")]
+                    uptime: u64,
+                    #[json(skip)]
+                    started: Instant,
+                    extra: T,
+                }
+            "#,
+        );
+        assert_expands(
+            result,
+            quote! {
+                impl<T: ::hello_macro::json::ToJson> ::hello_macro::json::ToJson for Health<T> {
+                    fn write_json(&self, json: ::hello_macro::json::Writer<'_>) {
+                        json.object(|object| {
+                            object.field("status", &self.status);
+                            object.field("uptime_seconds", &self.uptime);
+                            object.field("extra", &self.extra);
+                        });
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn newtypes_are_what_they_wrap() {
+        assert_expands(
+            derive(impl_to_json, "struct Meters(u64);"),
+            quote! {
+                impl ::hello_macro::json::ToJson for Meters {
+                    fn write_json(&self, json: ::hello_macro::json::Writer<'_>) {
+                        ::hello_macro::json::ToJson::write_json(&self.0, json);
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn unit_variants_are_strings() {
+        assert_expands(
+            derive(
+                impl_to_json,
+                r#"enum State { Idle, #[json(rename = "busy")] Handling }"#,
+            ),
+            quote! {
+                impl ::hello_macro::json::ToJson for State {
+                    fn write_json(&self, json: ::hello_macro::json::Writer<'_>) {
+                        match *self {
+                            Self::Idle => json.string("Idle"),
+                            Self::Handling => json.string("busy"),
+                        }
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn points_at_mistakes() {
+        assert_fails_at(
+            derive(impl_to_json, "enum Shape { Point, Circle(f64) }"),
+            "ToJson can only be derived for enums whose variants have no fields",
+            "1:27 (f64)",
+        );
+        assert_fails_at(
+            derive(impl_to_json, "struct A { #[json(flatten)] b: B }"),
+            "expected `rename = \"...\"` or `skip`",
+            "1:19 flatten",
+        );
+    }
+}
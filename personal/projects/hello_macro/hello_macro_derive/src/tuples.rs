@@ -0,0 +1,265 @@
+//! `impl_for_tuples!(Trait, 1..=12, { ... })`: a trait's impls for tuples
+//! of every length in a range, from one impl written once.
+//!
+//! The standard library does this with a `macro_rules!` macro that's given
+//! every length's type parameters by hand, because a declarative macro
+//! can't count. A proc macro can, so this only needs the range. The body
+//! is the impl's items, where `#( ... )*` repeats once per element, with
+//! `#T` its type and `#i` its index, like `quote!`:
+//!
+//! ```text
+//! impl_for_tuples!(Summary, 1..=12, {
+//!     fn summarize(&self) -> String {
+//!         [#(self.#i.summarize()),*].join("; ")
+//!     }
+//! });
+//! ```
+//!
+//! Each element's type has to implement the trait too. Without a body,
+//! the impls are empty, for traits whose methods all have defaults.
+
+use proc_macro2::{Delimiter, Group, Ident, Literal, Span, TokenStream, TokenTree};
+use quote::{format_ident, quote};
+use syn::{
+    parse::{Parse, ParseStream},
+    LitInt, Path, Token,
+};
+
+struct ImplForTuples {
+    trait_: Path,
+    lengths: std::ops::RangeInclusive<usize>,
+    body: TokenStream,
+}
+
+impl Parse for ImplForTuples {
+    fn parse(input: ParseStream) -> syn::Result<ImplForTuples> {
+        let trait_ = input.parse()?;
+        input.parse::<Token![,]>()?;
+
+        let start: LitInt = input.parse()?;
+        let inclusive = if input.peek(Token![..=]) {
+            input.parse::<Token![..=]>()?;
+            true
+        } else {
+            input.parse::<Token![..]>()?;
+            false
+        };
+        let end: LitInt = input.parse()?;
+        let (first, last) = (start.base10_parse::<usize>()?, end.base10_parse::<usize>()?);
+        let last = if inclusive {
+            Some(last)
+        } else {
+            last.checked_sub(1)
+        };
+        let lengths = match last {
+            Some(last) if first <= last => first..=last,
+            _ => return Err(syn::Error::new(end.span(), "the range of lengths is empty")),
+        };
+
+        let mut body = TokenStream::new();
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let content;
+            syn::braced!(content in input);
+            body = content.parse()?;
+            input.parse::<Option<Token![,]>>()?;
+        }
+        Ok(ImplForTuples {
+            trait_,
+            lengths,
+            body,
+        })
+    }
+}
+
+pub(crate) fn impl_impl_for_tuples(input: TokenStream) -> syn::Result<TokenStream> {
+    let ImplForTuples {
+        trait_,
+        lengths,
+        body,
+    } = syn::parse2(input)?;
+
+    let mut impls = TokenStream::new();
+    for len in lengths {
+        let params: Vec<_> = (0..len).map(|i| format_ident!("T{i}")).collect();
+        let body = expand(body.clone(), len, None)?;
+        impls.extend(quote! {
+            impl<#(#params: #trait_),*> #trait_ for (#(#params,)*) {
+                #body
+            }
+        });
+    }
+    Ok(impls)
+}
+
+/// `template` for a tuple of `len` elements: each `#(...)*` repeated for
+/// every element, and within one, `#T` and `#i` replaced by `element`'s
+/// type and index.
+fn expand(template: TokenStream, len: usize, element: Option<usize>) -> syn::Result<TokenStream> {
+    let mut out = TokenStream::new();
+    let mut tokens = template.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        let pound = match token {
+            TokenTree::Punct(ref punct) if punct.as_char() == '#' => punct.clone(),
+            TokenTree::Group(group) => {
+                let mut expanded =
+                    Group::new(group.delimiter(), expand(group.stream(), len, element)?);
+                expanded.set_span(group.span());
+                out.extend([TokenTree::Group(expanded)]);
+                continue;
+            }
+            token => {
+                out.extend([token]);
+                continue;
+            }
+        };
+
+        match tokens.peek() {
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => {
+                let group = group.clone();
+                tokens.next();
+                if element.is_some() {
+                    return Err(syn::Error::new(pound.span(), "`#(...)*` can't be nested"));
+                }
+                let separator = match tokens.next() {
+                    Some(TokenTree::Punct(star)) if star.as_char() == '*' => None,
+                    Some(TokenTree::Punct(separator)) => match tokens.next() {
+                        Some(TokenTree::Punct(star)) if star.as_char() == '*' => Some(separator),
+                        _ => return Err(expected_star(group.span())),
+                    },
+                    _ => return Err(expected_star(group.span())),
+                };
+                for i in 0..len {
+                    if let (Some(separator), true) = (&separator, i > 0) {
+                        out.extend([TokenTree::Punct(separator.clone())]);
+                    }
+                    out.extend(expand(group.stream(), len, Some(i))?);
+                }
+            }
+            Some(TokenTree::Ident(ident)) => {
+                let ident = ident.clone();
+                tokens.next();
+                out.extend([substitute(&ident, element)?]);
+            }
+            // Anything else, like the `#` of an attribute, is left alone.
+            _ => out.extend([TokenTree::Punct(pound)]),
+        }
+    }
+    Ok(out)
+}
+
+/// What `#ident` stands for in the repetition for `element`.
+fn substitute(ident: &Ident, element: Option<usize>) -> syn::Result<TokenTree> {
+    let Some(i) = element else {
+        return Err(syn::Error::new(
+            ident.span(),
+            format!("`#{ident}` can only be used inside `#(...)*`"),
+        ));
+    };
+    if ident == "T" {
+        Ok(TokenTree::Ident(Ident::new(&format!("T{i}"), ident.span())))
+    } else if ident == "i" {
+        let mut index = Literal::usize_unsuffixed(i);
+        index.set_span(ident.span());
+        Ok(TokenTree::Literal(index))
+    } else {
+        Err(syn::Error::new(
+            ident.span(),
+            format!("unknown `#{ident}`: expected `#T` or `#i`"),
+        ))
+    }
+}
+
+fn expected_star(span: Span) -> syn::Error {
+    syn::Error::new(span, "expected `*` or a separator and `*` after `#(...)`")
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::quote;
+
+    use super::*;
+    use crate::expand_test::{assert_expands, assert_fails_at, call};
+
+    #[test]
+    fn repeats_the_body_per_element() {
+        assert_expands(
+            call(
+                impl_impl_for_tuples,
+                "Summary, 1..3, { fn summarize(&self) -> String { [#(self.#i.summarize()),*].join(\"; \") } }",
+            ),
+            quote! {
+                impl<T0: Summary> Summary for (T0,) {
+                    fn summarize(&self) -> String {
+                        [self.0.summarize()].join("; ")
+                    }
+                }
+                impl<T0: Summary, T1: Summary> Summary for (T0, T1,) {
+                    fn summarize(&self) -> String {
+                        [self.0.summarize(), self.1.summarize()].join("; ")
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn types_and_attributes_come_through() {
+        assert_expands(
+            call(
+                impl_impl_for_tuples,
+                "Size, 0..=2, { #[inline] fn size() -> usize { 0 #(+ <#T as Size>::size())* } }",
+            ),
+            quote! {
+                impl<> Size for () {
+                    #[inline]
+                    fn size() -> usize { 0 }
+                }
+                impl<T0: Size> Size for (T0,) {
+                    #[inline]
+                    fn size() -> usize { 0 + <T0 as Size>::size() }
+                }
+                impl<T0: Size, T1: Size> Size for (T0, T1,) {
+                    #[inline]
+                    fn size() -> usize { 0 + <T0 as Size>::size() + <T1 as Size>::size() }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn empty_bodies_for_marker_traits() {
+        assert_expands(
+            call(impl_impl_for_tuples, "marker::Plain, 2..=2"),
+            quote! {
+                impl<T0: marker::Plain, T1: marker::Plain> marker::Plain for (T0, T1,) {}
+            },
+        );
+    }
+
+    #[test]
+    fn points_at_mistakes() {
+        assert_fails_at(
+            call(impl_impl_for_tuples, "Summary, 3..3"),
+            "the range of lengths is empty",
+            "1:13 3",
+        );
+        assert_fails_at(
+            call(
+                impl_impl_for_tuples,
+                "Summary, 1..=2, { fn f(&self) -> #T; }",
+            ),
+            "`#T` can only be used inside `#(...)*`",
+            "1:35 T",
+        );
+        assert_fails_at(
+            call(impl_impl_for_tuples, "Summary, 1..=2, { #(self.#n)* }"),
+            "unknown `#n`: expected `#T` or `#i`",
+            "1:27 n",
+        );
+        assert_fails_at(
+            call(impl_impl_for_tuples, "Summary, 1..=2, { #(#i) }"),
+            "expected `*` or a separator and `*` after `#(...)`",
+            "1:20 (#i)",
+        );
+    }
+}
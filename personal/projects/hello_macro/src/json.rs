@@ -0,0 +1,296 @@
+//! Writing values as JSON, for `#[derive(ToJson)]`, without serde.
+//!
+//! A [`Writer`] writes exactly one value and is used up doing it, and
+//! objects and arrays are written by closures that get their own writers
+//! for the fields and items. So the nesting is the closures' nesting, and
+//! there's no way to leave an object open or write two values where one
+//! goes. Dropping a writer without writing its value is a `must_use`
+//! warning, so anything that compiles cleanly writes valid JSON.
+//!
+//! ```
+//! use hello_macro::json::{ToJson, Writer};
+//!
+//! let mut out = String::new();
+//! Writer::new(&mut out).object(|object| {
+//!     object.field("name", "Ferris");
+//!     object.field("legs", &10);
+//!     object.key("claws").array(|array| {
+//!         array.value(&true);
+//!         array.item().null();
+//!     });
+//! });
+//! assert_eq!(out, r#"{"name":"Ferris","legs":10,"claws":[true,null]}"#);
+//! assert_eq!((1, "two").to_json(), r#"[1,"two"]"#);
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write as _},
+    rc::Rc,
+    sync::Arc,
+};
+
+use hello_macro_derive::impl_for_tuples;
+
+/// A type that can be written as JSON.
+pub trait ToJson {
+    fn write_json(&self, json: Writer<'_>);
+
+    /// `self` as a JSON string.
+    fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(Writer::new(&mut out));
+        out
+    }
+}
+
+/// Writes one JSON value onto the end of a `String`.
+#[must_use = "a key or item with no value is invalid JSON"]
+pub struct Writer<'a> {
+    out: &'a mut String,
+}
+
+impl<'a> Writer<'a> {
+    pub fn new(out: &'a mut String) -> Writer<'a> {
+        Writer { out }
+    }
+
+    pub fn null(self) {
+        self.out.push_str("null");
+    }
+
+    pub fn bool(self, value: bool) {
+        self.out.push_str(if value { "true" } else { "false" });
+    }
+
+    pub fn u64(self, value: u64) {
+        write!(self.out, "{value}").unwrap();
+    }
+
+    pub fn i64(self, value: i64) {
+        write!(self.out, "{value}").unwrap();
+    }
+
+    /// A number, or `null` for infinities and NaN, which JSON can't write.
+    pub fn f64(self, value: f64) {
+        if value.is_finite() {
+            write!(self.out, "{value}").unwrap();
+        } else {
+            self.null();
+        }
+    }
+
+    pub fn string(self, value: &str) {
+        escape_into(self.out, value);
+    }
+
+    /// An array, whose items `items` writes.
+    pub fn array(self, items: impl FnOnce(&mut ArrayWriter<'_>)) {
+        self.out.push('[');
+        items(&mut ArrayWriter {
+            out: self.out,
+            empty: true,
+        });
+        self.out.push(']');
+    }
+
+    /// An object, whose fields `fields` writes.
+    pub fn object(self, fields: impl FnOnce(&mut ObjectWriter<'_>)) {
+        self.out.push('{');
+        fields(&mut ObjectWriter {
+            out: self.out,
+            empty: true,
+        });
+        self.out.push('}');
+    }
+
+    pub fn value(self, value: &(impl ToJson + ?Sized)) {
+        value.write_json(self);
+    }
+}
+
+/// Writes the items of an array.
+pub struct ArrayWriter<'a> {
+    out: &'a mut String,
+    empty: bool,
+}
+
+impl ArrayWriter<'_> {
+    /// A writer for the next item.
+    #[must_use = "an item with no value is invalid JSON"]
+    pub fn item(&mut self) -> Writer<'_> {
+        if !self.empty {
+            self.out.push(',');
+        }
+        self.empty = false;
+        Writer::new(self.out)
+    }
+
+    pub fn value(&mut self, value: &(impl ToJson + ?Sized)) {
+        self.item().value(value);
+    }
+}
+
+/// Writes the fields of an object.
+pub struct ObjectWriter<'a> {
+    out: &'a mut String,
+    empty: bool,
+}
+
+impl ObjectWriter<'_> {
+    /// Write `key`, returning the writer for its value.
+    #[must_use = "a key with no value is invalid JSON"]
+    pub fn key(&mut self, key: &str) -> Writer<'_> {
+        if !self.empty {
+            self.out.push(',');
+        }
+        self.empty = false;
+        escape_into(self.out, key);
+        self.out.push(':');
+        Writer::new(self.out)
+    }
+
+    pub fn field(&mut self, key: &str, value: &(impl ToJson + ?Sized)) {
+        self.key(key).value(value);
+    }
+}
+
+/// `value` as a quoted JSON string.
+fn escape_into(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < '\u{20}' => {
+                write!(out, "\\u{:04x}", c as u32).unwrap();
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// A [`fmt::Display`] type written as a JSON string of what it displays.
+pub struct AsString<T>(pub T);
+
+impl<T: fmt::Display> ToJson for AsString<T> {
+    fn write_json(&self, json: Writer<'_>) {
+        json.string(&self.0.to_string());
+    }
+}
+
+impl ToJson for bool {
+    fn write_json(&self, json: Writer<'_>) {
+        json.bool(*self);
+    }
+}
+
+macro_rules! to_json_as {
+    ($method:ident($as:ty): $($ty:ty),*) => {
+        $(
+            impl ToJson for $ty {
+                fn write_json(&self, json: Writer<'_>) {
+                    json.$method(*self as $as);
+                }
+            }
+        )*
+    };
+}
+
+to_json_as!(u64(u64): u8, u16, u32, u64, usize);
+to_json_as!(i64(i64): i8, i16, i32, i64, isize);
+to_json_as!(f64(f64): f32, f64);
+
+impl ToJson for str {
+    fn write_json(&self, json: Writer<'_>) {
+        json.string(self);
+    }
+}
+
+impl ToJson for String {
+    fn write_json(&self, json: Writer<'_>) {
+        json.string(self);
+    }
+}
+
+impl ToJson for char {
+    fn write_json(&self, json: Writer<'_>) {
+        json.string(self.encode_utf8(&mut [0; 4]));
+    }
+}
+
+/// `None` is `null`.
+impl<T: ToJson> ToJson for Option<T> {
+    fn write_json(&self, json: Writer<'_>) {
+        match self {
+            Some(value) => value.write_json(json),
+            None => json.null(),
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for [T] {
+    fn write_json(&self, json: Writer<'_>) {
+        json.array(|array| {
+            for item in self {
+                array.value(item);
+            }
+        });
+    }
+}
+
+impl<T: ToJson, const N: usize> ToJson for [T; N] {
+    fn write_json(&self, json: Writer<'_>) {
+        self.as_slice().write_json(json);
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn write_json(&self, json: Writer<'_>) {
+        self.as_slice().write_json(json);
+    }
+}
+
+/// A map with string keys is an object.
+impl<K: AsRef<str>, V: ToJson> ToJson for BTreeMap<K, V> {
+    fn write_json(&self, json: Writer<'_>) {
+        json.object(|object| {
+            for (key, value) in self {
+                object.field(key.as_ref(), value);
+            }
+        });
+    }
+}
+
+macro_rules! to_json_through {
+    ($($pointer:ident),*) => {
+        $(
+            impl<T: ToJson + ?Sized> ToJson for $pointer<T> {
+                fn write_json(&self, json: Writer<'_>) {
+                    (**self).write_json(json);
+                }
+            }
+        )*
+    };
+}
+
+to_json_through!(Box, Rc, Arc);
+
+impl<T: ToJson + ?Sized> ToJson for &T {
+    fn write_json(&self, json: Writer<'_>) {
+        (**self).write_json(json);
+    }
+}
+
+// A tuple is an array of its elements.
+impl_for_tuples!(ToJson, 1..=12, {
+    fn write_json(&self, json: Writer<'_>) {
+        json.array(|array| {
+            #(array.value(&self.#i);)*
+        });
+    }
+});
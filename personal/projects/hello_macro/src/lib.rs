@@ -0,0 +1,83 @@
+//! The trait from Listing 19-30, which `hello_macro_derive` implements for
+//! any type that asks with `#[derive(HelloMacro)]`:
+//!
+//! ```
+//! use hello_macro::HelloMacro;
+//! use hello_macro_derive::HelloMacro;
+//!
+//! #[derive(HelloMacro)]
+//! struct Pancakes;
+//!
+//! Pancakes::hello_macro();
+//! ```
+//!
+//! Deriving it for an enum lists the variants too, and adds a
+//! `which_variant` method naming the one a value is:
+//!
+//! ```
+//! # use hello_macro::HelloMacro;
+//! # use hello_macro_derive::HelloMacro;
+//! #[derive(HelloMacro)]
+//! enum Topping {
+//!     Syrup,
+//!     Berries(u32),
+//! }
+//!
+//! Topping::hello_macro();
+//! assert_eq!(Topping::Berries(12).which_variant(), "Berries");
+//! ```
+//!
+//! A `#[hello]` attribute changes what it says: this prints "Howdy, Macro!
+//! My name is Flapjacks!"
+//!
+//! ```
+//! # use hello_macro::HelloMacro;
+//! # use hello_macro_derive::HelloMacro;
+//! #[derive(HelloMacro)]
+//! #[hello(name = "Flapjacks", greeting = "Howdy")]
+//! struct Pancakes;
+//!
+//! Pancakes::hello_macro();
+//! ```
+
+pub mod json;
+pub mod sql;
+pub mod summary;
+
+pub trait HelloMacro {
+    fn hello_macro();
+}
+
+/// The names and types of a struct's fields, in order, as written in its
+/// definition. `#[derive(FieldInfo)]` implements it, which lets code that's
+/// generic over the struct describe it:
+///
+/// ```
+/// use hello_macro::FieldInfo;
+/// use hello_macro_derive::FieldInfo;
+///
+/// #[derive(FieldInfo)]
+/// struct Point<T> {
+///     x: T,
+///     y: T,
+///     label: Option<String>,
+/// }
+///
+/// fn describe<T: FieldInfo>() -> Vec<String> {
+///     T::fields()
+///         .iter()
+///         .map(|(name, ty)| format!("{name}: {ty}"))
+///         .collect()
+/// }
+///
+/// assert_eq!(
+///     describe::<Point<f64>>(),
+///     ["x: T", "y: T", "label: Option<String>"]
+/// );
+/// ```
+///
+/// Type names are the types as they're spelled in the struct, so a
+/// generic field's is its parameter's name.
+pub trait FieldInfo {
+    fn fields() -> &'static [(&'static str, &'static str)];
+}
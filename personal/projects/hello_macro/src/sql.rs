@@ -0,0 +1,130 @@
+//! The queries `sql!` builds, from the function-like macro example in
+//! "Macros": `sql!(SELECT * FROM posts WHERE id=1)`.
+//!
+//! The macro checks the query when the code compiles, so a typo in a
+//! keyword is a compile error pointing at it rather than a failed query
+//! at runtime. What it expands to is a `Query` made of `'static` parts,
+//! which can be a `const`.
+//!
+//! The grammar is a small subset of `SELECT`:
+//!
+//! ```text
+//! SELECT * | column, ...
+//! FROM table
+//! [WHERE column op value [AND column op value ...]]
+//! [ORDER BY column [ASC | DESC]]
+//! [LIMIT count]
+//! ```
+//!
+//! where `op` is one of `=`, `!=`, `<`, `<=`, `>` and `>=`, and a value is
+//! an integer, a string, `true` or `false`. Keywords can be any case.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Query {
+    pub columns: Columns,
+    pub table: &'static str,
+    /// Conditions that must all hold.
+    pub filters: &'static [Condition],
+    pub order_by: Option<(&'static str, Order)>,
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Columns {
+    All,
+    Named(&'static [&'static str]),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub column: &'static str,
+    pub op: Op,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value {
+    Int(i64),
+    Str(&'static str),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+/// The query in a standard form, with keywords in capitals.
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SELECT ")?;
+        match self.columns {
+            Columns::All => write!(f, "*")?,
+            Columns::Named(columns) => write!(f, "{}", columns.join(", "))?,
+        }
+        write!(f, " FROM {}", self.table)?;
+        for (i, condition) in self.filters.iter().enumerate() {
+            let keyword = if i == 0 { "WHERE" } else { "AND" };
+            write!(f, " {keyword} {condition}")?;
+        }
+        if let Some((column, order)) = self.order_by {
+            write!(f, " ORDER BY {column} {order}")?;
+        }
+        if let Some(limit) = self.limit {
+            write!(f, " LIMIT {limit}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.column, self.op, self.value)
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+        })
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{n}"),
+            // SQL escapes a quote by doubling it.
+            Value::Str(s) => write!(f, "'{}'", s.replace('\'', "''")),
+            Value::Bool(b) => write!(f, "{}", if *b { "TRUE" } else { "FALSE" }),
+        }
+    }
+}
+
+impl fmt::Display for Order {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Order::Asc => "ASC",
+            Order::Desc => "DESC",
+        })
+    }
+}
@@ -0,0 +1,74 @@
+//! The `Summary` trait from Listing 10-14, with the tweets and articles it
+//! summarizes, and impls for tuples of them up to twelve long.
+//!
+//! A tuple's summary is its elements', so `(tweet, article)` reads "Read
+//! more from @horse_ebooks, Iceburgh...". Writing that out for every length
+//! would be twelve nearly identical impls; `impl_for_tuples!` writes them.
+//!
+//! ```
+//! use hello_macro::summary::{NewsArticle, Summary, Tweet};
+//!
+//! let tweet = Tweet {
+//!     username: String::from("horse_ebooks"),
+//!     content: String::from("of course, as you probably already know, people"),
+//!     reply: false,
+//!     retweet: false,
+//! };
+//! let article = NewsArticle {
+//!     headline: String::from("Penguins win the Stanley Cup Championship!"),
+//!     location: String::from("Pittsburgh, PA, USA"),
+//!     author: String::from("Iceburgh"),
+//!     content: String::from("The Pittsburgh Penguins once again are the best hockey team in the NHL."),
+//! };
+//!
+//! assert_eq!(
+//!     (tweet, article).summarize(),
+//!     "(Read more from @horse_ebooks, Iceburgh...)"
+//! );
+//! ```
+
+use hello_macro_derive::impl_for_tuples;
+
+pub trait Summary {
+    fn summarize_author(&self) -> String;
+
+    fn summarize(&self) -> String {
+        format!("(Read more from {}...)", self.summarize_author())
+    }
+}
+
+pub struct NewsArticle {
+    pub headline: String,
+    pub location: String,
+    pub author: String,
+    pub content: String,
+}
+
+impl Summary for NewsArticle {
+    fn summarize_author(&self) -> String {
+        self.author.clone()
+    }
+
+    fn summarize(&self) -> String {
+        format!("{}, by {} ({})", self.headline, self.author, self.location)
+    }
+}
+
+pub struct Tweet {
+    pub username: String,
+    pub content: String,
+    pub reply: bool,
+    pub retweet: bool,
+}
+
+impl Summary for Tweet {
+    fn summarize_author(&self) -> String {
+        format!("@{}", self.username)
+    }
+}
+
+impl_for_tuples!(Summary, 1..=12, {
+    fn summarize_author(&self) -> String {
+        [#(self.#i.summarize_author()),*].join(", ")
+    }
+});
@@ -0,0 +1,49 @@
+use hello_macro_derive::{Getters, Setters};
+
+#[derive(Getters, Setters, Default)]
+struct Rectangle {
+    #[get(copy)]
+    width: u32,
+    #[get(copy)]
+    height: u32,
+    #[set(into)]
+    label: String,
+    tags: Vec<&'static str>,
+}
+
+#[derive(Getters, Setters)]
+struct Labelled<T: Clone> {
+    #[set(into)]
+    value: T,
+}
+
+#[test]
+fn getters_borrow_or_copy() {
+    let rect = Rectangle {
+        width: 30,
+        height: 50,
+        label: String::from("box"),
+        tags: vec!["red"],
+    };
+    let (width, height): (u32, u32) = (rect.width(), rect.height());
+    let label: &String = rect.label();
+    assert_eq!((width, height, label.as_str()), (30, 50, "box"));
+    assert_eq!(rect.tags(), &["red"]);
+}
+
+#[test]
+fn setters_chain_and_convert() {
+    let mut rect = Rectangle::default();
+    rect.set_width(3).set_height(4).set_label("small");
+    rect.set_tags(vec!["a", "b"]);
+    assert_eq!(rect.width() * rect.height(), 12);
+    assert_eq!(rect.label(), "small");
+    assert_eq!(rect.tags().len(), 2);
+}
+
+#[test]
+fn works_on_generic_structs() {
+    let mut labelled = Labelled { value: 1_u64 };
+    labelled.set_value(7_u8);
+    assert_eq!(*labelled.value(), 7);
+}
@@ -0,0 +1,20 @@
+//! Cargo sets the `CARGO_PKG_*` variables for every crate it compiles, so
+//! they're the ones these tests can count on.
+
+use hello_macro_derive::config_env;
+
+#[test]
+fn uses_the_variable_when_it_is_set() {
+    const NAME: &str = config_env!("CARGO_PKG_NAME", "unknown");
+    const MAJOR: u8 = config_env!("CARGO_PKG_VERSION_MAJOR", 9u8);
+    const MINOR: i64 = config_env!("CARGO_PKG_VERSION_MINOR", -1);
+    assert_eq!((NAME, MAJOR, MINOR), ("hello_macro", 0, 1));
+}
+
+#[test]
+fn falls_back_to_the_default() {
+    const PORT: u16 = config_env!("HELLO_MACRO_UNSET_PORT", 7878);
+    const RATIO: f32 = config_env!("HELLO_MACRO_UNSET_RATIO", 0.5f32);
+    const VERBOSE: bool = config_env!("HELLO_MACRO_UNSET_VERBOSE", false,);
+    assert_eq!((PORT, RATIO, VERBOSE), (7878, 0.5, false));
+}
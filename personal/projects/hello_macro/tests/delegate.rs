@@ -0,0 +1,75 @@
+use std::{collections::VecDeque, fmt};
+
+use hello_macro_derive::Delegate;
+
+/// Listing 19-23's wrapper, which has its own Display but otherwise wants
+/// to be the Vec it wraps.
+#[derive(Delegate)]
+#[delegate(Deref, IntoIterator, len, push, pop)]
+struct Wrapper(Vec<String>);
+
+impl fmt::Display for Wrapper {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}]", self.0.join(", "))
+    }
+}
+
+#[derive(Delegate)]
+#[delegate(DerefMut, Deref, AsRef, AsMut, Debug, Display)]
+struct Name(String);
+
+#[derive(Delegate, Default)]
+#[delegate(IntoIterator, is_empty, clear, contains, iter, iter_mut)]
+#[delegate(fn push_back(&mut self, value: T), fn front(&self) -> Option<&T>)]
+struct Queue<T>(VecDeque<T>);
+
+#[test]
+fn fills_in_listing_19_23s_wrapper() {
+    let mut w = Wrapper(vec![String::from("hello")]);
+    w.push(String::from("world"));
+    assert_eq!(w.len(), 2);
+    assert_eq!(w.to_string(), "[hello, world]");
+    assert!(w.starts_with(&[String::from("hello")]));
+
+    let lengths: Vec<usize> = (&w).into_iter().map(String::len).collect();
+    assert_eq!(lengths, [5, 5]);
+    for word in &mut w.0 {
+        word.make_ascii_uppercase();
+    }
+    assert_eq!(w.pop().as_deref(), Some("WORLD"));
+    assert_eq!(w.into_iter().collect::<Vec<_>>(), ["HELLO"]);
+}
+
+#[test]
+fn forwards_the_standard_traits() {
+    let mut name = Name(String::from("Ferris"));
+    name.push_str(" the crab");
+    name.as_mut().insert(0, '@');
+    let inner: &String = name.as_ref();
+    assert_eq!(inner, "@Ferris the crab");
+    assert_eq!(
+        format!("{name} {name:?}"),
+        "@Ferris the crab \"@Ferris the crab\""
+    );
+}
+
+#[test]
+fn forwards_methods_by_name_or_signature() {
+    let mut queue = Queue::default();
+    assert!(queue.is_empty());
+    queue.push_back(1);
+    queue.push_back(2);
+    assert_eq!(queue.front(), Some(&1));
+    assert!(queue.contains(&2));
+
+    for n in queue.iter_mut() {
+        *n *= 10;
+    }
+    assert_eq!(queue.iter().copied().collect::<Vec<_>>(), [10, 20]);
+    for n in &mut queue {
+        *n += 1;
+    }
+    assert_eq!((&queue).into_iter().sum::<i32>(), 32);
+    queue.clear();
+    assert!(queue.is_empty());
+}
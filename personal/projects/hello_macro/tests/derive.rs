@@ -0,0 +1,79 @@
+use std::{fmt::Debug, marker::PhantomData};
+
+use hello_macro::HelloMacro;
+use hello_macro_derive::HelloMacro;
+
+#[derive(HelloMacro)]
+struct Pancakes;
+
+#[derive(HelloMacro)]
+struct Wrapper<T>(T);
+
+#[derive(HelloMacro)]
+struct Bounded<'a, T: Debug + 'a, const N: usize>
+where
+    T: Clone,
+{
+    _items: [&'a T; N],
+}
+
+#[derive(HelloMacro)]
+struct Marker<T: ?Sized>(PhantomData<T>);
+
+#[derive(HelloMacro)]
+enum Breakfast<T> {
+    Pancakes,
+    Waffles(T),
+    FullEnglish { _beans: bool },
+}
+
+#[derive(HelloMacro)]
+enum Never {}
+
+#[derive(HelloMacro)]
+#[hello(name = "Flapjacks", greeting = "Howdy")]
+struct Hotcakes;
+
+#[derive(HelloMacro)]
+#[hello(greeting = "Hi")]
+#[hello(name = "Morning")]
+enum Meal {
+    Breakfast,
+}
+
+fn greet<T: HelloMacro>() {
+    T::hello_macro();
+}
+
+#[test]
+fn derives_for_a_plain_struct() {
+    greet::<Pancakes>();
+}
+
+#[test]
+fn derives_for_generic_structs() {
+    greet::<Wrapper<String>>();
+    greet::<Bounded<'static, u8, 3>>();
+    greet::<Marker<str>>();
+}
+
+#[test]
+fn derives_for_enums() {
+    greet::<Breakfast<u8>>();
+    greet::<Never>();
+
+    let orders = [
+        Breakfast::Pancakes,
+        Breakfast::Waffles(2),
+        Breakfast::FullEnglish { _beans: true },
+    ];
+    let names: Vec<_> = orders.iter().map(Breakfast::which_variant).collect();
+    assert_eq!(names, ["Pancakes", "Waffles", "FullEnglish"]);
+}
+
+#[test]
+fn derives_with_overrides() {
+    greet::<Hotcakes>();
+    greet::<Meal>();
+    assert_eq!(Meal::Breakfast.which_variant(), "Breakfast");
+}
@@ -0,0 +1,79 @@
+use std::fmt;
+
+use hello_macro_derive::Display;
+
+#[derive(Display)]
+#[display("({x}, {y})")]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Display)]
+#[display("{0} to {1}, {{exclusive}}")]
+struct Range(u32, u32);
+
+#[derive(Display)]
+#[display("{name:>6}|{ratio:.2}|{name}")]
+struct Padded {
+    name: String,
+    ratio: f64,
+}
+
+#[derive(Display)]
+#[display("<{inner}>")]
+struct Tagged<T: fmt::Display> {
+    inner: T,
+}
+
+#[derive(Display)]
+#[display("origin")]
+struct Origin;
+
+/// Listing 19-22's supertrait, which needs `Display` to frame a value.
+trait OutlinePrint: fmt::Display {
+    fn outline(&self) -> String {
+        let output = self.to_string();
+        let len = output.len();
+        let border = "*".repeat(len + 4);
+        format!(
+            "{border}\n*{}*\n* {output} *\n*{}*\n{border}",
+            " ".repeat(len + 2),
+            " ".repeat(len + 2)
+        )
+    }
+}
+
+impl OutlinePrint for Point {}
+
+#[test]
+fn interpolates_named_fields() {
+    let point = Point { x: 1, y: 3 };
+    assert_eq!(point.to_string(), "(1, 3)");
+    assert_eq!(
+        point.outline(),
+        "**********\n*        *\n* (1, 3) *\n*        *\n**********"
+    );
+}
+
+#[test]
+fn interpolates_tuple_fields_and_escapes_braces() {
+    assert_eq!(Range(2, 5).to_string(), "2 to 5, {exclusive}");
+}
+
+#[test]
+fn keeps_format_specs_and_repeats() {
+    let padded = Padded {
+        name: String::from("pi"),
+        ratio: 1.5,
+    };
+    assert_eq!(padded.to_string(), "    pi|1.50|pi");
+}
+
+#[test]
+fn works_on_generic_and_unit_structs() {
+    let nested = Tagged {
+        inner: Tagged { inner: Origin },
+    };
+    assert_eq!(nested.to_string(), "<<origin>>");
+}
@@ -0,0 +1,89 @@
+use std::{error::Error, io, num::ParseIntError};
+
+use hello_macro_derive::ErrorKind;
+
+#[derive(Debug, ErrorKind)]
+enum ConfigError {
+    #[error("couldn't read the config: {0}")]
+    Read(#[from] io::Error),
+    #[error("line {line}: `{value}` isn't a number")]
+    Number {
+        line: usize,
+        value: String,
+        #[source]
+        cause: ParseIntError,
+    },
+    #[error("the config is empty")]
+    Empty,
+}
+
+fn parse(line: usize, value: &str) -> Result<u32, ConfigError> {
+    value.parse().map_err(|cause| ConfigError::Number {
+        line,
+        value: value.to_string(),
+        cause,
+    })
+}
+
+fn read(path: &str) -> Result<String, ConfigError> {
+    // `?` uses the `From` that `#[from]` asked for.
+    let text = std::fs::read_to_string(path)?;
+    if text.is_empty() {
+        return Err(ConfigError::Empty);
+    }
+    Ok(text)
+}
+
+#[test]
+fn messages_use_the_fields() {
+    let err = parse(3, "x7").unwrap_err();
+    assert_eq!(err.to_string(), "line 3: `x7` isn't a number");
+    assert_eq!(ConfigError::Empty.to_string(), "the config is empty");
+}
+
+#[test]
+fn sources_are_the_marked_fields() {
+    let err = parse(3, "x7").unwrap_err();
+    let source = err.source().unwrap();
+    assert!(source.is::<ParseIntError>());
+
+    let err = read("/no/such/config").unwrap_err();
+    assert!(matches!(err, ConfigError::Read(_)));
+    assert!(err.source().unwrap().is::<io::Error>());
+    assert!(err.to_string().starts_with("couldn't read the config: "));
+
+    assert!(ConfigError::Empty.source().is_none());
+}
+
+/// A struct, generic, with a tuple field.
+#[derive(Debug, ErrorKind)]
+#[error("{0:?} is out of range")]
+struct OutOfRange<T: std::fmt::Debug>(T);
+
+/// A field named like the formatter, and a source that's only an error once
+/// it's unboxed.
+#[derive(Debug, ErrorKind)]
+#[error("{f} failed")]
+struct Failed {
+    f: &'static str,
+    #[source]
+    cause: Box<dyn Error + Send + Sync>,
+}
+
+#[test]
+fn any_field_name_and_boxed_sources_work() {
+    let err = Failed {
+        f: "parsing",
+        cause: "x7".parse::<u8>().unwrap_err().into(),
+    };
+    assert_eq!(err.to_string(), "parsing failed");
+    assert!(err.source().unwrap().is::<ParseIntError>());
+}
+
+#[test]
+fn structs_and_generics_work_too() {
+    let err = OutOfRange(300u16);
+    assert_eq!(err.to_string(), "300 is out of range");
+    assert!(err.source().is_none());
+    let _: &dyn Error = &err;
+}
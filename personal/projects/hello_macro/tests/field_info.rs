@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+
+use hello_macro::FieldInfo;
+use hello_macro_derive::FieldInfo;
+
+#[allow(dead_code)]
+#[derive(FieldInfo)]
+struct Config<'a> {
+    name: &'a str,
+    retries: u32,
+    headers: HashMap<String, Vec<String>>,
+    raw: *const [u8; 4],
+    hook: Box<dyn Fn(&mut u8) -> bool>,
+    path: std::path::PathBuf,
+}
+
+#[allow(dead_code)]
+#[derive(FieldInfo)]
+struct Pair<A, B: Clone>(A, B);
+
+#[derive(FieldInfo)]
+struct Unit;
+
+#[test]
+fn lists_named_fields_in_order() {
+    assert_eq!(
+        Config::fields(),
+        [
+            ("name", "&'a str"),
+            ("retries", "u32"),
+            ("headers", "HashMap<String, Vec<String>>"),
+            ("raw", "*const [u8; 4]"),
+            ("hook", "Box<dyn Fn(&mut u8) -> bool>"),
+            ("path", "std::path::PathBuf"),
+        ]
+    );
+}
+
+#[test]
+fn names_tuple_fields_by_index() {
+    assert_eq!(Pair::<u8, u8>::fields(), [("0", "A"), ("1", "B")]);
+    assert!(Unit::fields().is_empty());
+}
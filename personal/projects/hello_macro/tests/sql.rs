@@ -0,0 +1,52 @@
+use hello_macro::sql::{Columns, Condition, Op, Order, Query, Value};
+use hello_macro_derive::sql;
+
+#[test]
+fn parses_the_books_example() {
+    let sql = sql!(SELECT * FROM posts WHERE id=1);
+    assert_eq!(
+        sql,
+        Query {
+            columns: Columns::All,
+            table: "posts",
+            filters: &[Condition {
+                column: "id",
+                op: Op::Eq,
+                value: Value::Int(1),
+            }],
+            order_by: None,
+            limit: None,
+        }
+    );
+    assert_eq!(sql.to_string(), "SELECT * FROM posts WHERE id = 1");
+}
+
+#[test]
+fn parses_every_clause() {
+    const RECENT: Query = sql!(
+        select title, author, type from posts
+        where published = true and score >= -2 and author != "O'Brien"
+        order by created desc
+        limit 10
+    );
+    assert_eq!(RECENT.columns, Columns::Named(&["title", "author", "type"]));
+    assert_eq!(RECENT.order_by, Some(("created", Order::Desc)));
+    assert_eq!(RECENT.limit, Some(10));
+    assert_eq!(
+        RECENT.to_string(),
+        "SELECT title, author, type FROM posts \
+         WHERE published = TRUE AND score >= -2 AND author != 'O''Brien' \
+         ORDER BY created DESC LIMIT 10"
+    );
+}
+
+#[test]
+fn tells_two_character_operators_apart() {
+    let ops: Vec<Op> =
+        sql!(SELECT * FROM t WHERE a < 1 AND b <= 1 AND c > 1 AND d >= 1 ORDER BY a ASC)
+            .filters
+            .iter()
+            .map(|condition| condition.op)
+            .collect();
+    assert_eq!(ops, [Op::Lt, Op::Le, Op::Gt, Op::Ge]);
+}
@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+
+use hello_macro::json::{AsString, ToJson};
+use hello_macro_derive::ToJson;
+
+#[derive(ToJson)]
+struct Snapshot {
+    name: String,
+    #[json(rename = "count")]
+    total: u64,
+    ratio: f64,
+    tags: Vec<&'static str>,
+    parent: Option<Box<Snapshot>>,
+    #[json(skip)]
+    _cache: Vec<u8>,
+}
+
+#[derive(ToJson)]
+enum State {
+    Idle,
+    #[json(rename = "busy")]
+    Handling,
+}
+
+#[derive(ToJson)]
+struct Pair<T>(T, State);
+
+#[derive(ToJson)]
+struct Meters(u32);
+
+#[derive(ToJson)]
+struct Nothing;
+
+#[test]
+fn structs_are_objects() {
+    let snapshot = Snapshot {
+        name: String::from("pool \"main\""),
+        total: 3,
+        ratio: 0.5,
+        tags: vec!["a", "b"],
+        parent: Some(Box::new(Snapshot {
+            name: String::from("root"),
+            total: 0,
+            ratio: f64::NAN,
+            tags: vec![],
+            parent: None,
+            _cache: vec![],
+        })),
+        _cache: vec![1, 2, 3],
+    };
+    assert_eq!(
+        snapshot.to_json(),
+        r#"{"name":"pool \"main\"","count":3,"ratio":0.5,"tags":["a","b"],"parent":{"name":"root","count":0,"ratio":null,"tags":[],"parent":null}}"#
+    );
+}
+
+#[test]
+fn enums_tuples_and_newtypes() {
+    assert_eq!(
+        [State::Idle, State::Handling].to_json(),
+        r#"["Idle","busy"]"#
+    );
+    assert_eq!(Pair(-7i8, State::Idle).to_json(), r#"[-7,"Idle"]"#);
+    assert_eq!(Meters(12).to_json(), "12");
+    assert_eq!(Nothing.to_json(), "null");
+}
+
+#[test]
+fn std_types_write_as_expected() {
+    let map = BTreeMap::from([("b", vec![(1, 'x')]), ("a", vec![])]);
+    assert_eq!(map.to_json(), r#"{"a":[],"b":[[1,"x"]]}"#);
+    assert_eq!("tab\there\u{1}".to_json(), r#""tab\there\u0001""#);
+    assert_eq!(
+        AsString(std::net::Ipv4Addr::LOCALHOST).to_json(),
+        r#""127.0.0.1""#
+    );
+    assert_eq!(f32::INFINITY.to_json(), "null");
+    assert_eq!(u64::MAX.to_json(), "18446744073709551615");
+}
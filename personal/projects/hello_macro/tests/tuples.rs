@@ -0,0 +1,103 @@
+use hello_macro::summary::{Summary, Tweet};
+use hello_macro_derive::impl_for_tuples;
+
+fn tweet(username: &str) -> Tweet {
+    Tweet {
+        username: username.to_string(),
+        content: String::new(),
+        reply: false,
+        retweet: false,
+    }
+}
+
+#[test]
+fn summarizes_tuples_of_every_length() {
+    assert_eq!((tweet("a"),).summarize(), "(Read more from @a...)");
+    let twelve = (
+        tweet("a"),
+        tweet("b"),
+        tweet("c"),
+        tweet("d"),
+        tweet("e"),
+        tweet("f"),
+        tweet("g"),
+        tweet("h"),
+        tweet("i"),
+        tweet("j"),
+        tweet("k"),
+        tweet("l"),
+    );
+    assert_eq!(
+        twelve.summarize_author(),
+        "@a, @b, @c, @d, @e, @f, @g, @h, @i, @j, @k, @l"
+    );
+    // Tuples of tuples are tuples of summaries.
+    assert_eq!(
+        ((tweet("a"), tweet("b")), tweet("c")).summarize_author(),
+        "@a, @b, @c"
+    );
+}
+
+/// How many bytes a value takes when written out, worked out from its
+/// type alone.
+trait Width {
+    const WIDTH: usize;
+
+    fn names() -> Vec<&'static str>;
+}
+
+impl Width for u8 {
+    const WIDTH: usize = 1;
+
+    fn names() -> Vec<&'static str> {
+        vec!["u8"]
+    }
+}
+
+impl Width for u32 {
+    const WIDTH: usize = 4;
+
+    fn names() -> Vec<&'static str> {
+        vec!["u32"]
+    }
+}
+
+const fn sum(widths: &[usize]) -> usize {
+    let (mut total, mut i) = (0, 0);
+    while i < widths.len() {
+        total += widths[i];
+        i += 1;
+    }
+    total
+}
+
+impl_for_tuples!(Width, 0..4, {
+    const WIDTH: usize = sum(&[#(<#T as Width>::WIDTH),*]);
+
+    fn names() -> Vec<&'static str> {
+        std::iter::empty()#(.chain(#T::names()))*.collect()
+    }
+});
+
+#[test]
+fn types_stand_in_for_elements() {
+    assert_eq!(<()>::WIDTH, 0);
+    assert_eq!(<(u8, u32, u8)>::WIDTH, 6);
+    assert_eq!(<(u32, (u8, u8))>::WIDTH, 6);
+    assert_eq!(<(u8, u32)>::names(), ["u8", "u32"]);
+}
+
+trait Plain {
+    fn plain(&self) -> bool {
+        true
+    }
+}
+
+impl Plain for u8 {}
+
+impl_for_tuples!(Plain, 1..=3);
+
+#[test]
+fn marker_traits_need_no_body() {
+    assert!((1u8, 2u8, 3u8).plain());
+}
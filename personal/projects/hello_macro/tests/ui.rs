@@ -0,0 +1,8 @@
+/// Every derive's expansions that should compile, and misuses that
+/// shouldn't, with the errors they should give.
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/pass/*.rs");
+    t.compile_fail("tests/ui/fail/*.rs");
+}
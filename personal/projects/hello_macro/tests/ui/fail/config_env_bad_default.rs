@@ -0,0 +1,5 @@
+use hello_macro_derive::config_env;
+
+const SEPARATOR: char = config_env!("SEPARATOR", ',');
+
+fn main() {}
@@ -0,0 +1,6 @@
+use hello_macro_derive::config_env;
+
+// The package name is never a number.
+const WORKERS: usize = config_env!("CARGO_PKG_NAME", 4usize);
+
+fn main() {}
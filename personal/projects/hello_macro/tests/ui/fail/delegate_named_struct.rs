@@ -0,0 +1,9 @@
+use hello_macro_derive::Delegate;
+
+#[derive(Delegate)]
+#[delegate(Deref)]
+struct Wrapper {
+    items: Vec<String>,
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+use hello_macro_derive::Delegate;
+
+#[derive(Delegate)]
+#[delegate(len, truncate)]
+struct Wrapper(Vec<String>);
+
+fn main() {}
@@ -0,0 +1,9 @@
+use hello_macro_derive::Display;
+
+#[derive(Display)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+fn main() {}
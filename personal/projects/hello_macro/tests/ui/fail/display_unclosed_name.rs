@@ -0,0 +1,10 @@
+use hello_macro_derive::Display;
+
+#[derive(Display)]
+#[display("({x}, {y)")]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+fn main() {}
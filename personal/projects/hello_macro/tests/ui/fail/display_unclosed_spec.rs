@@ -0,0 +1,7 @@
+use hello_macro_derive::Display;
+
+#[derive(Display)]
+#[display("{0:>5")]
+struct Meters(f64);
+
+fn main() {}
@@ -0,0 +1,10 @@
+use hello_macro_derive::Display;
+
+#[derive(Display)]
+#[display("{int}")]
+union Bits {
+    int: u32,
+    float: f32,
+}
+
+fn main() {}
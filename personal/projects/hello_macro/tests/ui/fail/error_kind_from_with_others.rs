@@ -0,0 +1,11 @@
+use std::io;
+
+use hello_macro_derive::ErrorKind;
+
+#[derive(Debug, ErrorKind)]
+enum LoadError {
+    #[error("couldn't load {1}")]
+    Read(#[from] io::Error, String),
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use hello_macro_derive::FieldInfo;
+
+#[derive(FieldInfo)]
+enum Shape {
+    Circle { radius: f64 },
+}
+
+fn main() {}
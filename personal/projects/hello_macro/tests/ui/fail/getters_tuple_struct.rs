@@ -0,0 +1,6 @@
+use hello_macro_derive::Getters;
+
+#[derive(Getters)]
+struct Meters(f64);
+
+fn main() {}
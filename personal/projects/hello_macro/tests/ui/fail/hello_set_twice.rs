@@ -0,0 +1,8 @@
+use hello_macro_derive::HelloMacro;
+
+#[derive(HelloMacro)]
+#[hello(name = "Flapjacks")]
+#[hello(name = "Hotcakes")]
+struct Pancakes;
+
+fn main() {}
@@ -0,0 +1,8 @@
+use hello_macro_derive::Setters;
+
+#[derive(Setters)]
+enum Shape {
+    Circle { radius: f64 },
+}
+
+fn main() {}
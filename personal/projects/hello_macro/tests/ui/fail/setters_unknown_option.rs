@@ -0,0 +1,9 @@
+use hello_macro_derive::Setters;
+
+#[derive(Setters)]
+struct Config {
+    #[set(copy)]
+    port: u16,
+}
+
+fn main() {}
@@ -0,0 +1,5 @@
+use hello_macro_derive::sql;
+
+fn main() {
+    let _ = sql!(SELECT * FROM posts LIMIT 5 OFFSET 10);
+}
@@ -0,0 +1,13 @@
+use hello_macro_derive::impl_for_tuples;
+
+trait Count {
+    fn count(&self) -> usize;
+}
+
+impl_for_tuples!(Count, 1..=3, {
+    fn count(&self) -> usize {
+        #i
+    }
+});
+
+fn main() {}
@@ -0,0 +1,18 @@
+// Without the where clause on the impl, `Wrapper<T>` wouldn't be well
+// formed there: it needs `T: Default`.
+
+use hello_macro::HelloMacro;
+use hello_macro_derive::HelloMacro;
+
+#[derive(HelloMacro)]
+struct Wrapper<T>(T)
+where
+    T: Default;
+
+fn greet<T: HelloMacro>() {
+    T::hello_macro();
+}
+
+fn main() {
+    greet::<Wrapper<u8>>();
+}
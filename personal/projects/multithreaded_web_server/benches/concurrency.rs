@@ -0,0 +1,189 @@
+//! Serves the same synthetic workloads three ways, and compares them:
+//!
+//! - a thread spawned per connection, as in the book before the pool,
+//! - the thread pool (`server::run`),
+//! - the async front end, which still runs handlers on the pool.
+//!
+//! Each run starts a server on a free port and has a fixed number of client
+//! threads make requests over fresh connections until they've made their
+//! share, then prints a table:
+//!
+//! ```text
+//! cargo bench --bench concurrency
+//! ```
+
+use std::{
+    hint::black_box,
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use multithreaded_web_server::{
+    http::Response,
+    router::Router,
+    server::{self, Server},
+};
+use thread_pool::ThreadPool;
+
+const POOL_THREADS: usize = 4;
+const CLIENTS: usize = 16;
+const REQUESTS_PER_CLIENT: usize = 25;
+
+#[derive(Clone, Copy)]
+enum Model {
+    SpawnPerConnection,
+    ThreadPool,
+    #[cfg(unix)]
+    Async,
+}
+
+impl Model {
+    fn name(self) -> &'static str {
+        match self {
+            Model::SpawnPerConnection => "spawn per connection",
+            Model::ThreadPool => "thread pool",
+            #[cfg(unix)]
+            Model::Async => "async + pool",
+        }
+    }
+
+    /// Start serving on a free port, returning its address. The server runs
+    /// until the process exits.
+    fn start(self) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = Arc::new(Server::new(router()));
+
+        thread::spawn(move || match self {
+            Model::SpawnPerConnection => {
+                for stream in listener.incoming() {
+                    let stream = stream.unwrap();
+                    let server = Arc::clone(&server);
+                    thread::spawn(move || server::handle_connection(stream, &server));
+                }
+            }
+            Model::ThreadPool => {
+                let pool = ThreadPool::new(POOL_THREADS);
+                server::run(listener, &pool, server);
+            }
+            #[cfg(unix)]
+            Model::Async => {
+                let pool = Arc::new(ThreadPool::new(POOL_THREADS));
+                multithreaded_web_server::async_server::run(listener, pool, server).unwrap();
+            }
+        });
+        addr
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Workload {
+    Cpu,
+    Sleep,
+    Mixed,
+}
+
+impl Workload {
+    fn name(self) -> &'static str {
+        match self {
+            Workload::Cpu => "cpu",
+            Workload::Sleep => "sleep",
+            Workload::Mixed => "mixed",
+        }
+    }
+
+    /// The path the `n`th request asks for.
+    fn path(self, n: usize) -> &'static str {
+        match self {
+            Workload::Cpu => "/cpu",
+            Workload::Sleep => "/sleep",
+            Workload::Mixed if n.is_multiple_of(2) => "/cpu",
+            Workload::Mixed => "/sleep",
+        }
+    }
+}
+
+fn router() -> Router {
+    Router::new(|_| Response::not_found("no such workload"))
+        .route("/cpu", |_| {
+            // Roughly a tenth of a millisecond of arithmetic.
+            let sum = (0..100_000u64).fold(0u64, |sum, n| black_box(sum ^ n.wrapping_mul(31)));
+            Response::ok(sum.to_string())
+        })
+        .route("/sleep", |_| {
+            thread::sleep(Duration::from_millis(5));
+            Response::ok("slept")
+        })
+}
+
+fn request(addr: SocketAddr, path: &str) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET {path} HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+    assert!(response.starts_with(b"HTTP/1.1 200"));
+}
+
+/// Run the clients against `addr`, returning the total time taken and the
+/// mean time per request.
+fn drive(addr: SocketAddr, workload: Workload) -> (Duration, Duration) {
+    let started = Instant::now();
+    let clients: Vec<_> = (0..CLIENTS)
+        .map(|client| {
+            thread::spawn(move || {
+                let mut spent = Duration::ZERO;
+                for n in 0..REQUESTS_PER_CLIENT {
+                    let sent = Instant::now();
+                    request(addr, workload.path(client + n));
+                    spent += sent.elapsed();
+                }
+                spent
+            })
+        })
+        .collect();
+    let spent: Duration = clients.into_iter().map(|c| c.join().unwrap()).sum();
+
+    let requests = (CLIENTS * REQUESTS_PER_CLIENT) as u32;
+    (started.elapsed(), spent / requests)
+}
+
+fn main() {
+    let models = [
+        Model::SpawnPerConnection,
+        Model::ThreadPool,
+        #[cfg(unix)]
+        Model::Async,
+    ];
+    let workloads = [Workload::Cpu, Workload::Sleep, Workload::Mixed];
+    let requests = CLIENTS * REQUESTS_PER_CLIENT;
+
+    let mut rows = Vec::new();
+    for model in models {
+        let addr = model.start();
+        for workload in workloads {
+            let (total, latency) = drive(addr, workload);
+            let per_second = requests as f64 / total.as_secs_f64();
+            rows.push(format!(
+                "| {:<20} | {:<8} | {:>10.0} | {:>17.2} |",
+                model.name(),
+                workload.name(),
+                per_second,
+                latency.as_secs_f64() * 1000.0,
+            ));
+        }
+    }
+
+    println!(
+        "{requests} requests from {CLIENTS} clients, {POOL_THREADS} pool threads, \
+         {} CPUs\n",
+        thread::available_parallelism().map_or(1, |n| n.get())
+    );
+    println!("| model                | workload | requests/s | mean latency (ms) |");
+    println!("|----------------------|----------|------------|-------------------|");
+    for row in rows {
+        println!("{row}");
+    }
+}
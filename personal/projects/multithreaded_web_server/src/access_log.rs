@@ -0,0 +1,376 @@
+//! A file-backed access log that rotates itself by size and/or by day.
+//!
+//! Every worker writes through the same `AccessLog`. Writes and rotation
+//! happen under one lock, so a line is never split across files or written
+//! to a file that is being renamed away.
+//!
+//! Processes can't share that lock, so `--prefork`'s workers each open the
+//! log with [`AccessLog::open_shared`] instead, which also takes a lock on
+//! `PATH.lock` around every write and reopens the log whenever another
+//! process has rotated it away. Then only one of them rotates it, and
+//! `max_bytes` counts all their lines.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, prelude::*},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// When to start a new log file, and how many old ones to keep.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rotation {
+    /// Rotate before a write would take the file past this many bytes.
+    pub max_bytes: Option<u64>,
+    /// Rotate at the first write after midnight (UTC).
+    pub daily: bool,
+    /// How many rotated files (`access.log.1`, `access.log.2`, ...) to keep.
+    pub keep: usize,
+}
+
+impl Default for Rotation {
+    fn default() -> Rotation {
+        Rotation {
+            max_bytes: None,
+            daily: false,
+            keep: 5,
+        }
+    }
+}
+
+struct LogFile {
+    file: File,
+    size: u64,
+    day: u64,
+}
+
+pub struct AccessLog {
+    path: PathBuf,
+    rotation: Rotation,
+    current: Mutex<LogFile>,
+    /// `PATH.lock`, if other processes write the log too.
+    shared: Option<File>,
+}
+
+impl AccessLog {
+    /// Open (or create) the log at `path`, appending to what's already there.
+    pub fn open(path: impl Into<PathBuf>, rotation: Rotation) -> io::Result<AccessLog> {
+        let path = path.into();
+        let current = Mutex::new(open_file(&path, day(SystemTime::now()))?);
+        Ok(AccessLog {
+            path,
+            rotation,
+            current,
+            shared: None,
+        })
+    }
+
+    /// Like [`AccessLog::open`], for a log that other processes write to
+    /// and rotate as well.
+    ///
+    /// The lock belongs to the open file, which a forked child shares with
+    /// its parent, so each process has to call this after forking rather
+    /// than inherit an `AccessLog`.
+    #[cfg(unix)]
+    pub fn open_shared(path: impl Into<PathBuf>, rotation: Rotation) -> io::Result<AccessLog> {
+        let mut log = AccessLog::open(path, rotation)?;
+        let mut lock_path = log.path.clone().into_os_string();
+        lock_path.push(".lock");
+        log.shared = Some(
+            OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(lock_path)?,
+        );
+        Ok(log)
+    }
+
+    /// Log one request in Common Log Format (with the time as a Unix timestamp).
+    pub fn record(
+        &self,
+        peer: Option<SocketAddr>,
+        request_line: &str,
+        status: u16,
+        bytes: Option<u64>,
+    ) {
+        let now = SystemTime::now();
+        let peer = peer.map_or_else(|| "-".to_string(), |peer| peer.ip().to_string());
+        let timestamp = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let bytes = bytes.map_or_else(|| "-".to_string(), |bytes| bytes.to_string());
+        let line = format!("{peer} - - [{timestamp}] \"{request_line}\" {status} {bytes}");
+
+        if let Err(err) = self.write_line_at(&line, now) {
+            crate::log_error!("Failed to write access log: {err}");
+        }
+    }
+
+    pub fn write_line(&self, line: &str) -> io::Result<()> {
+        self.write_line_at(line, SystemTime::now())
+    }
+
+    fn write_line_at(&self, line: &str, now: SystemTime) -> io::Result<()> {
+        let mut current = self.current.lock().unwrap();
+        let _shared = match &self.shared {
+            Some(lock) => Some(SharedLock::take(lock, &mut current, &self.path)?),
+            None => None,
+        };
+        let len = line.len() as u64 + 1;
+
+        let too_big = self
+            .rotation
+            .max_bytes
+            .is_some_and(|max| current.size > 0 && current.size + len > max);
+        let new_day = self.rotation.daily && day(now) != current.day;
+        if too_big || new_day {
+            *current = self.rotate(day(now))?;
+        }
+
+        writeln!(current.file, "{line}")?;
+        current.size += len;
+        Ok(())
+    }
+
+    /// Shift `log.1` to `log.2` and so on, dropping the oldest, then move the
+    /// live file to `log.1` and start a fresh one.
+    fn rotate(&self, day: u64) -> io::Result<LogFile> {
+        if self.rotation.keep == 0 {
+            remove_if_exists(&self.path)?;
+        } else {
+            remove_if_exists(&self.rotated(self.rotation.keep))?;
+            for n in (1..self.rotation.keep).rev() {
+                rename_if_exists(&self.rotated(n), &self.rotated(n + 1))?;
+            }
+            rename_if_exists(&self.path, &self.rotated(1))?;
+        }
+        open_file(&self.path, day)
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{n}"));
+        PathBuf::from(name)
+    }
+}
+
+/// The lock on a shared log's `PATH.lock`, released when dropped.
+struct SharedLock<'a>(&'a File);
+
+impl SharedLock<'_> {
+    /// Wait for the other processes to finish with the log, then catch up
+    /// with what they did to it: reopen it if one of them rotated it away,
+    /// and count the lines they added.
+    #[cfg(unix)]
+    fn take<'a>(lock: &'a File, current: &mut LogFile, path: &Path) -> io::Result<SharedLock<'a>> {
+        use std::os::unix::fs::MetadataExt;
+
+        lock.lock()?;
+        let held = SharedLock(lock);
+        let ours = current.file.metadata()?;
+        match fs::metadata(path) {
+            Ok(live) if (live.dev(), live.ino()) == (ours.dev(), ours.ino()) => {
+                current.size = ours.len();
+            }
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            // The day it was last written, so a file nobody has written
+            // since midnight still rotates.
+            _ => {
+                *current = open_file(path, current.day)?;
+                let modified = current.file.metadata()?.modified()?;
+                current.day = day(modified);
+            }
+        }
+        Ok(held)
+    }
+
+    #[cfg(not(unix))]
+    fn take<'a>(
+        _lock: &'a File,
+        _current: &mut LogFile,
+        _path: &Path,
+    ) -> io::Result<SharedLock<'a>> {
+        unreachable!("shared logs are only opened on unix")
+    }
+}
+
+impl Drop for SharedLock<'_> {
+    fn drop(&mut self) {
+        let _ = self.0.unlock();
+    }
+}
+
+fn open_file(path: &Path, day: u64) -> io::Result<LogFile> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+    Ok(LogFile { file, size, day })
+}
+
+fn day(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / SECONDS_PER_DAY
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+fn rename_if_exists(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, thread, time::Duration};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("access-log-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn rotates_by_size_and_keeps_n_files() {
+        let dir = temp_dir("size");
+        let path = dir.join("access.log");
+        let log = AccessLog::open(
+            &path,
+            Rotation {
+                max_bytes: Some(10),
+                daily: false,
+                keep: 2,
+            },
+        )
+        .unwrap();
+
+        for line in ["first", "second", "third", "fourth"] {
+            log.write_line(line).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(
+            fs::read_to_string(dir.join("access.log.1")).unwrap(),
+            "third\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("access.log.2")).unwrap(),
+            "second\n"
+        );
+        assert!(!dir.join("access.log.3").exists());
+    }
+
+    #[test]
+    fn rotates_daily() {
+        let dir = temp_dir("daily");
+        let path = dir.join("access.log");
+        let log = AccessLog::open(
+            &path,
+            Rotation {
+                daily: true,
+                ..Rotation::default()
+            },
+        )
+        .unwrap();
+
+        let now = SystemTime::now();
+        log.write_line_at("today", now).unwrap();
+        log.write_line_at("tomorrow", now + Duration::from_secs(SECONDS_PER_DAY))
+            .unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "tomorrow\n");
+        assert_eq!(
+            fs::read_to_string(dir.join("access.log.1")).unwrap(),
+            "today\n"
+        );
+    }
+
+    #[test]
+    fn concurrent_writers_never_lose_or_split_lines() {
+        let dir = temp_dir("concurrent");
+        let path = dir.join("access.log");
+        let log = Arc::new(
+            AccessLog::open(
+                &path,
+                Rotation {
+                    max_bytes: Some(200),
+                    daily: false,
+                    keep: 100,
+                },
+            )
+            .unwrap(),
+        );
+
+        let writers: Vec<_> = (0..4)
+            .map(|writer| {
+                let log = Arc::clone(&log);
+                thread::spawn(move || {
+                    for n in 0..50 {
+                        log.write_line(&format!("writer {writer} line {n}"))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let mut lines = 0;
+        for entry in fs::read_dir(&dir).unwrap() {
+            let contents = fs::read_to_string(entry.unwrap().path()).unwrap();
+            assert!(contents.len() <= 200);
+            for line in contents.lines() {
+                assert!(line.starts_with("writer "), "mangled line {line:?}");
+                lines += 1;
+            }
+        }
+        assert_eq!(lines, 200);
+    }
+
+    #[test]
+    fn processes_sharing_a_log_rotate_it_once() {
+        let dir = temp_dir("shared");
+        let path = dir.join("access.log");
+        let rotation = Rotation {
+            max_bytes: Some(100),
+            daily: false,
+            keep: 100,
+        };
+        // Two opens lock independently, like two forked workers.
+        let logs = [
+            AccessLog::open_shared(&path, rotation.clone()).unwrap(),
+            AccessLog::open_shared(&path, rotation).unwrap(),
+        ];
+
+        for n in 0..40 {
+            logs[n % 2].write_line(&format!("line {n:02}")).unwrap();
+        }
+
+        let mut lines = Vec::new();
+        for n in (1..100).rev() {
+            if let Ok(contents) = fs::read_to_string(dir.join(format!("access.log.{n}"))) {
+                assert!(contents.len() <= 100);
+                lines.extend(contents.lines().map(String::from));
+            }
+        }
+        let live = fs::read_to_string(&path).unwrap();
+        assert!(live.len() <= 100);
+        lines.extend(live.lines().map(String::from));
+
+        let expected: Vec<_> = (0..40).map(|n| format!("line {n:02}")).collect();
+        assert_eq!(lines, expected);
+    }
+}
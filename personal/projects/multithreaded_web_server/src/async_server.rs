@@ -0,0 +1,430 @@
+//! The same server as `server`, but built on the in-crate async executor.
+//!
+//! Every connection is a task on a single thread, so slow clients cost a
+//! little memory rather than a worker. Handlers still run on the thread pool,
+//! and so does writing out their responses, since a handler or a streamed
+//! body that blocks (like `/sleep`, or a file) would otherwise stall every
+//! other connection until it returned.
+
+use std::{
+    future::Future,
+    io,
+    net::TcpListener,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use advanced_features::uninit_buf::UninitBuf;
+use thread_pool::{semaphore::Permit, ThreadPool};
+
+use crate::{
+    defer,
+    executor::{self, AsyncTcpListener, AsyncTcpStream, Executor},
+    http::{head_end, Body, Request, MAX_HEAD_SIZE},
+    server::{self, Server, ACCEPT_BACKOFF, MAX_BODY_SIZE, READ_TIMEOUT},
+    stats::{ConnectionState, ConnectionStats},
+    ServerError,
+};
+
+/// Serve connections from `listener` until accepting fails, running the
+/// handlers on `pool`.
+pub fn run(
+    listener: TcpListener,
+    pool: Arc<ThreadPool>,
+    server: Arc<Server>,
+) -> Result<(), ServerError> {
+    let listener = Arc::new(AsyncTcpListener::new(listener)?);
+    let executor = Executor::new();
+    let spawner = executor.spawner();
+    let failure = Arc::new(Mutex::new(None));
+
+    let admitting = Arc::clone(&server);
+    let accept = move || {
+        let listener = Arc::clone(&listener);
+        let server = Arc::clone(&admitting);
+        async move {
+            let permit = admit(&server).await;
+            let (stream, _) = listener.accept().await?;
+            Ok((stream, permit))
+        }
+    };
+    let serve = move |(stream, permit)| {
+        let server = Arc::clone(&server);
+        let pool = Arc::clone(&pool);
+        spawner.spawn(async move {
+            let _permit = permit;
+            if let Err(err) = handle_connection(stream, &pool, server).await {
+                crate::log_error!("Error handling connection: {err}");
+            }
+        });
+    };
+    let accept_failure = Arc::clone(&failure);
+    executor.spawner().spawn(async move {
+        if let Err(err) = accept_loop(accept, serve).await {
+            *accept_failure.lock().unwrap() = Some(err);
+        }
+    });
+
+    executor.run()?;
+    let failure = failure.lock().unwrap().take();
+    match failure {
+        Some(err) => Err(err.into()),
+        None => Ok(()),
+    }
+}
+
+/// Hand each accepted connection to `serve`, backing off when accepting
+/// fails for a reason that might clear up and giving up otherwise.
+///
+/// The backoff is what lets the other tasks run: without it a listener
+/// that keeps failing would never return `Pending`.
+async fn accept_loop<S, Fut>(
+    mut accept: impl FnMut() -> Fut,
+    mut serve: impl FnMut(S),
+) -> io::Result<()>
+where
+    Fut: Future<Output = io::Result<S>>,
+{
+    loop {
+        match accept().await {
+            Ok(stream) => serve(stream),
+            Err(err) if server::is_transient(&err) => {
+                crate::log_error!("Failed to accept connection: {}", ServerError::from(err));
+                executor::sleep(ACCEPT_BACKOFF).await;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Wait for a place under the server's connection cap. Places are given back
+/// on the pool's threads, which can't wake this one, so it checks back every
+/// [`ACCEPT_BACKOFF`].
+async fn admit(server: &Server) -> Option<Permit> {
+    loop {
+        if let Some(permit) = server.try_admit() {
+            return permit;
+        }
+        executor::sleep(ACCEPT_BACKOFF).await;
+    }
+}
+
+async fn handle_connection(
+    mut stream: AsyncTcpStream,
+    pool: &ThreadPool,
+    server: Arc<Server>,
+) -> Result<(), ServerError> {
+    let peer = stream.peer_addr();
+    let connections = Arc::clone(&server.connections);
+    let connection = connections.register(peer);
+    let stats = Arc::clone(connection.stats());
+    connections.start_request();
+    defer! { connections.finish_request(); }
+
+    stats.set_state(ConnectionState::Reading);
+    let request = read_request(&mut stream, READ_TIMEOUT, &stats).await;
+    stats.set_state(ConnectionState::Handling);
+    // A streamed body, like a file or an echoed request, is read while the
+    // response is written, and those reads block. Writing the response out
+    // on the pool keeps them off this thread, where they'd stall every other
+    // connection.
+    let job = pool.execute_future(move || {
+        let request_line = request.as_ref().ok().map(server::request_line);
+        let version = server::response_version(&request);
+        let (response, result) = server::route(request, &server.router);
+        server.log(peer, request_line.as_deref(), &response);
+
+        let mut bytes = Vec::new();
+        let written = response
+            .with_header("Connection", "close")
+            .write_to(&version, &mut bytes);
+        (written.map(|()| bytes), result)
+    });
+    let (bytes, result) = match job {
+        Ok(job) => job.await,
+        Err(err) => {
+            crate::log_error!("Dropping connection: {err}");
+            return Ok(());
+        }
+    };
+    let bytes = bytes?;
+    stats.set_state(ConnectionState::Writing);
+    stream.write_all(&bytes).await?;
+    stats.add_written(bytes.len() as u64);
+
+    result
+}
+
+/// Read the head and the whole body, since handlers can't wait on the
+/// executor for the body to arrive.
+///
+/// Each read gives up after `timeout`, so a client that goes quiet is
+/// dropped instead of holding its task and buffer forever.
+async fn read_request(
+    stream: &mut AsyncTcpStream,
+    timeout: Duration,
+    stats: &ConnectionStats,
+) -> Result<Request, ServerError> {
+    let mut buffer = Vec::new();
+    let mut chunk = UninitBuf::with_capacity(1024);
+    let end = loop {
+        // Reads stop at the size limit, so a head that fills it without
+        // ending is too large.
+        let room = MAX_HEAD_SIZE - buffer.len();
+        if room == 0 {
+            return Err(ServerError::TooLarge);
+        }
+        // Only the new bytes, and the three before them, can finish the
+        // blank line, so the rest isn't scanned again.
+        let from = buffer.len().saturating_sub(3);
+        read_more(stream, &mut chunk, &mut buffer, room, timeout, stats).await?;
+        if let Some(end) = head_end(&buffer[from..]) {
+            break from + end;
+        }
+    };
+
+    let mut request = Request::read_from(&mut &buffer[..end])?;
+    let length = request.content_length()?;
+    if length > MAX_BODY_SIZE {
+        return Err(ServerError::TooLarge);
+    }
+
+    // `length` is capped above, so it fits in a usize.
+    let total = end + length as usize;
+    while buffer.len() < total {
+        let room = total - buffer.len();
+        read_more(stream, &mut chunk, &mut buffer, room, timeout, stats)
+            .await
+            .map_err(|err| match err {
+                ServerError::Parse(_) => ServerError::Protocol(format!(
+                    "body ended after {} of {length} bytes",
+                    buffer.len() - end
+                )),
+                err => err,
+            })?;
+    }
+    request.body = Body::from_bytes(&buffer[end..total]);
+
+    Ok(request)
+}
+
+/// Read at most `max` more bytes onto the end of `buffer`, through `chunk`,
+/// waiting no longer than `timeout` for them and counting them in `stats`.
+async fn read_more(
+    stream: &mut AsyncTcpStream,
+    chunk: &mut UninitBuf<u8>,
+    buffer: &mut Vec<u8>,
+    max: usize,
+    timeout: Duration,
+    stats: &ConnectionStats,
+) -> Result<(), ServerError> {
+    chunk.clear();
+    let read = executor::timeout(timeout, stream.read_uninit_max(chunk, max))
+        .await
+        .ok_or(ServerError::Timeout)??;
+    match read {
+        0 => Err(ServerError::Parse(
+            "connection closed before request was complete".into(),
+        )),
+        _ => {
+            stats.add_read(read as u64);
+            buffer.extend_from_slice(chunk.assume_init_slice());
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::prelude::*,
+        net::TcpStream,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            mpsc,
+        },
+        thread,
+    };
+
+    use advanced_features::assert_err;
+
+    use super::*;
+    use thread_pool::Semaphore;
+
+    use crate::{http::Response, router::Router, stats::ConnectionRegistry};
+
+    #[test]
+    fn failing_accepts_let_other_tasks_run() {
+        let executor = Executor::new();
+        let other_ran = Arc::new(AtomicBool::new(false));
+        let result = Arc::new(Mutex::new(None));
+
+        let ran = Arc::clone(&other_ran);
+        let accept = move || {
+            let ran = ran.load(Ordering::SeqCst);
+            async move {
+                // Out of descriptors until the other task has had a turn.
+                let code = if ran { 22 } else { 24 };
+                Err::<(), _>(io::Error::from_raw_os_error(code))
+            }
+        };
+        let accepted = Arc::clone(&result);
+        executor.spawner().spawn(async move {
+            *accepted.lock().unwrap() = Some(accept_loop(accept, |()| {}).await);
+        });
+        let ran = Arc::clone(&other_ran);
+        executor
+            .spawner()
+            .spawn(async move { ran.store(true, Ordering::SeqCst) });
+
+        executor.run().unwrap();
+        assert!(other_ran.load(Ordering::SeqCst));
+        let err = assert_err!(result.lock().unwrap().take().unwrap());
+        assert_eq!(err.raw_os_error(), Some(22));
+    }
+
+    /// Run `read_request` on a fresh connection after the client has sent
+    /// `raw`, keeping the client connected until it returns.
+    fn read_after(raw: &[u8], timeout: Duration) -> Result<Request, ServerError> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let mut stream = AsyncTcpStream::new(stream).unwrap();
+        client.write_all(raw).unwrap();
+
+        let executor = Executor::new();
+        let result = Arc::new(Mutex::new(None));
+        let read = Arc::clone(&result);
+        executor.spawner().spawn(async move {
+            let connections = ConnectionRegistry::new();
+            let connection = connections.register(None);
+            let request = read_request(&mut stream, timeout, connection.stats()).await;
+            *read.lock().unwrap() = Some(request);
+        });
+        executor.run().unwrap();
+
+        let result = result.lock().unwrap().take().unwrap();
+        result
+    }
+
+    #[test]
+    fn reads_a_head_and_body() {
+        let raw = b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello";
+        let request = read_after(raw, READ_TIMEOUT).unwrap();
+        assert_eq!(request.body.into_bytes().unwrap(), b"hello");
+    }
+
+    #[test]
+    fn silent_clients_time_out() {
+        let result = read_after(b"", Duration::from_millis(20));
+        assert_err!(result, ServerError::Timeout);
+
+        let result = read_after(
+            b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhe",
+            Duration::from_millis(20),
+        );
+        assert_err!(result, ServerError::Timeout);
+    }
+
+    #[test]
+    fn oversized_heads_are_too_large() {
+        let result = read_after(&[b'a'; 2 * MAX_HEAD_SIZE], READ_TIMEOUT);
+        assert_err!(result, ServerError::TooLarge);
+    }
+
+    /// A body that can't be read until `release` is sent on.
+    struct Gated(Mutex<mpsc::Receiver<()>>, bool);
+
+    impl Read for Gated {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.1 {
+                return Ok(0);
+            }
+            self.0.lock().unwrap().recv().unwrap();
+            self.1 = true;
+            buf[..4].copy_from_slice(b"slow");
+            Ok(4)
+        }
+    }
+
+    #[test]
+    fn a_slow_streamed_body_does_not_stall_other_connections() {
+        let (release, gate) = mpsc::channel();
+        let gate = Mutex::new(Some(gate));
+        let router = Router::new(|_| Response::ok("fast")).route("/slow", move |_| {
+            let gate = gate.lock().unwrap().take().unwrap();
+            Response::stream(200, "OK", Gated(Mutex::new(gate), false), None)
+        });
+        let server = Arc::new(Server::new(router));
+        let pool = Arc::new(ThreadPool::new(2));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let get = move |path: &'static str| {
+            thread::spawn(move || {
+                let mut client = TcpStream::connect(addr).unwrap();
+                client
+                    .set_read_timeout(Some(Duration::from_secs(2)))
+                    .unwrap();
+                write!(client, "GET {path} HTTP/1.1\r\n\r\n").unwrap();
+                let mut reply = String::new();
+                client.read_to_string(&mut reply).map(|_| reply)
+            })
+        };
+        let slow = get("/slow");
+        let (first, _) = listener.accept().unwrap();
+        let fast = get("/fast");
+        let (second, _) = listener.accept().unwrap();
+
+        let executor = Executor::new();
+        for stream in [first, second] {
+            let stream = AsyncTcpStream::new(stream).unwrap();
+            let (pool, server) = (Arc::clone(&pool), Arc::clone(&server));
+            executor.spawner().spawn(async move {
+                handle_connection(stream, &pool, server).await.unwrap();
+            });
+        }
+        let served = thread::spawn(move || {
+            // The fast reply has to get out while the slow body is stuck.
+            let fast = fast.join().unwrap();
+            release.send(()).unwrap();
+            (fast, slow.join().unwrap())
+        });
+        executor.run().unwrap();
+
+        let (fast, slow) = served.join().unwrap();
+        assert!(fast.unwrap().ends_with("fast"));
+        assert!(slow.unwrap().contains("slow"));
+
+        let metrics = server.connections.metrics();
+        assert_eq!(metrics.get("connections_total"), Some(2));
+        assert_eq!(metrics.get("requests_total"), Some(2));
+        assert_eq!(server.connections.in_flight(), 0);
+        assert!(server.connections.snapshot().is_empty());
+    }
+
+    #[test]
+    fn accepting_waits_for_a_place_under_the_cap() {
+        let mut server = Server::new(Router::new(|_| Response::ok("")));
+        server.connection_limit = Some(Arc::new(Semaphore::new(1)));
+        let server = Arc::new(server);
+        let held = server.try_admit().unwrap();
+        assert!(server.try_admit().is_none());
+
+        let executor = Executor::new();
+        let admitted = Arc::new(AtomicBool::new(false));
+        let (waiting, done) = (Arc::clone(&server), Arc::clone(&admitted));
+        executor.spawner().spawn(async move {
+            let permit = admit(&waiting).await;
+            done.store(permit.is_some(), Ordering::SeqCst);
+        });
+        let release = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            drop(held);
+        });
+        executor.run().unwrap();
+
+        release.join().unwrap();
+        assert!(admitted.load(Ordering::SeqCst));
+    }
+}
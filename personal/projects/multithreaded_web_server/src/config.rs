@@ -0,0 +1,108 @@
+//! The optional config file, loaded once at startup with `--config PATH`.
+//!
+//! It's a small INI-style file: `# comments`, `[section]` headers and
+//! `key = value` lines. For now the only section is `[mime]`, mapping file
+//! extensions to the `Content-Type` the static handler sends for them:
+//!
+//! ```text
+//! [mime]
+//! wasm = application/wasm
+//! txt = text/plain; charset=latin1
+//! ```
+
+use std::{fs, io, path::Path};
+
+use hello_macro_derive::ToJson;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, ToJson)]
+pub struct Config {
+    /// Extension (without the dot) to `Content-Type`, in file order.
+    pub mime_types: Vec<(String, String)>,
+}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Config> {
+        Config::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> io::Result<Config> {
+        let mut config = Config::default();
+        let mut section = None;
+
+        for (number, line) in text.lines().enumerate() {
+            let invalid = |msg: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {msg}", number + 1),
+                )
+            };
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                match name.trim() {
+                    "mime" => section = Some("mime"),
+                    name => return Err(invalid(&format!("unknown section [{name}]"))),
+                }
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(invalid("expected `key = value`"));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            if key.is_empty() || value.is_empty() {
+                return Err(invalid("expected `key = value`"));
+            }
+
+            match section {
+                Some("mime") => {
+                    let extension = key.trim_start_matches('.').to_ascii_lowercase();
+                    config.mime_types.push((extension, value.to_string()));
+                }
+                _ => return Err(invalid("setting outside of a section")),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use advanced_features::assert_err;
+
+    use super::*;
+
+    #[test]
+    fn parses_mime_overrides() {
+        let config = Config::parse(
+            "# extra types\n\
+             [mime]\n\
+             wasm = application/wasm\n\
+             .TXT=text/plain; charset=latin1\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.mime_types,
+            vec![
+                ("wasm".to_string(), "application/wasm".to_string()),
+                ("txt".to_string(), "text/plain; charset=latin1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_the_offending_line() {
+        let err = assert_err!(Config::parse("[mime]\nwasm\n"));
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with("line 2:"), "{err}");
+
+        assert_err!(Config::parse("[other]\n"));
+        assert_err!(Config::parse("wasm = application/wasm\n"));
+    }
+}
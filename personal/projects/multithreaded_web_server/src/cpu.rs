@@ -0,0 +1,299 @@
+//! What the CPU can do, and byte scanning that uses it.
+//!
+//! The server looks for bytes in buffers all the time, most of all for the
+//! blank line that ends a request's head. A loop checks one byte at a time;
+//! SIMD instructions check 16 (SSE2) or 32 (AVX2) at once. But a binary
+//! built for any x86_64 can only assume SSE2, and running an AVX2
+//! instruction on a CPU without it is undefined behavior, so which one to
+//! use is decided at runtime, once, by asking the CPU.
+//!
+//! The unsafety is all in here. A [`Scanner`] can only be made for an
+//! implementation the CPU supports, so holding one is the proof that
+//! calling it is sound, and everything outside this module is safe code.
+//! On other architectures there's only the scalar loop.
+
+use std::{fmt, sync::OnceLock};
+
+/// The instruction set extensions the scanners can use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Features {
+    pub sse2: bool,
+    pub avx2: bool,
+}
+
+/// The features of the CPU this is running on.
+pub fn features() -> Features {
+    #[cfg(target_arch = "x86_64")]
+    {
+        Features {
+            sse2: is_x86_feature_detected!("sse2"),
+            avx2: is_x86_feature_detected!("avx2"),
+        }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        Features {
+            sse2: false,
+            avx2: false,
+        }
+    }
+}
+
+/// The CPU's vendor, like `"GenuineIntel"` or `"AuthenticAMD"`, from
+/// `CPUID` leaf 0, or `None` off x86_64.
+///
+/// `CPUID` is asked directly here, where `is_x86_feature_detected!` would
+/// do it for us: the vendor is the 12 bytes it leaves in `ebx`, `edx` and
+/// `ecx`, in that order. Every x86_64 CPU has the instruction, which is why
+/// `__cpuid` is safe to call.
+pub fn vendor() -> Option<String> {
+    #[cfg(target_arch = "x86_64")]
+    {
+        let leaf = std::arch::x86_64::__cpuid(0);
+        let bytes: Vec<u8> = [leaf.ebx, leaf.edx, leaf.ecx]
+            .iter()
+            .flat_map(|register| register.to_le_bytes())
+            .collect();
+        Some(String::from_utf8_lossy(&bytes).into_owned())
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        None
+    }
+}
+
+/// One way of scanning bytes, which the CPU is known to support.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Scanner {
+    kind: Kind,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Scalar,
+    #[cfg(target_arch = "x86_64")]
+    Sse2,
+    #[cfg(target_arch = "x86_64")]
+    Avx2,
+}
+
+impl Scanner {
+    /// A byte at a time, which works everywhere.
+    pub const fn scalar() -> Scanner {
+        Scanner { kind: Kind::Scalar }
+    }
+
+    /// 16 bytes at a time, if the CPU has SSE2.
+    pub fn sse2() -> Option<Scanner> {
+        #[cfg(target_arch = "x86_64")]
+        if features().sse2 {
+            return Some(Scanner { kind: Kind::Sse2 });
+        }
+        None
+    }
+
+    /// 32 bytes at a time, if the CPU has AVX2.
+    pub fn avx2() -> Option<Scanner> {
+        #[cfg(target_arch = "x86_64")]
+        if features().avx2 {
+            return Some(Scanner { kind: Kind::Avx2 });
+        }
+        None
+    }
+
+    /// The fastest scanner this CPU supports, chosen the first time it's
+    /// asked for.
+    pub fn best() -> Scanner {
+        static BEST: OnceLock<Scanner> = OnceLock::new();
+        *BEST.get_or_init(|| {
+            Scanner::avx2()
+                .or_else(Scanner::sse2)
+                .unwrap_or(Scanner::scalar())
+        })
+    }
+
+    /// Every scanner this CPU supports, slowest first.
+    pub fn available() -> Vec<Scanner> {
+        [Some(Scanner::scalar()), Scanner::sse2(), Scanner::avx2()]
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    pub fn name(self) -> &'static str {
+        match self.kind {
+            Kind::Scalar => "scalar",
+            #[cfg(target_arch = "x86_64")]
+            Kind::Sse2 => "sse2",
+            #[cfg(target_arch = "x86_64")]
+            Kind::Avx2 => "avx2",
+        }
+    }
+
+    /// Where `needle` first appears in `haystack`.
+    pub fn find_byte(self, haystack: &[u8], needle: u8) -> Option<usize> {
+        match self.kind {
+            Kind::Scalar => scalar::find_byte(haystack, needle),
+            // SAFETY: a `Scanner` of this kind is only made after checking
+            // that the CPU has the feature.
+            #[cfg(target_arch = "x86_64")]
+            Kind::Sse2 => unsafe { x86::find_byte_sse2(haystack, needle) },
+            #[cfg(target_arch = "x86_64")]
+            Kind::Avx2 => unsafe { x86::find_byte_avx2(haystack, needle) },
+        }
+    }
+
+    /// Where `needle` first starts in `haystack`: each place its first
+    /// byte is found, checked for the rest.
+    pub fn find(self, haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        let Some((&first, rest)) = needle.split_first() else {
+            return Some(0);
+        };
+        let mut start = 0;
+        while let Some(found) = self.find_byte(&haystack[start..], first) {
+            let at = start + found;
+            if haystack[at + 1..].starts_with(rest) {
+                return Some(at);
+            }
+            start = at + 1;
+        }
+        None
+    }
+}
+
+impl fmt::Debug for Scanner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// [`Scanner::find`] with the [best](Scanner::best) scanner.
+pub fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    Scanner::best().find(haystack, needle)
+}
+
+mod scalar {
+    pub(super) fn find_byte(haystack: &[u8], needle: u8) -> Option<usize> {
+        haystack.iter().position(|&b| b == needle)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    use super::scalar;
+
+    /// Compares 16 bytes at a time with the needle, and turns the result
+    /// into a bit mask, where the lowest set bit is the first match. The
+    /// last few bytes, which don't make a whole block, go to the loop.
+    #[target_feature(enable = "sse2")]
+    pub(super) fn find_byte_sse2(haystack: &[u8], needle: u8) -> Option<usize> {
+        let needles = _mm_set1_epi8(needle as i8);
+        let mut blocks = haystack.chunks_exact(16);
+        for (i, block) in (&mut blocks).enumerate() {
+            // SAFETY: `block` is 16 readable bytes, and `loadu` doesn't
+            // need them aligned.
+            let bytes = unsafe { _mm_loadu_si128(block.as_ptr().cast()) };
+            let matches = _mm_movemask_epi8(_mm_cmpeq_epi8(bytes, needles));
+            if matches != 0 {
+                return Some(i * 16 + matches.trailing_zeros() as usize);
+            }
+        }
+        let done = haystack.len() - blocks.remainder().len();
+        scalar::find_byte(blocks.remainder(), needle).map(|found| done + found)
+    }
+
+    /// As [`find_byte_sse2`], 32 bytes at a time.
+    #[target_feature(enable = "avx2")]
+    pub(super) fn find_byte_avx2(haystack: &[u8], needle: u8) -> Option<usize> {
+        let needles = _mm256_set1_epi8(needle as i8);
+        let mut blocks = haystack.chunks_exact(32);
+        for (i, block) in (&mut blocks).enumerate() {
+            // SAFETY: `block` is 32 readable bytes, and `loadu` doesn't
+            // need them aligned.
+            let bytes = unsafe { _mm256_loadu_si256(block.as_ptr().cast()) };
+            let matches = _mm256_movemask_epi8(_mm256_cmpeq_epi8(bytes, needles));
+            if matches != 0 {
+                return Some(i * 32 + matches.trailing_zeros() as usize);
+            }
+        }
+        let done = haystack.len() - blocks.remainder().len();
+        // The tail is under 32 bytes, which SSE2 can still halve.
+        find_byte_sse2(blocks.remainder(), needle).map(|found| done + found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bytes that look random, but are the same every run.
+    fn noise(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(2_654_435_761) | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state % 251) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_scanner_agrees_with_position() {
+        for len in 0..200 {
+            let haystack = noise(len, len as u32);
+            for needle in [0, 7, 250, 255] {
+                let expected = haystack.iter().position(|&b| b == needle);
+                for scanner in Scanner::available() {
+                    assert_eq!(
+                        scanner.find_byte(&haystack, needle),
+                        expected,
+                        "{scanner:?} looking for {needle} in {len} bytes"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn finds_matches_at_block_edges() {
+        for at in [0, 15, 16, 31, 32, 33, 63, 64, 95] {
+            let mut haystack = vec![b'a'; 96];
+            haystack[at] = b'\r';
+            haystack[95] = b'\r';
+            for scanner in Scanner::available() {
+                assert_eq!(scanner.find_byte(&haystack, b'\r'), Some(at), "{scanner:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn finds_substrings() {
+        let head = b"GET / HTTP/1.1\r\nHost: x\r\n\r\nbody\r\n\r\n";
+        for scanner in Scanner::available() {
+            assert_eq!(scanner.find(head, b"\r\n\r\n"), Some(23));
+            assert_eq!(scanner.find(head, b"\r\n\r\nbody"), Some(23));
+            assert_eq!(scanner.find(head, b"\r\n\r\n\r\n"), None);
+            assert_eq!(scanner.find(head, b""), Some(0));
+            assert_eq!(scanner.find(b"", b"x"), None);
+            assert_eq!(scanner.find(b"\r\n\r", b"\r\n\r\n"), None);
+        }
+    }
+
+    #[test]
+    fn picks_a_supported_scanner() {
+        let best = Scanner::best();
+        assert_eq!(Scanner::available().last(), Some(&best));
+        #[cfg(target_arch = "x86_64")]
+        {
+            // Every x86_64 CPU has SSE2.
+            assert!(features().sse2);
+            assert_ne!(best, Scanner::scalar());
+            let vendor = vendor().unwrap();
+            assert_eq!(vendor.len(), 12, "{vendor:?}");
+        }
+    }
+}
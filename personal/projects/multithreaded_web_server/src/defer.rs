@@ -0,0 +1,97 @@
+//! Running cleanup when a scope ends, however it ends.
+//!
+//! Undoing a counter by hand at the bottom of a function misses every `?`
+//! and `return` above it. A [`Defer`] guard runs its closure when it's
+//! dropped, which happens on all of those paths, and on a panic too.
+
+/// Runs a closure when dropped, unless it's been cancelled.
+#[must_use = "the closure runs as soon as an unused guard is dropped"]
+pub struct Defer<F: FnOnce()> {
+    f: Option<F>,
+}
+
+impl<F: FnOnce()> Defer<F> {
+    pub fn new(f: F) -> Defer<F> {
+        Defer { f: Some(f) }
+    }
+
+    /// Drop the guard without running the closure, for when whatever it was
+    /// going to clean up has been handed on to something else.
+    pub fn cancel(mut self) {
+        self.f = None;
+    }
+}
+
+impl<F: FnOnce()> Drop for Defer<F> {
+    fn drop(&mut self) {
+        if let Some(f) = self.f.take() {
+            f();
+        }
+    }
+}
+
+/// Run the statements in the braces at the end of the enclosing scope.
+///
+/// Deferred blocks run in the reverse of the order they were written in,
+/// like any other locals being dropped. To be able to cancel one, make the
+/// [`Defer`] guard yourself.
+///
+/// ```
+/// use std::cell::RefCell;
+/// use multithreaded_web_server::defer;
+///
+/// let log = RefCell::new(Vec::new());
+/// {
+///     defer! { log.borrow_mut().push("first"); }
+///     defer! { log.borrow_mut().push("second"); }
+///     log.borrow_mut().push("body");
+/// }
+/// assert_eq!(*log.borrow(), ["body", "second", "first"]);
+/// ```
+#[macro_export]
+macro_rules! defer {
+    ($($body:tt)*) => {
+        let _guard = $crate::defer::Defer::new(|| { $($body)* });
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::Cell,
+        panic::{self, AssertUnwindSafe},
+    };
+
+    use super::*;
+
+    fn early_return(ran: &Cell<u32>, fail: bool) -> Result<(), ()> {
+        defer! { ran.set(ran.get() + 1); }
+        if fail {
+            return Err(());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn runs_on_every_way_out_of_a_scope() {
+        let ran = Cell::new(0);
+        early_return(&ran, false).unwrap();
+        early_return(&ran, true).unwrap_err();
+        assert_eq!(ran.get(), 2);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            defer! { ran.set(ran.get() + 1); }
+            panic!("handler failed");
+        }));
+        assert!(result.is_err());
+        assert_eq!(ran.get(), 3);
+    }
+
+    #[test]
+    fn a_cancelled_guard_does_nothing() {
+        let ran = Cell::new(false);
+        let guard = Defer::new(|| ran.set(true));
+        guard.cancel();
+        assert!(!ran.get());
+    }
+}
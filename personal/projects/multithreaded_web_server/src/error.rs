@@ -0,0 +1,94 @@
+use std::io;
+
+use hello_macro_derive::ErrorKind;
+
+/// Everything that can go wrong while serving a single connection.
+///
+/// Workers log these instead of unwrapping, so one bad request can't take a
+/// thread out of the pool.
+#[derive(Debug, ErrorKind)]
+pub enum ServerError {
+    /// Reading from or writing to the stream (or a file on disk) failed.
+    ///
+    /// `#[source]` rather than `#[from]`: the `From<io::Error>` below
+    /// turns some I/O errors into [`ServerError::Timeout`] instead.
+    #[error("I/O error: {0}")]
+    Io(#[source] io::Error),
+    /// The request wasn't something we know how to parse.
+    #[error("malformed request: {0}")]
+    Parse(String),
+    /// The request parsed, but broke the rules of HTTP, like a body that
+    /// doesn't match its `Content-Length`.
+    #[error("protocol error: {0}")]
+    Protocol(String),
+    /// The request body is bigger than we're willing to accept.
+    #[error("request body too large")]
+    TooLarge,
+    /// The code producing the response panicked.
+    #[error("handler panicked")]
+    HandlerPanic,
+    /// The client didn't send its request in time.
+    #[error("timed out waiting for the client")]
+    Timeout,
+}
+
+impl ServerError {
+    /// The status code to send back to the client for this error. Its reason
+    /// phrase is in [`reason_phrase`](crate::tables::reason_phrase).
+    pub fn status(&self) -> u16 {
+        match self {
+            ServerError::Parse(_) | ServerError::Protocol(_) => 400,
+            ServerError::TooLarge => 413,
+            ServerError::Timeout => 408,
+            ServerError::Io(_) | ServerError::HandlerPanic => 500,
+        }
+    }
+}
+
+impl From<io::Error> for ServerError {
+    fn from(err: io::Error) -> ServerError {
+        // A read timeout shows up as WouldBlock on Unix and TimedOut on Windows.
+        match err.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => ServerError::Timeout,
+            _ => ServerError::Io(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use advanced_features::assert_matches;
+
+    use super::*;
+
+    #[test]
+    fn timeouts_are_not_reported_as_io_errors() {
+        let err = ServerError::from(io::Error::from(io::ErrorKind::WouldBlock));
+        assert_matches!(err, ServerError::Timeout);
+
+        let err = ServerError::from(io::Error::from(io::ErrorKind::NotFound));
+        assert_matches!(err, ServerError::Io(_));
+    }
+
+    #[test]
+    fn client_errors_map_to_4xx() {
+        assert_eq!(ServerError::Parse("".into()).status(), 400);
+        assert_eq!(ServerError::Timeout.status(), 408);
+        assert_eq!(ServerError::TooLarge.status(), 413);
+        assert_eq!(ServerError::HandlerPanic.status(), 500);
+    }
+
+    #[test]
+    fn io_errors_keep_their_source() {
+        use std::error::Error;
+
+        let err = ServerError::from(io::Error::other("disk on fire"));
+        assert_eq!(err.to_string(), "I/O error: disk on fire");
+        assert_eq!(err.source().unwrap().to_string(), "disk on fire");
+        assert_eq!(
+            ServerError::Parse("GET".into()).to_string(),
+            "malformed request: GET"
+        );
+        assert!(ServerError::TooLarge.source().is_none());
+    }
+}
@@ -0,0 +1,389 @@
+//! An event-driven front end for the server.
+//!
+//! One thread accepts connections and reads request heads without blocking,
+//! and only hands a connection to the pool once its whole head has arrived,
+//! so slow clients never tie up a worker.
+
+use std::{
+    collections::HashMap,
+    io::{self, prelude::*, Cursor},
+    net::{TcpListener, TcpStream},
+    os::unix::io::{AsRawFd, RawFd},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use advanced_features::uninit_buf::UninitBuf;
+use thread_pool::{semaphore::Permit, ThreadPool};
+
+use crate::{
+    defer,
+    http::{head_end, Body, Request, Response, MAX_HEAD_SIZE},
+    poller::{self, Interest, Poller},
+    server::{self, Server, ACCEPT_BACKOFF, MAX_BODY_SIZE, READ_TIMEOUT},
+    stats::{ConnectionState, ConnectionStats, Counted},
+    ServerError,
+};
+
+struct PendingConnection {
+    stream: TcpStream,
+    head: Vec<u8>,
+    accepted: Instant,
+    /// Its place under the server's connection cap, if it has one.
+    permit: Option<Permit>,
+}
+
+/// Accept and read connections on `listener` until accepting fails for a
+/// reason that won't clear up, dispatching complete requests to `pool`.
+pub fn run(
+    listener: TcpListener,
+    pool: &ThreadPool,
+    server: Arc<Server>,
+) -> Result<(), ServerError> {
+    listener.set_nonblocking(true)?;
+
+    let mut poller = Poller::new();
+    poller.register(listener.as_raw_fd(), Interest::Readable);
+    let mut pending: HashMap<RawFd, PendingConnection> = HashMap::new();
+    // Every head is read through this, one connection at a time.
+    let mut chunk = UninitBuf::with_capacity(1024);
+    // When to start watching the listener again after backing off.
+    let mut paused_until: Option<Instant> = None;
+
+    loop {
+        let timeout = match paused_until {
+            Some(until) => until.saturating_duration_since(Instant::now()),
+            None => Duration::from_secs(1),
+        };
+        for fd in poller.wait(Some(timeout))? {
+            if fd == listener.as_raw_fd() {
+                match accept_all(&listener, &server, &mut poller, &mut pending) {
+                    Ok(true) => {}
+                    // At the connection cap. Permits come back on the
+                    // workers, which can't wake this thread, so check back
+                    // in a while.
+                    Ok(false) => {
+                        poller.deregister(fd);
+                        paused_until = Some(Instant::now() + ACCEPT_BACKOFF);
+                    }
+                    Err(err) => {
+                        if !server::is_transient(&err) {
+                            return Err(err.into());
+                        }
+                        crate::log_error!(
+                            "Failed to accept connection: {}",
+                            ServerError::from(err)
+                        );
+                        // The listener stays readable for as long as the
+                        // error lasts, so stop watching it for a while
+                        // rather than spinning on it.
+                        poller.deregister(fd);
+                        paused_until = Some(Instant::now() + ACCEPT_BACKOFF);
+                    }
+                }
+                continue;
+            }
+
+            let Some(connection) = pending.get_mut(&fd) else {
+                continue;
+            };
+            match read_head(connection, &mut chunk) {
+                Ok(None) => continue,
+                Ok(Some(end)) => {
+                    poller.deregister(fd);
+                    let connection = pending.remove(&fd).unwrap();
+                    dispatch(connection, end, pool, &server);
+                }
+                Err(err) => {
+                    poller.deregister(fd);
+                    let mut connection = pending.remove(&fd).unwrap();
+                    reject(&mut connection.stream, &err);
+                    crate::log_error!("Error reading request: {err}");
+                }
+            }
+        }
+
+        let now = Instant::now();
+        if paused_until.is_some_and(|until| now >= until) {
+            poller.register(listener.as_raw_fd(), Interest::Readable);
+            paused_until = None;
+        }
+
+        // Give up on clients that have been trickling in their request for too long.
+        pending.retain(|&fd, connection| {
+            if now.duration_since(connection.accepted) < READ_TIMEOUT {
+                return true;
+            }
+            poller.deregister(fd);
+            reject(&mut connection.stream, &ServerError::Timeout);
+            false
+        });
+    }
+}
+
+/// Accept every connection that's waiting, until accepting would block or
+/// fails. Returns `false` if it stopped at the server's connection cap
+/// instead.
+fn accept_all(
+    listener: &TcpListener,
+    server: &Server,
+    poller: &mut Poller,
+    pending: &mut HashMap<RawFd, PendingConnection>,
+) -> io::Result<bool> {
+    loop {
+        let Some(permit) = server.try_admit() else {
+            return Ok(false);
+        };
+        match listener.accept() {
+            Ok((stream, _)) => {
+                if let Err(err) = stream.set_nonblocking(true) {
+                    crate::log_error!("Failed to accept connection: {}", ServerError::from(err));
+                    continue;
+                }
+                let fd = stream.as_raw_fd();
+                poller.register(fd, Interest::Readable);
+                pending.insert(
+                    fd,
+                    PendingConnection {
+                        stream,
+                        head: Vec::new(),
+                        accepted: Instant::now(),
+                        permit,
+                    },
+                );
+            }
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(true),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Read whatever is available; returns where the head ends once the blank
+/// line ending it has arrived.
+///
+/// Reads stop at the size limit, so a client that keeps sending can't grow
+/// the head past it, and a head that fills it without ending is too large.
+fn read_head(
+    connection: &mut PendingConnection,
+    chunk: &mut UninitBuf<u8>,
+) -> Result<Option<usize>, ServerError> {
+    loop {
+        let room = MAX_HEAD_SIZE - connection.head.len();
+        if room == 0 {
+            return Err(ServerError::TooLarge);
+        }
+
+        chunk.clear();
+        match poller::read_uninit_max(connection.stream.as_raw_fd(), chunk, room) {
+            Ok(0) => {
+                return Err(ServerError::Parse(
+                    "connection closed before request was complete".into(),
+                ))
+            }
+            Ok(_) => {
+                // Only the new bytes, and the three before them, can finish
+                // the blank line, so the rest isn't scanned again.
+                let from = connection.head.len().saturating_sub(3);
+                connection.head.extend_from_slice(chunk.assume_init_slice());
+                if let Some(end) = head_end(&connection.head[from..]) {
+                    return Ok(Some(from + end));
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(ServerError::Io(err)),
+        }
+    }
+}
+
+fn dispatch(connection: PendingConnection, end: usize, pool: &ThreadPool, server: &Arc<Server>) {
+    let PendingConnection {
+        stream,
+        head,
+        permit,
+        ..
+    } = connection;
+    let server = Arc::clone(server);
+
+    let submitted = pool.execute(move || {
+        let _permit = permit;
+        let connection = server.connections.register(stream.peer_addr().ok());
+        let stats = connection.stats();
+        stats.add_read(head.len() as u64);
+        server.connections.start_request();
+        defer! { server.connections.finish_request(); }
+
+        stats.set_state(ConnectionState::Reading);
+        let result = stream
+            .set_nonblocking(false)
+            .map_err(ServerError::from)
+            .and_then(|()| {
+                let request = parse_request(&stream, head, end, READ_TIMEOUT, stats);
+                server::respond(stream, request, &server, stats)
+            });
+        if let Err(err) = result {
+            crate::log_error!("Error handling connection: {err}");
+        }
+    });
+    if let Err(err) = submitted {
+        crate::log_error!("Dropping connection: {err}");
+    }
+}
+
+/// Parse the head, which ends at `end`, and attach a body made of whatever
+/// arrived after it, followed by the rest of the connection.
+///
+/// Reads of the body give up after `timeout`, so a client that stalls
+/// partway through it can't hold on to a worker. What's read from the
+/// connection is counted in `stats`.
+fn parse_request(
+    stream: &TcpStream,
+    head: Vec<u8>,
+    end: usize,
+    timeout: Duration,
+    stats: &Arc<ConnectionStats>,
+) -> Result<Request, ServerError> {
+    let mut request = Request::read_from(&mut &head[..end])?;
+
+    let length = request.content_length()?;
+    if length > MAX_BODY_SIZE {
+        return Err(ServerError::TooLarge);
+    }
+    stream.set_read_timeout(Some(timeout))?;
+    let rest = Cursor::new(head[end..].to_vec());
+    let stream = Counted::new(stream.try_clone()?, Arc::clone(stats));
+    request.body = Body::new(rest.chain(stream), length);
+
+    Ok(request)
+}
+
+/// Tell the client why its connection is being dropped, without blocking
+/// the loop. The response fits in the socket's send buffer in one write;
+/// if it doesn't, the client isn't reading anyway.
+fn reject(stream: &mut TcpStream, err: &ServerError) {
+    let mut bytes = Vec::new();
+    if Response::from_error(err)
+        .write_to("HTTP/1.1", &mut bytes)
+        .is_ok()
+    {
+        // Best effort: the client may already be gone.
+        let _ = stream.write(&bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use advanced_features::assert_err;
+    use thread_pool::Semaphore;
+
+    use super::*;
+    use crate::{router::Router, stats::ConnectionRegistry};
+
+    /// A connection the test can write to, with the server's end pending.
+    fn connect() -> (TcpStream, PendingConnection) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        let connection = PendingConnection {
+            stream,
+            head: Vec::new(),
+            accepted: Instant::now(),
+            permit: None,
+        };
+        (client, connection)
+    }
+
+    /// `read_head` once the client's bytes have arrived.
+    fn read_after(
+        connection: &mut PendingConnection,
+        chunk: &mut UninitBuf<u8>,
+    ) -> Result<Option<usize>, ServerError> {
+        std::thread::sleep(Duration::from_millis(20));
+        read_head(connection, chunk)
+    }
+
+    #[test]
+    fn finds_a_blank_line_split_across_reads() {
+        let (mut client, mut connection) = connect();
+        let mut chunk = UninitBuf::with_capacity(1024);
+
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: x\r\n\r")
+            .unwrap();
+        assert!(matches!(read_after(&mut connection, &mut chunk), Ok(None)));
+        client.write_all(b"\nbody").unwrap();
+        assert!(matches!(
+            read_after(&mut connection, &mut chunk),
+            Ok(Some(27))
+        ));
+    }
+
+    #[test]
+    fn stops_reading_once_the_head_is_too_big() {
+        let (mut client, mut connection) = connect();
+        let mut chunk = UninitBuf::with_capacity(1024);
+
+        client.write_all(&[b'a'; 4 * MAX_HEAD_SIZE]).unwrap();
+        let result = read_after(&mut connection, &mut chunk);
+        assert_err!(result, ServerError::TooLarge);
+        assert!(connection.head.len() <= MAX_HEAD_SIZE);
+    }
+
+    #[test]
+    fn stalled_body_times_out() {
+        // The client stays connected but never sends the last three bytes.
+        let (_client, connection) = connect();
+        let head = b"POST /echo HTTP/1.1\r\nContent-Length: 5\r\n\r\nhe".to_vec();
+
+        let stream = connection.stream;
+        stream.set_nonblocking(false).unwrap();
+        let connections = ConnectionRegistry::new();
+        let registered = connections.register(None);
+        let request = parse_request(
+            &stream,
+            head,
+            42,
+            Duration::from_millis(50),
+            registered.stats(),
+        )
+        .unwrap();
+        assert_err!(request.body.into_bytes(), ServerError::Timeout);
+    }
+
+    #[test]
+    fn stops_accepting_at_the_connection_cap() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let mut server = Server::new(Router::new(|_| Response::ok("")));
+        server.connection_limit = Some(Arc::new(Semaphore::new(1)));
+        let mut poller = Poller::new();
+        let mut pending = HashMap::new();
+
+        let _clients: Vec<_> = (0..2)
+            .map(|_| TcpStream::connect(listener.local_addr().unwrap()).unwrap())
+            .collect();
+        std::thread::sleep(Duration::from_millis(20));
+        let accepted = accept_all(&listener, &server, &mut poller, &mut pending).unwrap();
+        assert!(!accepted);
+        assert_eq!(pending.len(), 1);
+
+        // Closing the connection gives its place to the next one.
+        pending.clear();
+        std::thread::sleep(Duration::from_millis(20));
+        let accepted = accept_all(&listener, &server, &mut poller, &mut pending).unwrap();
+        assert!(!accepted);
+        assert_eq!(pending.len(), 1);
+    }
+
+    #[test]
+    fn rejecting_writes_the_error_without_blocking() {
+        let (mut client, mut connection) = connect();
+        reject(&mut connection.stream, &ServerError::TooLarge);
+        drop(connection);
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 413 "), "{response}");
+    }
+}
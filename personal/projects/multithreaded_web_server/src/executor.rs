@@ -0,0 +1,493 @@
+//! A minimal single-threaded futures executor.
+//!
+//! Tasks sit in a ready queue and are polled until they return `Pending`.
+//! Sockets that would block register the task's waker with the reactor,
+//! which sleeps in `poll(2)` whenever no task is ready and wakes exactly the
+//! tasks whose sockets became ready. A task woken from another thread (say,
+//! by a job finishing on the thread pool) also pokes a socket pair the
+//! reactor watches, so the sleep doesn't miss it. A task can also
+//! [`sleep`], and the reactor wakes it once the time is up, or give up on a
+//! future after a while with [`timeout`].
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    future::{poll_fn, Future},
+    io::{self, prelude::*},
+    net::{SocketAddr, TcpListener, TcpStream},
+    os::unix::{
+        io::{AsRawFd, RawFd},
+        net::UnixStream,
+    },
+    pin::{pin, Pin},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    task::{Context, Poll, Wake, Waker},
+    time::{Duration, Instant},
+};
+
+use advanced_features::uninit_buf::UninitBuf;
+
+use crate::{
+    defer,
+    poller::{self, Interest, Poller},
+};
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+struct Task {
+    future: Mutex<Option<BoxFuture>>,
+    ready: mpsc::Sender<Arc<Task>>,
+    notify: Arc<UnixStream>,
+}
+
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        // The executor only goes away once every task has finished.
+        let _ = self.ready.send(Arc::clone(&self));
+        // If the socket's buffer is full, the executor has plenty of
+        // wake-ups queued already.
+        let _ = (&*self.notify).write(&[1]);
+    }
+}
+
+/// Runs spawned futures to completion on the current thread.
+pub struct Executor {
+    ready: mpsc::Receiver<Arc<Task>>,
+    /// Readable whenever a task has been woken.
+    wakeups: UnixStream,
+    spawner: Spawner,
+}
+
+/// A cloneable handle for spawning more tasks onto an [`Executor`], usable
+/// from inside running tasks.
+#[derive(Clone)]
+pub struct Spawner {
+    ready: mpsc::Sender<Arc<Task>>,
+    notify: Arc<UnixStream>,
+    live: Arc<AtomicUsize>,
+}
+
+impl Executor {
+    pub fn new() -> Executor {
+        let (sender, ready) = mpsc::channel();
+        let (notify, wakeups) = UnixStream::pair().expect("failed to create a socket pair");
+        notify
+            .set_nonblocking(true)
+            .and_then(|()| wakeups.set_nonblocking(true))
+            .expect("failed to make the socket pair non-blocking");
+
+        Executor {
+            ready,
+            wakeups,
+            spawner: Spawner {
+                ready: sender,
+                notify: Arc::new(notify),
+                live: Arc::new(AtomicUsize::new(0)),
+            },
+        }
+    }
+
+    pub fn spawner(&self) -> Spawner {
+        self.spawner.clone()
+    }
+
+    /// Poll tasks until all of them have finished.
+    pub fn run(&self) -> io::Result<()> {
+        while self.spawner.live.load(Ordering::SeqCst) > 0 {
+            while let Ok(task) = self.ready.try_recv() {
+                self.poll_task(task);
+            }
+
+            if self.spawner.live.load(Ordering::SeqCst) > 0 {
+                REACTOR.with(|reactor| reactor.borrow_mut().wait(&self.wakeups))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn poll_task(&self, task: Arc<Task>) {
+        let mut slot = task.future.lock().unwrap();
+        let Some(mut future) = slot.take() else {
+            // Woken after it already finished.
+            return;
+        };
+
+        let waker = Waker::from(Arc::clone(&task));
+        let mut cx = Context::from_waker(&waker);
+        match future.as_mut().poll(&mut cx) {
+            Poll::Pending => *slot = Some(future),
+            Poll::Ready(()) => {
+                self.spawner.live.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+impl Default for Executor {
+    fn default() -> Executor {
+        Executor::new()
+    }
+}
+
+impl Spawner {
+    pub fn spawn(&self, future: impl Future<Output = ()> + Send + 'static) {
+        self.live.fetch_add(1, Ordering::SeqCst);
+        let task = Arc::new(Task {
+            future: Mutex::new(Some(Box::pin(future))),
+            ready: self.ready.clone(),
+            notify: Arc::clone(&self.notify),
+        });
+        let _ = self.ready.send(task);
+    }
+}
+
+/// Which task to wake when each socket becomes ready, and which to wake
+/// when their time is up.
+#[derive(Default)]
+struct Reactor {
+    waiting: HashMap<RawFd, (Interest, Waker)>,
+    /// By timer id, so a sleep polled again replaces its waker rather than
+    /// adding another.
+    sleeping: HashMap<u64, (Instant, Waker)>,
+    next_timer: u64,
+}
+
+thread_local! {
+    static REACTOR: RefCell<Reactor> = RefCell::new(Reactor::default());
+}
+
+/// Run `f` on this thread's reactor, for cleanup that may happen while the
+/// thread is exiting and the reactor is already gone.
+fn with_reactor(f: impl FnOnce(&mut Reactor)) {
+    let _ = REACTOR.try_with(|reactor| f(&mut reactor.borrow_mut()));
+}
+
+impl Reactor {
+    fn register(&mut self, fd: RawFd, interest: Interest, waker: Waker) {
+        self.waiting.insert(fd, (interest, waker));
+    }
+
+    /// Wake `waker` at `deadline`, under `timer` if it has an id already,
+    /// returning the id.
+    fn set_timer(&mut self, timer: Option<u64>, deadline: Instant, waker: Waker) -> u64 {
+        let timer = timer.unwrap_or_else(|| {
+            self.next_timer += 1;
+            self.next_timer
+        });
+        self.sleeping.insert(timer, (deadline, waker));
+        timer
+    }
+
+    /// Sleep until a socket a task is waiting on becomes ready, a sleeping
+    /// task's time is up, or `wakeups` says a task was woken some other way.
+    fn wait(&mut self, mut wakeups: &UnixStream) -> io::Result<()> {
+        let mut poller = Poller::new();
+        poller.register(wakeups.as_raw_fd(), Interest::Readable);
+        for (&fd, &(interest, _)) in &self.waiting {
+            poller.register(fd, interest);
+        }
+
+        let timeout = self
+            .sleeping
+            .values()
+            .map(|&(deadline, _)| deadline.saturating_duration_since(Instant::now()))
+            .min();
+        let ready = poller.wait(timeout)?;
+
+        let now = Instant::now();
+        self.sleeping.retain(|_, (deadline, waker)| {
+            if *deadline > now {
+                return true;
+            }
+            waker.wake_by_ref();
+            false
+        });
+        for fd in ready {
+            if fd == wakeups.as_raw_fd() {
+                let mut buf = [0; 64];
+                while matches!(wakeups.read(&mut buf), Ok(n) if n > 0) {}
+            } else if let Some((_, waker)) = self.waiting.remove(&fd) {
+                waker.wake();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Retry `op` until it stops returning `WouldBlock`, parking the task on the
+/// reactor in between.
+async fn would_block<T>(
+    fd: RawFd,
+    interest: Interest,
+    mut op: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    // However this ends, even dropped by `timeout`, the socket mustn't be
+    // left waking a task that's done with it.
+    defer! { with_reactor(|reactor| { reactor.waiting.remove(&fd); }); }
+    poll_fn(|cx| match op() {
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+            REACTOR.with(|reactor| {
+                reactor
+                    .borrow_mut()
+                    .register(fd, interest, cx.waker().clone())
+            });
+            Poll::Pending
+        }
+        result => Poll::Ready(result),
+    })
+    .await
+}
+
+/// Let the other tasks run for `duration`.
+pub async fn sleep(duration: Duration) {
+    Sleep {
+        deadline: Instant::now() + duration,
+        timer: None,
+    }
+    .await
+}
+
+/// The future behind [`sleep`], which holds on to its timer so polling it
+/// again only updates the waker, and dropping it early cancels it.
+struct Sleep {
+    deadline: Instant,
+    timer: Option<u64>,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            return Poll::Ready(());
+        }
+        let timer = REACTOR.with(|reactor| {
+            reactor
+                .borrow_mut()
+                .set_timer(self.timer, self.deadline, cx.waker().clone())
+        });
+        self.timer = Some(timer);
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(timer) = self.timer {
+            with_reactor(|reactor| {
+                reactor.sleeping.remove(&timer);
+            });
+        }
+    }
+}
+
+/// Run `future` until it finishes or `duration` passes, whichever comes
+/// first. `None` means time ran out, and `future` is dropped unfinished.
+pub async fn timeout<F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    let mut future = pin!(future);
+    let mut sleep = pin!(sleep(duration));
+    poll_fn(|cx| {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return Poll::Ready(Some(output));
+        }
+        sleep.as_mut().poll(cx).map(|()| None)
+    })
+    .await
+}
+
+/// A non-blocking listener whose `accept` yields to other tasks.
+pub struct AsyncTcpListener {
+    inner: TcpListener,
+}
+
+impl AsyncTcpListener {
+    pub fn new(inner: TcpListener) -> io::Result<AsyncTcpListener> {
+        inner.set_nonblocking(true)?;
+        Ok(AsyncTcpListener { inner })
+    }
+
+    pub async fn accept(&self) -> io::Result<(AsyncTcpStream, SocketAddr)> {
+        let (stream, addr) = would_block(self.inner.as_raw_fd(), Interest::Readable, || {
+            self.inner.accept()
+        })
+        .await?;
+        Ok((AsyncTcpStream::new(stream)?, addr))
+    }
+}
+
+/// A non-blocking stream whose reads and writes yield to other tasks.
+pub struct AsyncTcpStream {
+    inner: TcpStream,
+}
+
+impl AsyncTcpStream {
+    pub fn new(inner: TcpStream) -> io::Result<AsyncTcpStream> {
+        inner.set_nonblocking(true)?;
+        Ok(AsyncTcpStream { inner })
+    }
+
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.inner.peer_addr().ok()
+    }
+
+    pub async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let fd = self.inner.as_raw_fd();
+        let inner = &mut self.inner;
+        would_block(fd, Interest::Readable, || inner.read(buf)).await
+    }
+
+    /// `read`, into a buffer that doesn't need zeroing first.
+    pub async fn read_uninit(&mut self, buf: &mut UninitBuf<u8>) -> io::Result<usize> {
+        self.read_uninit_max(buf, usize::MAX).await
+    }
+
+    /// Like [`read_uninit`](Self::read_uninit), but reads at most `max` bytes.
+    pub async fn read_uninit_max(
+        &mut self,
+        buf: &mut UninitBuf<u8>,
+        max: usize,
+    ) -> io::Result<usize> {
+        let fd = self.inner.as_raw_fd();
+        would_block(fd, Interest::Readable, || {
+            poller::read_uninit_max(fd, buf, max)
+        })
+        .await
+    }
+
+    pub async fn write_all(&mut self, mut buf: &[u8]) -> io::Result<()> {
+        let fd = self.inner.as_raw_fd();
+        while !buf.is_empty() {
+            let inner = &mut self.inner;
+            let written = would_block(fd, Interest::Writable, || inner.write(buf)).await?;
+            if written == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            buf = &buf[written..];
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_tasks_spawned_by_other_tasks() {
+        let executor = Executor::new();
+        let spawner = executor.spawner();
+        let (sender, receiver) = mpsc::channel();
+
+        executor.spawner().spawn(async move {
+            for i in 0..3 {
+                let sender = sender.clone();
+                spawner.spawn(async move { sender.send(i).unwrap() });
+            }
+        });
+        executor.run().unwrap();
+
+        let mut got: Vec<i32> = receiver.try_iter().collect();
+        got.sort();
+        assert_eq!(got, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn echoes_over_a_socket() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let listener = AsyncTcpListener::new(listener).unwrap();
+
+        let executor = Executor::new();
+        executor.spawner().spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 5];
+            let n = stream.read(&mut buf).await.unwrap();
+            stream.write_all(&buf[..n]).await.unwrap();
+        });
+
+        let client = std::thread::spawn(move || {
+            let mut client = TcpStream::connect(addr).unwrap();
+            client.write_all(b"hello").unwrap();
+            let mut reply = String::new();
+            client.read_to_string(&mut reply).unwrap();
+            reply
+        });
+
+        executor.run().unwrap();
+        assert_eq!(client.join().unwrap(), "hello");
+    }
+
+    #[test]
+    fn timeout_gives_up_on_a_read_that_never_finishes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let mut stream = AsyncTcpStream::new(stream).unwrap();
+
+        let executor = Executor::new();
+        let (sender, receiver) = mpsc::channel();
+        executor.spawner().spawn(async move {
+            let mut buf = [0; 5];
+            let read = timeout(Duration::from_millis(20), stream.read(&mut buf)).await;
+            sender.send(read.is_none()).unwrap();
+        });
+        executor.run().unwrap();
+
+        assert!(receiver.recv().unwrap());
+        // Neither the socket nor the timer is left behind.
+        REACTOR.with(|reactor| {
+            let reactor = reactor.borrow();
+            assert!(reactor.waiting.is_empty());
+            assert!(reactor.sleeping.is_empty());
+        });
+    }
+
+    #[test]
+    fn a_sleep_polled_again_keeps_one_timer() {
+        let executor = Executor::new();
+        executor.spawner().spawn(async {
+            let mut sleep = pin!(sleep(Duration::from_millis(20)));
+            for _ in 0..10 {
+                // Woken by something other than the timer, again and again.
+                poll_fn(|cx| {
+                    assert!(sleep.as_mut().poll(cx).is_pending());
+                    Poll::Ready(())
+                })
+                .await;
+            }
+            REACTOR.with(|reactor| assert_eq!(reactor.borrow().sleeping.len(), 1));
+            sleep.await;
+        });
+        executor.run().unwrap();
+    }
+
+    #[test]
+    fn tasks_can_be_woken_from_other_threads() {
+        let executor = Executor::new();
+        let (sender, receiver) = mpsc::channel();
+
+        executor.spawner().spawn(async move {
+            let mut woken = false;
+            poll_fn(|cx| {
+                if woken {
+                    return Poll::Ready(());
+                }
+                woken = true;
+                let waker = cx.waker().clone();
+                std::thread::spawn(move || {
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                    waker.wake();
+                });
+                Poll::Pending
+            })
+            .await;
+            sender.send("woken").unwrap();
+        });
+        executor.run().unwrap();
+
+        assert_eq!(receiver.recv().unwrap(), "woken");
+    }
+}
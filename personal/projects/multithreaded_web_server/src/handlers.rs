@@ -0,0 +1,187 @@
+//! Dynamic handlers that generate their content per request.
+
+use std::{
+    io::{prelude::*, Cursor},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use hello_macro::json::ToJson;
+use hello_macro_derive::ToJson;
+use thread_pool::{histogram::BUCKET_BOUNDS, metrics, LatencyHistogram, ThreadPool};
+
+use crate::{
+    http::{Request, Response},
+    router::Handler,
+    template::Templates,
+};
+
+/// Responds with the current Unix time in seconds.
+pub struct Time;
+
+impl Handler for Time {
+    fn call(&self, _request: Request) -> Response {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Response::ok(now.to_string()).with_header("Content-Type", "text/plain")
+    }
+}
+
+/// Sends the request line, headers and body back to the client, streaming
+/// the body straight from the request into the response.
+pub struct Echo;
+
+impl Handler for Echo {
+    fn call(&self, request: Request) -> Response {
+        let mut head = format!("{} {} {}\n", request.method, request.path, request.version);
+        for (name, value) in &request.headers {
+            head.push_str(&format!("{name}: {value}\n"));
+        }
+        if !request.body.is_empty() {
+            head.push('\n');
+        }
+
+        let length = head.len() as u64 + request.body.len();
+        let body = Cursor::new(head).chain(request.body);
+        Response::stream(200, "OK", body, Some(length)).with_header("Content-Type", "text/plain")
+    }
+}
+
+/// Counts how many times it has been called.
+#[derive(Default)]
+pub struct Counter {
+    hits: metrics::Counter,
+}
+
+impl Handler for Counter {
+    fn call(&self, _request: Request) -> Response {
+        let hits = self.hits.inc();
+        Response::ok(hits.to_string()).with_header("Content-Type", "text/plain")
+    }
+}
+
+/// Greets `?name=` (or a stranger) with the `greet.html` template.
+pub struct Greet(pub Templates);
+
+impl Handler for Greet {
+    fn call(&self, request: Request) -> Response {
+        let name = request.query("name").filter(|name| !name.is_empty());
+        let vars = [
+            ("name", name.unwrap_or("stranger")),
+            ("agent", request.header("User-Agent").unwrap_or("you")),
+        ];
+        match self.0.get("greet.html") {
+            Ok(template) => Response::ok(template.render(&vars))
+                .with_header("Content-Type", "text/html; charset=utf-8"),
+            Err(err) => {
+                crate::log_error!("Error loading greet.html: {err}");
+                Response::from_error(&err)
+            }
+        }
+    }
+}
+
+/// The book's slow request: waits five seconds, then serves the fallback.
+pub struct Sleep<H>(pub H);
+
+impl<H: Handler> Handler for Sleep<H> {
+    fn call(&self, mut request: Request) -> Response {
+        thread::sleep(Duration::from_secs(5));
+        request.path = "/".to_string();
+        self.0.call(request)
+    }
+}
+
+/// Reports the thread pool's counters, one `name value` pair per line, and
+/// its latency histograms as cumulative `name{le="seconds"} count` lines.
+/// With `?format=json` it's the same [`Metrics`](thread_pool::Metrics) as
+/// one JSON object instead.
+pub struct PoolMetrics(pub Arc<ThreadPool>);
+
+impl Handler for PoolMetrics {
+    fn call(&self, request: Request) -> Response {
+        let metrics = self.0.metrics();
+        if request.query("format") == Some("json") {
+            return Response::json(&metrics);
+        }
+        let mut body = format!(
+            "pool_threads {}\n\
+             pool_queued_jobs {}\n\
+             pool_running_jobs {}\n\
+             pool_completed_jobs {}\n\
+             pool_panicked_jobs {}\n\
+             pool_overdue_jobs {}\n",
+            metrics.threads,
+            metrics.queued,
+            metrics.running,
+            metrics.completed,
+            metrics.panicked,
+            metrics.overdue
+        );
+        write_histogram(&mut body, "pool_queue_wait_seconds", &metrics.queue_wait);
+        write_histogram(&mut body, "pool_run_time_seconds", &metrics.run_time);
+        Response::ok(body).with_header("Content-Type", "text/plain")
+    }
+}
+
+/// Reports that the server is up, for how long, and how busy its pool is,
+/// as JSON.
+pub struct Health {
+    started: Instant,
+    pool: Arc<ThreadPool>,
+}
+
+impl Health {
+    pub fn new(pool: Arc<ThreadPool>) -> Health {
+        Health {
+            started: Instant::now(),
+            pool,
+        }
+    }
+}
+
+#[derive(ToJson)]
+struct HealthReport {
+    status: &'static str,
+    uptime_seconds: u64,
+    pool_threads: usize,
+    queued_jobs: usize,
+    running_jobs: usize,
+}
+
+impl Handler for Health {
+    fn call(&self, _request: Request) -> Response {
+        let metrics = self.pool.metrics();
+        Response::json(&HealthReport {
+            status: "ok",
+            uptime_seconds: self.started.elapsed().as_secs(),
+            pool_threads: metrics.threads,
+            queued_jobs: metrics.queued,
+            running_jobs: metrics.running,
+        })
+    }
+}
+
+/// Always responds with the same value, as JSON.
+pub struct Json<T>(pub T);
+
+impl<T: ToJson + Send + Sync> Handler for Json<T> {
+    fn call(&self, _request: Request) -> Response {
+        Response::json(&self.0)
+    }
+}
+
+fn write_histogram(body: &mut String, name: &str, histogram: &LatencyHistogram) {
+    let mut total = 0;
+    for (bucket, count) in histogram.buckets.iter().enumerate() {
+        total += count;
+        let le = BUCKET_BOUNDS
+            .get(bucket)
+            .map_or("+Inf".to_string(), |bound| bound.as_secs_f64().to_string());
+        body.push_str(&format!("{name}_bucket{{le=\"{le}\"}} {total}\n"));
+    }
+    body.push_str(&format!("{name}_count {total}\n"));
+}
@@ -0,0 +1,633 @@
+use std::{
+    fmt,
+    io::{self, prelude::*, Cursor},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use hello_macro::json::ToJson;
+
+use crate::{cpu, tables::reason_phrase, ServerError};
+
+/// One line of a request head without its line ending, or `None` at the end
+/// of the input. Running into the size limit before the line ends means the
+/// head is too large.
+fn read_head_line(head: &mut io::Take<impl BufRead>) -> Result<Option<String>, ServerError> {
+    let mut line = String::new();
+    let read = head.read_line(&mut line).map_err(|err| match err.kind() {
+        io::ErrorKind::InvalidData => ServerError::Parse("request head is not UTF-8".into()),
+        _ => err.into(),
+    })?;
+    if head.limit() == 0 && !line.ends_with('\n') {
+        return Err(ServerError::TooLarge);
+    }
+    if read == 0 {
+        return Ok(None);
+    }
+
+    let line = line.strip_suffix('\n').unwrap_or(&line);
+    Ok(Some(line.strip_suffix('\r').unwrap_or(line).to_string()))
+}
+
+/// A parsed HTTP request: the request line, its headers and its body.
+#[derive(Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub version: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Body,
+}
+
+impl Request {
+    /// Read a request line and headers, stopping at the blank line that ends
+    /// the head of the request. The body is left on the reader and is empty
+    /// until the caller attaches one. A head longer than [`MAX_HEAD_SIZE`]
+    /// is [`ServerError::TooLarge`].
+    pub fn read_from(reader: &mut impl BufRead) -> Result<Request, ServerError> {
+        let mut head = Read::take(reader, MAX_HEAD_SIZE as u64);
+
+        let request_line = match read_head_line(&mut head)? {
+            Some(line) => line,
+            None => {
+                return Err(ServerError::Parse(
+                    "connection closed before request line".into(),
+                ))
+            }
+        };
+        let mut request = Request::parse_request_line(&request_line)?;
+
+        while let Some(line) = read_head_line(&mut head)? {
+            if line.is_empty() {
+                return Ok(request);
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| ServerError::Parse(format!("bad header {line:?}")))?;
+            request
+                .headers
+                .push((name.trim().to_string(), value.trim().to_string()));
+        }
+
+        Err(ServerError::Parse(
+            "connection closed inside headers".into(),
+        ))
+    }
+
+    fn parse_request_line(line: &str) -> Result<Request, ServerError> {
+        let parts: Vec<&str> = line.split(' ').collect();
+        match parts[..] {
+            [method, path, version] if path.starts_with('/') && version.starts_with("HTTP/") => {
+                Ok(Request {
+                    method: method.to_string(),
+                    path: path.to_string(),
+                    version: version.to_string(),
+                    headers: Vec::new(),
+                    body: Body::empty(),
+                })
+            }
+            _ => Err(ServerError::Parse(format!("bad request line {line:?}"))),
+        }
+    }
+
+    /// Look up a header by name, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The value of `name` in the query string, as sent: `+` and `%xx`
+    /// escapes aren't decoded. A name with no `=` has an empty value.
+    pub fn query(&self, name: &str) -> Option<&str> {
+        let (_, query) = self.path.split_once('?')?;
+        query
+            .split('&')
+            .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+            .find(|&(key, _)| key == name)
+            .map(|(_, value)| value)
+    }
+
+    /// How many body bytes follow the head, according to its headers.
+    pub fn content_length(&self) -> Result<u64, ServerError> {
+        if self.header("Transfer-Encoding").is_some() {
+            return Err(ServerError::Protocol(
+                "chunked request bodies aren't supported".into(),
+            ));
+        }
+
+        let mut lengths = self
+            .headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("Content-Length"))
+            .map(|(_, value)| {
+                value
+                    .parse::<u64>()
+                    .map_err(|_| ServerError::Protocol(format!("bad Content-Length {value:?}")))
+            });
+
+        let length = match lengths.next() {
+            Some(length) => length?,
+            None => return Ok(0),
+        };
+        for other in lengths {
+            if other? != length {
+                return Err(ServerError::Protocol(
+                    "conflicting Content-Length headers".into(),
+                ));
+            }
+        }
+        Ok(length)
+    }
+
+    /// Whether the client wants to send another request on this connection.
+    pub fn keep_alive(&self) -> bool {
+        match self.header("Connection") {
+            Some(value) if value.eq_ignore_ascii_case("close") => false,
+            Some(value) if value.eq_ignore_ascii_case("keep-alive") => true,
+            _ => self.version == "HTTP/1.1",
+        }
+    }
+}
+
+/// The body of a request, read from the connection on demand.
+///
+/// `Body` implements [`Read`], so handlers can stream it instead of buffering
+/// it. Reads stop at the declared length. If the client sends fewer bytes
+/// than it promised, reading fails with `UnexpectedEof` rather than waiting
+/// forever or quietly returning a short body.
+pub struct Body {
+    reader: Box<dyn Read + Send>,
+    length: u64,
+    remaining: Arc<AtomicU64>,
+}
+
+impl Body {
+    pub fn new(reader: impl Read + Send + 'static, length: u64) -> Body {
+        Body {
+            reader: Box::new(reader),
+            length,
+            remaining: Arc::new(AtomicU64::new(length)),
+        }
+    }
+
+    pub fn empty() -> Body {
+        Body::new(io::empty(), 0)
+    }
+
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Body {
+        let bytes = bytes.into();
+        let length = bytes.len() as u64;
+        Body::new(Cursor::new(bytes), length)
+    }
+
+    /// The length declared by the request.
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// How many bytes haven't been read yet.
+    pub fn remaining(&self) -> u64 {
+        self.remaining.load(Ordering::SeqCst)
+    }
+
+    /// A handle on the unread byte count that outlives the body, so the
+    /// server can tell how much a handler left behind.
+    pub(crate) fn remaining_handle(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.remaining)
+    }
+
+    /// Read the rest of the body into memory.
+    pub fn into_bytes(mut self) -> Result<Vec<u8>, ServerError> {
+        let mut bytes = Vec::with_capacity(self.remaining().min(64 * 1024) as usize);
+        match self.read_to_end(&mut bytes) {
+            Ok(_) => Ok(bytes),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                Err(ServerError::Protocol(err.to_string()))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl Read for Body {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.remaining();
+        if remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let limit = buf.len().min(remaining.try_into().unwrap_or(usize::MAX));
+        let read = self.reader.read(&mut buf[..limit])?;
+        if read == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "body ended after {} of {} bytes",
+                    self.length - remaining,
+                    self.length
+                ),
+            ));
+        }
+
+        self.remaining.fetch_sub(read as u64, Ordering::SeqCst);
+        Ok(read)
+    }
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Body")
+            .field("length", &self.length)
+            .field("remaining", &self.remaining())
+            .finish()
+    }
+}
+
+/// Requests with a head bigger than this are rejected by the front ends
+/// that read heads themselves.
+pub const MAX_HEAD_SIZE: usize = 8 * 1024;
+
+/// Where the head of a request ends in `buffer`, if it has arrived yet.
+pub fn head_end(buffer: &[u8]) -> Option<usize> {
+    cpu::find(buffer, b"\r\n\r\n").map(|start| start + 4)
+}
+
+/// A response waiting to be written back to the client.
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub reason: &'static str,
+    pub headers: Vec<(String, String)>,
+    pub body: ResponseBody,
+}
+
+/// What gets sent after the response head.
+pub enum ResponseBody {
+    /// Bytes already in memory.
+    Bytes(Vec<u8>),
+    /// Bytes copied from a reader while the response is written. Without a
+    /// known length the body is sent with chunked transfer encoding, or to
+    /// an HTTP/1.0 client, which can't decode that, ended by closing the
+    /// connection.
+    Reader {
+        reader: Box<dyn Read + Send>,
+        length: Option<u64>,
+    },
+}
+
+impl ResponseBody {
+    /// The body, if it's already in memory.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            ResponseBody::Bytes(bytes) => Some(bytes),
+            ResponseBody::Reader { .. } => None,
+        }
+    }
+}
+
+impl fmt::Debug for ResponseBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResponseBody::Bytes(bytes) => f.debug_tuple("Bytes").field(&bytes.len()).finish(),
+            ResponseBody::Reader { length, .. } => {
+                f.debug_struct("Reader").field("length", length).finish()
+            }
+        }
+    }
+}
+
+impl Response {
+    pub fn new(status: u16, reason: &'static str, body: impl Into<Vec<u8>>) -> Response {
+        Response {
+            status,
+            reason,
+            headers: Vec::new(),
+            body: ResponseBody::Bytes(body.into()),
+        }
+    }
+
+    /// A response whose body is streamed from `reader` as it is written.
+    ///
+    /// Pass the length if it's known up front; otherwise the body is sent in
+    /// chunks, or until the connection closes.
+    pub fn stream(
+        status: u16,
+        reason: &'static str,
+        reader: impl Read + Send + 'static,
+        length: Option<u64>,
+    ) -> Response {
+        Response {
+            status,
+            reason,
+            headers: Vec::new(),
+            body: ResponseBody::Reader {
+                reader: Box::new(reader),
+                length,
+            },
+        }
+    }
+
+    /// A response with `status`'s usual reason phrase, or none if it's a
+    /// status the server doesn't know.
+    pub fn from_status(status: u16, body: impl Into<Vec<u8>>) -> Response {
+        Response::new(status, reason_phrase(status).unwrap_or(""), body)
+    }
+
+    pub fn ok(body: impl Into<Vec<u8>>) -> Response {
+        Response::from_status(200, body)
+    }
+
+    pub fn not_found(body: impl Into<Vec<u8>>) -> Response {
+        Response::from_status(404, body)
+    }
+
+    /// A 200 response with `value` written as JSON.
+    pub fn json(value: &impl ToJson) -> Response {
+        Response::ok(value.to_json()).with_header("Content-Type", "application/json")
+    }
+
+    /// The response sent when serving a request failed with `err`.
+    pub fn from_error(err: &ServerError) -> Response {
+        let status = err.status();
+        let reason = reason_phrase(status).unwrap_or("");
+        Response::new(status, reason, reason)
+    }
+
+    pub fn with_header(mut self, name: &str, value: impl Into<String>) -> Response {
+        self.headers.push((name.to_string(), value.into()));
+        self
+    }
+
+    /// Whether the body only ends when the connection closes, when written
+    /// in reply to a request of `version`. Only HTTP/1.1 clients can decode
+    /// a chunked body, so one of unknown length has no other way to end.
+    pub fn is_close_delimited(&self, version: &str) -> bool {
+        matches!(self.body, ResponseBody::Reader { length: None, .. }) && version != "HTTP/1.1"
+    }
+
+    /// Write the response to a request of `version`, which decides how a
+    /// body of unknown length is framed.
+    pub fn write_to(self, version: &str, writer: &mut impl Write) -> io::Result<()> {
+        let close_delimited = self.is_close_delimited(version);
+        let Response {
+            status,
+            reason,
+            headers,
+            body,
+        } = self;
+
+        let mut head = format!("HTTP/1.1 {status} {reason}\r\n");
+        let has_connection = headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("Connection"));
+        for (name, value) in headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+
+        match body {
+            ResponseBody::Bytes(bytes) => {
+                head.push_str(&format!("Content-Length: {}\r\n\r\n", bytes.len()));
+                writer.write_all(head.as_bytes())?;
+                writer.write_all(&bytes)?;
+            }
+            ResponseBody::Reader {
+                reader,
+                length: Some(length),
+            } => {
+                head.push_str(&format!("Content-Length: {length}\r\n\r\n"));
+                writer.write_all(head.as_bytes())?;
+                let copied = io::copy(&mut reader.take(length), writer)?;
+                if copied < length {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        format!("response body ended after {copied} of {length} bytes"),
+                    ));
+                }
+            }
+            ResponseBody::Reader {
+                mut reader,
+                length: None,
+            } if close_delimited => {
+                if !has_connection {
+                    head.push_str("Connection: close\r\n");
+                }
+                head.push_str("\r\n");
+                writer.write_all(head.as_bytes())?;
+                io::copy(&mut reader, writer)?;
+            }
+            ResponseBody::Reader {
+                mut reader,
+                length: None,
+            } => {
+                head.push_str("Transfer-Encoding: chunked\r\n\r\n");
+                writer.write_all(head.as_bytes())?;
+                write_chunked(&mut reader, writer)?;
+            }
+        }
+
+        writer.flush()
+    }
+}
+
+fn write_chunked(reader: &mut impl Read, writer: &mut impl Write) -> io::Result<()> {
+    let mut buffer = [0; 8 * 1024];
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        if read == 0 {
+            return writer.write_all(b"0\r\n\r\n");
+        }
+        writer.write_all(format!("{read:x}\r\n").as_bytes())?;
+        writer.write_all(&buffer[..read])?;
+        writer.write_all(b"\r\n")?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use advanced_features::assert_err;
+
+    use super::*;
+
+    #[test]
+    fn reads_request_line_and_headers() {
+        let mut raw = &b"GET /echo HTTP/1.1\r\nHost: localhost\r\nX-Test:  yes \r\n\r\n"[..];
+        let request = Request::read_from(&mut raw).unwrap();
+
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/echo");
+        assert_eq!(request.header("host"), Some("localhost"));
+        assert_eq!(request.header("X-TEST"), Some("yes"));
+    }
+
+    #[test]
+    fn rejects_heads_that_are_too_big_or_not_utf8() {
+        let raw = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(MAX_HEAD_SIZE));
+        let result = Request::read_from(&mut raw.as_bytes());
+        assert_err!(result, ServerError::TooLarge);
+
+        let mut raw = &b"GET / HTTP/1.1\r\nX: \xff\r\n\r\n"[..];
+        assert_err!(Request::read_from(&mut raw), ServerError::Parse(_));
+    }
+
+    #[test]
+    fn finds_query_parameters() {
+        let mut raw = &b"GET /greet?name=Ferris&loud&x=1 HTTP/1.1\r\n\r\n"[..];
+        let request = Request::read_from(&mut raw).unwrap();
+        assert_eq!(request.query("name"), Some("Ferris"));
+        assert_eq!(request.query("loud"), Some(""));
+        assert_eq!(request.query("y"), None);
+
+        let mut raw = &b"GET /greet HTTP/1.1\r\n\r\n"[..];
+        assert_eq!(Request::read_from(&mut raw).unwrap().query("name"), None);
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        let mut raw = &b"garbage\r\n\r\n"[..];
+        assert_err!(Request::read_from(&mut raw), ServerError::Parse(_));
+    }
+
+    fn request_with(headers: &[(&str, &str)]) -> Request {
+        let mut raw = &b"POST / HTTP/1.1\r\n\r\n"[..];
+        let mut request = Request::read_from(&mut raw).unwrap();
+        for (name, value) in headers {
+            request.headers.push((name.to_string(), value.to_string()));
+        }
+        request
+    }
+
+    #[test]
+    fn content_length_must_be_consistent() {
+        assert_eq!(request_with(&[]).content_length().unwrap(), 0);
+        assert_eq!(
+            request_with(&[("content-length", "5"), ("Content-Length", "5")])
+                .content_length()
+                .unwrap(),
+            5
+        );
+        assert_err!(
+            request_with(&[("Content-Length", "5"), ("Content-Length", "6")]).content_length(),
+            ServerError::Protocol(_)
+        );
+        assert_err!(
+            request_with(&[("Content-Length", "-1")]).content_length(),
+            ServerError::Protocol(_)
+        );
+    }
+
+    #[test]
+    fn body_stops_at_content_length() {
+        let body = Body::new(&b"hello, and the next request"[..], 5);
+        assert_eq!(body.into_bytes().unwrap(), b"hello");
+    }
+
+    #[test]
+    fn short_body_is_a_protocol_error() {
+        let body = Body::new(&b"hel"[..], 5);
+        assert_err!(body.into_bytes(), ServerError::Protocol(_));
+    }
+
+    #[test]
+    fn body_can_be_streamed() {
+        let mut body = Body::new(&b"hello world"[..], 11);
+        let mut first = [0; 5];
+        body.read_exact(&mut first).unwrap();
+
+        assert_eq!(&first, b"hello");
+        assert_eq!(body.remaining(), 6);
+    }
+
+    #[test]
+    fn streamed_response_with_length() {
+        let mut out = Vec::new();
+        Response::stream(200, "OK", &b"streamed and more"[..], Some(8))
+            .write_to("HTTP/1.1", &mut out)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Length: 8\r\n\r\nstreamed"
+        );
+    }
+
+    #[test]
+    fn streamed_response_without_length_is_chunked() {
+        let mut out = Vec::new();
+        Response::stream(200, "OK", &b"chunky"[..], None)
+            .write_to("HTTP/1.1", &mut out)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n6\r\nchunky\r\n0\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn streamed_response_to_http_1_0_ends_with_the_connection() {
+        let mut out = Vec::new();
+        Response::stream(200, "OK", &b"chunky"[..], None)
+            .write_to("HTTP/1.0", &mut out)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "HTTP/1.1 200 OK\r\nConnection: close\r\n\r\nchunky"
+        );
+    }
+
+    #[test]
+    fn finds_the_end_of_the_head() {
+        assert_eq!(head_end(b"GET / HTTP/1.1\r\n\r\nbody"), Some(18));
+        assert_eq!(head_end(b"GET / HTTP/1.1\r\n"), None);
+    }
+
+    #[test]
+    fn writes_status_headers_and_length() {
+        let mut out = Vec::new();
+        Response::ok("hi")
+            .with_header("Content-Type", "text/plain")
+            .write_to("HTTP/1.1", &mut out)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 2\r\n\r\nhi"
+        );
+    }
+
+    #[test]
+    fn errors_use_the_reason_phrase_table() {
+        let response = Response::from_error(&ServerError::TooLarge);
+        assert_eq!(
+            (response.status, response.reason),
+            (413, "PAYLOAD TOO LARGE")
+        );
+        assert_eq!(response.body.as_bytes(), Some(&b"PAYLOAD TOO LARGE"[..]));
+    }
+
+    #[test]
+    fn writes_json_bodies() {
+        let mut out = Vec::new();
+        Response::json(&vec![("a", 1)])
+            .write_to("HTTP/1.1", &mut out)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 9\r\n\r\n[[\"a\",1]]"
+        );
+    }
+}
@@ -0,0 +1,26 @@
+pub mod access_log;
+#[cfg(unix)]
+pub mod async_server;
+pub mod config;
+pub mod cpu;
+pub mod defer;
+pub mod error;
+#[cfg(unix)]
+pub mod event_loop;
+#[cfg(unix)]
+pub mod executor;
+pub mod handlers;
+pub mod http;
+pub mod log;
+pub mod plugins;
+#[cfg(unix)]
+pub mod poller;
+#[cfg(target_os = "linux")]
+pub mod prefork;
+pub mod router;
+pub mod server;
+pub mod stats;
+pub mod tables;
+pub mod template;
+
+pub use error::ServerError;
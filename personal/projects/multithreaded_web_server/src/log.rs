@@ -0,0 +1,237 @@
+//! Leveled logging for the server's own messages, as opposed to the access
+//! log's one line per request.
+//!
+//! The [`log_error!`], [`log_info!`] and [`log_debug!`] macros check the
+//! level before anything else, so a message below it costs a comparison:
+//! its arguments are never formatted. Those that pass go to the sink, with
+//! the module, file and line they were logged from. The sink is stderr
+//! until [`set_sink`] replaces it.
+//!
+//! [`log_error!`]: crate::log_error
+//! [`log_info!`]: crate::log_info
+//! [`log_debug!`]: crate::log_debug
+
+use std::{
+    fmt,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        RwLock,
+    },
+};
+
+/// How much a message matters, least verbose first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Error = 1,
+    Info,
+    Debug,
+}
+
+/// One message, ready to be written.
+#[derive(Debug)]
+pub struct Record<'a> {
+    pub level: Level,
+    pub module_path: &'static str,
+    pub file: &'static str,
+    pub line: u32,
+    pub args: fmt::Arguments<'a>,
+}
+
+/// Where records go.
+pub trait Sink: Send + Sync {
+    fn write(&self, record: &Record<'_>);
+}
+
+impl<F: Fn(&Record<'_>) + Send + Sync> Sink for F {
+    fn write(&self, record: &Record<'_>) {
+        self(record)
+    }
+}
+
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+static SINK: RwLock<Option<Box<dyn Sink>>> = RwLock::new(None);
+
+/// Log messages at `level` and below, and drop the rest.
+pub fn set_max_level(level: Level) {
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Send every record to `sink` from now on.
+pub fn set_sink(sink: impl Sink + 'static) {
+    *SINK.write().unwrap_or_else(|err| err.into_inner()) = Some(Box::new(sink));
+}
+
+/// Write `record` to the sink, whatever its level; the macros have
+/// already checked it.
+pub fn write(record: &Record<'_>) {
+    match &*SINK.read().unwrap_or_else(|err| err.into_inner()) {
+        Some(sink) => sink.write(record),
+        None => eprintln!("{record}"),
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Level::Error => "ERROR",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+        })
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Level, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "error" => Ok(Level::Error),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            _ => Err(format!("unknown log level {s:?}")),
+        }
+    }
+}
+
+/// `[LEVEL module file:line] message`
+impl fmt::Display for Record<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{:<5} {} {}:{}] {}",
+            self.level, self.module_path, self.file, self.line, self.args
+        )
+    }
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log {
+    ($level:expr, $($arg:tt)+) => {
+        if $crate::log::enabled($level) {
+            $crate::log::write(&$crate::log::Record {
+                level: $level,
+                module_path: ::std::module_path!(),
+                file: ::std::file!(),
+                line: ::std::line!(),
+                args: ::std::format_args!($($arg)+),
+            });
+        }
+    };
+}
+
+/// Log a message at [`Level::Error`], with `format!`'s arguments.
+#[macro_export]
+macro_rules! log_error {
+    ($($arg:tt)+) => {
+        $crate::__log!($crate::log::Level::Error, $($arg)+)
+    };
+}
+
+/// Log a message at [`Level::Info`], with `format!`'s arguments.
+#[macro_export]
+macro_rules! log_info {
+    ($($arg:tt)+) => {
+        $crate::__log!($crate::log::Level::Info, $($arg)+)
+    };
+}
+
+/// Log a message at [`Level::Debug`], with `format!`'s arguments.
+#[macro_export]
+macro_rules! log_debug {
+    ($($arg:tt)+) => {
+        $crate::__log!($crate::log::Level::Debug, $($arg)+)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::Cell,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+
+    /// The level and sink are global, so the tests take turns with them,
+    /// and only look at records from this module: the rest of the crate's
+    /// tests log too.
+    static GLOBALS: Mutex<()> = Mutex::new(());
+
+    fn capture() -> Arc<Mutex<Vec<String>>> {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&lines);
+        set_sink(move |record: &Record<'_>| {
+            if record.module_path == module_path!() {
+                sink.lock().unwrap().push(record.to_string());
+            }
+        });
+        lines
+    }
+
+    #[test]
+    fn records_where_each_message_came_from() {
+        let _globals = GLOBALS.lock().unwrap_or_else(|err| err.into_inner());
+        let lines = capture();
+        set_max_level(Level::Info);
+
+        let line = line!() + 1;
+        log_error!("failed: {}", "disk full");
+        log_info!("listening on port {port}", port = 7878);
+
+        assert_eq!(
+            *lines.lock().unwrap(),
+            [
+                format!(
+                    "[ERROR {} src/log.rs:{line}] failed: disk full",
+                    module_path!()
+                ),
+                format!(
+                    "[INFO  {} src/log.rs:{}] listening on port 7878",
+                    module_path!(),
+                    line + 1
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn messages_below_the_level_are_never_formatted() {
+        struct Counted<'a>(&'a Cell<u32>);
+
+        impl fmt::Display for Counted<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.set(self.0.get() + 1);
+                f.write_str("counted")
+            }
+        }
+
+        let _globals = GLOBALS.lock().unwrap_or_else(|err| err.into_inner());
+        let lines = capture();
+        let formatted = Cell::new(0);
+
+        set_max_level(Level::Error);
+        log_debug!("{}", Counted(&formatted));
+        log_info!("{}", Counted(&formatted));
+        assert_eq!(formatted.get(), 0);
+        assert!(lines.lock().unwrap().is_empty());
+
+        set_max_level(Level::Debug);
+        log_debug!("{}", Counted(&formatted));
+        assert_eq!(formatted.get(), 1);
+        assert_eq!(lines.lock().unwrap().len(), 1);
+        set_max_level(Level::Info);
+    }
+
+    #[test]
+    fn levels_parse_from_flags() {
+        assert_eq!("DEBUG".parse(), Ok(Level::Debug));
+        assert!("verbose".parse::<Level>().is_err());
+        assert!(Level::Error < Level::Debug);
+    }
+}
@@ -1523,3 +1523,212 @@
 // Keep in mind that there is a welcoming community of other Rustaceans who would love to help you with any challenges you encounter on your Rust journey.
 
 
+use hello_macro::json::AsString;
+use hello_macro_derive::{config_env, ToJson};
+use multithreaded_web_server::{
+    access_log::{AccessLog, Rotation},
+    config::Config,
+    handlers::{Counter, Echo, Greet, Health, Json, PoolMetrics, Sleep, Time},
+    log, log_info,
+    plugins::Plugins,
+    router::{MimeTypes, Router, StaticFiles},
+    server::{self, Server},
+    stats::{ConnectionRegistry, Connections},
+    template::Templates,
+    ServerError,
+};
+use std::{
+    env,
+    net::{Ipv4Addr, SocketAddrV4, TcpListener},
+    sync::Arc,
+};
+use thread_pool::{Semaphore, ThreadPool};
+
+// Set `SERVER_PORT` and `SERVER_POOL_SIZE` when building to change these.
+const ADDR: SocketAddrV4 =
+    SocketAddrV4::new(Ipv4Addr::LOCALHOST, config_env!("SERVER_PORT", 7878u16));
+const POOL_SIZE: usize = config_env!("SERVER_POOL_SIZE", 4usize);
+
+fn main() {
+    // `--log-level error|info|debug` sets how much the server says about
+    // itself; it's `info` by default.
+    if let Some(level) = flag_str("--log-level") {
+        match level.parse() {
+            Ok(level) => log::set_max_level(level),
+            Err(err) => {
+                eprintln!("--log-level: {err}");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    // `--config PATH` reads extra settings, such as MIME type overrides.
+    let config = flag_str("--config").map_or_else(Config::default, |path| {
+        Config::load(&path).unwrap_or_else(|err| {
+            eprintln!("Failed to load config {path}: {err}");
+            std::process::exit(1);
+        })
+    });
+    let mut mime_types = MimeTypes::default();
+    mime_types.extend(config.mime_types.iter().cloned());
+
+    // `--access-log PATH` logs every request to PATH, rotating it once it
+    // passes `--log-max-bytes N` and/or every day with `--log-daily`, and
+    // keeping `--log-keep N` old files (0 keeps none).
+    let access_log_path = flag_str("--access-log");
+    let rotation = Rotation {
+        max_bytes: flag_value("--log-max-bytes").map(|bytes| bytes as u64),
+        daily: env::args().any(|arg| arg == "--log-daily"),
+        keep: flag_count("--log-keep").unwrap_or(Rotation::default().keep),
+    };
+    let access_log = access_log_path.as_ref().map(|path| {
+        let access_log = AccessLog::open(path, rotation.clone()).unwrap_or_else(|err| {
+            eprintln!(
+                "Failed to open access log {path}: {}",
+                ServerError::from(err)
+            );
+            std::process::exit(1);
+        });
+        Arc::new(access_log)
+    });
+
+    // `--max-connections N` serves at most N connections at a time.
+    let max_connections = flag_value("--max-connections");
+    let connection_limit = max_connections.map(|n| Arc::new(Semaphore::new(n)));
+
+    // What `/debug/config` reports the server was started with.
+    let settings = Arc::new(Settings {
+        address: AsString(ADDR),
+        pool_size: POOL_SIZE,
+        max_connections,
+        config,
+    });
+
+    // The routes, given the pool serving them so that `/metrics` can report
+    // on it.
+    let make_server = |pool: &Arc<ThreadPool>, access_log: Option<Arc<AccessLog>>| {
+        let static_files = || StaticFiles::new("public").with_mime_types(mime_types.clone());
+        let connections = Arc::new(ConnectionRegistry::new());
+        let router = Router::new(static_files())
+            .route("/sleep", Sleep(static_files()))
+            .route("/time", Time)
+            .route("/echo", Echo)
+            .route("/counter", Counter::default())
+            .route("/greet", Greet(Templates::new("templates")))
+            .route("/debug/connections", Connections(Arc::clone(&connections)))
+            .route("/metrics", PoolMetrics(Arc::clone(pool)))
+            .route("/health", Health::new(Arc::clone(pool)))
+            .route("/debug/config", Json(Arc::clone(&settings)))
+            .mount("/plugins", Plugins::builtin());
+
+        Arc::new(Server {
+            router,
+            connections,
+            access_log,
+            connection_limit: connection_limit.clone(),
+        })
+    };
+
+    // Worker panics are logged with the request that caused them.
+    let new_pool = || {
+        let pool = ThreadPool::builder()
+            .core_threads(POOL_SIZE)
+            .panic_handler(server::log_panic)
+            .build();
+        Arc::new(pool)
+    };
+
+    // `--prefork N` forks N processes that each listen on the port and run
+    // their own pool. This has to happen before any threads are started.
+    // Each one opens the access log for itself, to share it with the others.
+    #[cfg(target_os = "linux")]
+    if let Some(processes) = flag_value("--prefork") {
+        drop(access_log);
+        let result = multithreaded_web_server::prefork::run(ADDR, processes, |listener| {
+            let access_log = match &access_log_path {
+                Some(path) => Some(Arc::new(AccessLog::open_shared(path, rotation.clone())?)),
+                None => None,
+            };
+            let pool = new_pool();
+            server::run(listener, &pool, make_server(&pool, access_log));
+            Ok(())
+        });
+        if let Err(err) = result {
+            eprintln!("Supervisor failed: {err}");
+        }
+        return;
+    }
+
+    let listener = match TcpListener::bind(ADDR) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("Failed to bind: {}", ServerError::from(err));
+            std::process::exit(1);
+        }
+    };
+
+    let pool = new_pool();
+    let server = make_server(&pool, access_log);
+
+    // `--async` runs every connection as a task on the in-crate executor,
+    // handing only the handlers to the pool.
+    #[cfg(unix)]
+    if env::args().any(|arg| arg == "--async") {
+        if let Err(err) = multithreaded_web_server::async_server::run(listener, pool, server) {
+            eprintln!("Async server failed: {err}");
+        }
+        return;
+    }
+
+    // `--event-loop` reads request heads on a single poll-driven thread and
+    // only hands complete requests to the pool.
+    #[cfg(unix)]
+    if env::args().any(|arg| arg == "--event-loop") {
+        if let Err(err) = multithreaded_web_server::event_loop::run(listener, &pool, server) {
+            eprintln!("Event loop failed: {err}");
+        }
+        return;
+    }
+
+    log_info!("Listening on {ADDR} with {POOL_SIZE} threads");
+    server::run(listener, &pool, server);
+
+    println!("Shutting down.");
+}
+
+/// The settings the server was started with.
+#[derive(ToJson)]
+struct Settings {
+    address: AsString<SocketAddrV4>,
+    pool_size: usize,
+    max_connections: Option<usize>,
+    config: Config,
+}
+
+/// The argument following `flag` on the command line, if it was given.
+fn flag_str(flag: &str) -> Option<String> {
+    env::args().skip_while(|arg| arg != flag).nth(1)
+}
+
+/// The number following `flag` on the command line, if it was given.
+fn flag_value(flag: &str) -> Option<usize> {
+    match flag_count(flag) {
+        Some(0) => {
+            eprintln!("{flag} expects a positive number");
+            std::process::exit(2);
+        }
+        value => value,
+    }
+}
+
+/// Like [`flag_value`], but for flags where 0 makes sense too.
+fn flag_count(flag: &str) -> Option<usize> {
+    match flag_str(flag).map(|value| value.parse()) {
+        Some(Ok(value)) => Some(value),
+        Some(Err(_)) => {
+            eprintln!("{flag} expects a number");
+            std::process::exit(2);
+        }
+        None => None,
+    }
+}
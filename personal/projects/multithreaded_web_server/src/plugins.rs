@@ -0,0 +1,103 @@
+//! Handlers that are plain functions, served by name under one mount.
+//!
+//! A handler with no state doesn't need a type of its own to implement
+//! [`Handler`] on: a `fn(&Request) -> Response` will do. [`PLUGINS`] lists
+//! the built-in ones, and [`Plugins`] serves any registry of them, so that
+//! `/plugins/hello` calls the plugin called `hello`.
+
+use advanced_features::plugins::Registry;
+
+use crate::{
+    http::{Request, Response},
+    router::Handler,
+};
+
+// The built-in plugins.
+advanced_features::plugins! {
+    pub static PLUGINS: fn(&Request) -> Response = {
+        /// Greets the client by its `User-Agent`.
+        "hello" => fn hello(request) {
+            let agent = request.header("User-Agent").unwrap_or("stranger");
+            text(format!("Hello, {agent}!\n"))
+        }
+        /// Lists the request's headers, one `name: value` per line.
+        "headers" => fn headers(request) {
+            let mut body = String::new();
+            for (name, value) in &request.headers {
+                body.push_str(&format!("{name}: {value}\n"));
+            }
+            text(body)
+        }
+    }
+}
+
+fn text(body: String) -> Response {
+    Response::ok(body).with_header("Content-Type", "text/plain")
+}
+
+/// Calls the plugin named by the last part of the request's path, and
+/// lists them all for a path ending in `/` or with no plugin name.
+pub struct Plugins(pub Registry<Request, Response>);
+
+impl Plugins {
+    /// The plugins in [`PLUGINS`].
+    pub fn builtin() -> Plugins {
+        Plugins(Registry::from(PLUGINS))
+    }
+}
+
+impl Handler for Plugins {
+    fn call(&self, request: Request) -> Response {
+        let path = request.path.split('?').next().unwrap_or_default();
+        let name = match path.rsplit_once('/') {
+            Some((parent, name)) if !parent.is_empty() => name,
+            _ => "",
+        };
+        if name.is_empty() {
+            let names: Vec<_> = self.0.names().collect();
+            return text(names.join("\n") + "\n");
+        }
+        self.0
+            .dispatch(name, &request)
+            .unwrap_or_else(|| Response::not_found(format!("No plugin called {name}\n")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::Body;
+
+    fn get(path: &str) -> Request {
+        Request {
+            method: "GET".to_string(),
+            path: path.to_string(),
+            version: "HTTP/1.1".to_string(),
+            headers: vec![("User-Agent".to_string(), "curl".to_string())],
+            body: Body::empty(),
+        }
+    }
+
+    fn body(response: &Response) -> &str {
+        std::str::from_utf8(response.body.as_bytes().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn dispatches_by_the_last_path_segment() {
+        let plugins = Plugins::builtin();
+        let response = plugins.call(get("/plugins/hello?x=1"));
+        assert_eq!((response.status, body(&response)), (200, "Hello, curl!\n"));
+        let response = plugins.call(get("/plugins/headers"));
+        assert_eq!(body(&response), "User-Agent: curl\n");
+    }
+
+    #[test]
+    fn lists_plugins_and_refuses_unknown_ones() {
+        let plugins = Plugins::builtin();
+        for path in ["/plugins", "/plugins/"] {
+            assert_eq!(body(&plugins.call(get(path))), "headers\nhello\n");
+        }
+        let response = plugins.call(get("/plugins/nope"));
+        assert_eq!(response.status, 404);
+    }
+}
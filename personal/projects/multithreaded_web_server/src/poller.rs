@@ -0,0 +1,167 @@
+//! A thin, safe wrapper around `poll(2)`, and around `read(2)` for the
+//! readers it wakes.
+//!
+//! `poll` is older and slower than epoll or kqueue, but it is available on
+//! every Unix and needs nothing beyond the libc that std already links.
+
+use std::{
+    io,
+    os::raw::{c_int, c_short, c_void},
+    os::unix::io::RawFd,
+    time::Duration,
+};
+
+use advanced_features::uninit_buf::UninitBuf;
+
+const POLLIN: c_short = 0x001;
+const POLLOUT: c_short = 0x004;
+const POLLERR: c_short = 0x008;
+const POLLHUP: c_short = 0x010;
+const POLLNVAL: c_short = 0x020;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct PollFd {
+    fd: c_int,
+    events: c_short,
+    revents: c_short,
+}
+
+#[cfg(target_os = "linux")]
+type NFds = std::os::raw::c_ulong;
+#[cfg(not(target_os = "linux"))]
+type NFds = std::os::raw::c_uint;
+
+extern "C" {
+    fn poll(fds: *mut PollFd, nfds: NFds, timeout: c_int) -> c_int;
+    fn read(fd: c_int, buf: *mut c_void, count: usize) -> isize;
+}
+
+/// What a registered descriptor is waiting for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interest {
+    Readable,
+    Writable,
+}
+
+/// The set of file descriptors we're waiting on.
+#[derive(Default)]
+pub struct Poller {
+    fds: Vec<PollFd>,
+}
+
+impl Poller {
+    pub fn new() -> Poller {
+        Poller::default()
+    }
+
+    pub fn register(&mut self, fd: RawFd, interest: Interest) {
+        let events = match interest {
+            Interest::Readable => POLLIN,
+            Interest::Writable => POLLOUT,
+        };
+        self.fds.push(PollFd {
+            fd,
+            events,
+            revents: 0,
+        });
+    }
+
+    pub fn deregister(&mut self, fd: RawFd) {
+        self.fds.retain(|pollfd| pollfd.fd != fd);
+    }
+
+    /// Block until at least one registered descriptor is ready (or hung up)
+    /// or `timeout` passes, and return the ones that are ready.
+    pub fn wait(&mut self, timeout: Option<Duration>) -> io::Result<Vec<RawFd>> {
+        let timeout = match timeout {
+            Some(timeout) => timeout.as_millis().min(c_int::MAX as u128) as c_int,
+            None => -1,
+        };
+
+        // SAFETY: `fds` is a valid, exclusively borrowed buffer of `len`
+        // `pollfd`s for the duration of the call, which is all poll needs.
+        let ready = unsafe { poll(self.fds.as_mut_ptr(), self.fds.len() as NFds, timeout) };
+        if ready < 0 {
+            let err = io::Error::last_os_error();
+            return match err.kind() {
+                io::ErrorKind::Interrupted => Ok(Vec::new()),
+                _ => Err(err),
+            };
+        }
+
+        Ok(self
+            .fds
+            .iter()
+            .filter(|pollfd| {
+                pollfd.revents & (POLLIN | POLLOUT | POLLERR | POLLHUP | POLLNVAL) != 0
+            })
+            .map(|pollfd| pollfd.fd)
+            .collect())
+    }
+}
+
+/// Read from `fd` into `buf`'s spare capacity, returning how much was read.
+///
+/// `Read::read` wants a `&mut [u8]`, which has to be initialized, so a
+/// reader would zero its buffer only for the kernel to overwrite it. This
+/// hands `read(2)` the uninitialized bytes instead.
+pub fn read_uninit(fd: RawFd, buf: &mut UninitBuf<u8>) -> io::Result<usize> {
+    read_uninit_max(fd, buf, usize::MAX)
+}
+
+/// Like [`read_uninit`], but reads at most `max` bytes.
+pub fn read_uninit_max(fd: RawFd, buf: &mut UninitBuf<u8>, max: usize) -> io::Result<usize> {
+    let spare = buf.spare_capacity_mut();
+    let len = spare.len().min(max);
+    // SAFETY: `spare` is valid for writes of `spare.len()` bytes, and
+    // `read` writes at most `len` of them.
+    let read = unsafe { read(fd, spare.as_mut_ptr().cast(), len) };
+    if read < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let read = read as usize;
+    // SAFETY: `read` returned how many of those bytes it wrote.
+    unsafe { buf.advance(read) };
+    Ok(read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Write, net::TcpListener, net::TcpStream, os::unix::io::AsRawFd};
+
+    #[test]
+    fn reports_only_readable_descriptors() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut poller = Poller::new();
+        poller.register(listener.as_raw_fd(), Interest::Readable);
+
+        let ready = poller.wait(Some(Duration::from_millis(10))).unwrap();
+        assert!(ready.is_empty());
+
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.write_all(b"x").unwrap();
+
+        let ready = poller.wait(Some(Duration::from_secs(1))).unwrap();
+        assert_eq!(ready, vec![listener.as_raw_fd()]);
+    }
+
+    #[test]
+    fn reads_into_uninitialized_bytes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        client.write_all(b"hello").unwrap();
+
+        let mut buf = UninitBuf::with_capacity(3);
+        assert_eq!(read_uninit(server.as_raw_fd(), &mut buf).unwrap(), 3);
+        assert_eq!(buf.assume_init_slice(), b"hel");
+        // Full, so there's nothing to read into.
+        assert_eq!(read_uninit(server.as_raw_fd(), &mut buf).unwrap(), 0);
+
+        buf.clear();
+        assert_eq!(read_uninit(server.as_raw_fd(), &mut buf).unwrap(), 2);
+        assert_eq!(buf.assume_init_slice(), b"lo");
+    }
+}
@@ -0,0 +1,234 @@
+//! Pre-fork mode: a supervisor process forks N children, each binding its own
+//! `SO_REUSEPORT` listener and running its own thread pool, and restarts any
+//! child that dies.
+//!
+//! The kernel load-balances new connections across the children's sockets,
+//! and a crash (even an abort that takes the whole process down) only costs
+//! one child's in-flight requests.
+
+use std::{
+    io, mem,
+    net::{SocketAddrV4, TcpListener},
+    os::raw::{c_int, c_uint, c_void},
+    os::unix::io::FromRawFd,
+    process, thread,
+    time::{Duration, Instant},
+};
+
+use crate::ServerError;
+
+type PidT = c_int;
+type SockLenT = c_uint;
+
+const AF_INET: c_int = 2;
+const SOCK_STREAM: c_int = 1;
+const SOCK_CLOEXEC: c_int = 0o2000000;
+const SOL_SOCKET: c_int = 1;
+const SO_REUSEADDR: c_int = 2;
+const SO_REUSEPORT: c_int = 15;
+const BACKLOG: c_int = 128;
+const PR_SET_PDEATHSIG: c_int = 1;
+const SIGTERM: c_int = 15;
+
+/// A child that dies sooner than this after starting is restarted with a
+/// delay, so a child that can't start doesn't turn into a fork bomb.
+const MIN_CHILD_LIFETIME: Duration = Duration::from_secs(1);
+
+#[repr(C)]
+struct SockAddrIn {
+    sin_family: u16,
+    sin_port: u16,
+    sin_addr: u32,
+    sin_zero: [u8; 8],
+}
+
+extern "C" {
+    fn fork() -> PidT;
+    fn getppid() -> PidT;
+    fn _exit(status: c_int) -> !;
+    fn waitpid(pid: PidT, status: *mut c_int, options: c_int) -> PidT;
+    fn socket(domain: c_int, ty: c_int, protocol: c_int) -> c_int;
+    fn setsockopt(
+        fd: c_int,
+        level: c_int,
+        name: c_int,
+        value: *const c_void,
+        len: SockLenT,
+    ) -> c_int;
+    fn bind(fd: c_int, addr: *const SockAddrIn, len: SockLenT) -> c_int;
+    fn listen(fd: c_int, backlog: c_int) -> c_int;
+    fn close(fd: c_int) -> c_int;
+    fn prctl(option: c_int, ...) -> c_int;
+}
+
+/// Turn a `-1` return value into the current `errno`.
+fn check(ret: c_int) -> io::Result<c_int> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+/// Bind a listener with `SO_REUSEPORT` set, so several processes can each
+/// have their own socket on the same address.
+pub fn reuseport_listener(addr: SocketAddrV4) -> io::Result<TcpListener> {
+    // SAFETY: plain syscalls on a descriptor we own; every pointer passed
+    // points at a live local of the size we report.
+    unsafe {
+        let fd = check(socket(AF_INET, SOCK_STREAM | SOCK_CLOEXEC, 0))?;
+
+        let setup = || -> io::Result<()> {
+            let on: c_int = 1;
+            for option in [SO_REUSEADDR, SO_REUSEPORT] {
+                check(setsockopt(
+                    fd,
+                    SOL_SOCKET,
+                    option,
+                    &on as *const c_int as *const c_void,
+                    mem::size_of::<c_int>() as SockLenT,
+                ))?;
+            }
+
+            let sockaddr = SockAddrIn {
+                sin_family: AF_INET as u16,
+                sin_port: addr.port().to_be(),
+                sin_addr: u32::from(*addr.ip()).to_be(),
+                sin_zero: [0; 8],
+            };
+            check(bind(
+                fd,
+                &sockaddr,
+                mem::size_of::<SockAddrIn>() as SockLenT,
+            ))?;
+            check(listen(fd, BACKLOG))?;
+            Ok(())
+        };
+
+        match setup() {
+            Ok(()) => Ok(TcpListener::from_raw_fd(fd)),
+            Err(err) => {
+                close(fd);
+                Err(err)
+            }
+        }
+    }
+}
+
+struct Child {
+    pid: PidT,
+    started: Instant,
+}
+
+/// Fork `workers` children that each call `serve` with their own listener on
+/// `addr`, and keep restarting them as they exit. Only returns if forking
+/// or waiting fails.
+///
+/// Must be called before the process starts any threads: only the forking
+/// thread survives in the child.
+pub fn run<F>(addr: SocketAddrV4, workers: usize, serve: F) -> Result<(), ServerError>
+where
+    F: Fn(TcpListener) -> Result<(), ServerError>,
+{
+    let mut children = Vec::with_capacity(workers);
+    for _ in 0..workers {
+        children.push(spawn_child(addr, &serve)?);
+    }
+
+    loop {
+        let mut status: c_int = 0;
+        // SAFETY: `status` is a valid out-pointer for the duration of the call.
+        let pid = match check(unsafe { waitpid(-1, &mut status, 0) }) {
+            Ok(pid) => pid,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+
+        let Some(slot) = children.iter().position(|child| child.pid == pid) else {
+            continue;
+        };
+        let child = children.swap_remove(slot);
+        crate::log_error!(
+            "Worker process {pid} {}; restarting.",
+            describe_exit(status)
+        );
+
+        let lifetime = child.started.elapsed();
+        if lifetime < MIN_CHILD_LIFETIME {
+            thread::sleep(MIN_CHILD_LIFETIME - lifetime);
+        }
+        children.push(spawn_child(addr, &serve)?);
+    }
+}
+
+fn spawn_child<F>(addr: SocketAddrV4, serve: &F) -> Result<Child, ServerError>
+where
+    F: Fn(TcpListener) -> Result<(), ServerError>,
+{
+    let supervisor = process::id();
+    // SAFETY: the supervisor is single-threaded, so the child starts from a
+    // consistent copy of it and is free to spawn threads of its own.
+    let pid = check(unsafe { fork() })?;
+    if pid > 0 {
+        crate::log_info!("Started worker process {pid}");
+        return Ok(Child {
+            pid,
+            started: Instant::now(),
+        });
+    }
+
+    // Don't outlive the supervisor.
+    // SAFETY: PR_SET_PDEATHSIG takes a single integer argument.
+    unsafe { prctl(PR_SET_PDEATHSIG, SIGTERM) };
+    // The supervisor may have died before that took effect, in which case
+    // no signal is coming and the child has been reparented.
+    // SAFETY: getppid can't fail, and _exit is safe to call at any point.
+    if unsafe { getppid() } as u32 != supervisor {
+        unsafe { _exit(1) };
+    }
+
+    let result = reuseport_listener(addr)
+        .map_err(ServerError::from)
+        .and_then(serve);
+    match result {
+        Ok(()) => process::exit(0),
+        Err(err) => {
+            crate::log_error!("Worker process failed: {err}");
+            process::exit(1);
+        }
+    }
+}
+
+fn describe_exit(status: c_int) -> String {
+    let signal = status & 0x7f;
+    if signal == 0 {
+        format!("exited with status {}", (status >> 8) & 0xff)
+    } else {
+        format!("was killed by signal {signal}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, TcpStream};
+
+    #[test]
+    fn two_listeners_can_share_a_port() {
+        let first = reuseport_listener(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = match first.local_addr().unwrap() {
+            std::net::SocketAddr::V4(addr) => addr,
+            addr => panic!("unexpected address {addr}"),
+        };
+
+        let second = reuseport_listener(addr).unwrap();
+        assert_eq!(second.local_addr().unwrap(), first.local_addr().unwrap());
+        TcpStream::connect(addr).unwrap();
+    }
+
+    #[test]
+    fn describes_exit_codes_and_signals() {
+        assert_eq!(describe_exit(3 << 8), "exited with status 3");
+        assert_eq!(describe_exit(9), "was killed by signal 9");
+    }
+}
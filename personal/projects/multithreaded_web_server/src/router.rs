@@ -0,0 +1,265 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    http::{Request, Response},
+    tables,
+};
+
+/// Something that can turn a request into a response.
+///
+/// Handlers are shared by every worker in the pool, so they have to be
+/// `Send + Sync`; any state they keep needs its own synchronization.
+pub trait Handler: Send + Sync {
+    fn call(&self, request: Request) -> Response;
+}
+
+impl<F> Handler for F
+where
+    F: Fn(Request) -> Response + Send + Sync,
+{
+    fn call(&self, request: Request) -> Response {
+        self(request)
+    }
+}
+
+/// Maps request paths to handlers, falling back to static files for
+/// anything that isn't registered.
+pub struct Router {
+    routes: HashMap<String, Box<dyn Handler>>,
+    mounts: Vec<(String, Box<dyn Handler>)>,
+    fallback: Box<dyn Handler>,
+}
+
+impl Router {
+    pub fn new(fallback: impl Handler + 'static) -> Router {
+        Router {
+            routes: HashMap::new(),
+            mounts: Vec::new(),
+            fallback: Box::new(fallback),
+        }
+    }
+
+    /// Register `handler` for requests whose path (without the query string)
+    /// is exactly `path`.
+    pub fn route(mut self, path: &str, handler: impl Handler + 'static) -> Router {
+        self.routes.insert(path.to_string(), Box::new(handler));
+        self
+    }
+
+    /// Register `handler` for `prefix` and every path under it, such as
+    /// `prefix/a/b`, where no exact route matches. Prefixes are tried in
+    /// the order they were mounted.
+    pub fn mount(mut self, prefix: &str, handler: impl Handler + 'static) -> Router {
+        self.mounts.push((prefix.to_string(), Box::new(handler)));
+        self
+    }
+
+    pub fn handle(&self, request: Request) -> Response {
+        let path = request.path.split('?').next().unwrap_or_default();
+        if let Some(handler) = self.routes.get(path) {
+            return handler.call(request);
+        }
+        let mounted = self.mounts.iter().find(|(prefix, _)| {
+            path.strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        });
+        match mounted {
+            Some((_, handler)) => handler.call(request),
+            None => self.fallback.call(request),
+        }
+    }
+}
+
+/// Serves files from a directory, answering `/` with `hello.html` and
+/// anything missing with `404.html`.
+pub struct StaticFiles {
+    root: PathBuf,
+    mime_types: MimeTypes,
+}
+
+impl StaticFiles {
+    pub fn new(root: impl Into<PathBuf>) -> StaticFiles {
+        StaticFiles {
+            root: root.into(),
+            mime_types: MimeTypes::default(),
+        }
+    }
+
+    /// Use `mime_types` to pick each file's `Content-Type`.
+    pub fn with_mime_types(mut self, mime_types: MimeTypes) -> StaticFiles {
+        self.mime_types = mime_types;
+        self
+    }
+
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let path = path.split('?').next().unwrap_or_default();
+        let relative = match path.trim_start_matches('/') {
+            "" => "hello.html",
+            relative => relative,
+        };
+
+        // Only plain file names and subdirectories; no `..` escaping the root.
+        let relative = Path::new(relative);
+        if relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            Some(self.root.join(relative))
+        } else {
+            None
+        }
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Response> {
+        let file = File::open(path)?;
+        let length = file.metadata()?.len();
+        Ok(Response::stream(200, "OK", file, Some(length))
+            .with_header("Content-Type", self.mime_types.content_type(path)))
+    }
+}
+
+impl Handler for StaticFiles {
+    fn call(&self, request: Request) -> Response {
+        if request.method != "GET" {
+            return Response::from_status(405, "");
+        }
+
+        let found = self
+            .resolve(&request.path)
+            .filter(|path| path.is_file())
+            .map(|path| self.read(&path));
+
+        let result = match found {
+            Some(result) => result,
+            None => self
+                .read(&self.root.join("404.html"))
+                .map(|response| Response {
+                    status: 404,
+                    reason: "NOT FOUND",
+                    ..response
+                }),
+        };
+
+        result.unwrap_or_else(|err| {
+            let err = err.into();
+            crate::log_error!("Error serving {}: {err}", request.path);
+            Response::from_error(&err)
+        })
+    }
+}
+
+/// File extension to `Content-Type`: the built-in table in
+/// [`tables`](crate::tables), with whatever the config file adds to or
+/// overrides in it.
+#[derive(Debug, Clone, Default)]
+pub struct MimeTypes {
+    overrides: HashMap<String, String>,
+}
+
+impl MimeTypes {
+    /// Map `extension` (without the dot, case-insensitive) to `content_type`,
+    /// replacing any existing mapping.
+    pub fn insert(&mut self, extension: &str, content_type: impl Into<String>) {
+        self.overrides
+            .insert(extension.to_ascii_lowercase(), content_type.into());
+    }
+
+    pub fn content_type(&self, path: &Path) -> &str {
+        let Some(extension) = path.extension().and_then(|ext| ext.to_str()) else {
+            return "application/octet-stream";
+        };
+        match self.overrides.get(&extension.to_ascii_lowercase()) {
+            Some(content_type) => content_type,
+            None => tables::mime_type(extension).unwrap_or("application/octet-stream"),
+        }
+    }
+}
+
+impl<S: AsRef<str>, T: Into<String>> Extend<(S, T)> for MimeTypes {
+    fn extend<I: IntoIterator<Item = (S, T)>>(&mut self, iter: I) {
+        for (extension, content_type) in iter {
+            self.insert(extension.as_ref(), content_type);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::Body;
+
+    fn get(path: &str) -> Request {
+        Request {
+            method: "GET".to_string(),
+            path: path.to_string(),
+            version: "HTTP/1.1".to_string(),
+            headers: Vec::new(),
+            body: Body::empty(),
+        }
+    }
+
+    #[test]
+    fn registered_routes_win_over_the_fallback() {
+        let router =
+            Router::new(|_| Response::not_found("fallback")).route("/hi", |_| Response::ok("hi"));
+
+        assert_eq!(
+            router.handle(get("/hi?x=1")).body.as_bytes(),
+            Some(&b"hi"[..])
+        );
+        assert_eq!(
+            router.handle(get("/other")).body.as_bytes(),
+            Some(&b"fallback"[..])
+        );
+    }
+
+    #[test]
+    fn mounts_cover_the_paths_under_them() {
+        let router = Router::new(|_| Response::not_found("fallback"))
+            .route("/api/exact", |_| Response::ok("exact"))
+            .mount("/api", |request: Request| Response::ok(request.path));
+
+        let body = |path| router.handle(get(path)).body.as_bytes().map(<[u8]>::to_vec);
+        assert_eq!(body("/api"), Some(b"/api".to_vec()));
+        assert_eq!(body("/api/a/b?c"), Some(b"/api/a/b?c".to_vec()));
+        assert_eq!(body("/api/exact"), Some(b"exact".to_vec()));
+        assert_eq!(body("/apiary"), Some(b"fallback".to_vec()));
+    }
+
+    #[test]
+    fn static_files_stay_inside_the_root() {
+        let files = StaticFiles::new(".");
+        assert!(files.resolve("/../Cargo.toml").is_none());
+        assert_eq!(files.resolve("/"), Some(PathBuf::from("./hello.html")));
+    }
+
+    #[test]
+    fn static_files_serve_only_the_public_directory() {
+        let files = StaticFiles::new(concat!(env!("CARGO_MANIFEST_DIR"), "/public"));
+        assert_eq!(files.call(get("/")).status, 200);
+        assert_eq!(files.call(get("/Cargo.toml")).status, 404);
+        assert_eq!(files.call(get("/src/main.rs")).status, 404);
+    }
+
+    #[test]
+    fn mime_overrides_merge_with_the_built_in_table() {
+        let mut types = MimeTypes::default();
+        types.extend([("wasm", "application/wasm"), ("TXT", "text/plain")]);
+
+        assert_eq!(types.content_type(Path::new("a.wasm")), "application/wasm");
+        assert_eq!(types.content_type(Path::new("a.txt")), "text/plain");
+        assert_eq!(
+            types.content_type(Path::new("a.HTML")),
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(
+            types.content_type(Path::new("a.bin")),
+            "application/octet-stream"
+        );
+    }
+}
@@ -0,0 +1,442 @@
+use std::{
+    cell::RefCell,
+    io::{self, prelude::*, BufReader},
+    net::{SocketAddr, TcpListener, TcpStream},
+    panic::{self, AssertUnwindSafe, PanicHookInfo},
+    sync::{atomic::Ordering, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use thread_pool::{semaphore::Permit, Semaphore, ThreadPool};
+
+use crate::{
+    access_log::AccessLog,
+    defer,
+    http::{Body, Request, Response, ResponseBody},
+    router::Router,
+    stats::{ConnectionRegistry, ConnectionState, ConnectionStats, Counted},
+    ServerError,
+};
+
+pub const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Request bodies bigger than this are refused without being read.
+pub const MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+/// How long to stop accepting after a failure that might clear up, such as
+/// running out of file descriptors.
+pub(crate) const ACCEPT_BACKOFF: Duration = Duration::from_millis(50);
+
+/// The most unread body we'll skip to keep a connection alive.
+const MAX_DRAIN: u64 = 64 * 1024;
+
+thread_local! {
+    /// The request line of the request whose handler this thread is running.
+    static CURRENT_REQUEST: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Everything a worker needs to serve a connection.
+pub struct Server {
+    pub router: Router,
+    pub connections: Arc<ConnectionRegistry>,
+    pub access_log: Option<Arc<AccessLog>>,
+    /// Caps how many connections are served at once. Once they're all
+    /// taken, the front end stops accepting until one closes.
+    pub connection_limit: Option<Arc<Semaphore>>,
+}
+
+impl Server {
+    pub fn new(router: Router) -> Server {
+        Server {
+            router,
+            connections: Arc::new(ConnectionRegistry::new()),
+            access_log: None,
+            connection_limit: None,
+        }
+    }
+
+    /// A permit for one more connection, without waiting for one. `None`
+    /// means the server is at its cap. `Some(None)` means there's no cap,
+    /// or it's been closed and no longer applies, as `run` treats it too.
+    pub(crate) fn try_admit(&self) -> Option<Option<Permit>> {
+        match &self.connection_limit {
+            Some(limit) => match limit.try_acquire() {
+                Some(permit) => Some(Some(permit)),
+                None if limit.is_closed() => Some(None),
+                None => None,
+            },
+            None => Some(None),
+        }
+    }
+
+    pub(crate) fn log(
+        &self,
+        peer: Option<SocketAddr>,
+        request_line: Option<&str>,
+        response: &Response,
+    ) {
+        if let Some(access_log) = &self.access_log {
+            let bytes = match &response.body {
+                ResponseBody::Bytes(bytes) => Some(bytes.len() as u64),
+                ResponseBody::Reader { length, .. } => *length,
+            };
+            access_log.record(peer, request_line.unwrap_or("-"), response.status, bytes);
+        }
+    }
+}
+
+/// Accept connections on `listener` forever, handling each one on `pool`.
+pub fn run(listener: TcpListener, pool: &ThreadPool, server: Arc<Server>) {
+    loop {
+        let permit = server
+            .connection_limit
+            .as_ref()
+            .and_then(Semaphore::acquire);
+        let stream = match listener.accept() {
+            Ok((stream, peer)) => {
+                crate::log_debug!("Accepted connection from {peer}");
+                stream
+            }
+            Err(err) => {
+                // Running out of descriptors doesn't make the next accept
+                // fail any slower, so give connections time to close.
+                let transient = is_transient(&err);
+                crate::log_error!("Failed to accept connection: {}", ServerError::from(err));
+                if transient {
+                    thread::sleep(ACCEPT_BACKOFF);
+                }
+                continue;
+            }
+        };
+
+        let server = Arc::clone(&server);
+        let submitted = pool.execute(move || {
+            let _permit = permit;
+            if let Err(err) = handle_connection(stream, &server) {
+                crate::log_error!("Error handling connection: {err}");
+            }
+        });
+        if let Err(err) = submitted {
+            crate::log_error!("Dropping connection: {err}");
+        }
+    }
+}
+
+/// Whether a failed `accept` is worth retrying: the client gave up first,
+/// or the process or system ran short of descriptors or memory.
+pub(crate) fn is_transient(err: &io::Error) -> bool {
+    const ENOMEM: i32 = 12;
+    const ENFILE: i32 = 23;
+    const EMFILE: i32 = 24;
+    #[cfg(target_os = "linux")]
+    const ENOBUFS: i32 = 105;
+    #[cfg(not(target_os = "linux"))]
+    const ENOBUFS: i32 = 55;
+
+    matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::OutOfMemory
+    ) || matches!(err.raw_os_error(), Some(ENOMEM | ENFILE | EMFILE | ENOBUFS))
+}
+
+/// Serve requests from `stream` until the client closes the connection or
+/// an error makes it unusable.
+///
+/// Errors are answered with a matching status before being returned, so the
+/// caller only has to log them.
+pub fn handle_connection(stream: TcpStream, server: &Server) -> Result<(), ServerError> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let peer = stream.peer_addr().ok();
+    let connection = server.connections.register(peer);
+    let stats = connection.stats();
+
+    let mut writer = Counted::new(stream.try_clone()?, Arc::clone(stats));
+    let reader = Arc::new(Mutex::new(BufReader::new(Counted::new(
+        stream,
+        Arc::clone(stats),
+    ))));
+
+    loop {
+        stats.set_state(ConnectionState::Idle);
+
+        // A client that leaves (or goes quiet) between requests is done, not broken.
+        match reader.lock().unwrap().fill_buf() {
+            Ok([]) => return Ok(()),
+            Ok(_) => {}
+            Err(err) => {
+                return match ServerError::from(err) {
+                    ServerError::Timeout => Ok(()),
+                    err => Err(err),
+                }
+            }
+        }
+
+        stats.set_state(ConnectionState::Reading);
+        // Finished at the end of this iteration, including the early
+        // returns below.
+        server.connections.start_request();
+        defer! { server.connections.finish_request(); }
+        let request = read_request(&reader);
+        let keep_alive = matches!(&request, Ok(request) if request.keep_alive());
+        let unread = request
+            .as_ref()
+            .ok()
+            .map(|request| request.body.remaining_handle());
+        let request_line = request.as_ref().ok().map(request_line);
+        let version = response_version(&request);
+
+        stats.set_state(ConnectionState::Handling);
+        let (response, result) = route(request, &server.router);
+        server.log(peer, request_line.as_deref(), &response);
+
+        // Anything the handler (or a streamed response) leaves unread is still
+        // on the connection, in front of the next request. Skip it after the
+        // response is written, unless there's too much to be worth it.
+        // A body the client can only find the end of by the connection
+        // closing rules it out too.
+        let keep_alive = keep_alive
+            && result.is_ok()
+            && !response.is_close_delimited(&version)
+            && unread
+                .as_ref()
+                .is_some_and(|unread| unread.load(Ordering::SeqCst) <= MAX_DRAIN);
+
+        let response = if keep_alive {
+            response
+        } else {
+            response.with_header("Connection", "close")
+        };
+        stats.set_state(ConnectionState::Writing);
+        response.write_to(&version, &mut writer)?;
+        result?;
+
+        if !keep_alive {
+            return Ok(());
+        }
+        if let Some(unread) = unread {
+            stats.set_state(ConnectionState::Reading);
+            drain(&reader, unread.load(Ordering::SeqCst))?;
+        }
+    }
+}
+
+/// Route an already-read request, write the response to `stream` and close
+/// the connection, keeping the connection's `stats` up to date.
+pub fn respond(
+    stream: TcpStream,
+    request: Result<Request, ServerError>,
+    server: &Server,
+    stats: &Arc<ConnectionStats>,
+) -> Result<(), ServerError> {
+    let peer = stream.peer_addr().ok();
+    let request_line = request.as_ref().ok().map(request_line);
+    let version = response_version(&request);
+    stats.set_state(ConnectionState::Handling);
+    let (response, result) = route(request, &server.router);
+    server.log(peer, request_line.as_deref(), &response);
+
+    stats.set_state(ConnectionState::Writing);
+    response
+        .with_header("Connection", "close")
+        .write_to(&version, &mut Counted::new(stream, Arc::clone(stats)))?;
+    result
+}
+
+pub(crate) fn request_line(request: &Request) -> String {
+    format!("{} {} {}", request.method, request.path, request.version)
+}
+
+/// The HTTP version to answer `request` in terms of, taking 1.1 when it
+/// couldn't be read.
+pub(crate) fn response_version(request: &Result<Request, ServerError>) -> String {
+    request
+        .as_ref()
+        .map_or("HTTP/1.1", |request| request.version.as_str())
+        .to_string()
+}
+
+/// The request line of the request being handled on this thread, if any.
+pub fn current_request() -> Option<String> {
+    CURRENT_REQUEST
+        .try_with(|request| request.borrow().clone())
+        .ok()
+        .flatten()
+}
+
+/// A panic handler for the pool that logs which request a handler was
+/// serving when it panicked.
+pub fn log_panic(info: &PanicHookInfo<'_>, worker: usize) {
+    match current_request() {
+        Some(request) => {
+            crate::log_error!("Worker {worker} panicked handling \"{request}\": {info}")
+        }
+        None => crate::log_error!("Worker {worker} panicked: {info}"),
+    }
+}
+
+/// Run `router` on `request`, turning failures into error responses.
+pub(crate) fn route(
+    request: Result<Request, ServerError>,
+    router: &Router,
+) -> (Response, Result<(), ServerError>) {
+    let response = request.and_then(|request| {
+        CURRENT_REQUEST.with(|current| *current.borrow_mut() = Some(request_line(&request)));
+        let response = panic::catch_unwind(AssertUnwindSafe(|| router.handle(request)))
+            .map_err(|_| ServerError::HandlerPanic);
+        CURRENT_REQUEST.with(|current| *current.borrow_mut() = None);
+        response
+    });
+
+    match response {
+        Ok(response) => (response, Ok(())),
+        Err(err) => (Response::from_error(&err), Err(err)),
+    }
+}
+
+type ConnectionReader = BufReader<Counted<TcpStream>>;
+
+/// Reads from a connection shared between the server and the body of the
+/// request currently being handled.
+struct SharedReader(Arc<Mutex<ConnectionReader>>);
+
+impl Read for SharedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().read(buf)
+    }
+}
+
+fn read_request(reader: &Arc<Mutex<ConnectionReader>>) -> Result<Request, ServerError> {
+    let mut request = Request::read_from(&mut *reader.lock().unwrap())?;
+
+    let length = request.content_length()?;
+    if length > MAX_BODY_SIZE {
+        return Err(ServerError::TooLarge);
+    }
+    request.body = Body::new(SharedReader(Arc::clone(reader)), length);
+
+    Ok(request)
+}
+
+/// Skip `unread` bytes of body so the connection is ready for the next
+/// request.
+fn drain(reader: &Mutex<ConnectionReader>, unread: u64) -> Result<(), ServerError> {
+    let mut reader = reader.lock().unwrap();
+    let skipped = io::copy(&mut (&mut *reader).take(unread), &mut io::sink())?;
+    if skipped < unread {
+        return Err(ServerError::Protocol(format!(
+            "body ended {} bytes short of its Content-Length",
+            unread - skipped
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use advanced_features::assert_err;
+
+    use super::*;
+    use crate::handlers::Echo;
+
+    /// Run `handle_connection` on one connection, send `raw`, and return
+    /// everything the server wrote back plus the connection's result.
+    fn exchange(router: Router, raw: &[u8]) -> (String, Result<(), ServerError>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let server = thread::spawn(move || handle_connection(stream, &Server::new(router)));
+
+        client.write_all(raw).unwrap();
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+        let mut reply = String::new();
+        client.read_to_string(&mut reply).unwrap();
+
+        (reply, server.join().unwrap())
+    }
+
+    fn ignores_body() -> Router {
+        Router::new(|_| Response::ok("done"))
+    }
+
+    #[test]
+    fn unread_body_is_drained_before_the_next_request() {
+        let (reply, result) = exchange(
+            ignores_body(),
+            b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhelloGET / HTTP/1.1\r\n\r\n",
+        );
+
+        assert_eq!(reply.matches("HTTP/1.1 200 OK").count(), 2);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn short_body_is_reported_as_a_protocol_error() {
+        let (reply, result) = exchange(
+            ignores_body(),
+            b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nhel",
+        );
+
+        assert!(reply.starts_with("HTTP/1.1 200 OK"));
+        assert_err!(result, ServerError::Protocol(_));
+    }
+
+    #[test]
+    fn streamed_echo_leaves_the_connection_in_sync() {
+        let router = Router::new(|_| Response::ok("done")).route("/echo", Echo);
+        let (reply, result) = exchange(
+            router,
+            b"POST /echo HTTP/1.1\r\nContent-Length: 5\r\n\r\nhelloGET / HTTP/1.1\r\n\r\n",
+        );
+
+        assert!(reply.contains("\n\nhello"));
+        assert!(reply.ends_with("done"));
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn streamed_reply_to_http_1_0_closes_the_connection() {
+        let router = Router::new(|_| Response::stream(200, "OK", &b"to the end"[..], None));
+        let (reply, result) = exchange(
+            router,
+            b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\nGET / HTTP/1.0\r\n\r\n",
+        );
+
+        assert_eq!(
+            reply,
+            "HTTP/1.1 200 OK\r\nConnection: close\r\n\r\nto the end"
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn handlers_can_see_the_request_they_are_handling() {
+        let router = Router::new(|_| Response::ok(current_request().unwrap()));
+        let (reply, result) = exchange(router, b"GET /where HTTP/1.1\r\n\r\n");
+
+        assert!(reply.ends_with("GET /where HTTP/1.1"));
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn running_out_of_descriptors_is_transient() {
+        assert!(is_transient(&io::Error::from_raw_os_error(24)));
+        assert!(is_transient(&io::ErrorKind::ConnectionAborted.into()));
+        assert!(!is_transient(&io::Error::from_raw_os_error(22)));
+    }
+
+    #[test]
+    fn oversized_bodies_are_refused() {
+        let raw = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY_SIZE + 1
+        );
+        let (reply, result) = exchange(ignores_body(), raw.as_bytes());
+
+        assert!(reply.starts_with("HTTP/1.1 413 "));
+        assert_err!(result, ServerError::TooLarge);
+    }
+}
@@ -0,0 +1,317 @@
+//! Live bookkeeping of open connections, for the `/debug/connections` page.
+//!
+//! Every front end registers its connections, but the event loop only does
+//! once a connection's head has arrived and it's handed to a worker.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, prelude::*},
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, AtomicU8, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use thread_pool::metrics::{Counter, Gauge, Registry, Snapshot};
+
+use crate::{
+    http::{Request, Response},
+    router::Handler,
+};
+
+/// What a connection is doing right now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Waiting for the client to start its next request.
+    Idle,
+    Reading,
+    Handling,
+    Writing,
+}
+
+impl ConnectionState {
+    fn from_u8(value: u8) -> ConnectionState {
+        match value {
+            0 => ConnectionState::Idle,
+            1 => ConnectionState::Reading,
+            2 => ConnectionState::Handling,
+            _ => ConnectionState::Writing,
+        }
+    }
+}
+
+/// The counters for one connection, updated by the worker serving it.
+#[derive(Debug)]
+pub struct ConnectionStats {
+    id: u64,
+    peer: Option<SocketAddr>,
+    opened: Instant,
+    state: AtomicU8,
+    bytes_read: Counter,
+    bytes_written: Counter,
+}
+
+impl ConnectionStats {
+    pub fn set_state(&self, state: ConnectionState) {
+        self.state.store(state as u8, Ordering::Relaxed);
+    }
+
+    /// Count bytes read without going through [`Counted`], like a head read
+    /// before the connection was registered.
+    pub fn add_read(&self, bytes: u64) {
+        self.bytes_read.add(bytes);
+    }
+
+    pub fn add_written(&self, bytes: u64) {
+        self.bytes_written.add(bytes);
+    }
+}
+
+/// A point-in-time copy of one connection's stats.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    pub id: u64,
+    pub peer: Option<SocketAddr>,
+    pub state: ConnectionState,
+    pub age: Duration,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+}
+
+/// Every connection currently open, and totals across all of them.
+#[derive(Debug)]
+pub struct ConnectionRegistry {
+    next_id: AtomicU64,
+    metrics: Registry,
+    /// Requests between being started and finished, on any connection.
+    in_flight: Arc<Gauge>,
+    requests: Arc<Counter>,
+    opened: Arc<Counter>,
+    connections: Mutex<BTreeMap<u64, Arc<ConnectionStats>>>,
+}
+
+impl ConnectionRegistry {
+    pub fn new() -> ConnectionRegistry {
+        let metrics = Registry::new();
+        ConnectionRegistry {
+            next_id: AtomicU64::new(0),
+            in_flight: metrics.gauge("requests_in_flight"),
+            requests: metrics.counter("requests_total"),
+            opened: metrics.counter("connections_total"),
+            metrics,
+            connections: Mutex::default(),
+        }
+    }
+
+    /// Start tracking a connection. It stays listed until the returned guard
+    /// is dropped.
+    pub fn register(&self, peer: Option<SocketAddr>) -> ConnectionGuard<'_> {
+        let stats = Arc::new(ConnectionStats {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            peer,
+            opened: Instant::now(),
+            state: AtomicU8::new(ConnectionState::Idle as u8),
+            bytes_read: Counter::new(),
+            bytes_written: Counter::new(),
+        });
+        self.opened.inc();
+        self.connections
+            .lock()
+            .unwrap()
+            .insert(stats.id, Arc::clone(&stats));
+
+        ConnectionGuard {
+            registry: self,
+            stats,
+        }
+    }
+
+    /// Count a request as in flight until the matching
+    /// [`finish_request`](ConnectionRegistry::finish_request).
+    pub fn start_request(&self) {
+        self.requests.inc();
+        self.in_flight.inc();
+    }
+
+    pub fn finish_request(&self) {
+        self.in_flight.dec();
+    }
+
+    pub fn in_flight(&self) -> u64 {
+        self.in_flight.get()
+    }
+
+    /// The totals: connections opened, requests started and requests in
+    /// flight.
+    pub fn metrics(&self) -> Snapshot {
+        self.metrics.snapshot()
+    }
+
+    pub fn snapshot(&self) -> Vec<ConnectionInfo> {
+        let now = Instant::now();
+        self.connections
+            .lock()
+            .unwrap()
+            .values()
+            .map(|stats| ConnectionInfo {
+                id: stats.id,
+                peer: stats.peer,
+                state: ConnectionState::from_u8(stats.state.load(Ordering::Relaxed)),
+                age: now.duration_since(stats.opened),
+                bytes_read: stats.bytes_read.get(),
+                bytes_written: stats.bytes_written.get(),
+            })
+            .collect()
+    }
+}
+
+impl Default for ConnectionRegistry {
+    fn default() -> ConnectionRegistry {
+        ConnectionRegistry::new()
+    }
+}
+
+/// Keeps a connection listed in its registry while it's alive.
+pub struct ConnectionGuard<'a> {
+    registry: &'a ConnectionRegistry,
+    stats: Arc<ConnectionStats>,
+}
+
+impl ConnectionGuard<'_> {
+    pub fn stats(&self) -> &Arc<ConnectionStats> {
+        &self.stats
+    }
+}
+
+impl Drop for ConnectionGuard<'_> {
+    fn drop(&mut self) {
+        self.registry
+            .connections
+            .lock()
+            .unwrap()
+            .remove(&self.stats.id);
+    }
+}
+
+/// Wraps a stream, adding everything read or written to a connection's stats.
+pub struct Counted<T> {
+    inner: T,
+    stats: Arc<ConnectionStats>,
+}
+
+impl<T> Counted<T> {
+    pub fn new(inner: T, stats: Arc<ConnectionStats>) -> Counted<T> {
+        Counted { inner, stats }
+    }
+}
+
+impl<T: Read> Read for Counted<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.stats.bytes_read.add(read as u64);
+        Ok(read)
+    }
+}
+
+impl<T: Write> Write for Counted<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.stats.bytes_written.add(written as u64);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Lists the open connections as a plain-text table.
+pub struct Connections(pub Arc<ConnectionRegistry>);
+
+impl Handler for Connections {
+    fn call(&self, _request: Request) -> Response {
+        let connections = self.0.snapshot();
+
+        let mut body = format!(
+            "{} open connections, {} requests in flight\n\n{}\n",
+            connections.len(),
+            self.0.in_flight(),
+            self.0.metrics()
+        );
+        let _ = writeln!(
+            body,
+            "{:>6}  {:<21}  {:<8}  {:>9}  {:>10}  {:>10}",
+            "id", "peer", "state", "age (ms)", "read", "written"
+        );
+        for info in connections {
+            let peer = info
+                .peer
+                .map_or_else(|| "-".to_string(), |peer| peer.to_string());
+            let _ = writeln!(
+                body,
+                "{:>6}  {:<21}  {:<8}  {:>9}  {:>10}  {:>10}",
+                info.id,
+                peer,
+                format!("{:?}", info.state),
+                info.age.as_millis(),
+                info.bytes_read,
+                info.bytes_written
+            );
+        }
+
+        Response::ok(body).with_header("Content-Type", "text/plain")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connections_are_listed_until_their_guard_drops() {
+        let registry = ConnectionRegistry::new();
+        let first = registry.register(None);
+        let second = registry.register(None);
+        second.stats().set_state(ConnectionState::Handling);
+
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[1].state, ConnectionState::Handling);
+
+        drop(first);
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot[0].id, 1);
+    }
+
+    #[test]
+    fn counts_requests_in_flight() {
+        let registry = ConnectionRegistry::new();
+        registry.start_request();
+        registry.start_request();
+        registry.finish_request();
+        assert_eq!(registry.in_flight(), 1);
+
+        let _connection = registry.register(None);
+        assert_eq!(
+            registry.metrics().to_string(),
+            "connections_total 1\nrequests_in_flight 1\nrequests_total 2\n"
+        );
+    }
+
+    #[test]
+    fn counts_bytes_in_both_directions() {
+        let registry = ConnectionRegistry::new();
+        let guard = registry.register(None);
+
+        let mut reader = Counted::new(&b"hello"[..], Arc::clone(guard.stats()));
+        let mut sink = Counted::new(Vec::new(), Arc::clone(guard.stats()));
+        io::copy(&mut reader, &mut sink).unwrap();
+
+        let info = &registry.snapshot()[0];
+        assert_eq!((info.bytes_read, info.bytes_written), (5, 5));
+    }
+}
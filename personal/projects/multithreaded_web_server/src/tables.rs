@@ -0,0 +1,221 @@
+//! Lookup tables built at compile time: the built-in MIME types, and the
+//! reason phrase for each status code.
+//!
+//! Each is written as a plain list and turned into a lookup table by a
+//! `const fn`, so the work is done once, by the compiler, into a `static`
+//! array. A mistake in a list, like an extension or status code listed
+//! twice, panics in that `const fn`, which makes it a compile error rather
+//! than a surprise at runtime.
+
+use std::cmp::Ordering;
+
+/// The built-in MIME types, by lowercase file extension.
+const MIME_LIST: [(&str, &str); 14] = [
+    ("html", "text/html; charset=utf-8"),
+    ("htm", "text/html; charset=utf-8"),
+    ("css", "text/css"),
+    ("js", "text/javascript"),
+    ("json", "application/json"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("svg", "image/svg+xml"),
+    ("ico", "image/x-icon"),
+    ("webp", "image/webp"),
+    ("wasm", "application/wasm"),
+];
+
+/// [`MIME_LIST`], sorted by extension so it can be binary searched.
+static MIME_TYPES: [(&str, &str); MIME_LIST.len()] = sort_by_extension(MIME_LIST);
+
+/// The built-in `Content-Type` for files with extension `extension`, in any
+/// case.
+pub fn mime_type(extension: &str) -> Option<&'static str> {
+    let extension = extension.bytes().map(|b| b.to_ascii_lowercase());
+    MIME_TYPES
+        .binary_search_by(|(key, _)| key.bytes().cmp(extension.clone()))
+        .ok()
+        .map(|found| MIME_TYPES[found].1)
+}
+
+/// Reason phrases, in the uppercase the book's responses use.
+const REASON_LIST: [(u16, &str); 17] = [
+    (100, "CONTINUE"),
+    (200, "OK"),
+    (201, "CREATED"),
+    (204, "NO CONTENT"),
+    (301, "MOVED PERMANENTLY"),
+    (304, "NOT MODIFIED"),
+    (400, "BAD REQUEST"),
+    (403, "FORBIDDEN"),
+    (404, "NOT FOUND"),
+    (405, "METHOD NOT ALLOWED"),
+    (408, "REQUEST TIMEOUT"),
+    (413, "PAYLOAD TOO LARGE"),
+    (429, "TOO MANY REQUESTS"),
+    (500, "INTERNAL SERVER ERROR"),
+    (501, "NOT IMPLEMENTED"),
+    (503, "SERVICE UNAVAILABLE"),
+    (505, "HTTP VERSION NOT SUPPORTED"),
+];
+
+const FIRST_STATUS: u16 = 100;
+
+/// [`REASON_LIST`] indexed by status code less 100, with `""` for codes
+/// that aren't listed.
+static REASONS: [&str; 500] = index_by_status(&REASON_LIST);
+
+/// The reason phrase for `status`, if it's one the server knows.
+pub const fn reason_phrase(status: u16) -> Option<&'static str> {
+    if status < FIRST_STATUS || status >= FIRST_STATUS + REASONS.len() as u16 {
+        return None;
+    }
+    let reason = REASONS[(status - FIRST_STATUS) as usize];
+    if reason.is_empty() {
+        None
+    } else {
+        Some(reason)
+    }
+}
+
+/// `list`, sorted by extension: an insertion sort, since `const fn`s can't
+/// call `sort`, or use `for` loops.
+///
+/// Panics if an extension isn't lowercase, or is in `list` twice.
+const fn sort_by_extension<const N: usize>(
+    mut list: [(&'static str, &'static str); N],
+) -> [(&'static str, &'static str); N] {
+    let mut i = 0;
+    while i < N {
+        let extension = list[i].0.as_bytes();
+        let mut b = 0;
+        while b < extension.len() {
+            assert!(
+                !extension[b].is_ascii_uppercase(),
+                "MIME extensions must be lowercase"
+            );
+            b += 1;
+        }
+
+        let mut j = i;
+        while j > 0 {
+            match compare(list[j - 1].0, list[j].0) {
+                Ordering::Less => break,
+                Ordering::Equal => panic!("MIME extension listed twice"),
+                Ordering::Greater => {
+                    let swap = list[j];
+                    list[j] = list[j - 1];
+                    list[j - 1] = swap;
+                    j -= 1;
+                }
+            }
+        }
+        i += 1;
+    }
+    list
+}
+
+/// `str::cmp`, which isn't a `const fn`: byte by byte, then by length.
+const fn compare(a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let mut i = 0;
+    while i < a.len() && i < b.len() {
+        if a[i] != b[i] {
+            return if a[i] < b[i] {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            };
+        }
+        i += 1;
+    }
+    if a.len() < b.len() {
+        Ordering::Less
+    } else if a.len() > b.len() {
+        Ordering::Greater
+    } else {
+        Ordering::Equal
+    }
+}
+
+/// Panics if a status is outside 100..600, or is in `list` twice.
+const fn index_by_status(list: &[(u16, &'static str)]) -> [&'static str; 500] {
+    let mut reasons = [""; 500];
+    let mut i = 0;
+    while i < list.len() {
+        let (status, reason) = list[i];
+        assert!(
+            status >= FIRST_STATUS && status < FIRST_STATUS + 500,
+            "status codes go from 100 to 599"
+        );
+        let slot = (status - FIRST_STATUS) as usize;
+        assert!(reasons[slot].is_empty(), "status code listed twice");
+        reasons[slot] = reason;
+        i += 1;
+    }
+    reasons
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// The tables give the same answers as maps built from the same lists
+    /// at runtime.
+    #[test]
+    fn tables_match_runtime_maps() {
+        let mime: HashMap<_, _> = MIME_LIST.into_iter().collect();
+        for (extension, content_type) in &mime {
+            assert_eq!(mime_type(extension), Some(*content_type));
+            assert_eq!(mime_type(&extension.to_uppercase()), Some(*content_type));
+        }
+        assert_eq!(MIME_TYPES.len(), mime.len());
+
+        let reasons: HashMap<_, _> = REASON_LIST.into_iter().collect();
+        for status in 0..=u16::MAX {
+            assert_eq!(reason_phrase(status), reasons.get(&status).copied());
+        }
+    }
+
+    #[test]
+    fn mime_table_is_sorted() {
+        assert!(MIME_TYPES.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(mime_type("exe"), None);
+        assert_eq!(mime_type(""), None);
+        assert_eq!(mime_type("htmlx"), None);
+    }
+
+    #[test]
+    fn compares_like_str() {
+        let words = ["", "a", "ab", "b", "ba", "htm", "html", "HTML"];
+        for a in words {
+            for b in words {
+                assert_eq!(compare(a, b), a.cmp(b), "{a:?} vs {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn reason_phrases_work_in_consts() {
+        const NOT_FOUND: Option<&str> = reason_phrase(404);
+        assert_eq!(NOT_FOUND, Some("NOT FOUND"));
+        assert_eq!(reason_phrase(99), None);
+        assert_eq!(reason_phrase(600), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "MIME extension listed twice")]
+    fn duplicate_extensions_are_refused() {
+        sort_by_extension([("png", "image/png"), ("css", "text/css"), ("png", "x")]);
+    }
+
+    #[test]
+    #[should_panic(expected = "status code listed twice")]
+    fn duplicate_statuses_are_refused() {
+        index_by_status(&[(200, "OK"), (200, "FINE")]);
+    }
+}
@@ -0,0 +1,156 @@
+//! HTML templates: files with `{{name}}` placeholders, filled in per
+//! request.
+//!
+//! Parsing a template means reading the file and splitting it at its
+//! placeholders, which only needs doing once per file, not once per
+//! request. [`Templates`] keeps the parsed templates in a bounded
+//! [`SyncMemo`], so every worker shares them and the ones in use stay
+//! loaded. Like the static files, they're read from disk, but unlike them
+//! they're read once: a changed template shows up after a restart.
+
+use std::{fs, path::PathBuf, sync::Arc};
+
+use advanced_features::memo::SyncMemo;
+
+use crate::ServerError;
+
+/// A parsed template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Placeholder(String),
+}
+
+impl Template {
+    /// Split `source` at its `{{name}}` placeholders. A `{{` with no `}}`
+    /// after it is just text.
+    pub fn parse(source: &str) -> Template {
+        let mut parts = Vec::new();
+        let mut rest = source;
+        while let Some((text, after)) = rest.split_once("{{") {
+            let Some((name, after)) = after.split_once("}}") else {
+                break;
+            };
+            parts.push(Part::Text(text.to_string()));
+            parts.push(Part::Placeholder(name.trim().to_string()));
+            rest = after;
+        }
+        parts.push(Part::Text(rest.to_string()));
+        parts.retain(|part| *part != Part::Text(String::new()));
+        Template { parts }
+    }
+
+    /// The template with each placeholder replaced by its value in `vars`,
+    /// HTML-escaped. Placeholders with no value are left empty.
+    pub fn render(&self, vars: &[(&str, &str)]) -> String {
+        let mut html = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => html.push_str(text),
+                Part::Placeholder(name) => {
+                    if let Some((_, value)) = vars.iter().find(|(key, _)| key == name) {
+                        escape_into(&mut html, value);
+                    }
+                }
+            }
+        }
+        html
+    }
+}
+
+fn escape_into(html: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+}
+
+/// A file's template, or why it couldn't be read. Only templates are kept,
+/// so one that's missing or unreadable is tried again on the next request.
+type Loaded = Result<Arc<Template>, Arc<ServerError>>;
+
+/// The templates in a directory, loaded the first time each is used.
+pub struct Templates {
+    root: PathBuf,
+    cache: SyncMemo<PathBuf, Loaded, fn(&PathBuf) -> Loaded>,
+}
+
+impl Templates {
+    /// How many parsed templates are kept.
+    pub const CAPACITY: usize = 32;
+
+    pub fn new(root: impl Into<PathBuf>) -> Templates {
+        Templates {
+            root: root.into(),
+            cache: SyncMemo::with_capacity(Templates::CAPACITY, load),
+        }
+    }
+
+    /// The template in the file `name`, under the root.
+    pub fn get(&self, name: &str) -> Result<Arc<Template>, Arc<ServerError>> {
+        self.cache.get_ok(&self.root.join(name))
+    }
+}
+
+fn load(path: &PathBuf) -> Loaded {
+    fs::read_to_string(path)
+        .map(|source| Arc::new(Template::parse(&source)))
+        .map_err(|err| Arc::new(err.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use advanced_features::assert_matches;
+
+    use super::*;
+
+    #[test]
+    fn fills_in_placeholders() {
+        let template = Template::parse("<p>{{ greeting }}, {{name}}!</p>");
+        assert_eq!(
+            template.render(&[("name", "Ferris"), ("greeting", "Hello")]),
+            "<p>Hello, Ferris!</p>"
+        );
+        assert_eq!(template.render(&[]), "<p>, !</p>");
+    }
+
+    #[test]
+    fn escapes_values() {
+        let template = Template::parse("<b>{{name}}</b>");
+        assert_eq!(
+            template.render(&[("name", "<script>'&\"")]),
+            "<b>&lt;script&gt;&#39;&amp;&quot;</b>"
+        );
+    }
+
+    #[test]
+    fn unclosed_placeholders_are_text() {
+        let template = Template::parse("{{a}} and {{b");
+        assert_eq!(template.render(&[("a", "1"), ("b", "2")]), "1 and {{b");
+        assert_eq!(Template::parse("").render(&[]), "");
+    }
+
+    #[test]
+    fn loads_each_template_once() {
+        let templates = Templates::new("templates");
+        let first = templates.get("greet.html").unwrap();
+        let second = templates.get("greet.html").unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(first.render(&[("name", "you")]).contains("Hello, you!"));
+
+        let err = templates.get("missing.html").unwrap_err();
+        assert_matches!(*err, ServerError::Io(_));
+        assert_eq!(templates.cache.len(), 1);
+    }
+}
@@ -0,0 +1,121 @@
+//! The blog post workflow from "Implementing an Object-Oriented Design
+//! Pattern", with the states as a [`state_machine!`] table instead of a
+//! `State` trait object per state.
+//!
+//! The book's design spreads the workflow over `Draft`, `PendingReview`
+//! and `Published`, each deciding where it goes next, and `Post` repeats
+//! the same take-and-replace dance for every method. Here the whole
+//! workflow is the table below, and adding a state or an event (like the
+//! `reject` the book suggests) is one more line in it.
+//!
+//! An event the current state has no transition for leaves the post as it
+//! was, as in the book, but says so with an [`InvalidTransition`] instead
+//! of quietly returning `self`.
+
+use advanced_features::{macros::InvalidTransition, state_machine};
+
+state_machine! {
+    /// Where a post is in the workflow.
+    pub enum State { Draft, PendingReview, Published }
+    /// What can happen to a post.
+    pub enum Event { RequestReview, Approve, Reject }
+
+    transitions {
+        Draft + RequestReview => PendingReview,
+        PendingReview + Approve => Published,
+        PendingReview + Reject => Draft,
+    }
+}
+
+pub type Result = std::result::Result<(), InvalidTransition<State, Event>>;
+
+#[derive(Debug)]
+pub struct Post {
+    state: State,
+    content: String,
+}
+
+impl Post {
+    pub fn new() -> Post {
+        Post {
+            state: State::Draft,
+            content: String::new(),
+        }
+    }
+
+    pub fn add_text(&mut self, text: &str) {
+        self.content.push_str(text);
+    }
+
+    /// The post's text once it's published, and nothing before then.
+    pub fn content(&self) -> &str {
+        match self.state {
+            State::Published => &self.content,
+            State::Draft | State::PendingReview => "",
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn request_review(&mut self) -> Result {
+        self.state.transition(Event::RequestReview)
+    }
+
+    pub fn approve(&mut self) -> Result {
+        self.state.transition(Event::Approve)
+    }
+
+    /// Send a post under review back to be redrafted.
+    pub fn reject(&mut self) -> Result {
+        self.state.transition(Event::Reject)
+    }
+}
+
+impl Default for Post {
+    fn default() -> Post {
+        Post::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_published_posts_have_content() {
+        let mut post = Post::new();
+
+        post.add_text("I ate a salad for lunch today");
+        assert_eq!("", post.content());
+
+        post.request_review().unwrap();
+        assert_eq!("", post.content());
+
+        post.approve().unwrap();
+        assert_eq!("I ate a salad for lunch today", post.content());
+    }
+
+    #[test]
+    fn a_draft_cannot_be_approved() {
+        let mut post = Post::new();
+
+        let err = post.approve().unwrap_err();
+        assert_eq!(err.to_string(), "can't Approve when Draft");
+        assert_eq!(post.state(), State::Draft);
+    }
+
+    #[test]
+    fn rejected_posts_go_back_to_draft() {
+        let mut post = Post::new();
+        post.request_review().unwrap();
+        post.reject().unwrap();
+        assert_eq!(post.state(), State::Draft);
+
+        post.request_review().unwrap();
+        post.approve().unwrap();
+        assert!(post.reject().is_err());
+        assert_eq!(post.state(), State::Published);
+    }
+}
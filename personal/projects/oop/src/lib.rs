@@ -0,0 +1,3 @@
+//! Code from the chapter, kept out of the notes in `main.rs`.
+
+pub mod blog;
@@ -895,3 +895,19 @@
 
 
 
+use oop::blog::Post;
+
+// Listing 17-11, run against the state-machine version of `Post` in
+// `blog.rs`.
+fn main() {
+    let mut post = Post::new();
+
+    post.add_text("I ate a salad for lunch today");
+    assert_eq!("", post.content());
+
+    post.request_review().unwrap();
+    assert_eq!("", post.content());
+
+    post.approve().unwrap();
+    assert_eq!("I ate a salad for lunch today", post.content());
+}
@@ -0,0 +1,181 @@
+//! A typed arena: values of one type allocated side by side, and referred
+//! to by [`Id`] handles rather than by `Box` or `Rc`.
+//!
+//! Nodes that point at each other through handles don't need reference
+//! counts or `RefCell`s, can't form leaking cycles (the arena owns them all
+//! and frees them all at once), and sit next to each other in memory. The
+//! price is that nothing is freed until the arena is. [`list`] and [`tree`]
+//! rebuild the chapter's cons list and tree this way; `benches/arena.rs`
+//! compares them with the `Box` and `Rc` versions.
+//!
+//! The arena bump-allocates: values go at the end of the current chunk,
+//! and a full chunk is left where it is while a new one, twice the size,
+//! takes over. Nothing already allocated ever moves.
+
+use std::{fmt, hash, marker::PhantomData};
+
+pub mod list;
+pub mod tree;
+
+/// How many values fit in the first chunk.
+const FIRST_CHUNK: usize = 16;
+
+pub struct Arena<T> {
+    chunks: Vec<Vec<T>>,
+    len: usize,
+}
+
+/// A handle to a value in an [`Arena`].
+///
+/// Handles are only meaningful to the arena that gave them out; using one
+/// with another arena gets the wrong value, or panics.
+pub struct Id<T> {
+    index: usize,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> Arena<T> {
+    pub fn new() -> Arena<T> {
+        Arena {
+            chunks: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn alloc(&mut self, value: T) -> Id<T> {
+        // Not `chunk.capacity()`: it's `usize::MAX` for zero-sized types, and
+        // `locate` expects each chunk to hold exactly its share.
+        let full = self
+            .chunks
+            .last()
+            .is_none_or(|chunk| chunk.len() == FIRST_CHUNK << (self.chunks.len() - 1));
+        if full {
+            let capacity = FIRST_CHUNK << self.chunks.len();
+            self.chunks.push(Vec::with_capacity(capacity));
+        }
+        self.chunks.last_mut().unwrap().push(value);
+
+        let id = Id {
+            index: self.len,
+            _type: PhantomData,
+        };
+        self.len += 1;
+        id
+    }
+
+    pub fn get(&self, id: Id<T>) -> &T {
+        let (chunk, offset) = locate(id.index);
+        &self.chunks[chunk][offset]
+    }
+
+    pub fn get_mut(&mut self, id: Id<T>) -> &mut T {
+        let (chunk, offset) = locate(id.index);
+        &mut self.chunks[chunk][offset]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Which chunk the `index`th value is in, and where in it. Chunk `n` holds
+/// `FIRST_CHUNK << n` values, so the chunks before it hold
+/// `FIRST_CHUNK * (2^n - 1)`.
+fn locate(index: usize) -> (usize, usize) {
+    let chunk = (index / FIRST_CHUNK + 1).ilog2() as usize;
+    let before = FIRST_CHUNK * ((1 << chunk) - 1);
+    (chunk, index - before)
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Arena<T> {
+        Arena::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Arena<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.chunks.iter().flatten())
+            .finish()
+    }
+}
+
+// Written out rather than derived, which would only implement them for
+// `Id<T>` where `T` implements them too.
+impl<T> Clone for Id<T> {
+    fn clone(&self) -> Id<T> {
+        *self
+    }
+}
+
+impl<T> Copy for Id<T> {}
+
+impl<T> PartialEq for Id<T> {
+    fn eq(&self, other: &Id<T>) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> Eq for Id<T> {}
+
+impl<T> hash::Hash for Id<T> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Id<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Id({})", self.index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hands_back_what_was_allocated() {
+        let mut arena = Arena::new();
+        let ids: Vec<_> = (0..1000).map(|n| arena.alloc(n)).collect();
+        assert_eq!(arena.len(), 1000);
+        assert!(ids.iter().enumerate().all(|(n, &id)| *arena.get(id) == n));
+
+        *arena.get_mut(ids[500]) = 0;
+        assert_eq!(*arena.get(ids[500]), 0);
+    }
+
+    #[test]
+    fn never_moves_a_value() {
+        let mut arena = Arena::new();
+        let first = arena.alloc(1);
+        let address: *const i32 = arena.get(first);
+        for n in 0..1000 {
+            arena.alloc(n);
+        }
+        assert_eq!(address, arena.get(first) as *const i32);
+    }
+
+    #[test]
+    fn zero_sized_values_fill_chunks_like_any_other() {
+        let mut arena = Arena::new();
+        let ids: Vec<_> = (0..100).map(|_| arena.alloc(())).collect();
+        assert_eq!(arena.len(), 100);
+        arena.get(ids[FIRST_CHUNK]);
+        arena.get(ids[99]);
+    }
+
+    #[test]
+    fn chunks_double_in_size() {
+        assert_eq!(locate(0), (0, 0));
+        assert_eq!(locate(FIRST_CHUNK - 1), (0, FIRST_CHUNK - 1));
+        assert_eq!(locate(FIRST_CHUNK), (1, 0));
+        assert_eq!(locate(3 * FIRST_CHUNK), (2, 0));
+        assert_eq!(locate(7 * FIRST_CHUNK - 1), (2, 4 * FIRST_CHUNK - 1));
+    }
+}
@@ -0,0 +1,71 @@
+//! The cons list with its nodes in an [`Arena`]: each `Cons` holds the
+//! [`Id`] of the rest of the list where the boxed version holds a `Box`.
+
+use super::{Arena, Id};
+
+#[derive(Debug)]
+pub enum List<T> {
+    Cons(T, Id<List<T>>),
+    Nil,
+}
+
+use List::{Cons, Nil};
+
+impl<T> List<T> {
+    /// Allocate a list of `values` in `arena`, returning its head.
+    pub fn alloc_in<I>(arena: &mut Arena<List<T>>, values: I) -> Id<List<T>>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: DoubleEndedIterator,
+    {
+        // Built from the back, since each node needs its tail's handle.
+        let nil = arena.alloc(Nil);
+        values
+            .into_iter()
+            .rev()
+            .fold(nil, |tail, value| arena.alloc(Cons(value, tail)))
+    }
+
+    /// The values of the list starting at `head`.
+    pub fn iter(arena: &Arena<List<T>>, head: Id<List<T>>) -> Iter<'_, T> {
+        Iter { arena, next: head }
+    }
+}
+
+pub struct Iter<'a, T> {
+    arena: &'a Arena<List<T>>,
+    next: Id<List<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        match self.arena.get(self.next) {
+            Cons(value, rest) => {
+                self.next = *rest;
+                Some(value)
+            }
+            Nil => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_can_share_a_tail() {
+        // Listing 15-18's a, b and c, without an Rc in sight.
+        let mut arena = Arena::new();
+        let a = List::alloc_in(&mut arena, [5, 10]);
+        let b = arena.alloc(Cons(3, a));
+        let c = arena.alloc(Cons(4, a));
+
+        let values = |head| List::iter(&arena, head).copied().collect::<Vec<_>>();
+        assert_eq!(values(b), [3, 5, 10]);
+        assert_eq!(values(c), [4, 5, 10]);
+        assert_eq!(arena.len(), 5);
+    }
+}
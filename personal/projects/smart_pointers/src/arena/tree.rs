@@ -0,0 +1,129 @@
+//! The tree with its nodes in an [`Arena`]. A child's handle to its parent
+//! needs no `Weak`: handles don't own anything, so they can't make a cycle
+//! that leaks.
+
+use std::collections::VecDeque;
+
+use super::{Arena, Id};
+
+#[derive(Debug)]
+pub struct Node<T> {
+    value: T,
+    parent: Option<Id<Node<T>>>,
+    children: Vec<Id<Node<T>>>,
+}
+
+#[derive(Debug)]
+pub struct Tree<T> {
+    nodes: Arena<Node<T>>,
+    root: Id<Node<T>>,
+}
+
+impl<T> Tree<T> {
+    pub fn new(value: T) -> Tree<T> {
+        let mut nodes = Arena::new();
+        let root = nodes.alloc(Node {
+            value,
+            parent: None,
+            children: vec![],
+        });
+        Tree { nodes, root }
+    }
+
+    pub fn root(&self) -> Id<Node<T>> {
+        self.root
+    }
+
+    pub fn value(&self, node: Id<Node<T>>) -> &T {
+        &self.nodes.get(node).value
+    }
+
+    pub fn parent(&self, node: Id<Node<T>>) -> Option<Id<Node<T>>> {
+        self.nodes.get(node).parent
+    }
+
+    pub fn children(&self, node: Id<Node<T>>) -> &[Id<Node<T>>] {
+        &self.nodes.get(node).children
+    }
+
+    /// Add a child holding `value` to `parent`, returning it.
+    pub fn add_child(&mut self, parent: Id<Node<T>>, value: T) -> Id<Node<T>> {
+        let child = self.nodes.alloc(Node {
+            value,
+            parent: Some(parent),
+            children: vec![],
+        });
+        self.nodes.get_mut(parent).children.push(child);
+        child
+    }
+
+    /// Every node, each before its children.
+    pub fn depth_first(&self) -> DepthFirst<'_, T> {
+        DepthFirst {
+            tree: self,
+            stack: vec![self.root],
+        }
+    }
+
+    /// Every node, a level at a time.
+    pub fn breadth_first(&self) -> BreadthFirst<'_, T> {
+        BreadthFirst {
+            tree: self,
+            queue: VecDeque::from([self.root]),
+        }
+    }
+}
+
+pub struct DepthFirst<'a, T> {
+    tree: &'a Tree<T>,
+    stack: Vec<Id<Node<T>>>,
+}
+
+impl<T> Iterator for DepthFirst<'_, T> {
+    type Item = Id<Node<T>>;
+
+    fn next(&mut self) -> Option<Id<Node<T>>> {
+        let node = self.stack.pop()?;
+        self.stack
+            .extend(self.tree.children(node).iter().rev().copied());
+        Some(node)
+    }
+}
+
+pub struct BreadthFirst<'a, T> {
+    tree: &'a Tree<T>,
+    queue: VecDeque<Id<Node<T>>>,
+}
+
+impl<T> Iterator for BreadthFirst<'_, T> {
+    type Item = Id<Node<T>>;
+
+    fn next(&mut self) -> Option<Id<Node<T>>> {
+        let node = self.queue.pop_front()?;
+        self.queue.extend(self.tree.children(node));
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walks_and_finds_parents() {
+        let mut tree = Tree::new(1);
+        let two = tree.add_child(tree.root(), 2);
+        let three = tree.add_child(tree.root(), 3);
+        let four = tree.add_child(two, 4);
+        tree.add_child(three, 5);
+
+        let values = |nodes: &mut dyn Iterator<Item = Id<Node<i32>>>| {
+            nodes.map(|node| *tree.value(node)).collect::<Vec<_>>()
+        };
+        assert_eq!(values(&mut tree.depth_first()), [1, 2, 4, 3, 5]);
+        assert_eq!(values(&mut tree.breadth_first()), [1, 2, 3, 4, 5]);
+
+        assert_eq!(tree.parent(four), Some(two));
+        assert_eq!(tree.parent(tree.root()), None);
+    }
+}
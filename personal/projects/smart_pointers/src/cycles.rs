@@ -0,0 +1,298 @@
+//! Finding the reference cycles that make `Rc` leak.
+//!
+//! A type that implements [`Traverse`] says which `Rc`s each of its values
+//! holds. [`find_cycles`] follows those strong references from some roots
+//! and reports every group of values that can reach each other through
+//! them (every strongly connected component, with more than one value or
+//! a value that holds itself). Those are exactly the values whose strong
+//! counts can never fall to zero by themselves.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    fmt,
+    rc::{Rc, Weak},
+};
+
+/// A value that can hold strong references to others of its type.
+pub trait Traverse: Sized {
+    /// The `Rc`s this value holds, each one keeping its target alive.
+    fn strong_children(&self) -> Vec<Rc<Self>>;
+}
+
+/// Values that keep each other alive.
+pub struct Cycle<T> {
+    /// The values in the cycle, held weakly so reporting it doesn't keep
+    /// them alive any longer.
+    pub nodes: Vec<Weak<T>>,
+    /// Each value's strong count when it was found.
+    pub strong_counts: Vec<usize>,
+}
+
+/// Every cycle of strong references reachable from `roots`.
+pub fn find_cycles<'a, T: Traverse + 'a>(
+    roots: impl IntoIterator<Item = &'a Rc<T>>,
+) -> Vec<Cycle<T>> {
+    let Graph { nodes, edges } = Graph::walk(roots);
+
+    strongly_connected(&edges)
+        .into_iter()
+        .filter(|component| match component[..] {
+            [node] => edges[node].contains(&node),
+            _ => true,
+        })
+        .map(|component| Cycle {
+            nodes: component
+                .iter()
+                .map(|&i| Rc::downgrade(&nodes[i]))
+                .collect(),
+            // Less the one `nodes` holds.
+            strong_counts: component
+                .iter()
+                .map(|&i| Rc::strong_count(&nodes[i]) - 1)
+                .collect(),
+        })
+        .collect()
+}
+
+/// The values reachable from some roots, numbered in the order they were
+/// found, and the strong references between them.
+struct Graph<T> {
+    nodes: Vec<Rc<T>>,
+    edges: Vec<Vec<usize>>,
+}
+
+impl<T: Traverse> Graph<T> {
+    fn walk<'a>(roots: impl IntoIterator<Item = &'a Rc<T>>) -> Graph<T>
+    where
+        T: 'a,
+    {
+        let mut graph = Graph {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        };
+        let mut numbers = HashMap::new();
+        let mut queue = VecDeque::new();
+
+        for root in roots {
+            graph.number(&mut numbers, &mut queue, Rc::clone(root));
+        }
+        while let Some(node) = queue.pop_front() {
+            for child in graph.nodes[node].strong_children() {
+                let child = graph.number(&mut numbers, &mut queue, child);
+                graph.edges[node].push(child);
+            }
+        }
+        graph
+    }
+
+    /// The number of `node`, giving it the next one (and queueing it to
+    /// be walked) if it hasn't been seen yet.
+    fn number(
+        &mut self,
+        numbers: &mut HashMap<*const T, usize>,
+        queue: &mut VecDeque<usize>,
+        node: Rc<T>,
+    ) -> usize {
+        if let Some(&number) = numbers.get(&Rc::as_ptr(&node)) {
+            return number;
+        }
+        let number = self.nodes.len();
+        numbers.insert(Rc::as_ptr(&node), number);
+        self.nodes.push(node);
+        self.edges.push(Vec::new());
+        queue.push_back(number);
+        number
+    }
+}
+
+/// Tarjan's algorithm, with an explicit stack so that long chains of
+/// references don't overflow the real one.
+fn strongly_connected(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let mut tarjan = Tarjan {
+        order: vec![None; edges.len()],
+        low: vec![0; edges.len()],
+        on_stack: vec![false; edges.len()],
+        stack: Vec::new(),
+        next: 0,
+    };
+    let mut components = Vec::new();
+
+    for start in 0..edges.len() {
+        if tarjan.order[start].is_some() {
+            continue;
+        }
+        tarjan.visit(start);
+        // Each node being visited, and how many of its edges it has followed.
+        let mut calls = vec![(start, 0)];
+
+        while let Some((node, followed)) = calls.last_mut() {
+            let node = *node;
+            if let Some(&child) = edges[node].get(*followed) {
+                *followed += 1;
+                match tarjan.order[child] {
+                    None => {
+                        tarjan.visit(child);
+                        calls.push((child, 0));
+                    }
+                    Some(order) if tarjan.on_stack[child] => {
+                        tarjan.low[node] = tarjan.low[node].min(order);
+                    }
+                    Some(_) => {}
+                }
+                continue;
+            }
+
+            calls.pop();
+            if let Some(&(parent, _)) = calls.last() {
+                tarjan.low[parent] = tarjan.low[parent].min(tarjan.low[node]);
+            }
+            if Some(tarjan.low[node]) == tarjan.order[node] {
+                components.push(tarjan.pop_component(node));
+            }
+        }
+    }
+    components
+}
+
+struct Tarjan {
+    /// The order each node was first visited in.
+    order: Vec<Option<usize>>,
+    /// The earliest-visited node each node is known to reach.
+    low: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    next: usize,
+}
+
+impl Tarjan {
+    fn visit(&mut self, node: usize) {
+        self.order[node] = Some(self.next);
+        self.low[node] = self.next;
+        self.next += 1;
+        self.stack.push(node);
+        self.on_stack[node] = true;
+    }
+
+    /// Pop the component whose first-visited node is `root`.
+    fn pop_component(&mut self, root: usize) -> Vec<usize> {
+        let mut component = Vec::new();
+        loop {
+            let node = self.stack.pop().unwrap();
+            self.on_stack[node] = false;
+            component.push(node);
+            if node == root {
+                return component;
+            }
+        }
+    }
+}
+
+impl<T> fmt::Display for Cycle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "a cycle of {} values with strong counts {:?}",
+            self.nodes.len(),
+            self.strong_counts
+        )
+    }
+}
+
+impl<T> fmt::Debug for Cycle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cycle")
+            .field("strong_counts", &self.strong_counts)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The list from Listing 15-25, whose tails can be changed to make the
+/// cycle in Listing 15-26.
+pub enum RcList {
+    Cons(i32, RefCell<Rc<RcList>>),
+    Nil,
+}
+
+impl RcList {
+    pub fn tail(&self) -> Option<&RefCell<Rc<RcList>>> {
+        match self {
+            RcList::Cons(_, item) => Some(item),
+            RcList::Nil => None,
+        }
+    }
+}
+
+impl Traverse for RcList {
+    fn strong_children(&self) -> Vec<Rc<RcList>> {
+        self.tail()
+            .map(|tail| vec![Rc::clone(&tail.borrow())])
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::Tree;
+
+    use RcList::{Cons, Nil};
+
+    #[test]
+    fn finds_the_cycle_from_listing_15_26() {
+        let a = Rc::new(Cons(5, RefCell::new(Rc::new(Nil))));
+        let b = Rc::new(Cons(10, RefCell::new(Rc::clone(&a))));
+        assert!(find_cycles([&b]).is_empty());
+
+        if let Some(link) = a.tail() {
+            *link.borrow_mut() = Rc::clone(&b);
+        }
+        let cycles = find_cycles([&a]);
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].nodes.len(), 2);
+        // Each held by the other, and by `a` or `b` here.
+        assert_eq!(cycles[0].strong_counts, [2, 2]);
+        assert_eq!(
+            cycles[0].to_string(),
+            "a cycle of 2 values with strong counts [2, 2]"
+        );
+
+        // Break the cycle so the test doesn't leak.
+        *a.tail().unwrap().borrow_mut() = Rc::new(Nil);
+        assert!(find_cycles([&a, &b]).is_empty());
+    }
+
+    #[test]
+    fn finds_a_value_that_holds_itself() {
+        let a = Rc::new(Cons(1, RefCell::new(Rc::new(Nil))));
+        *a.tail().unwrap().borrow_mut() = Rc::clone(&a);
+        let cycles = find_cycles([&a]);
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].strong_counts, [2]);
+
+        *a.tail().unwrap().borrow_mut() = Rc::new(Nil);
+    }
+
+    #[test]
+    fn a_tree_with_weak_parents_has_none() {
+        let tree = Tree::new(1);
+        let child = tree.root().add_child(2);
+        child.add_child(3);
+        assert!(find_cycles([tree.root()]).is_empty());
+    }
+
+    #[test]
+    fn walks_long_chains_without_recursing() {
+        let mut list = Rc::new(Nil);
+        for n in 0..100_000 {
+            list = Rc::new(Cons(n, RefCell::new(list)));
+        }
+        assert!(find_cycles([&list]).is_empty());
+
+        // Take the list apart from the front, which dropping it wouldn't.
+        while let Some(tail) = list.tail() {
+            let next = tail.replace(Rc::new(Nil));
+            list = next;
+        }
+    }
+}
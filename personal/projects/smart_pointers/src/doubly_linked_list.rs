@@ -0,0 +1,506 @@
+//! A doubly linked list built from `Rc<RefCell<Node>>`, with `Weak` for the
+//! links that point back.
+//!
+//! Each node owns the next one, and the list owns its first and last
+//! nodes. If `prev` were an `Rc` too, every pair of neighbours would be a
+//! reference cycle like the one in Listing 15-26, and nothing would ever be
+//! freed. With `prev` weak, dropping the list drops the first node, which
+//! drops the next, and so on.
+//!
+//! Values sit in a `RefCell`, so they can only be borrowed for as long as
+//! a `Ref` guard lives. That's why [`DoublyLinkedList::iter`] hands out
+//! clones, and [`CursorMut::current`] a `RefMut`.
+
+use std::{
+    cell::{Ref, RefCell, RefMut},
+    fmt,
+    rc::{Rc, Weak},
+};
+
+type Link<T> = Option<Rc<RefCell<Node<T>>>>;
+
+struct Node<T> {
+    value: T,
+    next: Link<T>,
+    prev: Option<Weak<RefCell<Node<T>>>>,
+}
+
+impl<T> Node<T> {
+    fn new(value: T) -> Rc<RefCell<Node<T>>> {
+        Rc::new(RefCell::new(Node {
+            value,
+            next: None,
+            prev: None,
+        }))
+    }
+
+    fn prev(&self) -> Link<T> {
+        self.prev.as_ref().and_then(Weak::upgrade)
+    }
+
+    /// Take the value out of a node that's been unlinked.
+    fn into_value(node: Rc<RefCell<Node<T>>>) -> T {
+        match Rc::try_unwrap(node) {
+            Ok(node) => node.into_inner().value,
+            Err(_) => unreachable!("an unlinked node has no other owners"),
+        }
+    }
+}
+
+pub struct DoublyLinkedList<T> {
+    head: Link<T>,
+    tail: Link<T>,
+    len: usize,
+}
+
+impl<T> DoublyLinkedList<T> {
+    pub fn new() -> DoublyLinkedList<T> {
+        DoublyLinkedList {
+            head: None,
+            tail: None,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn front(&self) -> Option<Ref<'_, T>> {
+        self.head
+            .as_ref()
+            .map(|node| Ref::map(node.borrow(), |node| &node.value))
+    }
+
+    pub fn back(&self) -> Option<Ref<'_, T>> {
+        self.tail
+            .as_ref()
+            .map(|node| Ref::map(node.borrow(), |node| &node.value))
+    }
+
+    pub fn push_front(&mut self, value: T) {
+        let node = Node::new(value);
+        match self.head.take() {
+            Some(old) => {
+                old.borrow_mut().prev = Some(Rc::downgrade(&node));
+                node.borrow_mut().next = Some(old);
+            }
+            None => self.tail = Some(Rc::clone(&node)),
+        }
+        self.head = Some(node);
+        self.len += 1;
+    }
+
+    pub fn push_back(&mut self, value: T) {
+        let node = Node::new(value);
+        match self.tail.take() {
+            Some(old) => {
+                node.borrow_mut().prev = Some(Rc::downgrade(&old));
+                old.borrow_mut().next = Some(Rc::clone(&node));
+            }
+            None => self.head = Some(Rc::clone(&node)),
+        }
+        self.tail = Some(node);
+        self.len += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        let old = self.head.take()?;
+        let next = old.borrow_mut().next.take();
+        match next {
+            Some(next) => {
+                next.borrow_mut().prev = None;
+                self.head = Some(next);
+            }
+            None => self.tail = None,
+        }
+        self.len -= 1;
+        Some(Node::into_value(old))
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        let old = self.tail.take()?;
+        let prev = old.borrow_mut().prev.take().and_then(|prev| prev.upgrade());
+        match prev {
+            Some(prev) => {
+                prev.borrow_mut().next = None;
+                self.tail = Some(prev);
+            }
+            None => self.head = None,
+        }
+        self.len -= 1;
+        Some(Node::into_value(old))
+    }
+
+    /// Clones of the values, front to back.
+    pub fn iter(&self) -> Iter<'_, T>
+    where
+        T: Clone,
+    {
+        Iter {
+            front: self.head.clone(),
+            back: self.tail.clone(),
+            remaining: self.len,
+            _list: self,
+        }
+    }
+
+    /// A cursor on the first value, or on the "ghost" position between the
+    /// ends if the list is empty.
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            current: self.head.clone(),
+            index: (!self.is_empty()).then_some(0),
+            list: self,
+        }
+    }
+
+    /// A cursor on the last value, or on the ghost if the list is empty.
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            current: self.tail.clone(),
+            index: self.len.checked_sub(1),
+            list: self,
+        }
+    }
+}
+
+impl<T> Default for DoublyLinkedList<T> {
+    fn default() -> DoublyLinkedList<T> {
+        DoublyLinkedList::new()
+    }
+}
+
+impl<T> Drop for DoublyLinkedList<T> {
+    fn drop(&mut self) {
+        // Dropping the head would drop each node from inside the last one's
+        // drop, which overflows the stack on a long enough list.
+        while self.pop_front().is_some() {}
+    }
+}
+
+impl<T> FromIterator<T> for DoublyLinkedList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> DoublyLinkedList<T> {
+        let mut list = DoublyLinkedList::new();
+        for value in iter {
+            list.push_back(value);
+        }
+        list
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for DoublyLinkedList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut list = f.debug_list();
+        let mut node = self.head.clone();
+        while let Some(current) = node {
+            list.entry(&current.borrow().value);
+            node = current.borrow().next.clone();
+        }
+        list.finish()
+    }
+}
+
+/// Clones of a [`DoublyLinkedList`]'s values, from either end.
+pub struct Iter<'a, T> {
+    front: Link<T>,
+    back: Link<T>,
+    remaining: usize,
+    /// Borrowing the list keeps it from changing under us.
+    _list: &'a DoublyLinkedList<T>,
+}
+
+impl<T: Clone> Iterator for Iter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let node = self.front.take()?;
+        let node = node.borrow();
+        self.front = node.next.clone();
+        Some(node.value.clone())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T: Clone> DoubleEndedIterator for Iter<'_, T> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let node = self.back.take()?;
+        let node = node.borrow();
+        self.back = node.prev();
+        Some(node.value.clone())
+    }
+}
+
+impl<T: Clone> ExactSizeIterator for Iter<'_, T> {}
+
+/// Takes the values out of a [`DoublyLinkedList`], from either end.
+pub struct IntoIter<T>(DoublyLinkedList<T>);
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.0.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        self.0.pop_back()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> IntoIterator for DoublyLinkedList<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter(self)
+    }
+}
+
+/// A position in a [`DoublyLinkedList`] that can move either way, and
+/// insert or remove values there.
+///
+/// Besides pointing at a value, a cursor can point at the ghost position
+/// between the back and the front. Moving forward from the back, or back
+/// from the front, goes there; moving on from it wraps round.
+pub struct CursorMut<'a, T> {
+    list: &'a mut DoublyLinkedList<T>,
+    current: Link<T>,
+    index: Option<usize>,
+}
+
+impl<T> CursorMut<'_, T> {
+    /// Where the cursor is, counting from the front; `None` at the ghost.
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    pub fn current(&mut self) -> Option<RefMut<'_, T>> {
+        self.current
+            .as_ref()
+            .map(|node| RefMut::map(node.borrow_mut(), |node| &mut node.value))
+    }
+
+    pub fn move_next(&mut self) {
+        self.current = match self.current.take() {
+            Some(node) => node.borrow().next.clone(),
+            None => self.list.head.clone(),
+        };
+        self.index = match (&self.current, self.index) {
+            (None, _) => None,
+            (Some(_), Some(index)) => Some(index + 1),
+            (Some(_), None) => Some(0),
+        };
+    }
+
+    pub fn move_prev(&mut self) {
+        self.current = match self.current.take() {
+            Some(node) => node.borrow().prev(),
+            None => self.list.tail.clone(),
+        };
+        self.index = match (&self.current, self.index) {
+            (None, _) => None,
+            (Some(_), Some(index)) => Some(index - 1),
+            (Some(_), None) => Some(self.list.len - 1),
+        };
+    }
+
+    /// Insert `value` after the cursor, or at the front if the cursor is
+    /// at the ghost.
+    pub fn insert_after(&mut self, value: T) {
+        let Some(current) = &self.current else {
+            self.list.push_front(value);
+            return;
+        };
+
+        let node = Node::new(value);
+        let next = current.borrow_mut().next.take();
+        match next {
+            Some(next) => {
+                next.borrow_mut().prev = Some(Rc::downgrade(&node));
+                node.borrow_mut().next = Some(next);
+            }
+            None => self.list.tail = Some(Rc::clone(&node)),
+        }
+        node.borrow_mut().prev = Some(Rc::downgrade(current));
+        current.borrow_mut().next = Some(node);
+        self.list.len += 1;
+    }
+
+    /// Insert `value` before the cursor, or at the back if the cursor is
+    /// at the ghost.
+    pub fn insert_before(&mut self, value: T) {
+        let Some(current) = &self.current else {
+            self.list.push_back(value);
+            return;
+        };
+
+        let node = Node::new(value);
+        let prev = current.borrow().prev();
+        match prev {
+            Some(prev) => {
+                node.borrow_mut().prev = Some(Rc::downgrade(&prev));
+                prev.borrow_mut().next = Some(Rc::clone(&node));
+            }
+            None => self.list.head = Some(Rc::clone(&node)),
+        }
+        node.borrow_mut().next = Some(Rc::clone(current));
+        current.borrow_mut().prev = Some(Rc::downgrade(&node));
+        self.list.len += 1;
+        self.index = self.index.map(|index| index + 1);
+    }
+
+    /// Remove the value at the cursor, moving the cursor on to the next
+    /// one. Returns `None`, and removes nothing, at the ghost.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let node = self.current.take()?;
+        let next = node.borrow_mut().next.take();
+        let prev = node
+            .borrow_mut()
+            .prev
+            .take()
+            .and_then(|prev| prev.upgrade());
+
+        match &prev {
+            Some(prev) => prev.borrow_mut().next = next.clone(),
+            None => self.list.head = next.clone(),
+        }
+        match &next {
+            Some(next) => next.borrow_mut().prev = prev.as_ref().map(Rc::downgrade),
+            None => self.list.tail = prev,
+        }
+        self.list.len -= 1;
+
+        if next.is_none() {
+            self.index = None;
+        }
+        self.current = next;
+        Some(Node::into_value(node))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values<T: Clone>(list: &DoublyLinkedList<T>) -> Vec<T> {
+        list.iter().collect()
+    }
+
+    #[test]
+    fn pushes_and_pops_at_both_ends() {
+        let mut list = DoublyLinkedList::new();
+        list.push_back(2);
+        list.push_front(1);
+        list.push_back(3);
+        assert_eq!(values(&list), [1, 2, 3]);
+        assert_eq!((*list.front().unwrap(), *list.back().unwrap()), (1, 3));
+
+        assert_eq!(list.pop_back(), Some(3));
+        assert_eq!(list.pop_front(), Some(1));
+        assert_eq!(list.pop_back(), Some(2));
+        assert_eq!(list.pop_front(), None);
+        assert!(list.is_empty());
+        assert!(list.front().is_none() && list.back().is_none());
+    }
+
+    #[test]
+    fn iterates_from_either_end() {
+        let list: DoublyLinkedList<_> = (1..=4).collect();
+        assert_eq!(list.iter().rev().collect::<Vec<_>>(), [4, 3, 2, 1]);
+
+        let mut iter = list.iter();
+        assert_eq!((iter.next(), iter.next_back()), (Some(1), Some(4)));
+        assert_eq!(iter.len(), 2);
+        assert_eq!(iter.collect::<Vec<_>>(), [2, 3]);
+
+        let mut owned = list.into_iter();
+        assert_eq!((owned.next_back(), owned.next()), (Some(4), Some(1)));
+        assert_eq!(owned.collect::<Vec<_>>(), [2, 3]);
+    }
+
+    #[test]
+    fn a_cursor_walks_and_edits() {
+        let mut list: DoublyLinkedList<_> = [1, 3, 5].into_iter().collect();
+        let mut cursor = list.cursor_front_mut();
+        cursor.move_next();
+        assert_eq!(
+            (cursor.index(), cursor.current().as_deref()),
+            (Some(1), Some(&3))
+        );
+
+        cursor.insert_before(2);
+        cursor.insert_after(4);
+        assert_eq!(cursor.index(), Some(2));
+        *cursor.current().unwrap() *= 10;
+
+        cursor.move_prev();
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.index(), None);
+        cursor.move_prev();
+        assert_eq!(cursor.current().as_deref(), Some(&5));
+        assert_eq!(values(&list), [1, 2, 30, 4, 5]);
+    }
+
+    #[test]
+    fn a_cursor_removes_values() {
+        let mut list: DoublyLinkedList<_> = (1..=4).collect();
+        let mut cursor = list.cursor_front_mut();
+        assert_eq!(cursor.remove_current(), Some(1));
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some(3));
+        assert_eq!(cursor.remove_current(), Some(4));
+        assert_eq!(cursor.remove_current(), None);
+
+        // At the ghost, inserting goes at the ends.
+        cursor.insert_after(0);
+        cursor.insert_before(9);
+        assert_eq!(values(&list), [0, 2, 9]);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.pop_back(), Some(9));
+        assert_eq!(list.pop_front(), Some(0));
+    }
+
+    #[test]
+    fn dropping_frees_every_node() {
+        let value = Rc::new(());
+        let mut list: DoublyLinkedList<_> = (0..10).map(|_| Rc::clone(&value)).collect();
+        let mut cursor = list.cursor_back_mut();
+        cursor.insert_before(Rc::clone(&value));
+        cursor.remove_current();
+        assert_eq!(Rc::strong_count(&value), 11);
+
+        drop(list);
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn drops_a_long_list_without_recursing() {
+        let list: DoublyLinkedList<_> = (0..200_000).collect();
+        drop(list);
+    }
+}
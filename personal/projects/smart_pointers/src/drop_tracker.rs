@@ -0,0 +1,169 @@
+//! Recording the order values are dropped in, to test it instead of
+//! printing it.
+//!
+//! Listing 15-14's `CustomSmartPointer` prints a line when it's dropped,
+//! and the book reads the order off the output. Wrapping values with a
+//! [`DropTracker`] writes their labels to a shared log instead, which a
+//! test can check:
+//!
+//! ```
+//! use smart_pointers::drop_tracker::DropTracker;
+//!
+//! let tracker = DropTracker::new();
+//! {
+//!     let _c = tracker.track("my stuff", ());
+//!     let _d = tracker.track("other stuff", ());
+//! }
+//! tracker.assert_dropped(&["other stuff", "my stuff"]);
+//! ```
+
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    rc::Rc,
+};
+
+use crate::recorder::Recorder;
+
+/// Hands out [`Tracked`] values, and keeps the log they write to.
+#[derive(Debug, Default)]
+pub struct DropTracker {
+    log: Rc<Recorder<String>>,
+}
+
+impl DropTracker {
+    pub fn new() -> DropTracker {
+        DropTracker::default()
+    }
+
+    /// Wrap `value` so that dropping it logs `label`.
+    pub fn track<T>(&self, label: &str, value: T) -> Tracked<T> {
+        Tracked {
+            value,
+            label: label.to_string(),
+            log: Rc::clone(&self.log),
+        }
+    }
+
+    /// The labels of every value dropped so far, in the order they went.
+    pub fn dropped(&self) -> Vec<String> {
+        self.log.calls().to_vec()
+    }
+
+    pub fn is_dropped(&self, label: &str) -> bool {
+        self.log.calls().iter().any(|dropped| dropped == label)
+    }
+
+    /// Panic unless exactly these values have been dropped, in this order.
+    #[track_caller]
+    pub fn assert_dropped(&self, labels: &[&str]) {
+        self.log.assert_called_with(labels);
+    }
+
+    #[track_caller]
+    pub fn assert_alive(&self, label: &str) {
+        assert!(
+            !self.is_dropped(label),
+            "expected {label:?} to be alive, but it was dropped"
+        );
+    }
+}
+
+/// A value that logs its label to its [`DropTracker`] when it's dropped.
+pub struct Tracked<T> {
+    value: T,
+    label: String,
+    log: Rc<Recorder<String>>,
+}
+
+impl<T> Tracked<T> {
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+impl<T> Deref for Tracked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Tracked<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T> Drop for Tracked<T> {
+    fn drop(&mut self) {
+        self.log.record(self.label.clone());
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Tracked<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tracked")
+            .field("label", &self.label)
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_a_value_early_with_mem_drop() {
+        // Listing 15-16.
+        let tracker = DropTracker::new();
+        let c = tracker.track("some data", String::from("some data"));
+        assert_eq!(c.len(), 9);
+        tracker.assert_alive("some data");
+
+        drop(c);
+        tracker.assert_dropped(&["some data"]);
+    }
+
+    #[test]
+    fn drops_fields_and_elements_in_order() {
+        struct Pair {
+            _first: Tracked<()>,
+            _second: Tracked<()>,
+        }
+
+        let tracker = DropTracker::new();
+        {
+            let _pair = Pair {
+                _first: tracker.track("first field", ()),
+                _second: tracker.track("second field", ()),
+            };
+            let _items = [tracker.track("item 0", ()), tracker.track("item 1", ())];
+        }
+        // Locals last to first; fields and elements first to last.
+        tracker.assert_dropped(&["item 0", "item 1", "first field", "second field"]);
+    }
+
+    #[test]
+    fn an_rc_drops_its_value_with_the_last_clone() {
+        let tracker = DropTracker::new();
+        let a = Rc::new(tracker.track("shared", 5));
+        let b = Rc::clone(&a);
+
+        drop(a);
+        tracker.assert_alive("shared");
+        assert_eq!(**b, 5);
+        drop(b);
+        assert!(tracker.is_dropped("shared"));
+    }
+
+    #[test]
+    #[should_panic(expected = "expected \"x\" to be alive")]
+    fn says_which_value_was_dropped_too_soon() {
+        let tracker = DropTracker::new();
+        drop(tracker.track("x", ()));
+        tracker.assert_alive("x");
+    }
+}
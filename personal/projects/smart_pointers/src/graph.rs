@@ -0,0 +1,246 @@
+//! A directed graph whose nodes are `Rc<RefCell<Node>>`.
+//!
+//! A node can have any number of edges pointing at it, so it has as many
+//! owners: each edge is an `Rc`. Edges are added after the nodes exist,
+//! which needs a `RefCell` around each node to mutate it through one of
+//! its shared owners.
+//!
+//! Unlike the tree, a graph can't make its back edges `Weak`: any edge can
+//! close a cycle, and there's no telling which one is "back". So cycles of
+//! `Rc`s are expected, and would leak like Listing 15-26 if nothing broke
+//! them. The [`Graph`] breaks them itself: it keeps every node, and when
+//! it's dropped it clears every node's edges, leaving nothing holding any
+//! node but the graph.
+
+use std::{
+    cell::RefCell,
+    collections::{HashSet, VecDeque},
+    fmt,
+    rc::Rc,
+    slice,
+};
+
+use crate::cycles::Traverse;
+
+pub type NodeRef<T> = Rc<RefCell<Node<T>>>;
+
+pub struct Node<T> {
+    pub value: T,
+    edges: Vec<NodeRef<T>>,
+}
+
+impl<T> Node<T> {
+    /// The nodes this node has an edge to, in the order they were added.
+    pub fn neighbors(&self) -> slice::Iter<'_, NodeRef<T>> {
+        self.edges.iter()
+    }
+}
+
+impl<T> Traverse for RefCell<Node<T>> {
+    fn strong_children(&self) -> Vec<NodeRef<T>> {
+        self.borrow().edges.clone()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Node<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Following edges could go round a cycle forever.
+        f.debug_struct("Node")
+            .field("value", &self.value)
+            .field("edges", &self.edges.len())
+            .finish()
+    }
+}
+
+pub struct Graph<T> {
+    nodes: Vec<NodeRef<T>>,
+}
+
+impl<T> Graph<T> {
+    pub fn new() -> Graph<T> {
+        Graph { nodes: Vec::new() }
+    }
+
+    pub fn add_node(&mut self, value: T) -> NodeRef<T> {
+        let node = Rc::new(RefCell::new(Node {
+            value,
+            edges: Vec::new(),
+        }));
+        self.nodes.push(Rc::clone(&node));
+        node
+    }
+
+    /// Add an edge from `from` to `to`, both of which should be nodes of
+    /// this graph.
+    pub fn add_edge(&mut self, from: &NodeRef<T>, to: &NodeRef<T>) {
+        from.borrow_mut().edges.push(Rc::clone(to));
+    }
+
+    pub fn nodes(&self) -> &[NodeRef<T>] {
+        &self.nodes
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Every node reachable from `start`, nearest first.
+    pub fn breadth_first(&self, start: &NodeRef<T>) -> BreadthFirst<T> {
+        BreadthFirst {
+            queue: VecDeque::from([Rc::clone(start)]),
+            seen: HashSet::from([Rc::as_ptr(start)]),
+        }
+    }
+
+    /// Every node reachable from `start`, following each path as far as it
+    /// goes before backtracking.
+    pub fn depth_first(&self, start: &NodeRef<T>) -> DepthFirst<T> {
+        DepthFirst {
+            stack: vec![Rc::clone(start)],
+            seen: HashSet::new(),
+        }
+    }
+}
+
+impl<T> Default for Graph<T> {
+    fn default() -> Graph<T> {
+        Graph::new()
+    }
+}
+
+impl<T> Drop for Graph<T> {
+    fn drop(&mut self) {
+        for node in &self.nodes {
+            // A node someone is still borrowing keeps its edges, and so
+            // does whatever they keep alive. Better that than a panic in
+            // drop.
+            if let Ok(mut node) = node.try_borrow_mut() {
+                node.edges.clear();
+            }
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Graph<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Graph").field(&self.nodes).finish()
+    }
+}
+
+/// See [`Graph::breadth_first`].
+pub struct BreadthFirst<T> {
+    queue: VecDeque<NodeRef<T>>,
+    seen: HashSet<*const RefCell<Node<T>>>,
+}
+
+impl<T> Iterator for BreadthFirst<T> {
+    type Item = NodeRef<T>;
+
+    fn next(&mut self) -> Option<NodeRef<T>> {
+        let node = self.queue.pop_front()?;
+        for neighbor in node.borrow().neighbors() {
+            if self.seen.insert(Rc::as_ptr(neighbor)) {
+                self.queue.push_back(Rc::clone(neighbor));
+            }
+        }
+        Some(node)
+    }
+}
+
+/// See [`Graph::depth_first`].
+pub struct DepthFirst<T> {
+    stack: Vec<NodeRef<T>>,
+    seen: HashSet<*const RefCell<Node<T>>>,
+}
+
+impl<T> Iterator for DepthFirst<T> {
+    type Item = NodeRef<T>;
+
+    fn next(&mut self) -> Option<NodeRef<T>> {
+        // A node can be pushed more than once before it's visited; only the
+        // first time it comes off counts.
+        let node = loop {
+            let node = self.stack.pop()?;
+            if self.seen.insert(Rc::as_ptr(&node)) {
+                break node;
+            }
+        };
+        // Pushed last to first, so the first neighbor comes off next.
+        let neighbors = node.borrow().neighbors().rev().cloned().collect::<Vec<_>>();
+        self.stack.extend(neighbors);
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Weak;
+
+    use super::*;
+    use crate::cycles::find_cycles;
+
+    /// Edges 1 → 2, 1 → 3, 2 → 4, 2 → 5, 3 → 2 and 5 → 1, so that 1, 2,
+    /// 3 and 5 are all on cycles.
+    fn graph() -> (Graph<i32>, Vec<NodeRef<i32>>) {
+        let mut graph = Graph::new();
+        let nodes: Vec<_> = (1..=5).map(|value| graph.add_node(value)).collect();
+        for (from, to) in [(1, 2), (1, 3), (2, 4), (2, 5), (3, 2), (5, 1)] {
+            graph.add_edge(&nodes[from - 1], &nodes[to - 1]);
+        }
+        (graph, nodes)
+    }
+
+    fn values(nodes: impl Iterator<Item = NodeRef<i32>>) -> Vec<i32> {
+        nodes.map(|node| node.borrow().value).collect()
+    }
+
+    #[test]
+    fn walks_breadth_first_and_depth_first() {
+        let (graph, nodes) = graph();
+        assert_eq!(values(graph.breadth_first(&nodes[0])), [1, 2, 3, 4, 5]);
+        assert_eq!(values(graph.depth_first(&nodes[0])), [1, 2, 4, 5, 3]);
+        assert_eq!(values(graph.depth_first(&nodes[2])), [3, 2, 4, 5, 1]);
+    }
+
+    #[test]
+    fn lists_neighbors_and_changes_values() {
+        let (graph, nodes) = graph();
+        let neighbors: Vec<_> = nodes[1]
+            .borrow()
+            .neighbors()
+            .map(|node| node.borrow().value)
+            .collect();
+        assert_eq!(neighbors, [4, 5]);
+
+        nodes[3].borrow_mut().value = 40;
+        assert_eq!(values(graph.breadth_first(&nodes[1])), [2, 40, 5, 1, 3]);
+    }
+
+    #[test]
+    fn dropping_the_graph_breaks_its_cycles() {
+        let (graph, nodes) = graph();
+        let cycles = find_cycles(graph.nodes());
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].nodes.len(), 4);
+
+        let weak: Vec<Weak<_>> = nodes.iter().map(Rc::downgrade).collect();
+        drop(nodes);
+        drop(graph);
+        assert!(weak.iter().all(|node| node.upgrade().is_none()));
+    }
+
+    #[test]
+    fn prints_without_following_edges() {
+        let mut graph = Graph::new();
+        let node = graph.add_node('a');
+        graph.add_edge(&node, &node);
+        assert_eq!(
+            format!("{graph:?}"),
+            "Graph([RefCell { value: Node { value: 'a', edges: 1 } }])"
+        );
+    }
+}
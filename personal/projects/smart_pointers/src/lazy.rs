@@ -0,0 +1,210 @@
+//! Smart pointers whose value is only computed the first time it's needed.
+//!
+//! [`Lazy`] is for a single thread. [`SyncLazy`] can be shared between
+//! threads, and so can be a `static`, which is where lazy values are most
+//! useful: a table that takes a function call to build, like a map of MIME
+//! types, can't be a plain `static`, since those have to be built at
+//! compile time.
+//!
+//! Both hand out `&T` once the value exists, and never change it after, so
+//! the only mutation that needs thinking about is the first one.
+
+use std::{
+    cell::{Cell, UnsafeCell},
+    fmt,
+    ops::Deref,
+    sync::Once,
+};
+
+pub struct Lazy<T, F = fn() -> T> {
+    value: UnsafeCell<Option<T>>,
+    init: Cell<Option<F>>,
+}
+
+impl<T, F: FnOnce() -> T> Lazy<T, F> {
+    pub const fn new(init: F) -> Lazy<T, F> {
+        Lazy {
+            value: UnsafeCell::new(None),
+            init: Cell::new(Some(init)),
+        }
+    }
+
+    /// The value, computing it first if this is the first time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if computing the value panicked before, or if it needs the
+    /// value itself.
+    pub fn force(this: &Lazy<T, F>) -> &T {
+        // SAFETY: this shared borrow ends before the one below begins.
+        if let Some(value) = unsafe { &*this.value.get() } {
+            return value;
+        }
+
+        let init = this
+            .init
+            .take()
+            .expect("Lazy's value panicked or depended on itself");
+        let value = init();
+        // SAFETY: the value was None, so we've never handed out a reference
+        // to it, and `init` is gone, so no call to `force` from inside it
+        // can have got this far. This is the only &mut there'll ever be.
+        unsafe { *this.value.get() = Some(value) };
+        // SAFETY: it never changes again, so shared borrows are fine.
+        unsafe { (*this.value.get()).as_ref().unwrap() }
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for Lazy<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        Lazy::force(self)
+    }
+}
+
+impl<T: fmt::Debug, F> fmt::Debug for Lazy<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // SAFETY: a shared borrow, while nothing is writing (we're not
+        // inside `force`, which doesn't call out while it has the value
+        // borrowed).
+        match unsafe { &*self.value.get() } {
+            Some(value) => f.debug_tuple("Lazy").field(value).finish(),
+            None => f.write_str("Lazy(<uninit>)"),
+        }
+    }
+}
+
+/// [`Lazy`] for values shared between threads.
+///
+/// ```
+/// use std::collections::HashMap;
+/// use smart_pointers::lazy::SyncLazy;
+///
+/// static MIME_TYPES: SyncLazy<HashMap<&str, &str>> = SyncLazy::new(|| {
+///     HashMap::from([("html", "text/html"), ("css", "text/css")])
+/// });
+///
+/// assert_eq!(MIME_TYPES["css"], "text/css");
+/// ```
+pub struct SyncLazy<T, F = fn() -> T> {
+    once: Once,
+    value: UnsafeCell<Option<T>>,
+    init: UnsafeCell<Option<F>>,
+}
+
+// SAFETY: `value` and `init` are only written inside `call_once`, which
+// runs on one thread while the others wait, and `value` is only read once
+// that's returned. The value is shared between threads (so T: Sync) and
+// may be created on one and dropped on another (so T: Send), and `init`
+// may run on any thread (so F: Send).
+unsafe impl<T: Send + Sync, F: Send> Sync for SyncLazy<T, F> {}
+
+impl<T, F: FnOnce() -> T> SyncLazy<T, F> {
+    pub const fn new(init: F) -> SyncLazy<T, F> {
+        SyncLazy {
+            once: Once::new(),
+            value: UnsafeCell::new(None),
+            init: UnsafeCell::new(Some(init)),
+        }
+    }
+
+    /// The value, computing it on this thread if no thread has yet, or
+    /// waiting for the thread that's computing it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if computing the value panicked before.
+    pub fn force(this: &SyncLazy<T, F>) -> &T {
+        this.once.call_once(|| {
+            // SAFETY: `call_once` runs this on one thread at a time, and at
+            // most once, so nobody else is touching either cell.
+            let init = unsafe { (*this.init.get()).take() }.unwrap();
+            let value = init();
+            // SAFETY: still inside `call_once`, so this is the only access,
+            // and the value was None, so no reference to it is out yet.
+            unsafe { *this.value.get() = Some(value) };
+        });
+        // SAFETY: `call_once` only returns once the closure has finished on
+        // some thread (it panics instead if the closure did), and everything
+        // the closure wrote happens-before the return. So the value is
+        // Some, and since the closure never runs again, nothing mutates it
+        // while this shared borrow lives.
+        unsafe { (*this.value.get()).as_ref().unwrap() }
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for SyncLazy<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        SyncLazy::force(self)
+    }
+}
+
+impl<T: fmt::Debug, F> fmt::Debug for SyncLazy<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.once.is_completed() {
+            return f.write_str("SyncLazy(<uninit>)");
+        }
+        // SAFETY: complete, so written and never written again.
+        let value = unsafe { (*self.value.get()).as_ref().unwrap() };
+        f.debug_tuple("SyncLazy").field(value).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        panic::{self, AssertUnwindSafe},
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use thread_pool::ThreadPool;
+
+    use super::*;
+
+    #[test]
+    fn computes_on_first_use_only() {
+        let calls = Cell::new(0);
+        let lazy = Lazy::new(|| {
+            calls.set(calls.get() + 1);
+            String::from("value")
+        });
+        assert_eq!(calls.get(), 0);
+        assert_eq!(format!("{lazy:?}"), "Lazy(<uninit>)");
+
+        assert_eq!(lazy.len(), 5);
+        assert_eq!(*lazy, "value");
+        assert_eq!(calls.get(), 1);
+        assert_eq!(format!("{lazy:?}"), "Lazy(\"value\")");
+    }
+
+    #[test]
+    fn a_panicking_init_poisons_it() {
+        let lazy: Lazy<i32, _> = Lazy::new(|| panic!("no value"));
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| *lazy)).is_err());
+        let second = panic::catch_unwind(AssertUnwindSafe(|| *lazy));
+        let message = *second.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.contains("panicked or depended on itself"));
+    }
+
+    #[test]
+    fn threads_share_one_computation() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        static TABLE: SyncLazy<Vec<u64>> = SyncLazy::new(|| {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+            (0..100).collect()
+        });
+
+        let pool = ThreadPool::new(4);
+        let sums: Vec<_> = (0..16)
+            .map(|_| pool.execute(|| TABLE.iter().sum::<u64>()).unwrap())
+            .collect();
+        for sum in sums {
+            assert_eq!(sum.join().unwrap(), 4950);
+        }
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+        assert!(format!("{TABLE:?}").starts_with("SyncLazy([0, 1, 2"));
+    }
+}
@@ -0,0 +1,20 @@
+pub mod arena;
+pub mod cycles;
+pub mod doubly_linked_list;
+pub mod drop_tracker;
+pub mod graph;
+pub mod lazy;
+pub mod limit_tracker;
+pub mod list;
+pub mod my_arc;
+pub mod my_box;
+pub mod my_cell;
+pub mod my_once_cell;
+pub mod my_rc;
+pub mod my_ref_cell;
+pub mod raw_ptr;
+pub mod recorder;
+pub mod self_ref;
+pub mod shared_list;
+pub mod small_box;
+pub mod tree;
@@ -0,0 +1,393 @@
+//! The quota tracker from Listing 15-20, and the mock messenger from
+//! Listing 15-22 that its tests use.
+//!
+//! Anything that can take a message is a [`Messenger`]: as well as types
+//! that implement it themselves, any `Fn(&str)` closure does, and so does
+//! the sending half of a channel. Logging warnings to stderr needs no new
+//! type at all:
+//!
+//! ```
+//! use smart_pointers::limit_tracker::LimitTracker;
+//!
+//! let log = |msg: &str| eprintln!("quota: {msg}");
+//! let mut tracker = LimitTracker::new(&log, 100);
+//! tracker.set_value(80);
+//! ```
+
+use std::sync::mpsc;
+
+pub trait Messenger {
+    fn send(&self, msg: &str);
+}
+
+impl<F: Fn(&str)> Messenger for F {
+    fn send(&self, msg: &str) {
+        self(msg)
+    }
+}
+
+/// Sends each message down the channel. If the receiver has gone, nobody
+/// is listening, and the message is dropped.
+impl Messenger for mpsc::Sender<String> {
+    fn send(&self, msg: &str) {
+        let _ = mpsc::Sender::send(self, msg.to_string());
+    }
+}
+
+/// A [`Messenger`] that sends to a channel of its own, for handing the
+/// messages to another thread.
+#[derive(Debug, Clone)]
+pub struct ChannelMessenger {
+    sender: mpsc::Sender<String>,
+}
+
+impl ChannelMessenger {
+    /// A messenger, and the receiver its messages arrive at.
+    pub fn channel() -> (ChannelMessenger, mpsc::Receiver<String>) {
+        let (sender, receiver) = mpsc::channel();
+        (ChannelMessenger { sender }, receiver)
+    }
+}
+
+impl Messenger for ChannelMessenger {
+    fn send(&self, msg: &str) {
+        Messenger::send(&self.sender, msg);
+    }
+}
+
+/// How close to its quota a [`LimitTracker`]'s value is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum QuotaLevel {
+    #[default]
+    Ok,
+    Warning,
+    Urgent,
+    Exceeded,
+}
+
+/// A [`LimitTracker`]'s level changing, up or down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuotaEvent {
+    pub from: QuotaLevel,
+    pub to: QuotaLevel,
+    pub value: usize,
+    pub max: usize,
+}
+
+impl QuotaEvent {
+    pub fn is_rise(&self) -> bool {
+        self.to > self.from
+    }
+}
+
+/// The fractions of the max at which a [`LimitTracker`] moves up a level,
+/// and what it says when it does.
+///
+/// A tracker only sends a message when its level goes up, so setting the
+/// same value twice sends one message, not two. With some hysteresis, it
+/// also takes more than dipping just under a threshold to leave a level:
+/// the value has to fall that much further below it first, so a value
+/// wobbling around a threshold doesn't send a message on every rise.
+#[derive(Debug, Clone)]
+pub struct Thresholds {
+    /// Sorted by `at`.
+    table: Vec<Threshold>,
+    hysteresis: f64,
+}
+
+#[derive(Debug, Clone)]
+struct Threshold {
+    at: f64,
+    level: QuotaLevel,
+    message: String,
+}
+
+impl Thresholds {
+    /// No thresholds, so the level is always [`QuotaLevel::Ok`].
+    pub fn new() -> Thresholds {
+        Thresholds {
+            table: Vec::new(),
+            hysteresis: 0.0,
+        }
+    }
+
+    /// Move up to `level` once the value is `at` times the max, sending
+    /// `message`.
+    pub fn with_threshold(mut self, at: f64, level: QuotaLevel, message: &str) -> Thresholds {
+        let index = self.table.partition_point(|threshold| threshold.at <= at);
+        self.table.insert(
+            index,
+            Threshold {
+                at,
+                level,
+                message: message.to_string(),
+            },
+        );
+        self
+    }
+
+    /// Only move down a level once the value is `hysteresis` times the max
+    /// below that level's threshold.
+    pub fn with_hysteresis(mut self, hysteresis: f64) -> Thresholds {
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    /// The level for a value that's `fraction` of the max, coming from
+    /// `current`. Levels up to `current` are held on to down to their
+    /// threshold less the hysteresis; higher ones need the whole threshold.
+    fn level(&self, fraction: f64, current: QuotaLevel) -> QuotaLevel {
+        self.table
+            .iter()
+            .rev()
+            .find(|threshold| {
+                let slack = if threshold.level <= current {
+                    self.hysteresis
+                } else {
+                    0.0
+                };
+                fraction >= threshold.at - slack
+            })
+            .map_or(QuotaLevel::Ok, |threshold| threshold.level)
+    }
+
+    fn message(&self, level: QuotaLevel) -> Option<&str> {
+        self.table
+            .iter()
+            .find(|threshold| threshold.level == level)
+            .map(|threshold| threshold.message.as_str())
+    }
+}
+
+/// The book's thresholds: a warning at 75%, an urgent one at 90%, and an
+/// error at 100%.
+impl Default for Thresholds {
+    fn default() -> Thresholds {
+        Thresholds::new()
+            .with_threshold(
+                0.75,
+                QuotaLevel::Warning,
+                "Warning: You've used up over 75% of your quota!",
+            )
+            .with_threshold(
+                0.9,
+                QuotaLevel::Urgent,
+                "Urgent warning: You've used up over 90% of your quota!",
+            )
+            .with_threshold(1.0, QuotaLevel::Exceeded, "Error: You are over your quota!")
+    }
+}
+
+pub struct LimitTracker<'a, T: Messenger + ?Sized> {
+    messenger: &'a T,
+    value: usize,
+    max: usize,
+    thresholds: Thresholds,
+    level: QuotaLevel,
+}
+
+impl<'a, T> LimitTracker<'a, T>
+where
+    T: Messenger + ?Sized,
+{
+    pub fn new(messenger: &'a T, max: usize) -> LimitTracker<'a, T> {
+        LimitTracker {
+            messenger,
+            value: 0,
+            max,
+            thresholds: Thresholds::default(),
+            level: QuotaLevel::Ok,
+        }
+    }
+
+    pub fn with_thresholds(mut self, thresholds: Thresholds) -> LimitTracker<'a, T> {
+        self.thresholds = thresholds;
+        self
+    }
+
+    pub fn value(&self) -> usize {
+        self.value
+    }
+
+    pub fn level(&self) -> QuotaLevel {
+        self.level
+    }
+
+    /// Set the value, sending the new level's message if it's gone up.
+    ///
+    /// Returns the change of level, if there was one, for callers that want
+    /// to act on it rather than (or as well as) be told.
+    pub fn set_value(&mut self, value: usize) -> Option<QuotaEvent> {
+        let event = self.check(value);
+        self.value = value;
+
+        if let Some(event) = event {
+            if event.is_rise() {
+                if let Some(message) = self.thresholds.message(event.to) {
+                    self.messenger.send(message);
+                }
+            }
+            self.level = event.to;
+        }
+        event
+    }
+
+    /// What [`set_value`](LimitTracker::set_value) would return for `value`,
+    /// without setting it or sending anything.
+    pub fn check(&self, value: usize) -> Option<QuotaEvent> {
+        let percentage_of_max = value as f64 / self.max as f64;
+        let level = self.thresholds.level(percentage_of_max, self.level);
+
+        (level != self.level).then_some(QuotaEvent {
+            from: self.level,
+            to: level,
+            value,
+            max: self.max,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, thread};
+
+    use super::*;
+    use crate::recorder::Recorder;
+
+    struct MockMessenger {
+        sent_messages: RefCell<Vec<String>>,
+    }
+
+    impl MockMessenger {
+        fn new() -> MockMessenger {
+            MockMessenger {
+                sent_messages: RefCell::new(vec![]),
+            }
+        }
+    }
+
+    impl Messenger for MockMessenger {
+        fn send(&self, message: &str) {
+            self.sent_messages.borrow_mut().push(String::from(message));
+        }
+    }
+
+    #[test]
+    fn it_sends_an_over_75_percent_warning_message() {
+        let mock_messenger = MockMessenger::new();
+        let mut limit_tracker = LimitTracker::new(&mock_messenger, 100);
+
+        limit_tracker.set_value(80);
+
+        assert_eq!(mock_messenger.sent_messages.borrow().len(), 1);
+    }
+
+    #[test]
+    fn only_sends_when_the_level_goes_up() {
+        let recorder = Recorder::new();
+        let mut limit_tracker = LimitTracker::new(&recorder, 100);
+
+        for value in [80, 85, 95, 80, 50, 80] {
+            limit_tracker.set_value(value);
+        }
+
+        recorder.assert_called_with(&[
+            "Warning: You've used up over 75% of your quota!",
+            "Urgent warning: You've used up over 90% of your quota!",
+            "Warning: You've used up over 75% of your quota!",
+        ]);
+        assert_eq!(limit_tracker.level(), QuotaLevel::Warning);
+    }
+
+    #[test]
+    fn returns_changes_of_level() {
+        let messenger = |_: &str| {};
+        let mut limit_tracker = LimitTracker::new(&messenger, 10);
+
+        assert_eq!(
+            limit_tracker.check(10).map(|event| event.to),
+            Some(QuotaLevel::Exceeded)
+        );
+        assert_eq!(limit_tracker.level(), QuotaLevel::Ok);
+
+        assert_eq!(limit_tracker.set_value(5), None);
+        assert_eq!(limit_tracker.value(), 5);
+        let rise = limit_tracker.set_value(10).unwrap();
+        assert_eq!(
+            rise,
+            QuotaEvent {
+                from: QuotaLevel::Ok,
+                to: QuotaLevel::Exceeded,
+                value: 10,
+                max: 10
+            }
+        );
+        assert!(rise.is_rise());
+        assert_eq!(limit_tracker.set_value(11), None);
+        assert!(!limit_tracker.set_value(8).unwrap().is_rise());
+    }
+
+    #[test]
+    fn hysteresis_holds_a_level_until_the_value_falls_well_below_it() {
+        let recorder = Recorder::new();
+        let thresholds = Thresholds::new()
+            .with_threshold(0.5, QuotaLevel::Warning, "half")
+            .with_threshold(0.8, QuotaLevel::Urgent, "most")
+            .with_hysteresis(0.1);
+        let mut limit_tracker = LimitTracker::new(&recorder, 100).with_thresholds(thresholds);
+
+        let mut levels = Vec::new();
+        for value in [50, 45, 50, 39, 50, 75, 80] {
+            limit_tracker.set_value(value);
+            levels.push(limit_tracker.level());
+        }
+
+        use QuotaLevel::*;
+        assert_eq!(
+            levels,
+            [Warning, Warning, Warning, Ok, Warning, Warning, Urgent]
+        );
+        recorder.assert_called_with(&["half", "half", "most"]);
+    }
+
+    #[test]
+    fn a_closure_is_a_messenger() {
+        let sent = RefCell::new(Vec::new());
+        let messenger = |msg: &str| sent.borrow_mut().push(msg.to_string());
+        let mut limit_tracker = LimitTracker::new(&messenger, 10);
+
+        limit_tracker.set_value(9);
+        limit_tracker.set_value(10);
+
+        assert_eq!(
+            *sent.borrow(),
+            [
+                "Urgent warning: You've used up over 90% of your quota!",
+                "Error: You are over your quota!"
+            ]
+        );
+    }
+
+    #[test]
+    fn channels_carry_messages_to_another_thread() {
+        let (sender, receiver) = mpsc::channel();
+        let (messenger, from_messenger) = ChannelMessenger::channel();
+
+        thread::spawn(move || {
+            LimitTracker::new(&sender, 100).set_value(75);
+            let dynamic: &dyn Messenger = &messenger;
+            LimitTracker::new(dynamic, 100).set_value(100);
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            ["Warning: You've used up over 75% of your quota!"]
+        );
+        assert_eq!(
+            from_messenger.try_iter().collect::<Vec<_>>(),
+            ["Error: You are over your quota!"]
+        );
+    }
+}
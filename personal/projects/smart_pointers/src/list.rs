@@ -0,0 +1,378 @@
+//! The cons list from the start of the chapter, made generic so it can hold
+//! more than `i32`s, and given iterators so it works with `for` loops,
+//! adapters like `map`, and `collect`, and the methods you'd expect of a
+//! singly linked list.
+
+use std::mem;
+
+/// Build a [`List`] from its values, front first, without nesting
+/// `Cons(.., Box::new(..))` by hand:
+///
+/// ```
+/// use smart_pointers::{cons_list, list::List::{Cons, Nil}};
+///
+/// let list = cons_list![1, 2, 3];
+/// assert_eq!(list, Cons(1, Box::new(Cons(2, Box::new(Cons(3, Box::new(Nil)))))));
+/// ```
+#[macro_export]
+macro_rules! cons_list {
+    () => {
+        $crate::list::List::Nil
+    };
+    ($head:expr $(, $tail:expr)* $(,)?) => {
+        $crate::list::List::Cons($head, Box::new($crate::cons_list!($($tail),*)))
+    };
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum List<T> {
+    Cons(T, Box<List<T>>),
+    #[default]
+    Nil,
+}
+
+use List::{Cons, Nil};
+
+impl<T> List<T> {
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            list: self,
+            remaining: None,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut { list: Some(self) }
+    }
+
+    /// How many values there are. Lists don't keep count, so this walks
+    /// the whole list.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Nil)
+    }
+
+    pub fn contains(&self, value: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        self.iter().any(|v| v == value)
+    }
+
+    pub fn push_front(&mut self, value: T) {
+        let rest = mem::take(self);
+        *self = Cons(value, Box::new(rest));
+    }
+
+    /// Add `other`'s values after this list's.
+    pub fn append(&mut self, other: List<T>) {
+        let mut end = self;
+        while let Cons(_, rest) = end {
+            end = rest;
+        }
+        *end = other;
+    }
+
+    /// Reverse the list in place, reusing its boxes.
+    pub fn reverse(&mut self) {
+        let mut reversed = Nil;
+        let mut list = mem::take(self);
+        while let Cons(value, mut rest) = list {
+            list = mem::replace(&mut *rest, reversed);
+            reversed = Cons(value, rest);
+        }
+        *self = reversed;
+    }
+
+    /// Split the list into its first `at` values and the rest.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the list has fewer than `at` values.
+    pub fn split_at(mut self, at: usize) -> (List<T>, List<T>) {
+        let mut end = &mut self;
+        for _ in 0..at {
+            match end {
+                Cons(_, rest) => end = rest,
+                Nil => panic!("can't split a list at {at}, past its end"),
+            }
+        }
+        let rest = mem::take(end);
+        (self, rest)
+    }
+}
+
+/// Borrows each value in a [`List`], from the front.
+///
+/// Iterating from the back works too, but each step walks forward from the
+/// front of what's left, since the list only links one way.
+pub struct Iter<'a, T> {
+    list: &'a List<T>,
+    /// How many values are left, once iterating from the back has needed
+    /// to count them.
+    remaining: Option<usize>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+        match self.list {
+            Cons(value, rest) => {
+                self.list = rest;
+                if let Some(remaining) = &mut self.remaining {
+                    *remaining -= 1;
+                }
+                Some(value)
+            }
+            Nil => None,
+        }
+    }
+}
+
+impl<T> DoubleEndedIterator for Iter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let remaining = self.remaining.get_or_insert_with(|| {
+            Iter {
+                list: self.list,
+                remaining: None,
+            }
+            .count()
+        });
+        if *remaining == 0 {
+            return None;
+        }
+        *remaining -= 1;
+
+        let mut list = self.list;
+        for _ in 0..*remaining {
+            if let Cons(_, rest) = list {
+                list = rest;
+            }
+        }
+        match list {
+            Cons(value, _) => Some(value),
+            Nil => None,
+        }
+    }
+}
+
+/// Mutably borrows each value in a [`List`], from the front.
+///
+/// Unlike [`Iter`], this can't go from the back: handing out a value from
+/// the back means borrowing through the nodes in front of it, which the
+/// values already handed out from the front are still borrowing.
+pub struct IterMut<'a, T> {
+    list: Option<&'a mut List<T>>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        match self.list.take()? {
+            Cons(value, rest) => {
+                self.list = Some(rest);
+                Some(value)
+            }
+            Nil => None,
+        }
+    }
+}
+
+/// Takes each value out of a [`List`], from the front (or the back).
+pub struct IntoIter<T>(List<T>);
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        match mem::take(&mut self.0) {
+            Cons(value, rest) => {
+                self.0 = *rest;
+                Some(value)
+            }
+            Nil => None,
+        }
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        let len = self.0.iter().count();
+        let mut list = &mut self.0;
+        for _ in 1..len {
+            if let Cons(_, rest) = list {
+                list = rest;
+            }
+        }
+        match mem::take(list) {
+            Cons(value, _) => Some(value),
+            Nil => None,
+        }
+    }
+}
+
+impl<T> FromIterator<T> for List<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> List<T> {
+        let mut list = Nil;
+        // Keep hold of the `Nil` at the end, and replace it with each value.
+        let mut end = &mut list;
+        for value in iter {
+            *end = Cons(value, Box::new(Nil));
+            if let Cons(_, rest) = end {
+                end = rest;
+            }
+        }
+        list
+    }
+}
+
+impl<T> IntoIterator for List<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter(self)
+    }
+}
+
+impl<'a, T> IntoIterator for &'a List<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut List<T> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> IterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn one_two_three() -> List<i32> {
+        cons_list![1, 2, 3]
+    }
+
+    #[test]
+    fn iterates_from_either_end() {
+        let list = one_two_three();
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(list.iter().rev().copied().collect::<Vec<_>>(), [3, 2, 1]);
+
+        // The two ends meet in the middle without handing anything out twice.
+        let mut iter = list.iter();
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next_back(), Some(&3));
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next_back(), None);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn changes_values_in_place() {
+        let mut list = one_two_three();
+        for value in &mut list {
+            *value *= 10;
+        }
+        assert_eq!(list.iter().sum::<i32>(), 60);
+    }
+
+    #[test]
+    fn gives_up_its_values() {
+        let list = cons_list![String::from("a"), String::from("b")];
+        let mut iter = list.clone().into_iter();
+        assert_eq!(iter.next_back().as_deref(), Some("b"));
+        assert_eq!(iter.next_back().as_deref(), Some("a"));
+        assert_eq!(iter.next_back(), None);
+
+        let joined: String = list.into_iter().collect();
+        assert_eq!(joined, "ab");
+    }
+
+    #[test]
+    fn an_empty_list_has_nothing_to_give() {
+        let mut list: List<i32> = cons_list![];
+        assert_eq!(list.iter().next(), None);
+        assert_eq!(list.iter().next_back(), None);
+        assert_eq!(list.iter_mut().next(), None);
+        assert_eq!(list.into_iter().next_back(), None);
+    }
+
+    #[test]
+    fn collects_in_order() {
+        let list: List<i32> = (1..=3).collect();
+        assert_eq!(list, one_two_three());
+        assert_eq!(
+            list.into_iter().rev().collect::<List<_>>(),
+            cons_list![3, 2, 1]
+        );
+
+        assert_eq!(std::iter::empty::<i32>().collect::<List<_>>(), Nil);
+    }
+
+    #[test]
+    fn grows_at_either_end() {
+        let mut list = cons_list![2];
+        list.push_front(1);
+        list.append(cons_list![3, 4]);
+        assert_eq!(list, cons_list![1, 2, 3, 4]);
+        assert_eq!(list.len(), 4);
+
+        let mut empty = cons_list![];
+        empty.append(list);
+        assert_eq!(empty.len(), 4);
+    }
+
+    #[test]
+    fn answers_queries() {
+        let list = one_two_three();
+        assert!(list.contains(&2));
+        assert!(!list.contains(&4));
+        assert!(!list.is_empty());
+        assert!(List::<i32>::Nil.is_empty());
+        assert_eq!(List::<i32>::Nil.len(), 0);
+    }
+
+    #[test]
+    fn reverses_in_place() {
+        let mut list = one_two_three();
+        list.reverse();
+        assert_eq!(list, cons_list![3, 2, 1]);
+
+        let mut empty: List<i32> = cons_list![];
+        empty.reverse();
+        assert_eq!(empty, Nil);
+    }
+
+    #[test]
+    fn splits_anywhere_up_to_the_end() {
+        assert_eq!(
+            one_two_three().split_at(1),
+            (cons_list![1], cons_list![2, 3])
+        );
+        assert_eq!(one_two_three().split_at(0), (Nil, one_two_three()));
+        assert_eq!(one_two_three().split_at(3), (one_two_three(), Nil));
+    }
+
+    #[test]
+    #[should_panic(expected = "past its end")]
+    fn splitting_past_the_end_panics() {
+        one_two_three().split_at(4);
+    }
+}
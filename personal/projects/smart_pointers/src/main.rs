@@ -1613,3 +1613,24 @@
 // 3 1
 
 // The three strong refs are r1, r5, and r6. The one weak ref is r4. r2 is dropped at the end of its scope.
+
+use smart_pointers::{cons_list, my_box::MyBox};
+
+fn hello(name: &str) {
+    println!("Hello, {name}!");
+}
+
+fn main() {
+    let mut m = MyBox::new(String::from("Rust"));
+    hello(&m);
+    m.push_str("acean");
+    hello(&m);
+
+    let list = cons_list![1, 2, 3];
+
+    for value in &list {
+        println!("{value}");
+    }
+    let doubled: Vec<i32> = list.iter().map(|value| value * 2).collect();
+    println!("doubled = {doubled:?}");
+}
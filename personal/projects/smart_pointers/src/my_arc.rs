@@ -0,0 +1,196 @@
+//! `MyArc<T>`: `MyRc` with atomic counts, so clones can live on different
+//! threads.
+//!
+//! The counting is the same. What changes is that two threads can clone or
+//! drop at once, so the count is an `AtomicUsize`, and the orderings have
+//! to make sure the thread that drops the value sees everything the other
+//! threads did with it first.
+
+use std::{
+    fmt,
+    marker::PhantomData,
+    ops::Deref,
+    process,
+    ptr::NonNull,
+    sync::atomic::{self, AtomicUsize, Ordering},
+};
+
+struct Inner<T> {
+    strong: AtomicUsize,
+    value: T,
+}
+
+pub struct MyArc<T> {
+    ptr: NonNull<Inner<T>>,
+    _owns: PhantomData<Inner<T>>,
+}
+
+// SAFETY: sending a MyArc to another thread can give that thread the last
+// reference, which drops the value there (so T: Send), and leaves a &T
+// shared between threads (so T: Sync).
+unsafe impl<T: Send + Sync> Send for MyArc<T> {}
+// SAFETY: a &MyArc can be cloned into a MyArc, so it needs the same.
+unsafe impl<T: Send + Sync> Sync for MyArc<T> {}
+
+/// Counts past this are taken to be clones leaked in a loop, and abort
+/// rather than risk the count wrapping round to zero.
+const MAX_STRONG: usize = isize::MAX as usize;
+
+impl<T> MyArc<T> {
+    pub fn new(value: T) -> MyArc<T> {
+        let inner = Box::new(Inner {
+            strong: AtomicUsize::new(1),
+            value,
+        });
+        MyArc {
+            ptr: NonNull::from(Box::leak(inner)),
+            _owns: PhantomData,
+        }
+    }
+
+    fn inner(&self) -> &Inner<T> {
+        // SAFETY: while there's a MyArc, its allocation hasn't been freed.
+        unsafe { self.ptr.as_ref() }
+    }
+
+    /// How many `MyArc`s share the value. Other threads may change it as
+    /// soon as it's read.
+    pub fn strong_count(this: &MyArc<T>) -> usize {
+        this.inner().strong.load(Ordering::Acquire)
+    }
+
+    /// Whether `this` and `other` share a value.
+    pub fn ptr_eq(this: &MyArc<T>, other: &MyArc<T>) -> bool {
+        this.ptr == other.ptr
+    }
+
+    /// A mutable reference to the value, if no other `MyArc` shares it.
+    pub fn get_mut(this: &mut MyArc<T>) -> Option<&mut T> {
+        // Acquire, to see whatever the clones that were dropped did.
+        if this.inner().strong.load(Ordering::Acquire) != 1 {
+            return None;
+        }
+        // SAFETY: ours is the only MyArc, and `&mut this` means no one
+        // else is borrowing through it.
+        Some(unsafe { &mut this.ptr.as_mut().value })
+    }
+}
+
+impl<T> Clone for MyArc<T> {
+    fn clone(&self) -> MyArc<T> {
+        // Relaxed is enough: we already have a reference, so nobody can be
+        // dropping the value, and a new one doesn't publish anything.
+        let old = self.inner().strong.fetch_add(1, Ordering::Relaxed);
+        if old > MAX_STRONG {
+            process::abort();
+        }
+        MyArc {
+            ptr: self.ptr,
+            _owns: PhantomData,
+        }
+    }
+}
+
+impl<T> Deref for MyArc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner().value
+    }
+}
+
+impl<T> Drop for MyArc<T> {
+    fn drop(&mut self) {
+        // Release, so our uses of the value happen before whichever thread
+        // ends up dropping it...
+        if self.inner().strong.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+        // ...and Acquire, for that thread to see all of them.
+        atomic::fence(Ordering::Acquire);
+
+        // SAFETY: that was the last MyArc, so nothing else can reach the
+        // allocation, and it came from a Box.
+        drop(unsafe { Box::from_raw(self.ptr.as_ptr()) });
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MyArc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicBool, Mutex};
+
+    use thread_pool::ThreadPool;
+
+    use super::*;
+
+    /// Records being dropped, and panics if it happens twice.
+    struct DropOnce<'a>(&'a AtomicBool);
+
+    impl Drop for DropOnce<'_> {
+        fn drop(&mut self) {
+            assert!(!self.0.swap(true, Ordering::SeqCst), "dropped twice");
+        }
+    }
+
+    #[test]
+    fn shares_a_value_across_threads() {
+        static DROPPED: AtomicBool = AtomicBool::new(false);
+        let pool = ThreadPool::new(4);
+        let shared = MyArc::new((Mutex::new(0), DropOnce(&DROPPED)));
+
+        for _ in 0..64 {
+            let shared = MyArc::clone(&shared);
+            pool.execute(move || {
+                for _ in 0..100 {
+                    let clone = MyArc::clone(&shared);
+                    *clone.0.lock().unwrap() += 1;
+                }
+            })
+            .unwrap();
+        }
+        pool.wait_idle();
+
+        assert_eq!(*shared.0.lock().unwrap(), 6400);
+        assert_eq!(MyArc::strong_count(&shared), 1);
+        assert!(!DROPPED.load(Ordering::SeqCst));
+        drop(shared);
+        assert!(DROPPED.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn the_last_clone_can_drop_on_any_thread() {
+        static DROPPED: AtomicBool = AtomicBool::new(false);
+        let pool = ThreadPool::new(4);
+        let shared = MyArc::new(DropOnce(&DROPPED));
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let shared = MyArc::clone(&shared);
+                pool.execute(move || drop(shared)).unwrap()
+            })
+            .collect();
+        drop(shared);
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert!(DROPPED.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn get_mut_needs_the_only_reference() {
+        let mut a = MyArc::new(1);
+        let b = MyArc::clone(&a);
+        assert!(MyArc::get_mut(&mut a).is_none());
+        assert!(MyArc::ptr_eq(&a, &b));
+
+        drop(b);
+        *MyArc::get_mut(&mut a).unwrap() += 1;
+        assert_eq!(*a, 2);
+    }
+}
@@ -0,0 +1,202 @@
+//! `MyBox<T>` from Listings 15-8 and 15-10, to show what `Deref` (and
+//! here `DerefMut`) give a smart pointer.
+//!
+//! The chapter's `MyBox` kept its value inline, which rules out unsized
+//! values: a `MyBox<[u8]>` or `MyBox<dyn Fn(i32) -> i32>` has no size to
+//! keep inline. So this one does what `Box` does and puts its value on the
+//! heap, behind a pointer that can be fat. It takes that allocation from a
+//! `Box`, since only `Box` itself can unsize a value on stable Rust.
+
+use std::{
+    marker::PhantomData,
+    mem,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+};
+
+use crate::raw_ptr::copy_nonoverlapping_checked;
+
+pub struct MyBox<T: ?Sized> {
+    ptr: NonNull<T>,
+    /// We own a `T`, for the drop checker's sake.
+    _owns: PhantomData<T>,
+}
+
+// SAFETY: a MyBox owns its value outright, just like a Box.
+unsafe impl<T: ?Sized + Send> Send for MyBox<T> {}
+// SAFETY: a &MyBox only hands out a &T.
+unsafe impl<T: ?Sized + Sync> Sync for MyBox<T> {}
+
+impl<T> MyBox<T> {
+    pub fn new(x: T) -> MyBox<T> {
+        MyBox::from_box(Box::new(x))
+    }
+}
+
+impl<T: ?Sized> MyBox<T> {
+    /// Take over `boxed`'s allocation. Unsize the `Box` first to get a
+    /// `MyBox` of a slice or trait object:
+    ///
+    /// ```
+    /// use smart_pointers::my_box::MyBox;
+    ///
+    /// let add_one: MyBox<dyn Fn(i32) -> i32> = MyBox::from_box(Box::new(|x| x + 1));
+    /// assert_eq!(add_one(1), 2);
+    /// ```
+    pub fn from_box(boxed: Box<T>) -> MyBox<T> {
+        MyBox {
+            ptr: NonNull::from(Box::leak(boxed)),
+            _owns: PhantomData,
+        }
+    }
+
+    /// Give the allocation back to a `Box`, say to call a `dyn FnOnce`.
+    pub fn into_box(self) -> Box<T> {
+        let ptr = self.ptr.as_ptr();
+        mem::forget(self);
+        // SAFETY: `ptr` came from a Box, and forgetting `self` means it's
+        // ours alone to turn back into one.
+        unsafe { Box::from_raw(ptr) }
+    }
+}
+
+impl<T: ?Sized> Deref for MyBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: `ptr` points to a live value we own, borrowed along with
+        // `self`.
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MyBox<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: as for `deref`, and `&mut self` makes this borrow unique.
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T: ?Sized> Drop for MyBox<T> {
+    fn drop(&mut self) {
+        // SAFETY: `ptr` came from a Box and hasn't been freed; this is the
+        // last use of it.
+        drop(unsafe { Box::from_raw(self.ptr.as_ptr()) });
+    }
+}
+
+impl<T: ?Sized> From<Box<T>> for MyBox<T> {
+    fn from(boxed: Box<T>) -> MyBox<T> {
+        MyBox::from_box(boxed)
+    }
+}
+
+impl<T> From<Vec<T>> for MyBox<[T]> {
+    fn from(vec: Vec<T>) -> MyBox<[T]> {
+        MyBox::from_box(vec.into_boxed_slice())
+    }
+}
+
+/// A copy of `values`, like `Box<[T]>::from(&[T])`. `Copy` values can be
+/// copied as bytes, all at once.
+impl<T: Copy> From<&[T]> for MyBox<[T]> {
+    fn from(values: &[T]) -> MyBox<[T]> {
+        let mut boxed = Box::new_uninit_slice(values.len());
+        // SAFETY: `boxed` is a new allocation of `values.len()` values, so
+        // it can't overlap `values`, and copying all of them initializes it.
+        unsafe {
+            copy_nonoverlapping_checked(values.as_ptr(), boxed.as_mut_ptr().cast(), values.len());
+            MyBox::from_box(boxed.assume_init())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+
+    fn hello(name: &str) -> String {
+        format!("Hello, {name}!")
+    }
+
+    fn exclaim(name: &mut String) {
+        name.push('!');
+    }
+
+    #[test]
+    fn assigns_through_the_box() {
+        let mut y = MyBox::new(5);
+        *y = 6;
+        *y += 1;
+        assert_eq!(*y, 7);
+    }
+
+    #[test]
+    fn coerces_shared_to_shared() {
+        // &MyBox<String> -> &String -> &str
+        let m = MyBox::new(String::from("Rust"));
+        assert_eq!(hello(&m), "Hello, Rust!");
+    }
+
+    #[test]
+    fn coerces_mutable_to_mutable() {
+        // &mut MyBox<String> -> &mut String
+        let mut m = MyBox::new(String::from("Rust"));
+        exclaim(&mut m);
+        m.make_ascii_uppercase();
+        assert_eq!(*m, "RUST!");
+    }
+
+    #[test]
+    fn coerces_mutable_to_shared() {
+        // &mut MyBox<String> -> &String -> &str, but never the other way.
+        let mut m = MyBox::new(String::from("Rust"));
+        let m = &mut m;
+        assert_eq!(hello(m), "Hello, Rust!");
+    }
+
+    #[test]
+    fn holds_trait_objects() {
+        let ops: Vec<MyBox<dyn Fn(i32) -> i32>> = vec![
+            MyBox::from_box(Box::new(|x| x + 1)),
+            MyBox::from_box(Box::new(|x| x * 10)),
+        ];
+        assert_eq!(ops.iter().fold(1, |x, op| op(x)), 20);
+
+        // The pool's `Job` type, which has to go back into a Box to be called.
+        let job: MyBox<dyn FnOnce() -> String + Send> = MyBox::from_box(Box::new(|| "done".into()));
+        assert_eq!(job.into_box()(), "done");
+    }
+
+    #[test]
+    fn holds_slices() {
+        let mut bytes: MyBox<[u8]> = vec![3, 1, 2].into();
+        bytes.sort();
+        assert_eq!(&*bytes, [1, 2, 3]);
+        assert_eq!(bytes.len(), 3);
+    }
+
+    #[test]
+    fn copies_slices() {
+        let original = [1u16, 2, 3];
+        let mut copy = MyBox::from(&original[..]);
+        copy[0] = 10;
+        assert_eq!(&*copy, [10, 2, 3]);
+        assert_eq!(original, [1, 2, 3]);
+        assert_eq!(MyBox::<[()]>::from(&[(); 4][..]).len(), 4);
+        assert!(MyBox::<[u8]>::from(&[][..]).is_empty());
+    }
+
+    #[test]
+    fn drops_its_value_once() {
+        let value = Rc::new(());
+        let boxed = MyBox::new(Rc::clone(&value));
+        assert_eq!(Rc::strong_count(&value), 2);
+
+        let boxed = MyBox::from_box(boxed.into_box());
+        drop(boxed);
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+}
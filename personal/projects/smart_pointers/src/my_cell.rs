@@ -0,0 +1,131 @@
+//! `MyCell<T>`: `Cell` from scratch, the simplest kind of interior
+//! mutability.
+//!
+//! # Why this is sound
+//!
+//! Mutating through a shared reference is only undefined behaviour if the
+//! value is also borrowed somewhere else at the time. `MyCell` makes sure
+//! it never is:
+//!
+//! - It never hands out a reference to its value. [`get`](MyCell::get)
+//!   copies the value out, and [`set`](MyCell::set) and
+//!   [`replace`](MyCell::replace) move values in and out, so nothing
+//!   outside can be pointing at the value when it changes.
+//! - Every access is over before the method returns, and none of them
+//!   call out to code that could reach the same cell in the middle (no
+//!   `Drop` runs inside `replace`, and `get` is limited to `Copy` types,
+//!   whose copies are plain bit copies).
+//! - It isn't `Sync`, so only one thread can be using a `&MyCell` at a
+//!   time, and the accesses above can never overlap. Two threads calling
+//!   `set` at once would be a data race:
+//!
+//! ```compile_fail
+//! use smart_pointers::my_cell::MyCell;
+//!
+//! let cell = MyCell::new(0);
+//! std::thread::scope(|s| {
+//!     s.spawn(|| cell.set(1));
+//!     s.spawn(|| cell.set(2));
+//! });
+//! ```
+//!
+//! The `UnsafeCell` inside is what makes it `!Sync`, and what tells the
+//! compiler the value may change behind a `&MyCell`, so it mustn't assume
+//! otherwise when optimizing.
+
+use std::{cell::UnsafeCell, fmt, mem};
+
+pub struct MyCell<T> {
+    value: UnsafeCell<T>,
+}
+
+impl<T> MyCell<T> {
+    pub fn new(value: T) -> MyCell<T> {
+        MyCell {
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn set(&self, value: T) {
+        // Drop the old value only once we're done with the cell: its Drop
+        // could otherwise reach this cell while we still had it borrowed.
+        drop(self.replace(value));
+    }
+
+    /// Put `value` in the cell, returning the value it replaced.
+    pub fn replace(&self, value: T) -> T {
+        // SAFETY: nothing else can be borrowing the value (see the module
+        // docs), and this borrow ends before we return.
+        mem::replace(unsafe { &mut *self.value.get() }, value)
+    }
+
+    /// A mutable reference to the value, which needs no checks: `&mut self`
+    /// already proves nobody else is using the cell.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Copy> MyCell<T> {
+    pub fn get(&self) -> T {
+        // SAFETY: as in `replace`; copying a Copy value runs no code that
+        // could touch the cell.
+        unsafe { *self.value.get() }
+    }
+}
+
+impl<T: Default> Default for MyCell<T> {
+    fn default() -> MyCell<T> {
+        MyCell::new(T::default())
+    }
+}
+
+impl<T: Copy + fmt::Debug> fmt::Debug for MyCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MyCell")
+            .field("value", &self.get())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn changes_behind_a_shared_reference() {
+        let cell = MyCell::new(1);
+        let (a, b) = (&cell, &cell);
+        a.set(2);
+        assert_eq!(b.get(), 2);
+        assert_eq!(b.replace(3), 2);
+        assert_eq!(cell.into_inner(), 3);
+    }
+
+    #[test]
+    fn holds_values_that_are_not_copy() {
+        let mut cell = MyCell::new(String::from("a"));
+        cell.get_mut().push('b');
+        assert_eq!(cell.replace(String::new()), "ab");
+    }
+
+    #[test]
+    fn can_be_sent_but_not_shared() {
+        // A MyCell can move to another thread; it's only sharing one
+        // between threads that the module docs show won't compile.
+        let cell = MyCell::new(1);
+        let cell = thread::spawn(move || {
+            cell.set(2);
+            cell
+        })
+        .join()
+        .unwrap();
+        assert_eq!(cell.get(), 2);
+    }
+}
@@ -0,0 +1,305 @@
+//! Cells that can be written once, and then only read.
+//!
+//! Once a value is in, it never changes, so handing out `&T` is fine. That
+//! makes these the safe replacement for a `static mut` that's set once at
+//! startup and read everywhere after: [`MyOnceLock`] can be a `static`, and
+//! reading it needs no `unsafe`.
+//!
+//! [`MyOnceCell`] is the single-threaded version. [`MyOnceLock`] tracks its
+//! state in an atomic, so threads can race to set it and exactly one wins.
+
+use std::{
+    cell::UnsafeCell,
+    fmt,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicU8, Ordering},
+    thread,
+};
+
+pub struct MyOnceCell<T> {
+    value: UnsafeCell<Option<T>>,
+}
+
+impl<T> MyOnceCell<T> {
+    pub const fn new() -> MyOnceCell<T> {
+        MyOnceCell {
+            value: UnsafeCell::new(None),
+        }
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        // SAFETY: the only write is in `set`, which never happens once
+        // there's a value to borrow.
+        unsafe { &*self.value.get() }.as_ref()
+    }
+
+    /// Put `value` in the cell, or hand it back if the cell is already full.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        if self.get().is_some() {
+            return Err(value);
+        }
+        // SAFETY: the cell is empty, so there are no borrows of its value,
+        // and we don't call out while we have it borrowed.
+        unsafe { *self.value.get() = Some(value) };
+        Ok(())
+    }
+
+    /// The value, setting it to `init()` first if the cell is empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `init` sets the cell itself.
+    pub fn get_or_init(&self, init: impl FnOnce() -> T) -> &T {
+        if let Some(value) = self.get() {
+            return value;
+        }
+        if self.set(init()).is_err() {
+            panic!("MyOnceCell was set while initializing it");
+        }
+        self.get().unwrap()
+    }
+
+    pub fn into_inner(self) -> Option<T> {
+        self.value.into_inner()
+    }
+}
+
+impl<T> Default for MyOnceCell<T> {
+    fn default() -> MyOnceCell<T> {
+        MyOnceCell::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MyOnceCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MyOnceCell").field(&self.get()).finish()
+    }
+}
+
+const EMPTY: u8 = 0;
+const WRITING: u8 = 1;
+const FULL: u8 = 2;
+
+/// [`MyOnceCell`] for sharing between threads.
+///
+/// ```
+/// use smart_pointers::my_once_cell::MyOnceLock;
+///
+/// static GREETING: MyOnceLock<String> = MyOnceLock::new();
+///
+/// assert!(GREETING.get().is_none());
+/// GREETING.set(String::from("hello")).unwrap();
+/// assert_eq!(GREETING.get().unwrap(), "hello");
+/// ```
+pub struct MyOnceLock<T> {
+    state: AtomicU8,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// SAFETY: the value is only written by the one thread that moved `state`
+// from EMPTY to WRITING, and only read after it's been published as FULL,
+// with Release/Acquire ordering the write before the reads. It's shared
+// between threads (so T: Sync), and may be set on one and dropped on
+// another (so T: Send).
+unsafe impl<T: Send + Sync> Sync for MyOnceLock<T> {}
+
+impl<T> MyOnceLock<T> {
+    pub const fn new() -> MyOnceLock<T> {
+        MyOnceLock {
+            state: AtomicU8::new(EMPTY),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        if self.state.load(Ordering::Acquire) != FULL {
+            return None;
+        }
+        // SAFETY: FULL, so the value was written, and the Acquire load
+        // means we see that write. It's never written again.
+        Some(unsafe { (*self.value.get()).assume_init_ref() })
+    }
+
+    /// Put `value` in the lock, or hand it back if another thread has set it
+    /// (or is setting it).
+    pub fn set(&self, value: T) -> Result<(), T> {
+        if self.claim() {
+            // SAFETY: we won the claim, so we're the only writer.
+            unsafe { self.publish(value) };
+            Ok(())
+        } else {
+            Err(value)
+        }
+    }
+
+    /// The value, setting it to `init()` first if it's empty. If another
+    /// thread is setting it, this waits for that thread instead.
+    ///
+    /// If `init` panics the lock is left empty for the next caller to try
+    /// again. If `init` calls `get_or_init` on the same lock, it never
+    /// returns.
+    pub fn get_or_init(&self, init: impl FnOnce() -> T) -> &T {
+        let mut init = Some(init);
+        loop {
+            if let Some(value) = self.get() {
+                return value;
+            }
+            if self.claim() {
+                // Give the claim back if `init` panics, rather than leave
+                // every other thread waiting for a value that won't come.
+                let unclaim = Unclaim(&self.state);
+                let value = (init.take().unwrap())();
+                std::mem::forget(unclaim);
+                // SAFETY: we won the claim, so we're the only writer.
+                unsafe { self.publish(value) };
+            } else {
+                thread::yield_now();
+            }
+        }
+    }
+
+    pub fn into_inner(mut self) -> Option<T> {
+        self.take()
+    }
+
+    /// Move from EMPTY to WRITING, if nobody else has.
+    fn claim(&self) -> bool {
+        self.state
+            .compare_exchange(EMPTY, WRITING, Ordering::Acquire, Ordering::Acquire)
+            .is_ok()
+    }
+
+    /// # Safety
+    ///
+    /// The caller must hold the claim.
+    unsafe fn publish(&self, value: T) {
+        // SAFETY: the caller is the only writer, and nobody reads until
+        // the store below.
+        unsafe { (*self.value.get()).write(value) };
+        self.state.store(FULL, Ordering::Release);
+    }
+
+    fn take(&mut self) -> Option<T> {
+        if *self.state.get_mut() != FULL {
+            return None;
+        }
+        *self.state.get_mut() = EMPTY;
+        // SAFETY: it was FULL, and now that it's EMPTY it won't be read or
+        // dropped again.
+        Some(unsafe { self.value.get_mut().assume_init_read() })
+    }
+}
+
+struct Unclaim<'a>(&'a AtomicU8);
+
+impl Drop for Unclaim<'_> {
+    fn drop(&mut self) {
+        self.0.store(EMPTY, Ordering::Release);
+    }
+}
+
+impl<T> Drop for MyOnceLock<T> {
+    fn drop(&mut self) {
+        drop(self.take());
+    }
+}
+
+impl<T> Default for MyOnceLock<T> {
+    fn default() -> MyOnceLock<T> {
+        MyOnceLock::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MyOnceLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MyOnceLock").field(&self.get()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        panic::{self, AssertUnwindSafe},
+        rc::Rc,
+        sync::{atomic::AtomicUsize, Arc},
+    };
+
+    use thread_pool::ThreadPool;
+
+    use super::*;
+
+    #[test]
+    fn a_cell_is_set_once() {
+        let cell = MyOnceCell::new();
+        assert_eq!(cell.get(), None);
+        assert_eq!(cell.set(1), Ok(()));
+        assert_eq!(cell.set(2), Err(2));
+        assert_eq!(*cell.get_or_init(|| 3), 1);
+        assert_eq!(format!("{cell:?}"), "MyOnceCell(Some(1))");
+        assert_eq!(cell.into_inner(), Some(1));
+    }
+
+    #[test]
+    fn a_cell_can_be_set_while_its_value_is_borrowed() {
+        let cell = MyOnceCell::new();
+        let first = cell.get_or_init(|| String::from("first"));
+        assert!(cell.set(String::from("second")).is_err());
+        assert_eq!(first, "first");
+    }
+
+    #[test]
+    #[should_panic(expected = "set while initializing")]
+    fn a_cell_panics_if_init_sets_it() {
+        let cell = MyOnceCell::new();
+        cell.get_or_init(|| {
+            cell.set(1).unwrap();
+            2
+        });
+    }
+
+    #[test]
+    fn one_thread_wins_the_race_to_set_a_lock() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        let lock = Arc::new(MyOnceLock::new());
+
+        let pool = ThreadPool::new(4);
+        let handles: Vec<_> = (0..16)
+            .map(|n| {
+                let lock = Arc::clone(&lock);
+                pool.execute(move || {
+                    *lock.get_or_init(|| {
+                        CALLS.fetch_add(1, Ordering::SeqCst);
+                        n
+                    })
+                })
+                .unwrap()
+            })
+            .collect();
+        let seen: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+        assert!(seen.iter().all(|&n| n == seen[0]));
+        assert_eq!(lock.set(100), Err(100));
+    }
+
+    #[test]
+    fn a_lock_is_left_empty_if_init_panics() {
+        let lock = MyOnceLock::new();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            lock.get_or_init(|| panic!("no value"));
+        }));
+        assert!(result.is_err());
+        assert_eq!(lock.get(), None);
+        assert_eq!(*lock.get_or_init(|| 1), 1);
+    }
+
+    #[test]
+    fn a_lock_drops_its_value() {
+        let value = Rc::new(());
+        let lock = MyOnceLock::new();
+        lock.set(Rc::clone(&value)).unwrap();
+        assert_eq!(Rc::strong_count(&value), 2);
+        drop(lock);
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+}
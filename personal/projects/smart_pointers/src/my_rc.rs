@@ -0,0 +1,254 @@
+//! `MyRc<T>` and `MyWeak<T>`: reference counting from scratch, to see what
+//! `Rc::strong_count`, `Rc::downgrade` and `Weak::upgrade` are counting.
+//!
+//! The value and both counts share one allocation. The value is dropped
+//! when the last `MyRc` goes, and the allocation is freed when the last
+//! `MyWeak` does too, so a `MyWeak` can always read the counts to find out
+//! whether the value is still there. As in `Rc`, all the `MyRc`s together
+//! hold one weak reference, which keeps the allocation alive while any of
+//! them are still running their `Drop`.
+
+use std::{cell::Cell, fmt, marker::PhantomData, mem::ManuallyDrop, ops::Deref, ptr::NonNull};
+
+struct Inner<T> {
+    strong: Cell<usize>,
+    /// The `MyWeak`s, plus one for all the `MyRc`s.
+    weak: Cell<usize>,
+    value: ManuallyDrop<T>,
+}
+
+pub struct MyRc<T> {
+    ptr: NonNull<Inner<T>>,
+    _owns: PhantomData<Inner<T>>,
+}
+
+pub struct MyWeak<T> {
+    ptr: NonNull<Inner<T>>,
+}
+
+impl<T> MyRc<T> {
+    pub fn new(value: T) -> MyRc<T> {
+        let inner = Box::new(Inner {
+            strong: Cell::new(1),
+            weak: Cell::new(1),
+            value: ManuallyDrop::new(value),
+        });
+        MyRc {
+            ptr: NonNull::from(Box::leak(inner)),
+            _owns: PhantomData,
+        }
+    }
+
+    fn inner(&self) -> &Inner<T> {
+        // SAFETY: while there's a MyRc, its allocation hasn't been freed.
+        unsafe { self.ptr.as_ref() }
+    }
+
+    pub fn strong_count(this: &MyRc<T>) -> usize {
+        this.inner().strong.get()
+    }
+
+    pub fn weak_count(this: &MyRc<T>) -> usize {
+        this.inner().weak.get() - 1
+    }
+
+    pub fn downgrade(this: &MyRc<T>) -> MyWeak<T> {
+        let weak = &this.inner().weak;
+        weak.set(weak.get() + 1);
+        MyWeak { ptr: this.ptr }
+    }
+
+    /// Whether `this` and `other` share a value.
+    pub fn ptr_eq(this: &MyRc<T>, other: &MyRc<T>) -> bool {
+        this.ptr == other.ptr
+    }
+}
+
+impl<T> MyWeak<T> {
+    fn inner(&self) -> &Inner<T> {
+        // SAFETY: while there's a MyWeak, its allocation hasn't been freed.
+        unsafe { self.ptr.as_ref() }
+    }
+
+    /// A `MyRc` for the value, unless it has already been dropped.
+    pub fn upgrade(&self) -> Option<MyRc<T>> {
+        let strong = &self.inner().strong;
+        if strong.get() == 0 {
+            return None;
+        }
+        strong.set(strong.get() + 1);
+        Some(MyRc {
+            ptr: self.ptr,
+            _owns: PhantomData,
+        })
+    }
+
+    pub fn strong_count(&self) -> usize {
+        self.inner().strong.get()
+    }
+}
+
+/// Drop a weak reference, freeing the allocation if it was the last.
+///
+/// # Safety
+///
+/// `ptr` must be live, and the caller must be giving up a weak reference.
+unsafe fn release_weak<T>(ptr: NonNull<Inner<T>>) {
+    // SAFETY: the caller promises `ptr` is live.
+    let weak = unsafe { &ptr.as_ref().weak };
+    weak.set(weak.get() - 1);
+    if weak.get() == 0 {
+        // SAFETY: it came from a Box, and no references to it are left.
+        // The value is in a ManuallyDrop, so this only frees the memory.
+        drop(unsafe { Box::from_raw(ptr.as_ptr()) });
+    }
+}
+
+impl<T> Clone for MyRc<T> {
+    fn clone(&self) -> MyRc<T> {
+        let strong = &self.inner().strong;
+        strong.set(strong.get() + 1);
+        MyRc {
+            ptr: self.ptr,
+            _owns: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for MyWeak<T> {
+    fn clone(&self) -> MyWeak<T> {
+        let weak = &self.inner().weak;
+        weak.set(weak.get() + 1);
+        MyWeak { ptr: self.ptr }
+    }
+}
+
+impl<T> Deref for MyRc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner().value
+    }
+}
+
+impl<T> Drop for MyRc<T> {
+    fn drop(&mut self) {
+        let strong = &self.inner().strong;
+        strong.set(strong.get() - 1);
+        if strong.get() > 0 {
+            return;
+        }
+
+        // SAFETY: this was the last MyRc, so nothing can reach the value
+        // any more; MyWeaks check `strong` first and find it zero.
+        unsafe { ManuallyDrop::drop(&mut (*self.ptr.as_ptr()).value) };
+        // SAFETY: the weak reference the MyRcs shared goes with the last.
+        unsafe { release_weak(self.ptr) };
+    }
+}
+
+impl<T> Drop for MyWeak<T> {
+    fn drop(&mut self) {
+        // SAFETY: this MyWeak kept `ptr` alive, and is giving it up.
+        unsafe { release_weak(self.ptr) };
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MyRc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T> fmt::Debug for MyWeak<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(MyWeak)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    /// Counts how many times it has been dropped.
+    struct Node {
+        next: RefCell<Option<MyRc<Node>>>,
+        parent: RefCell<Option<MyWeak<Node>>>,
+        drops: MyRc<Cell<usize>>,
+    }
+
+    impl Node {
+        fn new(drops: &MyRc<Cell<usize>>) -> MyRc<Node> {
+            MyRc::new(Node {
+                next: RefCell::new(None),
+                parent: RefCell::new(None),
+                drops: MyRc::clone(drops),
+            })
+        }
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            self.drops.set(self.drops.get() + 1);
+        }
+    }
+
+    #[test]
+    fn counts_strong_and_weak_references() {
+        let a = MyRc::new(5);
+        let b = MyRc::clone(&a);
+        let weak = MyRc::downgrade(&a);
+        assert_eq!((MyRc::strong_count(&a), MyRc::weak_count(&a)), (2, 1));
+        assert!(MyRc::ptr_eq(&a, &b));
+
+        drop(a);
+        assert_eq!(*weak.upgrade().unwrap(), 5);
+        drop(b);
+        assert!(weak.upgrade().is_none());
+        assert_eq!(weak.strong_count(), 0);
+    }
+
+    #[test]
+    fn a_strong_cycle_leaks() {
+        // Listing 15-26: a points to b and b points back to a.
+        let drops = MyRc::new(Cell::new(0));
+        let a = Node::new(&drops);
+        let b = Node::new(&drops);
+        *b.next.borrow_mut() = Some(MyRc::clone(&a));
+        *a.next.borrow_mut() = Some(MyRc::clone(&b));
+
+        let still_there = MyRc::downgrade(&a);
+        drop(a);
+        drop(b);
+        assert_eq!(drops.get(), 0);
+
+        // Nothing outside the cycle owns it, yet it's still there. Breaking
+        // it by hand lets both nodes go.
+        let a = still_there.upgrade().unwrap();
+        a.next.borrow_mut().take();
+        drop(a);
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[test]
+    fn a_weak_back_pointer_does_not() {
+        // Listing 15-28: the child only has a weak pointer to its parent.
+        let drops = MyRc::new(Cell::new(0));
+        let parent = Node::new(&drops);
+        let child = Node::new(&drops);
+        *parent.next.borrow_mut() = Some(MyRc::clone(&child));
+        *child.parent.borrow_mut() = Some(MyRc::downgrade(&parent));
+
+        let found = child.parent.borrow().as_ref().unwrap().upgrade().unwrap();
+        assert!(MyRc::ptr_eq(&found, &parent));
+        drop(found);
+
+        drop(parent);
+        assert_eq!(drops.get(), 1);
+        assert!(child.parent.borrow().as_ref().unwrap().upgrade().is_none());
+        drop(child);
+        assert_eq!(drops.get(), 2);
+    }
+}
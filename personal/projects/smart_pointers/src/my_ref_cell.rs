@@ -0,0 +1,340 @@
+//! `MyRefCell<T>`: `RefCell` from scratch, with the borrow checking moved
+//! to runtime.
+//!
+//! The cell keeps a count of the borrows it has handed out: how many
+//! shared ones, or that there's one mutable one. Borrowing checks the count
+//! first, and the [`MyRef`] and [`MyRefMut`] guards give their borrow back
+//! when they're dropped, so the rules the compiler enforces for `&` and
+//! `&mut` hold at runtime instead.
+//!
+//! Breaking them is what Listing 15-23 does on purpose, borrowing the mock
+//! messenger's messages mutably twice, and `RefCell` panics. `MyRefCell`
+//! can also say so without panicking: [`try_borrow_mut`] returns an error,
+//! and [`stats`] counts the conflicts, so a test can assert on them.
+//!
+//! [`try_borrow_mut`]: MyRefCell::try_borrow_mut
+//! [`stats`]: MyRefCell::stats
+
+use std::{
+    cell::{Cell, UnsafeCell},
+    error::Error,
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+/// The count in a cell that's borrowed mutably.
+const WRITING: isize = -1;
+
+pub struct MyRefCell<T> {
+    /// 0 when unborrowed, the number of shared borrows when positive, and
+    /// `WRITING` while borrowed mutably.
+    borrow: Cell<isize>,
+    conflicts: Cell<usize>,
+    value: UnsafeCell<T>,
+}
+
+/// A snapshot of a [`MyRefCell`]'s borrows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BorrowStats {
+    /// Shared borrows that haven't been given back yet.
+    pub shared: usize,
+    /// Mutable borrows that haven't been given back yet: 0 or 1.
+    pub exclusive: usize,
+    /// Borrows refused so far, whether they panicked or returned an error.
+    pub conflicts: usize,
+}
+
+/// Why [`MyRefCell::try_borrow`] failed: the value is borrowed mutably.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BorrowError;
+
+/// Why [`MyRefCell::try_borrow_mut`] failed: the value is borrowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BorrowMutError;
+
+impl<T> MyRefCell<T> {
+    pub fn new(value: T) -> MyRefCell<T> {
+        MyRefCell {
+            borrow: Cell::new(0),
+            conflicts: Cell::new(0),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Borrow the value, panicking if it's borrowed mutably.
+    #[track_caller]
+    pub fn borrow(&self) -> MyRef<'_, T> {
+        match self.try_borrow() {
+            Ok(value) => value,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    /// Borrow the value mutably, panicking if it's borrowed at all.
+    #[track_caller]
+    pub fn borrow_mut(&self) -> MyRefMut<'_, T> {
+        match self.try_borrow_mut() {
+            Ok(value) => value,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    pub fn try_borrow(&self) -> Result<MyRef<'_, T>, BorrowError> {
+        let borrow = self.borrow.get();
+        if borrow == WRITING {
+            self.conflicts.set(self.conflicts.get() + 1);
+            return Err(BorrowError);
+        }
+        assert!(borrow < isize::MAX, "too many shared borrows");
+        self.borrow.set(borrow + 1);
+        Ok(MyRef { cell: self })
+    }
+
+    pub fn try_borrow_mut(&self) -> Result<MyRefMut<'_, T>, BorrowMutError> {
+        if self.borrow.get() != 0 {
+            self.conflicts.set(self.conflicts.get() + 1);
+            return Err(BorrowMutError);
+        }
+        self.borrow.set(WRITING);
+        Ok(MyRefMut { cell: self })
+    }
+
+    pub fn stats(&self) -> BorrowStats {
+        let borrow = self.borrow.get();
+        BorrowStats {
+            shared: borrow.max(0) as usize,
+            exclusive: usize::from(borrow == WRITING),
+            conflicts: self.conflicts.get(),
+        }
+    }
+
+    /// A mutable reference to the value, which needs no checks: `&mut self`
+    /// already proves there are no guards left.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Default> Default for MyRefCell<T> {
+    fn default() -> MyRefCell<T> {
+        MyRefCell::new(T::default())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MyRefCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("MyRefCell");
+        // Printing isn't a borrow the caller asked for, so it mustn't count
+        // as a conflict. It still holds a real shared borrow, since the
+        // value's own `Debug` could reach the cell and try to borrow it
+        // mutably.
+        match self.borrow.get() {
+            WRITING => d.field("value", &format_args!("<borrowed>")),
+            borrow => {
+                self.borrow.set(borrow + 1);
+                let value = MyRef { cell: self };
+                d.field("value", &*value)
+            }
+        };
+        d.finish()
+    }
+}
+
+/// A shared borrow of a [`MyRefCell`]'s value.
+pub struct MyRef<'b, T> {
+    cell: &'b MyRefCell<T>,
+}
+
+impl<T> Deref for MyRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: this guard's count keeps `try_borrow_mut` from handing out
+        // a mutable borrow while it's alive.
+        unsafe { &*self.cell.value.get() }
+    }
+}
+
+impl<T> Drop for MyRef<'_, T> {
+    fn drop(&mut self) {
+        self.cell.borrow.set(self.cell.borrow.get() - 1);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MyRef<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// A mutable borrow of a [`MyRefCell`]'s value.
+pub struct MyRefMut<'b, T> {
+    cell: &'b MyRefCell<T>,
+}
+
+impl<T> Deref for MyRefMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: while this guard is alive the count is `WRITING`, so no
+        // other borrow exists; this one is tied to `&self`.
+        unsafe { &*self.cell.value.get() }
+    }
+}
+
+impl<T> DerefMut for MyRefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: as in `deref`, and tied to `&mut self`, so the guard can't
+        // hand out two at once.
+        unsafe { &mut *self.cell.value.get() }
+    }
+}
+
+impl<T> Drop for MyRefMut<'_, T> {
+    fn drop(&mut self) {
+        self.cell.borrow.set(0);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MyRefMut<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl fmt::Display for BorrowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "already mutably borrowed")
+    }
+}
+
+impl Error for BorrowError {}
+
+impl fmt::Display for BorrowMutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "already borrowed")
+    }
+}
+
+impl Error for BorrowMutError {}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::{Rc, Weak};
+
+    use super::*;
+    use crate::limit_tracker::{LimitTracker, Messenger};
+
+    #[test]
+    fn shares_or_lends_out_one_mutable_borrow() {
+        let cell = MyRefCell::new(vec![1]);
+        {
+            let (a, b) = (cell.borrow(), cell.borrow());
+            assert_eq!((a.len(), b.len()), (1, 1));
+            assert_eq!(
+                cell.stats(),
+                BorrowStats {
+                    shared: 2,
+                    ..BorrowStats::default()
+                }
+            );
+            assert_eq!(cell.try_borrow_mut().unwrap_err(), BorrowMutError);
+        }
+
+        cell.borrow_mut().push(2);
+        let mut writer = cell.borrow_mut();
+        writer.push(3);
+        assert_eq!(cell.try_borrow().unwrap_err(), BorrowError);
+        assert_eq!(
+            cell.stats(),
+            BorrowStats {
+                shared: 0,
+                exclusive: 1,
+                conflicts: 2
+            }
+        );
+        drop(writer);
+        assert_eq!(cell.into_inner(), [1, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "already borrowed")]
+    fn panics_on_a_second_mutable_borrow() {
+        let cell = MyRefCell::new(0);
+        let _one = cell.borrow_mut();
+        let _two = cell.borrow_mut();
+    }
+
+    /// Listing 15-23's messenger, which borrows its messages mutably twice,
+    /// but asks first instead of panicking.
+    struct MockMessenger {
+        sent_messages: MyRefCell<Vec<String>>,
+    }
+
+    impl Messenger for MockMessenger {
+        fn send(&self, message: &str) {
+            let mut one_borrow = self.sent_messages.borrow_mut();
+            if let Ok(mut two_borrow) = self.sent_messages.try_borrow_mut() {
+                two_borrow.push(String::from(message));
+            }
+            one_borrow.push(String::from(message));
+        }
+    }
+
+    #[test]
+    fn the_mock_messengers_double_borrow_is_a_conflict() {
+        let mock_messenger = MockMessenger {
+            sent_messages: MyRefCell::new(Vec::new()),
+        };
+        let mut limit_tracker = LimitTracker::new(&mock_messenger, 100);
+        limit_tracker.set_value(80);
+
+        let stats = mock_messenger.sent_messages.stats();
+        assert_eq!(stats.conflicts, 1);
+        assert_eq!((stats.shared, stats.exclusive), (0, 0));
+        assert_eq!(mock_messenger.sent_messages.borrow().len(), 1);
+    }
+
+    #[test]
+    fn debug_is_not_a_conflict() {
+        let cell = MyRefCell::new(5);
+        let writer = cell.borrow_mut();
+        assert_eq!(format!("{cell:?}"), "MyRefCell { value: <borrowed> }");
+        drop(writer);
+        assert_eq!(format!("{cell:?}"), "MyRefCell { value: 5 }");
+        assert_eq!(cell.stats(), BorrowStats::default());
+    }
+
+    /// A value whose `Debug` tries to borrow the cell it's in mutably.
+    struct Probe {
+        cell: Weak<MyRefCell<Probe>>,
+        borrowed_mut: Cell<Option<bool>>,
+    }
+
+    impl fmt::Debug for Probe {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let cell = self.cell.upgrade().unwrap();
+            let borrowed_mut = cell.try_borrow_mut().is_ok();
+            self.borrowed_mut.set(Some(borrowed_mut));
+            f.write_str("Probe")
+        }
+    }
+
+    #[test]
+    fn debug_holds_a_shared_borrow_while_printing() {
+        let cell = Rc::new_cyclic(|cell| {
+            MyRefCell::new(Probe {
+                cell: Weak::clone(cell),
+                borrowed_mut: Cell::new(None),
+            })
+        });
+
+        assert_eq!(format!("{cell:?}"), "MyRefCell { value: Probe }");
+        assert_eq!(cell.borrow().borrowed_mut.get(), Some(false));
+        assert_eq!(cell.stats().shared, 0);
+    }
+}
@@ -0,0 +1,190 @@
+//! Raw pointer operations that check their own safety conditions in debug
+//! builds.
+//!
+//! `ptr.add`, `ptr::copy_nonoverlapping` and `slice::from_raw_parts` each
+//! come with a list of things the caller must make sure of, and getting
+//! one wrong is undefined behavior that may well look like it works. These
+//! wrappers take the same promises, but `debug_assert!` the ones they can
+//! see: that an offset stays inside the values it's counted over, that a
+//! pointer isn't null or misaligned, and that a copy's source and
+//! destination don't overlap. A mistake then panics in tests with a
+//! message saying which promise was broken. Release builds check nothing,
+//! so they cost what the operations they wrap do.
+
+use std::{mem, ptr, slice};
+
+/// `start.add(count)`, where `start` points at the first of `len` values.
+///
+/// Debug builds panic if `count > len`. `count == len` is allowed: it's
+/// the pointer one past the end, which is fine to make but not to read.
+///
+/// # Safety
+///
+/// As for [`pointer::add`]: `start` must point to the first of `len`
+/// values of `T` in one allocation.
+#[track_caller]
+pub unsafe fn offset_checked<T>(start: *mut T, len: usize, count: usize) -> *mut T {
+    debug_assert!(
+        count <= len,
+        "offset {count} is past the end of {len} values"
+    );
+    // SAFETY: the caller guarantees `start` is the first of `len` values,
+    // and `count <= len`, so the result is in bounds or one past the end.
+    unsafe { start.add(count) }
+}
+
+/// Copy `count` values from `src` to `dst`, as
+/// [`ptr::copy_nonoverlapping`].
+///
+/// Debug builds panic if either pointer is null or misaligned, or if the
+/// two runs of `count` values overlap.
+///
+/// # Safety
+///
+/// As for [`ptr::copy_nonoverlapping`]: `src` must be valid for reading
+/// and `dst` for writing `count` values, both aligned, and not overlapping.
+#[track_caller]
+pub unsafe fn copy_nonoverlapping_checked<T>(src: *const T, dst: *mut T, count: usize) {
+    debug_assert_valid(src, "source");
+    debug_assert_valid(dst, "destination");
+    debug_assert!(
+        !overlaps(src, dst, count),
+        "copying {count} values from {src:p} to {dst:p}, which overlap"
+    );
+    // SAFETY: the caller guarantees the rest.
+    unsafe { ptr::copy_nonoverlapping(src, dst, count) }
+}
+
+/// A shared slice of the `len` values at `data`, as
+/// [`slice::from_raw_parts`].
+///
+/// Debug builds panic if `data` is null or misaligned, or if `len` values
+/// would be more than `isize::MAX` bytes.
+///
+/// # Safety
+///
+/// As for [`slice::from_raw_parts`]: `data` must point to `len`
+/// initialized values of `T` in one allocation, which nothing changes for
+/// as long as `'a`.
+#[track_caller]
+pub unsafe fn slice_from_parts_checked<'a, T>(data: *const T, len: usize) -> &'a [T] {
+    debug_assert_valid(data, "slice");
+    debug_assert_fits::<T>(len);
+    // SAFETY: the caller guarantees the rest.
+    unsafe { slice::from_raw_parts(data, len) }
+}
+
+/// A mutable slice of the `len` values at `data`, as
+/// [`slice::from_raw_parts_mut`], checked as [`slice_from_parts_checked`].
+///
+/// # Safety
+///
+/// As for [`slice::from_raw_parts_mut`]: as for
+/// [`slice_from_parts_checked`], and nothing else may use the values at all
+/// for as long as `'a`.
+#[track_caller]
+pub unsafe fn slice_from_parts_mut_checked<'a, T>(data: *mut T, len: usize) -> &'a mut [T] {
+    debug_assert_valid(data, "slice");
+    debug_assert_fits::<T>(len);
+    // SAFETY: the caller guarantees the rest.
+    unsafe { slice::from_raw_parts_mut(data, len) }
+}
+
+#[track_caller]
+fn debug_assert_valid<T>(ptr: *const T, what: &str) {
+    debug_assert!(!ptr.is_null(), "{what} pointer is null");
+    debug_assert!(
+        ptr.is_aligned(),
+        "{what} pointer {ptr:p} isn't aligned to {} bytes",
+        mem::align_of::<T>()
+    );
+}
+
+#[track_caller]
+fn debug_assert_fits<T>(len: usize) {
+    debug_assert!(
+        len.checked_mul(mem::size_of::<T>())
+            .is_some_and(|bytes| bytes <= isize::MAX as usize),
+        "{len} values are too many bytes for one slice"
+    );
+}
+
+/// Whether `count` values from `a` and from `b` share any bytes. Zero-sized
+/// runs never do.
+fn overlaps<T>(a: *const T, b: *const T, count: usize) -> bool {
+    let bytes = mem::size_of::<T>().saturating_mul(count);
+    let (a, b) = (a as usize, b as usize);
+    bytes != 0 && a.abs_diff(b) < bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn does_what_the_unchecked_versions_do() {
+        let mut values = [1u32, 2, 3, 4, 5];
+        let start = values.as_mut_ptr();
+        let mut copy = [0u32; 3];
+        // SAFETY: every pointer is inside `values` or `copy`, and the two
+        // don't overlap.
+        unsafe {
+            let end = offset_checked(start, 5, 5);
+            assert_eq!(end.offset_from(start), 5);
+            copy_nonoverlapping_checked(offset_checked(start, 5, 2), copy.as_mut_ptr(), 3);
+            assert_eq!(slice_from_parts_checked(copy.as_ptr(), 3), [3, 4, 5]);
+            slice_from_parts_mut_checked(start, 2).fill(0);
+        }
+        assert_eq!(values, [0, 0, 3, 4, 5]);
+    }
+
+    #[test]
+    fn overlap_is_by_bytes() {
+        let values = [0u16; 4];
+        let p = values.as_ptr();
+        let q = p.wrapping_add(2);
+        assert!(!overlaps(p, q, 2));
+        assert!(overlaps(p, q, 3));
+        assert!(overlaps(q, p, 3));
+        assert!(!overlaps(p, p, 0));
+        assert!(!overlaps([(); 2].as_ptr(), [(); 2].as_ptr(), 2));
+    }
+
+    #[cfg(debug_assertions)]
+    mod debug {
+        use super::*;
+
+        #[test]
+        #[should_panic(expected = "offset 4 is past the end of 3 values")]
+        fn offsets_stay_in_bounds() {
+            let mut values = [0u8; 3];
+            // SAFETY: never reached; the check panics first.
+            unsafe { offset_checked(values.as_mut_ptr(), 3, 4) };
+        }
+
+        #[test]
+        #[should_panic(expected = "which overlap")]
+        fn copies_do_not_overlap() {
+            let mut values = [0u8; 4];
+            let p = values.as_mut_ptr();
+            // SAFETY: never reached; the check panics first.
+            unsafe { copy_nonoverlapping_checked(p, p.add(1), 2) };
+        }
+
+        #[test]
+        #[should_panic(expected = "slice pointer is null")]
+        fn slices_are_not_null() {
+            // SAFETY: never reached; the check panics first.
+            unsafe { slice_from_parts_checked::<u8>(ptr::null(), 0) };
+        }
+
+        #[test]
+        #[should_panic(expected = "isn't aligned to 4 bytes")]
+        fn slices_are_aligned() {
+            let values = [0u32; 2];
+            let misaligned = values.as_ptr().cast::<u8>().wrapping_add(1).cast::<u32>();
+            // SAFETY: never reached; the check panics first.
+            unsafe { slice_from_parts_checked(misaligned, 1) };
+        }
+    }
+}
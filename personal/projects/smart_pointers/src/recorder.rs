@@ -0,0 +1,205 @@
+//! Test doubles that remember every call made to them, generalizing
+//! Listing 15-22's `MockMessenger`.
+//!
+//! A [`Recorder`] keeps its calls in a `RefCell`, so it can record through
+//! the `&self` that traits like [`Messenger`] give it. A [`SyncRecorder`]
+//! keeps them behind a `Mutex` instead, for callbacks that run on other
+//! threads, like a thread pool's hooks.
+
+use std::{
+    cell::{Ref, RefCell},
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::limit_tracker::Messenger;
+
+#[derive(Default)]
+pub struct Recorder<T> {
+    calls: RefCell<Vec<T>>,
+}
+
+impl<T> Recorder<T> {
+    pub fn new() -> Recorder<T> {
+        Recorder {
+            calls: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn record(&self, call: T) {
+        self.calls.borrow_mut().push(call);
+    }
+
+    /// Every call so far, oldest first.
+    pub fn calls(&self) -> Ref<'_, [T]> {
+        Ref::map(self.calls.borrow(), Vec::as_slice)
+    }
+
+    /// Every call so far, leaving the recorder empty.
+    pub fn take(&self) -> Vec<T> {
+        self.calls.take()
+    }
+
+    /// Panic unless the calls so far are exactly `expected`.
+    #[track_caller]
+    pub fn assert_called_with<U>(&self, expected: &[U])
+    where
+        T: PartialEq<U> + fmt::Debug,
+        U: fmt::Debug,
+    {
+        assert_calls(&self.calls(), expected);
+    }
+}
+
+impl Messenger for Recorder<String> {
+    fn send(&self, msg: &str) {
+        self.record(msg.to_string());
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Recorder<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Recorder").field(&&*self.calls()).finish()
+    }
+}
+
+/// [`Recorder`] for calls made from any thread.
+///
+/// ```
+/// use std::sync::Arc;
+/// use smart_pointers::recorder::SyncRecorder;
+/// use thread_pool::ThreadPool;
+///
+/// let started = Arc::new(SyncRecorder::new());
+/// let pool = ThreadPool::builder()
+///     .core_threads(1)
+///     .on_job_start(started.sink())
+///     .build();
+/// pool.execute(|| ()).unwrap().join().unwrap();
+/// drop(pool);
+///
+/// started.assert_called_with(&[0]);
+/// ```
+#[derive(Default)]
+pub struct SyncRecorder<T> {
+    calls: Mutex<Vec<T>>,
+}
+
+impl<T> SyncRecorder<T> {
+    pub fn new() -> SyncRecorder<T> {
+        SyncRecorder {
+            calls: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn record(&self, call: T) {
+        self.lock().push(call);
+    }
+
+    /// A closure that records each value it's called with, for handing to
+    /// APIs that take a callback.
+    pub fn sink(self: &Arc<Self>) -> impl Fn(T) + Send + Sync + 'static
+    where
+        T: Send + 'static,
+    {
+        let recorder = Arc::clone(self);
+        move |call| recorder.record(call)
+    }
+
+    /// Every call so far, oldest first. Other threads can't record any more
+    /// until the guard is dropped.
+    pub fn calls(&self) -> MutexGuard<'_, Vec<T>> {
+        self.lock()
+    }
+
+    pub fn take(&self) -> Vec<T> {
+        std::mem::take(&mut self.lock())
+    }
+
+    #[track_caller]
+    pub fn assert_called_with<U>(&self, expected: &[U])
+    where
+        T: PartialEq<U> + fmt::Debug,
+        U: fmt::Debug,
+    {
+        assert_calls(&self.lock(), expected);
+    }
+
+    /// The calls, even if a thread panicked while recording one: a push
+    /// can't leave the Vec half-changed.
+    fn lock(&self) -> MutexGuard<'_, Vec<T>> {
+        self.calls.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl Messenger for SyncRecorder<String> {
+    fn send(&self, msg: &str) {
+        self.record(msg.to_string());
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SyncRecorder<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SyncRecorder").field(&*self.lock()).finish()
+    }
+}
+
+#[track_caller]
+fn assert_calls<T, U>(calls: &[T], expected: &[U])
+where
+    T: PartialEq<U> + fmt::Debug,
+    U: fmt::Debug,
+{
+    assert!(
+        calls == expected,
+        "expected calls {expected:?}, but got {calls:?}"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use thread_pool::ThreadPool;
+
+    use super::*;
+
+    #[test]
+    fn records_calls_through_a_shared_reference() {
+        let recorder = Recorder::new();
+        let messenger: &dyn Messenger = &recorder;
+        messenger.send("one");
+        messenger.send("two");
+
+        recorder.assert_called_with(&["one", "two"]);
+        assert_eq!(recorder.calls().len(), 2);
+        assert_eq!(recorder.take(), ["one", "two"]);
+        assert!(recorder.calls().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "expected calls [2], but got [1]")]
+    fn says_what_it_got_when_an_assertion_fails() {
+        let recorder = Recorder::new();
+        recorder.record(1);
+        recorder.assert_called_with(&[2]);
+    }
+
+    #[test]
+    fn records_hooks_on_a_pool() {
+        let ended = Arc::new(SyncRecorder::new());
+        let sink = ended.sink();
+        let pool = ThreadPool::builder()
+            .core_threads(2)
+            .on_job_end(move |worker, _| sink(worker))
+            .build();
+
+        let handles: Vec<_> = (0..10).map(|_| pool.execute(|| ()).unwrap()).collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        drop(pool);
+
+        let workers = ended.take();
+        assert_eq!(workers.len(), 10);
+        assert!(workers.iter().all(|&worker| worker < 2));
+    }
+}
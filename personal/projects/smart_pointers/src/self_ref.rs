@@ -0,0 +1,119 @@
+//! A value that borrows from itself, which the borrow checker can't express
+//! with references, built with a raw pointer and kept sound with `Pin`.
+//!
+//! A [`SelfRefBuffer`] owns some text and a view of part of it, like a
+//! parser's output pointing back into its input. The view is a raw pointer
+//! into the text, which stays valid for as long as the text isn't changed
+//! or dropped. Two things make sure it isn't:
+//!
+//! - Nothing in the API changes the text.
+//! - The buffer is `!Unpin` (through `PhantomPinned`) and only ever handed
+//!   out as a `Pin<Box<SelfRefBuffer>>`, so safe code can never get a
+//!   `&mut SelfRefBuffer` to swap or replace the text behind the pointer's
+//!   back:
+//!
+//! ```compile_fail
+//! use smart_pointers::self_ref::SelfRefBuffer;
+//!
+//! let mut a = SelfRefBuffer::new("a", |text| text);
+//! let mut b = SelfRefBuffer::new("b", |text| text);
+//! std::mem::swap(&mut *a, &mut *b);
+//! ```
+//!
+//! Moving the `Pin<Box<_>>` itself is fine: that moves the pointer to the
+//! buffer, not the buffer.
+
+use std::{fmt, marker::PhantomPinned, pin::Pin};
+
+pub struct SelfRefBuffer {
+    text: String,
+    /// Points into `text`.
+    parsed: *const str,
+    _pinned: PhantomPinned,
+}
+
+impl SelfRefBuffer {
+    /// Own `text`, and keep the part of it that `parse` picks out.
+    ///
+    /// ```
+    /// use smart_pointers::self_ref::SelfRefBuffer;
+    ///
+    /// let request = SelfRefBuffer::new("GET /index.html HTTP/1.1", |line| {
+    ///     line.split(' ').nth(1).unwrap_or("")
+    /// });
+    /// assert_eq!(request.parsed(), "/index.html");
+    /// ```
+    pub fn new(
+        text: impl Into<String>,
+        parse: impl FnOnce(&str) -> &str,
+    ) -> Pin<Box<SelfRefBuffer>> {
+        let mut buffer = Box::pin(SelfRefBuffer {
+            text: text.into(),
+            parsed: "",
+            _pinned: PhantomPinned,
+        });
+        let parsed: *const str = parse(&buffer.text);
+        // SAFETY: setting a field doesn't move the buffer.
+        unsafe { buffer.as_mut().get_unchecked_mut().parsed = parsed };
+        buffer
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn parsed(&self) -> &str {
+        // SAFETY: `parse` was given `&self.text` and had to return a &str
+        // that lives as long as that borrow: part of the text, or a
+        // 'static str. The text hasn't changed since (see the module docs),
+        // and lives as long as `self`.
+        unsafe { &*self.parsed }
+    }
+}
+
+impl fmt::Debug for SelfRefBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SelfRefBuffer")
+            .field("text", &self.text())
+            .field("parsed", &self.parsed())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn first_word(text: &str) -> &str {
+        text.split_whitespace().next().unwrap_or("")
+    }
+
+    #[test]
+    fn the_view_points_into_the_text() {
+        let buffer = SelfRefBuffer::new("  hello world", first_word);
+        assert_eq!(buffer.text(), "  hello world");
+        assert_eq!(buffer.parsed(), "hello");
+        assert_eq!(buffer.parsed().as_ptr(), buffer.text()[2..].as_ptr());
+    }
+
+    #[test]
+    fn survives_moving_the_box() {
+        let buffers: Vec<_> = ["one two", "three four"]
+            .into_iter()
+            .map(|text| SelfRefBuffer::new(text, first_word))
+            .collect();
+        let moved: Vec<_> = buffers.into_iter().rev().collect();
+        assert_eq!(moved[0].parsed(), "three");
+        assert_eq!(moved[1].parsed(), "one");
+    }
+
+    #[test]
+    fn parse_can_return_something_else_that_lives_long_enough() {
+        let buffer = SelfRefBuffer::new("ignored", |_| "static");
+        assert_eq!(buffer.parsed(), "static");
+        assert_eq!(
+            format!("{buffer:?}"),
+            "SelfRefBuffer { text: \"ignored\", parsed: \"static\" }"
+        );
+    }
+}
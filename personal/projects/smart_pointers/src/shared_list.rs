@@ -0,0 +1,320 @@
+//! The doubly linked list again, with `Arc<Mutex<Node>>` where it had
+//! `Rc<RefCell<Node>>`, so that it can be shared between threads.
+//!
+//! `Rc` and `RefCell` are the single-threaded halves of the pattern: their
+//! counts and borrow flags aren't atomic, so the compiler won't let a
+//! [`DoublyLinkedList`] near another thread:
+//!
+//! ```compile_fail
+//! use smart_pointers::doubly_linked_list::DoublyLinkedList;
+//!
+//! let list: DoublyLinkedList<i32> = DoublyLinkedList::new();
+//! std::thread::spawn(move || drop(list));
+//! ```
+//!
+//! `Arc` and `Mutex` are the thread-safe halves. A [`SharedList`] is a
+//! handle: clones of it share one list, and every method takes `&self`,
+//! locking the list for as long as it needs it. The other difference is
+//! that a `Ref` into a `RefCell` can be handed out, but a guard for part of
+//! a `Mutex` can't be on stable Rust, so [`front`](SharedList::front) and
+//! [`back`](SharedList::back) return clones.
+//!
+//! [`DoublyLinkedList`]: crate::doubly_linked_list::DoublyLinkedList
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, Weak},
+};
+
+type Link<T> = Option<Arc<Mutex<Node<T>>>>;
+
+struct Node<T> {
+    value: T,
+    next: Link<T>,
+    prev: Option<Weak<Mutex<Node<T>>>>,
+}
+
+impl<T> Node<T> {
+    fn new(value: T) -> Arc<Mutex<Node<T>>> {
+        Arc::new(Mutex::new(Node {
+            value,
+            next: None,
+            prev: None,
+        }))
+    }
+
+    /// Take the value out of a node that's been unlinked.
+    fn into_value(node: Arc<Mutex<Node<T>>>) -> T {
+        match Arc::try_unwrap(node) {
+            Ok(node) => node.into_inner().unwrap().value,
+            Err(_) => unreachable!("an unlinked node has no other owners"),
+        }
+    }
+}
+
+/// The list itself. Nodes are only changed with this locked, so their own
+/// locks are never contended, and never held two at a time.
+struct Ends<T> {
+    head: Link<T>,
+    tail: Link<T>,
+    len: usize,
+}
+
+impl<T> Drop for Ends<T> {
+    fn drop(&mut self) {
+        // As in DoublyLinkedList: dropping the head would recurse once per
+        // node.
+        let mut next = self.head.take();
+        while let Some(node) = next {
+            next = node.lock().unwrap().next.take();
+        }
+    }
+}
+
+pub struct SharedList<T> {
+    ends: Arc<Mutex<Ends<T>>>,
+}
+
+impl<T> SharedList<T> {
+    pub fn new() -> SharedList<T> {
+        SharedList {
+            ends: Arc::new(Mutex::new(Ends {
+                head: None,
+                tail: None,
+                len: 0,
+            })),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn front(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        let ends = self.lock();
+        let value = ends.head.as_ref()?.lock().unwrap().value.clone();
+        Some(value)
+    }
+
+    pub fn back(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        let ends = self.lock();
+        let value = ends.tail.as_ref()?.lock().unwrap().value.clone();
+        Some(value)
+    }
+
+    pub fn push_front(&self, value: T) {
+        let mut ends = self.lock();
+        let node = Node::new(value);
+        match ends.head.take() {
+            Some(old) => {
+                old.lock().unwrap().prev = Some(Arc::downgrade(&node));
+                node.lock().unwrap().next = Some(old);
+            }
+            None => ends.tail = Some(Arc::clone(&node)),
+        }
+        ends.head = Some(node);
+        ends.len += 1;
+    }
+
+    pub fn push_back(&self, value: T) {
+        let mut ends = self.lock();
+        let node = Node::new(value);
+        match ends.tail.take() {
+            Some(old) => {
+                node.lock().unwrap().prev = Some(Arc::downgrade(&old));
+                old.lock().unwrap().next = Some(Arc::clone(&node));
+            }
+            None => ends.head = Some(Arc::clone(&node)),
+        }
+        ends.tail = Some(node);
+        ends.len += 1;
+    }
+
+    pub fn pop_front(&self) -> Option<T> {
+        let mut ends = self.lock();
+        let old = ends.head.take()?;
+        let next = old.lock().unwrap().next.take();
+        match next {
+            Some(next) => {
+                next.lock().unwrap().prev = None;
+                ends.head = Some(next);
+            }
+            None => ends.tail = None,
+        }
+        ends.len -= 1;
+        Some(Node::into_value(old))
+    }
+
+    pub fn pop_back(&self) -> Option<T> {
+        let mut ends = self.lock();
+        let old = ends.tail.take()?;
+        let prev = old
+            .lock()
+            .unwrap()
+            .prev
+            .take()
+            .and_then(|prev| prev.upgrade());
+        match prev {
+            Some(prev) => {
+                prev.lock().unwrap().next = None;
+                ends.tail = Some(prev);
+            }
+            None => ends.head = None,
+        }
+        ends.len -= 1;
+        Some(Node::into_value(old))
+    }
+
+    /// Clones of the values, front to back, all taken under one lock so no
+    /// other thread's changes are half-seen.
+    pub fn to_vec(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let ends = self.lock();
+        let mut values = Vec::with_capacity(ends.len);
+        let mut next = ends.head.clone();
+        while let Some(node) = next {
+            let node = node.lock().unwrap();
+            values.push(node.value.clone());
+            next = node.next.clone();
+        }
+        values
+    }
+
+    /// Whether `other` is a handle to the same list.
+    pub fn ptr_eq(&self, other: &SharedList<T>) -> bool {
+        Arc::ptr_eq(&self.ends, &other.ends)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Ends<T>> {
+        self.ends.lock().unwrap()
+    }
+}
+
+/// Another handle to the same list, not a copy of it.
+impl<T> Clone for SharedList<T> {
+    fn clone(&self) -> SharedList<T> {
+        SharedList {
+            ends: Arc::clone(&self.ends),
+        }
+    }
+}
+
+impl<T> Default for SharedList<T> {
+    fn default() -> SharedList<T> {
+        SharedList::new()
+    }
+}
+
+impl<T> FromIterator<T> for SharedList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> SharedList<T> {
+        let list = SharedList::new();
+        for value in iter {
+            list.push_back(value);
+        }
+        list
+    }
+}
+
+impl<T: Clone + fmt::Debug> fmt::Debug for SharedList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.to_vec()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use thread_pool::ThreadPool;
+
+    use super::*;
+
+    #[test]
+    fn works_like_the_single_threaded_list() {
+        let list: SharedList<_> = (1..=3).collect();
+        list.push_front(0);
+        list.push_back(4);
+        assert_eq!(list.to_vec(), [0, 1, 2, 3, 4]);
+        assert_eq!((list.front(), list.back()), (Some(0), Some(4)));
+
+        assert_eq!(list.pop_front(), Some(0));
+        assert_eq!(list.pop_back(), Some(4));
+        assert_eq!(list.len(), 3);
+        assert_eq!(format!("{list:?}"), "[1, 2, 3]");
+    }
+
+    #[test]
+    fn clones_share_the_list() {
+        let list = SharedList::new();
+        let other = list.clone();
+        other.push_back("shared");
+        assert!(list.ptr_eq(&other));
+        assert_eq!(list.pop_front(), Some("shared"));
+        assert!(other.is_empty());
+    }
+
+    #[test]
+    fn survives_many_workers_at_once() {
+        const WORKERS: usize = 8;
+        const PER_WORKER: usize = 1000;
+
+        let pool = ThreadPool::new(4);
+        let list = SharedList::new();
+        let handles: Vec<_> = (0..WORKERS)
+            .map(|worker| {
+                let list = list.clone();
+                pool.execute(move || {
+                    for n in 0..PER_WORKER {
+                        let value = worker * PER_WORKER + n;
+                        if n % 2 == 0 {
+                            list.push_front(value);
+                        } else {
+                            list.push_back(value);
+                        }
+                        // Pop one of every three, from either end.
+                        if n % 3 == 0 {
+                            let popped = if worker % 2 == 0 {
+                                list.pop_front()
+                            } else {
+                                list.pop_back()
+                            };
+                            assert!(popped.is_some());
+                        }
+                    }
+                })
+                .unwrap()
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let popped_per_worker = PER_WORKER.div_ceil(3);
+        let left = list.to_vec();
+        assert_eq!(left.len(), WORKERS * (PER_WORKER - popped_per_worker));
+        assert_eq!(list.len(), left.len());
+
+        // Every value is still there at most once, and the links agree in
+        // both directions.
+        let mut sorted = left.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), left.len());
+        let mut backwards = Vec::new();
+        while let Some(value) = list.pop_back() {
+            backwards.push(value);
+        }
+        backwards.reverse();
+        assert_eq!(backwards, left);
+    }
+}
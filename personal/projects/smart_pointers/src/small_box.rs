@@ -0,0 +1,206 @@
+//! `SmallBox<T, N>`: a box that keeps values of up to `N` bytes inside
+//! itself, and only allocates for bigger ones.
+//!
+//! Whether a `T` goes inline is decided by its size and alignment alone,
+//! so it's the same for every `SmallBox<T, N>` and costs nothing to check.
+//! Inline values move when the box moves, as if it were a plain `T`; the
+//! box only promises that `&*b` is a `&T`, not that the `T` stays put.
+
+use std::{
+    fmt,
+    marker::PhantomData,
+    mem::{self, ManuallyDrop, MaybeUninit},
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+};
+
+pub struct SmallBox<T, const N: usize> {
+    storage: Storage<N>,
+    _owns: PhantomData<T>,
+}
+
+/// `N` bytes, aligned like a pointer, or a pointer to the heap.
+#[repr(C)]
+union Storage<const N: usize> {
+    inline: [MaybeUninit<u8>; N],
+    heap: NonNull<()>,
+    _align: [usize; 0],
+}
+
+impl<T, const N: usize> SmallBox<T, N> {
+    /// Whether `T`s are stored inline: they fit in `N` bytes, and need no
+    /// stricter alignment than the buffer has.
+    pub const INLINE: bool =
+        mem::size_of::<T>() <= N && mem::align_of::<T>() <= mem::align_of::<Storage<N>>();
+
+    pub fn new(value: T) -> SmallBox<T, N> {
+        let storage = if Self::INLINE {
+            let mut storage = Storage {
+                inline: [MaybeUninit::uninit(); N],
+            };
+            // SAFETY: INLINE says the buffer is big enough and aligned.
+            unsafe { ptr::write(ptr::addr_of_mut!(storage.inline).cast::<T>(), value) };
+            storage
+        } else {
+            Storage {
+                heap: NonNull::from(Box::leak(Box::new(value))).cast(),
+            }
+        };
+        SmallBox {
+            storage,
+            _owns: PhantomData,
+        }
+    }
+
+    pub fn is_inline(&self) -> bool {
+        Self::INLINE
+    }
+
+    pub fn into_inner(self) -> T {
+        let mut this = ManuallyDrop::new(self);
+        let value = this.as_mut_ptr();
+        if Self::INLINE {
+            // SAFETY: the value is initialized, and `this` won't drop it.
+            unsafe { ptr::read(value) }
+        } else {
+            // SAFETY: the pointer came from `Box::leak`, and `this` won't
+            // free it.
+            *unsafe { Box::from_raw(value) }
+        }
+    }
+
+    fn as_ptr(&self) -> *const T {
+        if Self::INLINE {
+            ptr::addr_of!(self.storage.inline).cast()
+        } else {
+            // SAFETY: not INLINE, so `new` stored the heap pointer.
+            unsafe { self.storage.heap }.cast().as_ptr()
+        }
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut T {
+        if Self::INLINE {
+            ptr::addr_of_mut!(self.storage.inline).cast()
+        } else {
+            // SAFETY: as in `as_ptr`.
+            unsafe { self.storage.heap }.cast().as_ptr()
+        }
+    }
+}
+
+impl<T, const N: usize> Deref for SmallBox<T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the box always holds an initialized T, wherever it is.
+        unsafe { &*self.as_ptr() }
+    }
+}
+
+impl<T, const N: usize> DerefMut for SmallBox<T, N> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: as in `deref`, and `&mut self` makes it unique.
+        unsafe { &mut *self.as_mut_ptr() }
+    }
+}
+
+impl<T, const N: usize> Drop for SmallBox<T, N> {
+    fn drop(&mut self) {
+        let value = self.as_mut_ptr();
+        if Self::INLINE {
+            // SAFETY: initialized, and never used again.
+            unsafe { ptr::drop_in_place(value) };
+        } else {
+            // SAFETY: the pointer came from `Box::leak`, and is never used
+            // again.
+            drop(unsafe { Box::from_raw(value) });
+        }
+    }
+}
+
+// SAFETY: a SmallBox owns its T like a Box does, so it can cross threads
+// whenever the T can.
+unsafe impl<T: Send, const N: usize> Send for SmallBox<T, N> {}
+// SAFETY: a &SmallBox only gives out a &T, wherever the T is stored, so
+// sharing it across threads is sharing a &T, which is fine when T is Sync.
+unsafe impl<T: Sync, const N: usize> Sync for SmallBox<T, N> {}
+
+impl<T: Clone, const N: usize> Clone for SmallBox<T, N> {
+    fn clone(&self) -> SmallBox<T, N> {
+        SmallBox::new((**self).clone())
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for SmallBox<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[test]
+    fn keeps_small_values_inline() {
+        let mut small = SmallBox::<u64, 16>::new(1);
+        assert!(small.is_inline());
+        *small += 1;
+        assert_eq!(*small, 2);
+        assert_eq!(mem::size_of_val(&small), 16);
+
+        let big = SmallBox::<[u64; 4], 16>::new([1, 2, 3, 4]);
+        assert!(!big.is_inline());
+        assert_eq!(big.iter().sum::<u64>(), 10);
+        assert_eq!(mem::size_of_val(&big), 16);
+    }
+
+    #[test]
+    fn sends_over_aligned_values_to_the_heap() {
+        #[repr(align(64))]
+        struct Aligned(u8);
+
+        let aligned = SmallBox::<Aligned, 128>::new(Aligned(7));
+        assert!(!aligned.is_inline());
+        assert_eq!(aligned.0, 7);
+        assert_eq!(&*aligned as *const Aligned as usize % 64, 0);
+    }
+
+    #[test]
+    fn survives_being_moved() {
+        let boxes: Vec<_> = (0..10)
+            .map(|n| SmallBox::<String, 32>::new(n.to_string()))
+            .collect();
+        let moved: Vec<_> = boxes.into_iter().rev().collect();
+        assert_eq!(*moved[0], "9");
+        assert_eq!(moved[9].clone().into_inner(), "0");
+    }
+
+    #[test]
+    fn drops_its_value_once_either_way() {
+        let value = Rc::new(());
+        let inline = SmallBox::<_, 8>::new(Rc::clone(&value));
+        let heap = SmallBox::<_, 0>::new(Rc::clone(&value));
+        assert!(inline.is_inline() && !heap.is_inline());
+        assert_eq!(Rc::strong_count(&value), 3);
+
+        drop(inline);
+        let taken = heap.into_inner();
+        assert_eq!(Rc::strong_count(&value), 2);
+        drop(taken);
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn holds_a_job_for_another_thread() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let job = SmallBox::<_, 32>::new(move || sender.send(42).unwrap());
+        assert!(job.is_inline());
+        std::thread::spawn(move || (job.into_inner())())
+            .join()
+            .unwrap();
+        assert_eq!(receiver.recv(), Ok(42));
+    }
+}
@@ -0,0 +1,225 @@
+//! The tree from Listings 15-28 and 15-29, made generic and reusable.
+//!
+//! Parents own their children through `Rc`, and children point back at
+//! their parent through `Weak`, so a child can find its parent without
+//! keeping it alive. Dropping the [`Tree`] drops the root, and with it
+//! every node nobody else is holding on to.
+
+use std::{
+    cell::{Ref, RefCell},
+    collections::VecDeque,
+    fmt,
+    rc::{Rc, Weak},
+};
+
+use crate::cycles::Traverse;
+
+pub struct Node<T> {
+    value: T,
+    parent: RefCell<Weak<Node<T>>>,
+    children: RefCell<Vec<Rc<Node<T>>>>,
+}
+
+impl<T> Node<T> {
+    fn new(value: T) -> Rc<Node<T>> {
+        Rc::new(Node {
+            value,
+            parent: RefCell::new(Weak::new()),
+            children: RefCell::new(vec![]),
+        })
+    }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// The node's parent, unless it's the root or its parent has been
+    /// dropped.
+    pub fn parent(&self) -> Option<Rc<Node<T>>> {
+        self.parent.borrow().upgrade()
+    }
+
+    pub fn children(&self) -> Ref<'_, [Rc<Node<T>>]> {
+        Ref::map(self.children.borrow(), Vec::as_slice)
+    }
+
+    /// Add a child holding `value`, returning it so it can have children
+    /// of its own.
+    pub fn add_child(self: &Rc<Self>, value: T) -> Rc<Node<T>> {
+        let child = Node::new(value);
+        *child.parent.borrow_mut() = Rc::downgrade(self);
+        self.children.borrow_mut().push(Rc::clone(&child));
+        child
+    }
+
+    /// How many parents there are above this node.
+    pub fn depth(&self) -> usize {
+        let mut depth = 0;
+        let mut node = self.parent();
+        while let Some(parent) = node {
+            depth += 1;
+            node = parent.parent();
+        }
+        depth
+    }
+
+    /// This node and everything below it, each node before its children.
+    pub fn depth_first(self: &Rc<Self>) -> DepthFirst<T> {
+        DepthFirst {
+            stack: vec![Rc::clone(self)],
+        }
+    }
+
+    /// This node and everything below it, a level at a time.
+    pub fn breadth_first(self: &Rc<Self>) -> BreadthFirst<T> {
+        BreadthFirst {
+            queue: VecDeque::from([Rc::clone(self)]),
+        }
+    }
+}
+
+impl<T> Traverse for Node<T> {
+    fn strong_children(&self) -> Vec<Rc<Node<T>>> {
+        self.children().to_vec()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Node<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Like Listing 15-28's derived Debug, which doesn't follow `parent`.
+        f.debug_struct("Node")
+            .field("value", &self.value)
+            .field("children", &&*self.children())
+            .finish()
+    }
+}
+
+pub struct Tree<T> {
+    root: Rc<Node<T>>,
+}
+
+impl<T> Tree<T> {
+    pub fn new(value: T) -> Tree<T> {
+        Tree {
+            root: Node::new(value),
+        }
+    }
+
+    pub fn root(&self) -> &Rc<Node<T>> {
+        &self.root
+    }
+
+    pub fn depth_first(&self) -> DepthFirst<T> {
+        self.root.depth_first()
+    }
+
+    pub fn breadth_first(&self) -> BreadthFirst<T> {
+        self.root.breadth_first()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Tree<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Tree").field(&self.root).finish()
+    }
+}
+
+/// A pre-order walk of a subtree. See [`Node::depth_first`].
+pub struct DepthFirst<T> {
+    stack: Vec<Rc<Node<T>>>,
+}
+
+impl<T> Iterator for DepthFirst<T> {
+    type Item = Rc<Node<T>>;
+
+    fn next(&mut self) -> Option<Rc<Node<T>>> {
+        let node = self.stack.pop()?;
+        // Pushed last to first, so the first child comes off next.
+        self.stack.extend(node.children().iter().rev().cloned());
+        Some(node)
+    }
+}
+
+/// A level-order walk of a subtree. See [`Node::breadth_first`].
+pub struct BreadthFirst<T> {
+    queue: VecDeque<Rc<Node<T>>>,
+}
+
+impl<T> Iterator for BreadthFirst<T> {
+    type Item = Rc<Node<T>>;
+
+    fn next(&mut self) -> Option<Rc<Node<T>>> {
+        let node = self.queue.pop_front()?;
+        self.queue.extend(node.children().iter().cloned());
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 1
+    /// ├── 2
+    /// │   ├── 4
+    /// │   └── 5
+    /// └── 3
+    ///     └── 6
+    fn tree() -> Tree<i32> {
+        let tree = Tree::new(1);
+        let two = tree.root().add_child(2);
+        let three = tree.root().add_child(3);
+        two.add_child(4);
+        two.add_child(5);
+        three.add_child(6);
+        tree
+    }
+
+    fn values(nodes: impl Iterator<Item = Rc<Node<i32>>>) -> Vec<i32> {
+        nodes.map(|node| *node.value()).collect()
+    }
+
+    #[test]
+    fn walks_depth_first_and_breadth_first() {
+        let tree = tree();
+        assert_eq!(values(tree.depth_first()), [1, 2, 4, 5, 3, 6]);
+        assert_eq!(values(tree.breadth_first()), [1, 2, 3, 4, 5, 6]);
+
+        let three = Rc::clone(&tree.root().children()[1]);
+        assert_eq!(values(three.depth_first()), [3, 6]);
+    }
+
+    #[test]
+    fn children_find_their_parents() {
+        let tree = tree();
+        let five = tree.depth_first().find(|node| *node.value() == 5).unwrap();
+        assert_eq!(*five.parent().unwrap().value(), 2);
+        assert_eq!(five.depth(), 2);
+        assert!(tree.root().parent().is_none());
+    }
+
+    #[test]
+    fn a_child_does_not_keep_its_parent_alive() {
+        // Listing 15-29, with the leaf outliving its branch.
+        let leaf = {
+            let branch = Tree::new(5);
+            let leaf = branch.root().add_child(3);
+            assert_eq!((Rc::strong_count(&leaf), Rc::weak_count(&leaf)), (2, 0));
+            assert_eq!(Rc::weak_count(branch.root()), 1);
+            leaf
+        };
+
+        assert!(leaf.parent().is_none());
+        assert_eq!(Rc::strong_count(&leaf), 1);
+    }
+
+    #[test]
+    fn prints_without_following_parents() {
+        let tree = Tree::new(5);
+        tree.root().add_child(3);
+        assert_eq!(
+            format!("{tree:?}"),
+            "Tree(Node { value: 5, children: [Node { value: 3, children: [] }] })"
+        );
+    }
+}
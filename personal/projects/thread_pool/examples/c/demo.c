@@ -0,0 +1,49 @@
+/* Square some numbers on a Rust thread pool, from C.
+ *
+ * From the thread_pool directory:
+ *
+ *     cargo build --release
+ *     cc examples/c/demo.c -Iinclude -Ltarget/release -lthread_pool -o demo
+ *     LD_LIBRARY_PATH=target/release ./demo
+ *
+ * (DYLD_LIBRARY_PATH on macOS.)
+ */
+
+#include <stdio.h>
+
+#include "thread_pool.h"
+
+struct square {
+    long input;
+    long output;
+};
+
+static void compute(void *context) {
+    struct square *square = context;
+    square->output = square->input * square->input;
+}
+
+int main(void) {
+    struct square squares[8];
+    threadpool_t *pool = threadpool_new(4);
+    if (pool == NULL) {
+        fprintf(stderr, "couldn't start the pool\n");
+        return 1;
+    }
+
+    for (int i = 0; i < 8; i++) {
+        squares[i].input = i + 1;
+        if (threadpool_execute(pool, compute, &squares[i]) != THREADPOOL_OK) {
+            fprintf(stderr, "couldn't queue job %d\n", i);
+            threadpool_free(pool);
+            return 1;
+        }
+    }
+    threadpool_wait(pool);
+
+    for (int i = 0; i < 8; i++) {
+        printf("%ld squared is %ld\n", squares[i].input, squares[i].output);
+    }
+    threadpool_free(pool);
+    return 0;
+}
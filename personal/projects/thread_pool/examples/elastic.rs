@@ -0,0 +1,39 @@
+//! A pool that grows past its core size while a burst of slow jobs is
+//! queued, then shrinks back once they're done.
+//!
+//! Run with `cargo run --example elastic`.
+
+use std::{thread, time::Duration};
+
+use thread_pool::{Priority, ThreadPool};
+
+fn main() {
+    let pool = ThreadPool::builder()
+        .core_threads(1)
+        .max_threads(4)
+        .idle_timeout(Duration::from_millis(200))
+        .on_job_end(|worker, elapsed| eprintln!("worker {worker} took {elapsed:?}"))
+        .build();
+
+    let handles: Vec<_> = (0..4)
+        .map(|i| {
+            pool.execute(move || {
+                thread::sleep(Duration::from_millis(100));
+                i
+            })
+            .unwrap()
+        })
+        .collect();
+    let urgent = pool
+        .execute_with_priority(Priority::High, || "urgent")
+        .unwrap();
+    eprintln!("{} threads while busy", pool.current_size());
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    eprintln!("{}", urgent.join().unwrap());
+
+    thread::sleep(Duration::from_millis(400));
+    eprintln!("{} threads once idle", pool.current_size());
+}
@@ -0,0 +1,41 @@
+//! Count words in a few texts in parallel, with scoped jobs borrowing the
+//! texts and the tally straight from `main`'s stack.
+//!
+//! Run with `cargo run --example word_count`.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use thread_pool::ThreadPool;
+
+fn main() {
+    let texts = [
+        "the quick brown fox jumps over the lazy dog",
+        "the dog barks and the fox runs",
+        "a lazy afternoon for a lazy dog",
+    ];
+    let counts = Mutex::new(HashMap::new());
+
+    let pool = ThreadPool::new(3);
+    pool.scope(|s| {
+        for text in &texts {
+            let counts = &counts;
+            s.execute(move || {
+                let mut local = HashMap::new();
+                for word in text.split_whitespace() {
+                    *local.entry(word).or_insert(0) += 1;
+                }
+
+                let mut counts = counts.lock().unwrap();
+                for (word, n) in local {
+                    *counts.entry(word).or_insert(0) += n;
+                }
+            });
+        }
+    });
+
+    let mut counts: Vec<_> = counts.into_inner().unwrap().into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    for (word, n) in counts.iter().take(5) {
+        println!("{word}: {n}");
+    }
+}
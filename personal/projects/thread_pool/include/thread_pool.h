@@ -0,0 +1,32 @@
+/* The C API of the thread_pool crate; see src/ffi.rs. */
+
+#ifndef THREAD_POOL_H
+#define THREAD_POOL_H
+
+#include <stddef.h>
+
+typedef struct ThreadPoolHandle threadpool_t;
+
+/* A job: called once, on a worker, with the context it was queued with. */
+typedef void (*threadpool_job)(void *context);
+
+#define THREADPOOL_OK 0
+#define THREADPOOL_INVALID (-1)
+#define THREADPOOL_SHUTDOWN (-2)
+#define THREADPOOL_FULL (-3)
+#define THREADPOOL_NO_WATCHDOG (-4)
+
+/* A pool of `threads` workers, or NULL if `threads` is 0. */
+threadpool_t *threadpool_new(size_t threads);
+
+/* Queue `job(context)`. `context` must stay valid until the job has run.
+ * Returns THREADPOOL_OK, or another THREADPOOL_* code if it wasn't queued. */
+int threadpool_execute(const threadpool_t *pool, threadpool_job job, void *context);
+
+/* Block until every queued job has run. */
+void threadpool_wait(const threadpool_t *pool);
+
+/* Run the jobs still queued, stop the workers and free the pool. */
+void threadpool_free(threadpool_t *pool);
+
+#endif
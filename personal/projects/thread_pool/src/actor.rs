@@ -0,0 +1,350 @@
+//! Actors: state that's only ever touched by the messages sent to it.
+//!
+//! An actor doesn't own a thread. Sending it a message puts the message in
+//! its mailbox and, if it isn't scheduled already, queues a job on the pool
+//! that works through the mailbox. So any number of actors can share a few
+//! workers, while each one still handles its messages one at a time and in
+//! the order they were sent.
+//!
+//! The job has to run for the messages to be handled, so it waits for room
+//! in a full queue whatever the pool's [`RejectionPolicy`] says, and if
+//! another submission evicts it anyway, the next message schedules another.
+
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::{ExecuteError, Priority, RejectionPolicy, ThreadPool};
+
+/// How many messages an actor handles before giving its worker back, so a
+/// busy actor can't hog it while other actors wait.
+const BATCH: usize = 32;
+
+/// Something that reacts to messages, one at a time.
+pub trait Actor: Send + 'static {
+    type Message: Send + 'static;
+
+    fn handle(&mut self, message: Self::Message);
+}
+
+/// Runs actors on a [`ThreadPool`].
+#[derive(Clone)]
+pub struct ActorSystem {
+    pool: Arc<ThreadPool>,
+}
+
+impl ActorSystem {
+    pub fn new(pool: Arc<ThreadPool>) -> ActorSystem {
+        ActorSystem { pool }
+    }
+
+    /// Start `actor`, returning the address to send it messages at.
+    pub fn spawn<A: Actor>(&self, actor: A) -> Addr<A> {
+        Addr {
+            cell: Arc::new(Cell {
+                actor: Mutex::new(actor),
+                mailbox: Mutex::new(VecDeque::new()),
+                next_ticket: AtomicU64::new(0),
+                scheduled: AtomicBool::new(false),
+                stopped: AtomicBool::new(false),
+                pool: Arc::clone(&self.pool),
+            }),
+        }
+    }
+}
+
+/// Where to send an actor its messages. Cloning it gives another address
+/// for the same actor.
+pub struct Addr<A: Actor> {
+    cell: Arc<Cell<A>>,
+}
+
+struct Cell<A: Actor> {
+    actor: Mutex<A>,
+    /// The messages, each with a ticket its sender can find it by again.
+    mailbox: Mutex<VecDeque<(u64, A::Message)>>,
+    next_ticket: AtomicU64,
+    /// Whether a job to work through the mailbox is queued or running.
+    scheduled: AtomicBool,
+    /// Set once the actor has panicked; it gets no more messages.
+    stopped: AtomicBool,
+    pool: Arc<ThreadPool>,
+}
+
+/// A message that couldn't be delivered, handed back to the sender.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendError<M>(pub M);
+
+impl<A: Actor> Addr<A> {
+    /// Put `message` in the actor's mailbox.
+    ///
+    /// Fails if the actor has stopped because a message made it panic, or
+    /// if the pool is shutting down.
+    pub fn send(&self, message: A::Message) -> Result<(), SendError<A::Message>> {
+        let cell = &self.cell;
+        let ticket = cell.next_ticket.fetch_add(1, Ordering::Relaxed);
+        {
+            // `run` stops the actor and empties its mailbox under this lock,
+            // so checking under it too means nothing lands in the mailbox
+            // once it's been emptied for good.
+            let mut mailbox = cell.mailbox.lock().unwrap();
+            if cell.stopped.load(Ordering::SeqCst) || cell.pool.is_shutdown() {
+                return Err(SendError(message));
+            }
+            mailbox.push_back((ticket, message));
+        }
+        if Cell::schedule(cell, RejectionPolicy::Block).is_ok() {
+            return Ok(());
+        }
+
+        // The pool shut down first, so nothing will handle the message.
+        let mut mailbox = cell.mailbox.lock().unwrap();
+        match mailbox.iter().position(|&(queued, _)| queued == ticket) {
+            Some(at) => Err(SendError(mailbox.remove(at).unwrap().1)),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether the actor has stopped because a message made it panic.
+    pub fn is_stopped(&self) -> bool {
+        self.cell.stopped.load(Ordering::SeqCst)
+    }
+}
+
+impl<A: Actor> Cell<A> {
+    /// Queue a job to work through the mailbox, unless one is queued or
+    /// running already. If the pool has just started shutting down, the
+    /// mailbox's messages are lost.
+    fn schedule(cell: &Arc<Cell<A>>, policy: RejectionPolicy) -> Result<(), ExecuteError> {
+        if cell.scheduled.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let mut job = MailboxJob(Some(Arc::clone(cell)));
+        let job = Box::new(move || {
+            if let Some(cell) = job.0.take() {
+                Cell::run(&cell);
+            }
+        });
+        cell.pool.enqueue_with(Priority::Normal, None, job, policy)
+    }
+
+    fn run(cell: &Arc<Cell<A>>) {
+        loop {
+            let mut actor = cell.actor.lock().unwrap();
+            for _ in 0..BATCH {
+                let Some((_, message)) = cell.mailbox.lock().unwrap().pop_front() else {
+                    break;
+                };
+                if panic::catch_unwind(AssertUnwindSafe(|| actor.handle(message))).is_err() {
+                    let mut mailbox = cell.mailbox.lock().unwrap();
+                    cell.stopped.store(true, Ordering::SeqCst);
+                    mailbox.clear();
+                    return;
+                }
+            }
+            drop(actor);
+
+            // Messages sent while `scheduled` was still set didn't schedule
+            // a job of their own, so look again after clearing it.
+            cell.scheduled.store(false, Ordering::SeqCst);
+            if cell.mailbox.lock().unwrap().is_empty() {
+                return;
+            }
+            // Hand the worker back, unless the queue has no room for the
+            // next job; waiting for some here could wait on this worker.
+            match Cell::schedule(cell, RejectionPolicy::Error) {
+                Err(ExecuteError::Full) if !cell.scheduled.swap(true, Ordering::SeqCst) => {}
+                _ => return,
+            }
+        }
+    }
+}
+
+/// The job that works through an actor's mailbox. If the pool drops it
+/// without running it, it clears `scheduled`, so the next `send` (or the
+/// job that just failed to queue it) can schedule another.
+struct MailboxJob<A: Actor>(Option<Arc<Cell<A>>>);
+
+impl<A: Actor> Drop for MailboxJob<A> {
+    fn drop(&mut self) {
+        if let Some(cell) = self.0.take() {
+            cell.scheduled.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+impl<A: Actor> Clone for Addr<A> {
+    fn clone(&self) -> Addr<A> {
+        Addr {
+            cell: Arc::clone(&self.cell),
+        }
+    }
+}
+
+impl<A: Actor> fmt::Debug for Addr<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Addr")
+            .field("stopped", &self.is_stopped())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<M> fmt::Display for SendError<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the actor can't take any more messages")
+    }
+}
+
+impl<M: fmt::Debug> Error for SendError<M> {}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, time::Duration};
+
+    use super::*;
+
+    #[derive(Debug)]
+    enum Message {
+        Add(u64),
+        Get(mpsc::Sender<u64>),
+        Panic,
+    }
+
+    struct Counter(u64);
+
+    impl Actor for Counter {
+        type Message = Message;
+
+        fn handle(&mut self, message: Message) {
+            match message {
+                Message::Add(n) => self.0 += n,
+                Message::Get(reply) => reply.send(self.0).unwrap(),
+                Message::Panic => panic!("counter broke"),
+            }
+        }
+    }
+
+    fn get(counter: &Addr<Counter>) -> u64 {
+        let (reply, value) = mpsc::channel();
+        counter.send(Message::Get(reply)).unwrap();
+        value.recv().unwrap()
+    }
+
+    #[test]
+    fn messages_are_handled_in_order_across_senders() {
+        let system = ActorSystem::new(Arc::new(ThreadPool::new(3)));
+        let counter = system.spawn(Counter(0));
+
+        let senders: Vec<_> = (0..4)
+            .map(|_| {
+                let counter = counter.clone();
+                std::thread::spawn(move || {
+                    for n in 1..=100 {
+                        counter.send(Message::Add(n)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for sender in senders {
+            sender.join().unwrap();
+        }
+
+        assert_eq!(get(&counter), 4 * 5050);
+    }
+
+    #[test]
+    fn many_actors_share_a_small_pool() {
+        let system = ActorSystem::new(Arc::new(ThreadPool::new(2)));
+        let counters: Vec<_> = (0..50).map(|_| system.spawn(Counter(0))).collect();
+        for (i, counter) in counters.iter().enumerate() {
+            counter.send(Message::Add(i as u64)).unwrap();
+        }
+
+        let total: u64 = counters.iter().map(get).sum();
+        assert_eq!(total, (0..50).sum());
+    }
+
+    #[test]
+    fn a_panicking_actor_stops() {
+        let system = ActorSystem::new(Arc::new(ThreadPool::new(1)));
+        let counter = system.spawn(Counter(0));
+        counter.send(Message::Panic).unwrap();
+
+        while !counter.is_stopped() {
+            std::thread::yield_now();
+        }
+        assert!(matches!(
+            counter.send(Message::Add(1)),
+            Err(SendError(Message::Add(1)))
+        ));
+    }
+
+    #[test]
+    fn no_message_is_left_in_a_stopped_actors_mailbox() {
+        let system = ActorSystem::new(Arc::new(ThreadPool::new(2)));
+        let counter = system.spawn(Counter(0));
+
+        let senders: Vec<_> = (0..4)
+            .map(|_| {
+                let counter = counter.clone();
+                std::thread::spawn(move || while counter.send(Message::Add(1)).is_ok() {})
+            })
+            .collect();
+        counter.send(Message::Panic).unwrap();
+        for sender in senders {
+            sender.join().unwrap();
+        }
+
+        assert!(counter.is_stopped());
+        assert!(counter.cell.mailbox.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn an_evicted_mailbox_job_is_scheduled_again() {
+        let pool = Arc::new(
+            ThreadPool::builder()
+                .core_threads(1)
+                .queue_capacity(1)
+                .rejection_policy(RejectionPolicy::DropOldest)
+                .build(),
+        );
+        let system = ActorSystem::new(Arc::clone(&pool));
+        let counter = system.spawn(Counter(0));
+
+        // Keep the worker busy, so the actor's job waits in the queue until
+        // the next job evicts it.
+        let (release, blocked) = mpsc::channel::<()>();
+        pool.execute(move || blocked.recv().unwrap()).unwrap();
+        counter.send(Message::Add(1)).unwrap();
+        let evicting = pool.execute(|| ()).unwrap();
+        release.send(()).unwrap();
+        evicting.join().unwrap();
+
+        counter.send(Message::Add(2)).unwrap();
+        let (reply, value) = mpsc::channel();
+        counter.send(Message::Get(reply)).unwrap();
+        assert_eq!(value.recv_timeout(Duration::from_secs(5)), Ok(3));
+    }
+
+    #[test]
+    fn an_actor_can_outlive_everything_else_holding_its_pool() {
+        let (reply, value) = mpsc::channel();
+        {
+            let system = ActorSystem::new(Arc::new(ThreadPool::new(1)));
+            let counter = system.spawn(Counter(0));
+            counter.send(Message::Add(2)).unwrap();
+            counter.send(Message::Get(reply)).unwrap();
+        }
+        // The job may have dropped the last handle to the pool on its own
+        // worker, which mustn't try to join itself.
+        assert_eq!(value.recv().unwrap(), 2);
+    }
+}
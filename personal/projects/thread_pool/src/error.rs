@@ -0,0 +1,36 @@
+use std::io;
+
+use hello_macro_derive::ErrorKind;
+
+/// Why a job couldn't be handed to a [`ThreadPool`](crate::ThreadPool).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ErrorKind)]
+pub enum ExecuteError {
+    /// The pool has started shutting down and takes no new jobs.
+    #[error("the thread pool is shutting down")]
+    Shutdown,
+    /// The queue is full and the pool's [`RejectionPolicy`] is `Error`.
+    ///
+    /// [`RejectionPolicy`]: crate::RejectionPolicy
+    #[error("the thread pool's queue is full")]
+    Full,
+    /// The job has a time limit, but the thread that watches for jobs
+    /// running over theirs couldn't be started.
+    #[error("couldn't start the thread pool's watchdog")]
+    NoWatchdog,
+}
+
+/// Why [`Builder::try_build`](crate::Builder::try_build) couldn't start a
+/// pool: the `PoolCreationError` the book suggests returning from a
+/// `build` instead of panicking in `new`.
+#[derive(Debug, ErrorKind)]
+pub enum PoolCreationError {
+    /// A pool with no threads would never run anything.
+    #[error("a thread pool needs at least one thread")]
+    NoThreads,
+    /// A bounded queue with no room would reject every job.
+    #[error("a bounded queue needs room for at least one job")]
+    NoQueueRoom,
+    /// The operating system wouldn't start a worker thread.
+    #[error("couldn't spawn a worker thread: {0}")]
+    Spawn(#[from] io::Error),
+}
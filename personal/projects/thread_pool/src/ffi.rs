@@ -0,0 +1,182 @@
+//! A C API for the pool, which makes the crate's `cdylib` a library C code
+//! can link: "Calling Rust Functions from Other Languages", for something
+//! bigger than `call_from_c`.
+//!
+//! C gets a pointer to an opaque [`ThreadPoolHandle`] and never looks
+//! inside it. Jobs are a C function pointer plus a `void *` context, which
+//! the pool passes back when it runs the function. `include/thread_pool.h`
+//! declares all of this for C, and `examples/c/demo.c` uses it; see that
+//! file for how to build it.
+//!
+//! Nothing here can unwind into C: a zero-sized pool is a null handle
+//! instead of a panic, and failures are return codes.
+
+use std::{ffi::c_void, os::raw::c_int};
+
+use crate::{ExecuteError, ThreadPool};
+
+/// A pool, as C sees it: only ever behind a pointer.
+pub struct ThreadPoolHandle {
+    pool: ThreadPool,
+}
+
+/// A job: called once, on a worker, with the context it was given.
+pub type ThreadPoolJob = extern "C" fn(context: *mut c_void);
+
+/// The job was queued.
+pub const THREADPOOL_OK: c_int = 0;
+/// The pool or the function pointer was null.
+pub const THREADPOOL_INVALID: c_int = -1;
+/// The pool is shutting down.
+pub const THREADPOOL_SHUTDOWN: c_int = -2;
+/// The pool's queue is full.
+pub const THREADPOOL_FULL: c_int = -3;
+/// The pool couldn't start the thread that enforces time limits.
+pub const THREADPOOL_NO_WATCHDOG: c_int = -4;
+
+/// The context pointer, which C promises is fine to use from a worker.
+struct Context(*mut c_void);
+
+// SAFETY: `threadpool_execute`'s caller guarantees the context can be used
+// from another thread.
+unsafe impl Send for Context {}
+
+/// Start a pool of `threads` workers, or return null if `threads` is 0 or
+/// the workers couldn't be spawned.
+///
+/// Free it with [`threadpool_free`].
+#[no_mangle]
+pub extern "C" fn threadpool_new(threads: usize) -> *mut ThreadPoolHandle {
+    // `build` rather than `new`, which would panic, and a panic can't
+    // unwind out into C.
+    match ThreadPool::build(threads) {
+        Ok(pool) => Box::into_raw(Box::new(ThreadPoolHandle { pool })),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Queue `job(context)` to run on one of the pool's workers.
+///
+/// Returns [`THREADPOOL_OK`], or one of the other `THREADPOOL_*` codes if
+/// it wasn't queued.
+///
+/// # Safety
+///
+/// `pool` must be null or a pool from [`threadpool_new`] that hasn't been
+/// freed. `context` must be safe for `job` to use from another thread, and
+/// stay valid until the job has run.
+#[no_mangle]
+pub unsafe extern "C" fn threadpool_execute(
+    pool: *const ThreadPoolHandle,
+    job: Option<ThreadPoolJob>,
+    context: *mut c_void,
+) -> c_int {
+    // SAFETY: the caller guarantees `pool` is null or a live pool.
+    let (Some(handle), Some(job)) = (unsafe { pool.as_ref() }, job) else {
+        return THREADPOOL_INVALID;
+    };
+    let context = Context(context);
+    let submitted = handle.pool.execute(move || {
+        let context = context;
+        job(context.0)
+    });
+    match submitted {
+        Ok(_) => THREADPOOL_OK,
+        Err(ExecuteError::Shutdown) => THREADPOOL_SHUTDOWN,
+        Err(ExecuteError::Full) => THREADPOOL_FULL,
+        Err(ExecuteError::NoWatchdog) => THREADPOOL_NO_WATCHDOG,
+    }
+}
+
+/// Block until every queued job has run.
+///
+/// # Safety
+///
+/// `pool` must be null or a pool from [`threadpool_new`] that hasn't been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn threadpool_wait(pool: *const ThreadPoolHandle) {
+    // SAFETY: the caller guarantees `pool` is null or a live pool.
+    if let Some(handle) = unsafe { pool.as_ref() } {
+        handle.pool.wait_idle();
+    }
+}
+
+/// Run the jobs still queued, stop the workers and free the pool. Does
+/// nothing if `pool` is null.
+///
+/// # Safety
+///
+/// `pool` must be null or a pool from [`threadpool_new`] that hasn't been
+/// freed, and mustn't be used again afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn threadpool_free(pool: *mut ThreadPoolHandle) {
+    if !pool.is_null() {
+        // SAFETY: the caller guarantees this is the `Box` `threadpool_new`
+        // made, given back once.
+        drop(unsafe { Box::from_raw(pool) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    extern "C" fn add_one(context: *mut c_void) {
+        // SAFETY: every test passes a pointer to an `AtomicUsize` that
+        // outlives the pool.
+        let counter = unsafe { &*(context as *const AtomicUsize) };
+        counter.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn context(counter: &AtomicUsize) -> *mut c_void {
+        counter as *const AtomicUsize as *mut c_void
+    }
+
+    #[test]
+    fn runs_c_jobs_with_their_context() {
+        let counter = AtomicUsize::new(0);
+        let pool = threadpool_new(4);
+        assert!(!pool.is_null());
+
+        unsafe {
+            for _ in 0..100 {
+                assert_eq!(
+                    threadpool_execute(pool, Some(add_one), context(&counter)),
+                    THREADPOOL_OK
+                );
+            }
+            threadpool_wait(pool);
+            assert_eq!(counter.load(Ordering::SeqCst), 100);
+
+            threadpool_execute(pool, Some(add_one), context(&counter));
+            threadpool_free(pool);
+        }
+        // Freeing runs what's still queued.
+        assert_eq!(counter.load(Ordering::SeqCst), 101);
+    }
+
+    #[test]
+    fn null_pools_and_jobs_are_refused() {
+        let counter = AtomicUsize::new(0);
+        assert!(threadpool_new(0).is_null());
+
+        unsafe {
+            assert_eq!(
+                threadpool_execute(std::ptr::null(), Some(add_one), context(&counter)),
+                THREADPOOL_INVALID
+            );
+            let pool = threadpool_new(1);
+            assert_eq!(
+                threadpool_execute(pool, None, context(&counter)),
+                THREADPOOL_INVALID
+            );
+            threadpool_wait(std::ptr::null());
+            threadpool_free(pool);
+            threadpool_free(std::ptr::null_mut());
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,113 @@
+//! Fixed-bucket latency histograms, cheap enough to update on every job.
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use hello_macro_derive::ToJson;
+
+/// The upper bound of each bucket but the last, which counts everything
+/// slower than 10 seconds.
+pub const BUCKET_BOUNDS: [Duration; 7] = [
+    Duration::from_micros(10),
+    Duration::from_micros(100),
+    Duration::from_millis(1),
+    Duration::from_millis(10),
+    Duration::from_millis(100),
+    Duration::from_secs(1),
+    Duration::from_secs(10),
+];
+
+const BUCKETS: usize = BUCKET_BOUNDS.len() + 1;
+
+#[derive(Default)]
+pub(crate) struct Histogram {
+    counts: [AtomicU64; BUCKETS],
+}
+
+impl Histogram {
+    pub(crate) fn record(&self, latency: Duration) {
+        let bucket = BUCKET_BOUNDS
+            .iter()
+            .position(|&bound| latency <= bound)
+            .unwrap_or(BUCKETS - 1);
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> LatencyHistogram {
+        LatencyHistogram {
+            buckets: self
+                .counts
+                .each_ref()
+                .map(|count| count.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// How many jobs fell into each latency bucket, as of a
+/// [`Metrics`](crate::Metrics) snapshot.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ToJson)]
+pub struct LatencyHistogram {
+    /// `buckets[i]` counts the jobs that took at most `BUCKET_BOUNDS[i]`
+    /// (and more than the bound before it). The last bucket counts the rest.
+    pub buckets: [u64; BUCKETS],
+}
+
+impl LatencyHistogram {
+    /// How many jobs were recorded.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// An upper bound on the latency of the fastest `quantile` of jobs, so
+    /// `quantile(0.99)` is roughly the p99. `None` if nothing was recorded,
+    /// or if those jobs took longer than the last bound.
+    pub fn quantile(&self, quantile: f64) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+
+        let wanted = (quantile.clamp(0.0, 1.0) * count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, &n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= wanted {
+                return BUCKET_BOUNDS.get(bucket).copied();
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latencies_land_in_the_first_bucket_that_fits() {
+        let histogram = Histogram::default();
+        histogram.record(Duration::from_micros(3));
+        histogram.record(Duration::from_micros(10));
+        histogram.record(Duration::from_millis(5));
+        histogram.record(Duration::from_secs(60));
+
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.buckets, [2, 0, 0, 1, 0, 0, 0, 1]);
+        assert_eq!(snapshot.count(), 4);
+    }
+
+    #[test]
+    fn quantiles_report_bucket_bounds() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.quantile(0.5), None);
+
+        histogram.buckets[1] = 98;
+        histogram.buckets[5] = 1;
+        histogram.buckets[7] = 1;
+        assert_eq!(histogram.quantile(0.5), Some(Duration::from_micros(100)));
+        assert_eq!(histogram.quantile(0.99), Some(Duration::from_secs(1)));
+        assert_eq!(histogram.quantile(1.0), None);
+    }
+}
@@ -0,0 +1,94 @@
+//! Callbacks the pool runs around every job, for logging, metrics or tracing
+//! without touching the workers themselves.
+
+use std::{
+    any::Any,
+    cell::RefCell,
+    fmt,
+    panic::{self, PanicHookInfo},
+    sync::{Arc, Once},
+    time::Duration,
+};
+
+use crate::WORKER_ID;
+
+type JobStart = dyn Fn(usize) + Send + Sync;
+type JobEnd = dyn Fn(usize, Duration) + Send + Sync;
+type WorkerPanic = dyn Fn(usize, &(dyn Any + Send)) + Send + Sync;
+type PanicHandler = dyn Fn(&PanicHookInfo<'_>, usize) + Send + Sync;
+
+thread_local! {
+    /// The panic handler of the pool whose worker runs on this thread.
+    static PANIC_HANDLER: RefCell<Option<Arc<PanicHandler>>> = const { RefCell::new(None) };
+}
+
+/// The hooks set on a [`Builder`](crate::Builder). Each one is called on the
+/// worker thread with that worker's id. A panic in the job hooks is caught and
+/// reported like one in a job.
+#[derive(Clone, Default)]
+pub(crate) struct Hooks {
+    pub(crate) job_start: Option<Arc<JobStart>>,
+    pub(crate) job_end: Option<Arc<JobEnd>>,
+    pub(crate) worker_panic: Option<Arc<WorkerPanic>>,
+    pub(crate) panic_handler: Option<Arc<PanicHandler>>,
+}
+
+impl Hooks {
+    pub(crate) fn job_start(&self, worker: usize) {
+        if let Some(hook) = &self.job_start {
+            hook(worker);
+        }
+    }
+
+    pub(crate) fn job_end(&self, worker: usize, elapsed: Duration) {
+        if let Some(hook) = &self.job_end {
+            hook(worker, elapsed);
+        }
+    }
+
+    pub(crate) fn worker_panic(&self, worker: usize, payload: &(dyn Any + Send)) {
+        if let Some(hook) = &self.worker_panic {
+            hook(worker, payload);
+        }
+    }
+
+    /// Route panics on the calling worker thread to the pool's panic
+    /// handler, if it has one.
+    ///
+    /// Panic hooks are process-wide, so the first pool to need one installs
+    /// a hook that checks which thread is panicking, and leaves panics on
+    /// any other thread to the hook that was there before.
+    pub(crate) fn install_panic_handler(&self) {
+        static INSTALL: Once = Once::new();
+
+        let Some(handler) = &self.panic_handler else {
+            return;
+        };
+        PANIC_HANDLER.with(|slot| *slot.borrow_mut() = Some(Arc::clone(handler)));
+
+        INSTALL.call_once(|| {
+            let previous = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                let handler = PANIC_HANDLER
+                    .try_with(|slot| slot.borrow().clone())
+                    .ok()
+                    .flatten();
+                match (handler, WORKER_ID.try_with(|id| id.get()).ok().flatten()) {
+                    (Some(handler), Some(worker)) => handler(info, worker),
+                    _ => previous(info),
+                }
+            }));
+        });
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field("job_start", &self.job_start.is_some())
+            .field("job_end", &self.job_end.is_some())
+            .field("worker_panic", &self.worker_panic.is_some())
+            .field("panic_handler", &self.panic_handler.is_some())
+            .finish()
+    }
+}
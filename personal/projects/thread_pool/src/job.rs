@@ -0,0 +1,124 @@
+//! Handles for getting a job's result back out of the pool.
+
+use std::{
+    future::Future,
+    panic,
+    pin::Pin,
+    sync::{mpsc, Arc, Mutex},
+    task::{Context, Poll, Waker},
+    thread,
+};
+
+/// The other end of a job submitted with [`ThreadPool::execute`].
+///
+/// Dropping the handle doesn't cancel the job; its result is just thrown
+/// away when it finishes.
+///
+/// [`ThreadPool::execute`]: crate::ThreadPool::execute
+#[derive(Debug)]
+pub struct JobHandle<T> {
+    result: mpsc::Receiver<thread::Result<T>>,
+}
+
+impl<T> JobHandle<T> {
+    /// Make a handle together with the sender its job reports to. Only one
+    /// result is ever sent, so the channel is used as a oneshot.
+    pub(crate) fn new() -> (JobHandle<T>, mpsc::SyncSender<thread::Result<T>>) {
+        let (sender, result) = mpsc::sync_channel(1);
+        (JobHandle { result }, sender)
+    }
+
+    /// Wait for the job to finish and return what it returned.
+    ///
+    /// Like [`thread::JoinHandle::join`], this is an `Err` holding the panic
+    /// payload if the job panicked.
+    pub fn join(self) -> thread::Result<T> {
+        self.result
+            .recv()
+            .unwrap_or_else(|_| Err(Box::new("job was dropped before it finished")))
+    }
+}
+
+/// A job submitted with [`ThreadPool::execute_future`], as a future that
+/// resolves to its result.
+///
+/// Whichever thread finishes the job wakes the task awaiting it, so this
+/// works with any executor.
+///
+/// [`ThreadPool::execute_future`]: crate::ThreadPool::execute_future
+pub struct JobFuture<T> {
+    state: Arc<Mutex<FutureState<T>>>,
+}
+
+enum FutureState<T> {
+    Running(Option<Waker>),
+    Done(thread::Result<T>),
+    Taken,
+}
+
+/// The job's side of a [`JobFuture`]. If it's dropped without completing,
+/// the future resolves to a panic instead of waiting forever.
+pub(crate) struct Completer<T> {
+    state: Arc<Mutex<FutureState<T>>>,
+}
+
+impl<T> JobFuture<T> {
+    pub(crate) fn new() -> (JobFuture<T>, Completer<T>) {
+        let state = Arc::new(Mutex::new(FutureState::Running(None)));
+        let completer = Completer {
+            state: Arc::clone(&state),
+        };
+        (JobFuture { state }, completer)
+    }
+}
+
+impl<T> Future for JobFuture<T> {
+    type Output = T;
+
+    /// Resolves to the job's return value, or resumes its panic in the
+    /// awaiting task if it panicked.
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.lock().unwrap();
+        match std::mem::replace(&mut *state, FutureState::Taken) {
+            FutureState::Running(_) => {
+                *state = FutureState::Running(Some(cx.waker().clone()));
+                Poll::Pending
+            }
+            FutureState::Done(Ok(value)) => Poll::Ready(value),
+            FutureState::Done(Err(payload)) => {
+                drop(state);
+                panic::resume_unwind(payload)
+            }
+            FutureState::Taken => panic!("JobFuture polled after completion"),
+        }
+    }
+}
+
+impl<T> Completer<T> {
+    pub(crate) fn complete(self, result: thread::Result<T>) {
+        self.finish(result);
+    }
+
+    fn finish(&self, result: thread::Result<T>) {
+        let mut state = self.state.lock().unwrap();
+        if let FutureState::Running(waker) =
+            std::mem::replace(&mut *state, FutureState::Done(result))
+        {
+            drop(state);
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        } else {
+            unreachable!("a job completes at most once");
+        }
+    }
+}
+
+impl<T> Drop for Completer<T> {
+    fn drop(&mut self) {
+        let running = matches!(*self.state.lock().unwrap(), FutureState::Running(_));
+        if running {
+            self.finish(Err(Box::new("job was dropped before it finished")));
+        }
+    }
+}
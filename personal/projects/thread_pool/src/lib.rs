@@ -0,0 +1,1822 @@
+//! A thread pool for running jobs on a fixed (or bounded) set of worker
+//! threads, grown out of the pool from the Rust book's final project.
+//!
+//! ```
+//! use thread_pool::ThreadPool;
+//!
+//! let pool = ThreadPool::new(4);
+//! let handles: Vec<_> = (1..=4).map(|n| pool.execute(move || n * n).unwrap()).collect();
+//! let squares: Vec<i32> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+//! assert_eq!(squares, [1, 4, 9, 16]);
+//! ```
+//!
+//! Besides plain [`ThreadPool::execute`], jobs can be given a
+//! [`Priority`], a time limit, borrow from the caller's stack with
+//! [`ThreadPool::scope`], or be awaited from async code with
+//! [`ThreadPool::execute_future`]. [`ThreadPool::par_map`] and
+//! [`ThreadPool::par_for_each`] spread an iterator across the workers, and
+//! [`actor`] runs message-driven actors on a pool. A bounded queue keeps
+//! track of its free slots with a [`Semaphore`], which can cap other things
+//! too. See [`Builder`] for a pool that grows under load, or [`global`] for one
+//! shared by the whole process.
+
+use std::{
+    any::Any,
+    cell::Cell,
+    io, mem,
+    panic::{self, AssertUnwindSafe, PanicHookInfo},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, OnceLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use hello_macro_derive::ToJson;
+
+pub mod actor;
+mod error;
+pub mod ffi;
+pub mod histogram;
+mod hooks;
+pub mod job;
+pub mod metrics;
+mod niceness;
+pub mod oneshot;
+mod par;
+mod queue;
+pub mod scope;
+pub mod semaphore;
+mod state;
+mod watchdog;
+
+pub use error::{ExecuteError, PoolCreationError};
+use histogram::Histogram;
+pub use histogram::LatencyHistogram;
+use hooks::Hooks;
+pub use job::{JobFuture, JobHandle};
+use metrics::{Counter, Gauge};
+pub use oneshot::Receiver;
+use queue::{JobQueue, Pop, QueuedJob, Rejected};
+pub use scope::Scope;
+pub use semaphore::Semaphore;
+use state::WorkerState;
+use watchdog::Watchdog;
+
+pub struct ThreadPool {
+    shared: Arc<Shared>,
+}
+
+pub(crate) type Job = Box<dyn FnOnce() + Send + 'static>;
+
+thread_local! {
+    /// The id of the worker running on this thread, if it is one.
+    static WORKER_ID: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// How urgently a job should be run. Queued jobs are started highest
+/// priority first, and in submission order within a priority.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// What happens to jobs still queued when a pool is dropped or
+/// [`close`](ThreadPool::close)d.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShutdownPolicy {
+    /// Let the workers run every queued job before they exit.
+    #[default]
+    DrainQueue,
+    /// Drop queued jobs without running them. Jobs already running still
+    /// finish.
+    Discard,
+}
+
+/// What to do with a job submitted while the queue is at the capacity set
+/// with [`Builder::queue_capacity`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RejectionPolicy {
+    /// Block the submitting thread until a worker makes room.
+    #[default]
+    Block,
+    /// Fail with [`ExecuteError::Full`].
+    Error,
+    /// Make room by dropping the oldest of the lowest-priority queued jobs.
+    /// Its handle reports an error, as if it had been abandoned on shutdown.
+    /// A job never evicts a more urgent one; if every queued job is, it
+    /// blocks like [`Block`](RejectionPolicy::Block).
+    DropOldest,
+    /// Run the job straight away on the submitting thread, which also slows
+    /// the submitter down until the workers catch up.
+    CallerRuns,
+}
+
+/// What the workers share with the pool.
+struct Shared {
+    queue: JobQueue,
+    /// The worker threads, so the pool can join them. A recycled worker
+    /// adds its own replacement here.
+    workers: Mutex<Vec<Worker>>,
+    /// How many workers are running.
+    size: AtomicUsize,
+    /// How many workers to keep alive even when they're idle.
+    core: AtomicUsize,
+    /// How many more workers should exit once they finish their current job.
+    retiring: AtomicUsize,
+    /// How many jobs are waiting for a worker.
+    queued: Gauge,
+    /// How many workers are running a job.
+    busy: Gauge,
+    /// Signalled whenever a worker finishes a job with nothing left queued.
+    idle: Condvar,
+    idle_lock: Mutex<()>,
+    completed: Counter,
+    panicked: Counter,
+    /// How long jobs sat in the queue before a worker picked them up.
+    queue_wait: Histogram,
+    /// How long jobs took to run.
+    run_time: Histogram,
+    next_id: AtomicUsize,
+    watchdog: Watchdog,
+    hooks: Hooks,
+    max: Option<usize>,
+    idle_timeout: Duration,
+    stack_size: Option<usize>,
+    nice: Option<i32>,
+    recycle_overdue: bool,
+    shutdown_policy: ShutdownPolicy,
+    rejection_policy: RejectionPolicy,
+    worker_state: Option<WorkerState>,
+}
+
+impl Shared {
+    /// Run `f`, catching (and counting) a panic instead of letting it unwind
+    /// into the worker.
+    fn catch_panic<T>(&self, f: impl FnOnce() -> T) -> thread::Result<T> {
+        let outcome = panic::catch_unwind(AssertUnwindSafe(f));
+        if let Err(payload) = &outcome {
+            self.panicked.inc();
+            if let Some(worker) = WORKER_ID.with(Cell::get) {
+                // A panic in the panic hook has nowhere left to be reported.
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+                    self.hooks.worker_panic(worker, &**payload);
+                }));
+            }
+        }
+        outcome
+    }
+
+    /// Run a job taken off the queue, as worker `id`, keeping the counters,
+    /// histograms, hooks and watchdog up to date. Returns whether the job ran
+    /// over its time limit.
+    fn run_job(&self, id: usize, job: QueuedJob) -> bool {
+        let QueuedJob {
+            job,
+            limit,
+            queued_at,
+        } = job;
+
+        // Busy first, so `wait_idle` never sees the job in neither.
+        self.busy.inc();
+        self.queued.dec();
+        if let Some(limit) = limit {
+            self.watchdog.start(id, limit);
+        }
+        // A panicking hook is reported like a panicking job, instead of
+        // taking the worker down before `busy` is put back.
+        let _ = self.catch_panic(|| self.hooks.job_start(id));
+        let started = Instant::now();
+        self.queue_wait.record(started - queued_at);
+        job();
+        let elapsed = started.elapsed();
+        self.run_time.record(elapsed);
+        let _ = self.catch_panic(|| self.hooks.job_end(id, elapsed));
+        let overdue = limit.is_some() && self.watchdog.finish(id);
+        self.busy.dec();
+        self.completed.inc();
+        if self.is_idle() {
+            let _lock = self.idle_lock.lock().unwrap();
+            self.idle.notify_all();
+        }
+        overdue
+    }
+
+    fn is_idle(&self) -> bool {
+        self.queued.get() == 0 && self.busy.get() == 0
+    }
+
+    /// The most workers the pool may grow to when jobs back up.
+    fn max(&self) -> usize {
+        self.max.unwrap_or(0).max(self.core.load(Ordering::SeqCst))
+    }
+
+    /// Retire the calling worker if `resize` asked for one to go.
+    fn take_retirement(&self) -> bool {
+        let taken = self
+            .retiring
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        if taken {
+            self.size.fetch_sub(1, Ordering::SeqCst);
+        }
+        taken
+    }
+
+    fn spawn_worker(self: &Arc<Self>, workers: &mut Vec<Worker>) -> io::Result<()> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        self.size.fetch_add(1, Ordering::SeqCst);
+        match Worker::new(id, Arc::clone(self)) {
+            Ok(worker) => {
+                workers.push(worker);
+                Ok(())
+            }
+            Err(err) => {
+                self.size.fetch_sub(1, Ordering::SeqCst);
+                Err(err)
+            }
+        }
+    }
+
+    /// Start a fresh worker in place of the calling one, which is about to
+    /// exit, unless the pool is shutting down. Jobs queued behind the one
+    /// that got it recycled still need someone to run them.
+    fn replace_worker(self: &Arc<Self>) {
+        let mut workers = self.workers.lock().unwrap();
+        workers.retain_mut(|worker| !worker.join_if_finished());
+        // Checked under the lock, so a pool that's stopping either sees
+        // the replacement in `workers` or it's never spawned.
+        if self.queue.is_closed() {
+            return;
+        }
+        if let Err(err) = self.spawn_worker(&mut workers) {
+            eprintln!("Couldn't replace a recycled worker: {err}");
+        }
+    }
+
+    /// Retire the calling worker if it's an idle extra above the core size.
+    fn retire_if_extra(&self) -> bool {
+        self.size
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |size| {
+                (size > self.core.load(Ordering::SeqCst)).then(|| size - 1)
+            })
+            .is_ok()
+    }
+}
+
+impl ThreadPool {
+    /// Create a new ThreadPool.
+    ///
+    /// The size is the number of threads in the pool.
+    ///
+    /// # Panics
+    ///
+    /// The `new` function will panic if the size is zero.
+    pub fn new(size: usize) -> ThreadPool {
+        Builder::new().core_threads(size).build()
+    }
+
+    /// Create a new ThreadPool, like [`ThreadPool::new`], but returning an
+    /// error instead of panicking if the size is zero or a thread can't be
+    /// spawned.
+    pub fn build(size: usize) -> Result<ThreadPool, PoolCreationError> {
+        Builder::new().core_threads(size).try_build()
+    }
+
+    /// Configure a pool that can grow past its core size under load.
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    /// Run `f` on one of the pool's threads.
+    ///
+    /// The returned handle can be used to wait for `f` and get its result;
+    /// it's fine to drop it for fire-and-forget jobs. A panic in `f` is
+    /// caught and handed to the handle, so it doesn't take the worker down.
+    ///
+    /// Fails with [`ExecuteError::Shutdown`] once the pool has started
+    /// shutting down.
+    pub fn execute<F, T>(&self, f: F) -> Result<JobHandle<T>, ExecuteError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.execute_with_priority(Priority::Normal, f)
+    }
+
+    /// Like [`execute`](ThreadPool::execute), but `f` jumps ahead of any
+    /// queued jobs with a lower priority.
+    pub fn execute_with_priority<F, T>(
+        &self,
+        priority: Priority,
+        f: F,
+    ) -> Result<JobHandle<T>, ExecuteError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.execute_job(priority, None, f)
+    }
+
+    /// Like [`execute`](ThreadPool::execute), but logs a warning if `f` runs
+    /// for longer than `limit`.
+    ///
+    /// The job isn't interrupted, since there's no safe way to stop a thread
+    /// from the outside. With [`Builder::recycle_overdue_workers`], the worker
+    /// that ran it starts a fresh thread in its place once it does finish,
+    /// and exits.
+    pub fn execute_with_timeout<F, T>(
+        &self,
+        limit: Duration,
+        f: F,
+    ) -> Result<JobHandle<T>, ExecuteError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.shared
+            .watchdog
+            .ensure_started(Arc::downgrade(&self.shared))?;
+        self.execute_job(Priority::Normal, Some(limit), f)
+    }
+
+    fn execute_job<F, T>(
+        &self,
+        priority: Priority,
+        limit: Option<Duration>,
+        f: F,
+    ) -> Result<JobHandle<T>, ExecuteError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (handle, result) = JobHandle::new();
+        let shared = Arc::clone(&self.shared);
+        self.enqueue(
+            priority,
+            limit,
+            Box::new(move || {
+                // Nobody is waiting if the handle was dropped.
+                let _ = result.send(shared.catch_panic(f));
+            }),
+        )?;
+
+        Ok(handle)
+    }
+
+    /// Like [`execute`](ThreadPool::execute), but `f` gets a mutable
+    /// reference to the state of the worker that runs it, made by the
+    /// initializer given to [`Builder::worker_state`].
+    ///
+    /// Only workers have state, so under [`RejectionPolicy::CallerRuns`] a
+    /// full queue blocks instead of running `f` on the calling thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pool's worker state isn't an `S`.
+    pub fn execute_with_state<S, F, T>(&self, f: F) -> Result<JobHandle<T>, ExecuteError>
+    where
+        S: 'static,
+        F: FnOnce(&mut S) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        WorkerState::check::<S>(self.shared.worker_state.as_ref());
+        let policy = match self.shared.rejection_policy {
+            RejectionPolicy::CallerRuns => RejectionPolicy::Block,
+            policy => policy,
+        };
+
+        let (handle, result) = JobHandle::new();
+        let shared = Arc::clone(&self.shared);
+        self.enqueue_with(
+            Priority::Normal,
+            None,
+            Box::new(move || {
+                // Nobody is waiting if the handle was dropped.
+                let _ = result.send(shared.catch_panic(move || state::with(f)));
+            }),
+            policy,
+        )?;
+        Ok(handle)
+    }
+
+    /// Like [`execute`](ThreadPool::execute), but returns a future that
+    /// resolves to `f`'s result, for handing blocking work to the pool from
+    /// async code.
+    pub fn execute_future<F, T>(&self, f: F) -> Result<JobFuture<T>, ExecuteError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (future, completer) = JobFuture::new();
+        let shared = Arc::clone(&self.shared);
+        self.enqueue(
+            Priority::Normal,
+            None,
+            Box::new(move || completer.complete(shared.catch_panic(f))),
+        )?;
+        Ok(future)
+    }
+
+    /// Like [`execute`](ThreadPool::execute), but the result comes back over
+    /// a lighter [`Receiver`] for callers that only want the value.
+    ///
+    /// If `f` panics, the panic is caught and counted as usual, and the
+    /// receiver gets a [`RecvError`](oneshot::RecvError) instead of the
+    /// payload.
+    pub fn submit<F, T>(&self, f: F) -> Result<Receiver<T>, ExecuteError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        let shared = Arc::clone(&self.shared);
+        self.enqueue(
+            Priority::Normal,
+            None,
+            Box::new(move || {
+                if let Ok(value) = shared.catch_panic(f) {
+                    sender.send(value);
+                }
+            }),
+        )?;
+        Ok(receiver)
+    }
+
+    /// Run jobs that borrow from the caller's stack.
+    ///
+    /// Jobs started with [`Scope::execute`] may borrow anything that outlives
+    /// the call to `scope`, because `scope` doesn't return until every one of
+    /// them has finished. If any of them panicked, `scope` panics too once
+    /// they're all done.
+    ///
+    /// The calling thread blocks while it waits, so calling `scope` from a
+    /// job on the same pool can deadlock if every worker ends up waiting.
+    pub fn scope<'env, F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Scope<'_, 'env>) -> R,
+    {
+        let scope = Scope::new(self);
+        let result = f(&scope);
+        scope.wait();
+        result
+    }
+
+    /// Queue a job, spawning an extra worker for it if none is free. If the
+    /// queue is full, the pool's [`RejectionPolicy`] decides what happens.
+    fn enqueue(
+        &self,
+        priority: Priority,
+        limit: Option<Duration>,
+        job: Job,
+    ) -> Result<(), ExecuteError> {
+        self.enqueue_with(priority, limit, job, self.shared.rejection_policy)
+    }
+
+    pub(crate) fn enqueue_with(
+        &self,
+        priority: Priority,
+        limit: Option<Duration>,
+        job: Job,
+        policy: RejectionPolicy,
+    ) -> Result<(), ExecuteError> {
+        let queued = self.shared.queued.inc() as usize;
+        let job = QueuedJob {
+            job,
+            limit,
+            queued_at: Instant::now(),
+        };
+        match self.shared.queue.push(priority, job, policy) {
+            Ok(None) => {}
+            Ok(Some(evicted)) => {
+                self.shared.queued.dec();
+                drop(evicted);
+            }
+            Err(Rejected::Full(job)) if policy == RejectionPolicy::CallerRuns => {
+                // Counted like any other job, under an id of its own, since
+                // the caller may be a worker that's timing a job already.
+                let id = self.shared.next_id.fetch_add(1, Ordering::SeqCst);
+                let caller = WORKER_ID.replace(Some(id));
+                self.shared.run_job(id, job);
+                WORKER_ID.set(caller);
+                return Ok(());
+            }
+            Err(rejected) => {
+                self.shared.queued.dec();
+                return match rejected {
+                    Rejected::Closed => Err(ExecuteError::Shutdown),
+                    Rejected::Full(_) => Err(ExecuteError::Full),
+                };
+            }
+        }
+
+        // Replace recycled workers, and spawn an extra one if the job would
+        // otherwise have to wait.
+        let size = self.shared.size.load(Ordering::SeqCst);
+        let idle = size.saturating_sub(self.shared.busy.get() as usize);
+        let core = self.shared.core.load(Ordering::SeqCst);
+        if size < core || (queued > idle && size < self.shared.max()) {
+            let mut workers = self.shared.workers.lock().unwrap();
+            workers.retain_mut(|worker| !worker.join_if_finished());
+            if self.shared.size.load(Ordering::SeqCst) < self.shared.max() {
+                // The job is queued either way; without the extra worker it
+                // just waits for one of the others.
+                if let Err(err) = self.shared.spawn_worker(&mut workers) {
+                    eprintln!("Couldn't spawn an extra worker: {err}");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the pool has started shutting down, so that submitting a job
+    /// fails.
+    pub fn is_shutdown(&self) -> bool {
+        self.shared.queue.is_closed()
+    }
+
+    /// Set the pool's core size to `size` threads.
+    ///
+    /// Growing spawns the new workers straight away. Shrinking lets the
+    /// surplus workers finish whatever job they're running before they exit,
+    /// so `current_size` catches up a little later.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size is zero, or if a new worker can't be spawned.
+    pub fn resize(&self, size: usize) {
+        assert!(size > 0);
+        self.try_resize(size)
+            .expect("failed to spawn a worker thread");
+    }
+
+    fn try_resize(&self, size: usize) -> io::Result<()> {
+        let mut workers = self.shared.workers.lock().unwrap();
+        workers.retain_mut(|worker| !worker.join_if_finished());
+
+        self.shared.core.store(size, Ordering::SeqCst);
+        let retiring = self.shared.retiring.load(Ordering::SeqCst);
+        let staying = self
+            .shared
+            .size
+            .load(Ordering::SeqCst)
+            .saturating_sub(retiring);
+
+        if size > staying {
+            // Call off pending retirements before spawning anyone new.
+            let keep = (size - staying).min(retiring);
+            self.shared.retiring.fetch_sub(keep, Ordering::SeqCst);
+            for _ in staying + keep..size {
+                self.shared.spawn_worker(&mut workers)?;
+            }
+        } else {
+            self.shared
+                .retiring
+                .fetch_add(staying - size, Ordering::SeqCst);
+            self.shared.queue.wake(staying - size);
+        }
+        Ok(())
+    }
+
+    /// How many worker threads are running right now.
+    pub fn current_size(&self) -> usize {
+        self.shared.size.load(Ordering::SeqCst)
+    }
+
+    /// How many worker threads the pool keeps alive when it's idle.
+    pub fn core_size(&self) -> usize {
+        self.shared.core.load(Ordering::SeqCst)
+    }
+
+    /// How many worker threads the pool may grow to when jobs back up.
+    pub fn max_size(&self) -> usize {
+        self.shared.max()
+    }
+
+    /// Stop the pool, giving its workers up to `timeout` to work through the
+    /// queue.
+    ///
+    /// Jobs still queued when the time is up are dropped without running
+    /// (their handles report an error), and workers still in the middle of a
+    /// job are detached: they finish that job and exit in the background.
+    pub fn shutdown(self, timeout: Duration) -> ShutdownReport {
+        self.shared.queue.close();
+
+        let deadline = Instant::now() + timeout;
+        let workers = mem::take(&mut *self.shared.workers.lock().unwrap());
+        while !workers.iter().all(Worker::is_finished) {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            thread::sleep((deadline - now).min(Duration::from_millis(10)));
+        }
+
+        let abandoned_jobs = self.discard_queued();
+
+        let mut detached_workers = 0;
+        for mut worker in workers {
+            if !worker.join_if_finished() {
+                detached_workers += 1;
+            }
+        }
+
+        ShutdownReport {
+            abandoned_jobs,
+            detached_workers,
+        }
+    }
+
+    /// Stop the pool and wait for its workers to exit, handling queued jobs
+    /// according to its [`ShutdownPolicy`]. Returns how many queued jobs
+    /// were dropped without running, which is always 0 with
+    /// [`ShutdownPolicy::DrainQueue`].
+    ///
+    /// Dropping the pool does the same, but throws the count away.
+    pub fn close(mut self) -> usize {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> usize {
+        self.shared.queue.close();
+        let discarded = match self.shared.shutdown_policy {
+            ShutdownPolicy::DrainQueue => 0,
+            ShutdownPolicy::Discard => self.discard_queued(),
+        };
+
+        // Taken out of the lock, since a worker being recycled needs it to
+        // add its replacement, and it can't be joined until it has.
+        let workers = mem::take(&mut *self.shared.workers.lock().unwrap());
+        for mut worker in workers {
+            if let Some(thread) = worker.thread.take() {
+                // A job can hold the last handle to its own pool (an actor
+                // does), and a thread can't join itself. It exits once it
+                // finds the queue closed.
+                // Jobs' panics are caught, so this is the worker's own; it's
+                // dying anyway, and panicking in a drop would abort.
+                if thread.thread().id() != thread::current().id() && thread.join().is_err() {
+                    eprintln!("Worker {} panicked", worker.id);
+                }
+            }
+        }
+        discarded
+    }
+
+    /// Drop every queued job, returning how many there were.
+    fn discard_queued(&self) -> usize {
+        let discarded = self.shared.queue.clear();
+        self.shared.queued.sub(discarded as u64);
+        discarded
+    }
+
+    /// Block until no jobs are queued and every worker is idle.
+    ///
+    /// Jobs submitted by other threads while this waits keep it waiting.
+    pub fn wait_idle(&self) {
+        let shared = &self.shared;
+        let mut lock = shared.idle_lock.lock().unwrap();
+        while !shared.is_idle() {
+            lock = shared.idle.wait(lock).unwrap();
+        }
+    }
+
+    /// A snapshot of the pool's counters.
+    pub fn metrics(&self) -> Metrics {
+        let shared = &self.shared;
+        Metrics {
+            threads: shared.size.load(Ordering::SeqCst),
+            queued: shared.queued.get() as usize,
+            running: shared.busy.get() as usize,
+            completed: shared.completed.get(),
+            panicked: shared.panicked.get(),
+            overdue: shared.watchdog.overdue(),
+            queue_wait: shared.queue_wait.snapshot(),
+            run_time: shared.run_time.snapshot(),
+        }
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// A pool shared by the whole process, with one worker per CPU, started the
+/// first time it's asked for.
+///
+/// Handy for code that just wants some work off the current thread without
+/// owning a pool. It's never shut down, so jobs still queued when `main`
+/// returns don't get to run.
+pub fn global() -> &'static ThreadPool {
+    static GLOBAL: OnceLock<ThreadPool> = OnceLock::new();
+    GLOBAL.get_or_init(|| Builder::new().build())
+}
+
+/// What [`ThreadPool::shutdown`] had to leave unfinished.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// Queued jobs that were dropped without ever running.
+    pub abandoned_jobs: usize,
+    /// Workers that were still running a job at the deadline.
+    pub detached_workers: usize,
+}
+
+/// What a [`ThreadPool`] was doing at the moment [`ThreadPool::metrics`] was
+/// called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ToJson)]
+pub struct Metrics {
+    /// Worker threads alive.
+    pub threads: usize,
+    /// Jobs waiting for a free worker.
+    pub queued: usize,
+    /// Jobs being run right now.
+    pub running: usize,
+    /// Jobs that have finished, including the ones that panicked.
+    pub completed: u64,
+    /// Jobs that panicked.
+    pub panicked: u64,
+    /// Jobs that ran past the limit they were submitted with.
+    pub overdue: u64,
+    /// How long jobs waited in the queue before a worker picked them up.
+    pub queue_wait: LatencyHistogram,
+    /// How long finished jobs took to run.
+    pub run_time: LatencyHistogram,
+}
+
+/// Settings for a [`ThreadPool`] whose size floats between a core and a
+/// maximum number of threads.
+///
+/// The pool starts with `core_threads` workers and keeps them alive. When a
+/// job is submitted and no worker is free, it spawns another, up to
+/// `max_threads`. Those extras exit again after sitting idle for
+/// `idle_timeout`.
+#[derive(Debug, Clone)]
+pub struct Builder {
+    core: usize,
+    max: Option<usize>,
+    idle_timeout: Duration,
+    stack_size: Option<usize>,
+    nice: Option<i32>,
+    recycle_overdue: bool,
+    shutdown_policy: ShutdownPolicy,
+    queue_capacity: Option<usize>,
+    rejection_policy: RejectionPolicy,
+    worker_state: Option<WorkerState>,
+    hooks: Hooks,
+}
+
+impl Builder {
+    pub fn new() -> Builder {
+        Builder {
+            core: thread::available_parallelism().map_or(1, |n| n.get()),
+            max: None,
+            idle_timeout: Duration::from_secs(60),
+            stack_size: None,
+            nice: None,
+            recycle_overdue: false,
+            shutdown_policy: ShutdownPolicy::default(),
+            queue_capacity: None,
+            rejection_policy: RejectionPolicy::default(),
+            worker_state: None,
+            hooks: Hooks::default(),
+        }
+    }
+
+    pub fn core_threads(mut self, core: usize) -> Builder {
+        self.core = core;
+        self
+    }
+
+    /// Defaults to the core size, so the pool never grows on its own.
+    pub fn max_threads(mut self, max: usize) -> Builder {
+        self.max = Some(max);
+        self
+    }
+
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Builder {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// The stack size, in bytes, of each worker thread. Raise it for jobs
+    /// that recurse deeply, or lower it to fit many workers in little memory.
+    /// Defaults to the standard library's default for spawned threads.
+    pub fn stack_size(mut self, bytes: usize) -> Builder {
+        self.stack_size = Some(bytes);
+        self
+    }
+
+    /// Run the workers at the OS scheduling priority given by the nice value
+    /// `nice`, from -20 (most favoured) to 19 (least). A positive value keeps
+    /// a pool of background work from starving more urgent threads, like a
+    /// server's accept loop.
+    ///
+    /// Supported on Linux and Windows. Elsewhere, or if the OS refuses (as it
+    /// usually does when raising priority without privileges), the workers
+    /// log the error and carry on at the default priority.
+    pub fn thread_priority(mut self, nice: i32) -> Builder {
+        self.nice = Some(nice);
+        self
+    }
+
+    /// Replace a worker with a fresh thread after it finishes a job that ran
+    /// over the limit given to [`ThreadPool::execute_with_timeout`], in case
+    /// the job left it in a bad state.
+    pub fn recycle_overdue_workers(mut self, recycle: bool) -> Builder {
+        self.recycle_overdue = recycle;
+        self
+    }
+
+    /// What to do with queued jobs when the pool is dropped or closed.
+    /// Defaults to [`ShutdownPolicy::DrainQueue`].
+    pub fn shutdown_policy(mut self, policy: ShutdownPolicy) -> Builder {
+        self.shutdown_policy = policy;
+        self
+    }
+
+    /// Hold at most `capacity` queued jobs, so a burst of submissions can't
+    /// pile up without bound. What happens to a job submitted while the
+    /// queue is full is up to the [`rejection_policy`](Builder::rejection_policy).
+    /// Unbounded by default.
+    pub fn queue_capacity(mut self, capacity: usize) -> Builder {
+        self.queue_capacity = Some(capacity);
+        self
+    }
+
+    /// Defaults to [`RejectionPolicy::Block`].
+    pub fn rejection_policy(mut self, policy: RejectionPolicy) -> Builder {
+        self.rejection_policy = policy;
+        self
+    }
+
+    /// Call `handler` instead of the standard panic hook when a job panics,
+    /// with the panic's message and location and the worker's id, so the
+    /// panic can be logged with whatever context the job left behind
+    /// (in a thread-local, say) rather than printed to stderr on its own.
+    ///
+    /// Like a panic hook, it runs on the panicking thread before the panic
+    /// unwinds, and for panics the job catches itself too. Panics on threads
+    /// that aren't this pool's workers still go to the hook that was
+    /// installed before.
+    pub fn panic_handler(
+        mut self,
+        handler: impl Fn(&PanicHookInfo<'_>, usize) + Send + Sync + 'static,
+    ) -> Builder {
+        self.hooks.panic_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Give every worker a value of its own, made by calling `init` with the
+    /// worker's id when the worker starts, and lent to the jobs submitted
+    /// with [`ThreadPool::execute_with_state`]. Handy for reusing buffers or
+    /// connections across jobs without a global.
+    ///
+    /// The state is made and dropped on the worker's own thread, so it
+    /// doesn't have to be `Send`.
+    pub fn worker_state<S: 'static>(
+        mut self,
+        init: impl Fn(usize) -> S + Send + Sync + 'static,
+    ) -> Builder {
+        self.worker_state = Some(WorkerState::new(init));
+        self
+    }
+
+    /// Call `hook` with the worker's id just before each job starts.
+    pub fn on_job_start(mut self, hook: impl Fn(usize) + Send + Sync + 'static) -> Builder {
+        self.hooks.job_start = Some(Arc::new(hook));
+        self
+    }
+
+    /// Call `hook` with the worker's id and how long the job took, just after
+    /// each job finishes (whether or not it panicked).
+    pub fn on_job_end(mut self, hook: impl Fn(usize, Duration) + Send + Sync + 'static) -> Builder {
+        self.hooks.job_end = Some(Arc::new(hook));
+        self
+    }
+
+    /// Call `hook` with the worker's id and the panic payload whenever a job
+    /// panics.
+    pub fn on_worker_panic(
+        mut self,
+        hook: impl Fn(usize, &(dyn Any + Send)) + Send + Sync + 'static,
+    ) -> Builder {
+        self.hooks.worker_panic = Some(Arc::new(hook));
+        self
+    }
+
+    /// Start the pool's core workers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the core size or the queue capacity is zero, or if a
+    /// worker can't be spawned. [`Builder::try_build`] returns those as
+    /// errors instead.
+    pub fn build(self) -> ThreadPool {
+        self.try_build().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Start the pool's core workers, or say why they couldn't be.
+    pub fn try_build(self) -> Result<ThreadPool, PoolCreationError> {
+        if self.core == 0 {
+            return Err(PoolCreationError::NoThreads);
+        }
+        if self.queue_capacity == Some(0) {
+            return Err(PoolCreationError::NoQueueRoom);
+        }
+
+        let shared = Arc::new(Shared {
+            queue: JobQueue::with_capacity(self.queue_capacity),
+            workers: Mutex::new(Vec::with_capacity(self.core)),
+            size: AtomicUsize::new(0),
+            core: AtomicUsize::new(0),
+            retiring: AtomicUsize::new(0),
+            queued: Gauge::new(),
+            busy: Gauge::new(),
+            idle: Condvar::new(),
+            idle_lock: Mutex::new(()),
+            completed: Counter::new(),
+            panicked: Counter::new(),
+            queue_wait: Histogram::default(),
+            run_time: Histogram::default(),
+            next_id: AtomicUsize::new(0),
+            watchdog: Watchdog::default(),
+            hooks: self.hooks,
+            max: self.max,
+            idle_timeout: self.idle_timeout,
+            stack_size: self.stack_size,
+            nice: self.nice,
+            recycle_overdue: self.recycle_overdue,
+            shutdown_policy: self.shutdown_policy,
+            rejection_policy: self.rejection_policy,
+            worker_state: self.worker_state,
+        });
+
+        let pool = ThreadPool { shared };
+        // If one fails, dropping the pool stops the ones that started.
+        pool.try_resize(self.core)?;
+        Ok(pool)
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Builder {
+        Builder::new()
+    }
+}
+
+struct Worker {
+    id: usize,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Worker {
+    fn new(id: usize, shared: Arc<Shared>) -> io::Result<Worker> {
+        let mut builder = thread::Builder::new();
+        if let Some(bytes) = shared.stack_size {
+            builder = builder.stack_size(bytes);
+        }
+
+        let thread = builder.spawn(move || {
+            WORKER_ID.with(|worker| worker.set(Some(id)));
+            shared.hooks.install_panic_handler();
+            if let Some(nice) = shared.nice {
+                if let Err(err) = niceness::set_current_thread(nice) {
+                    eprintln!("Worker {id} couldn't set its priority to {nice}: {err}");
+                }
+            }
+            if let Some(state) = &shared.worker_state {
+                state.install(id);
+            }
+            Worker::run(id, &shared);
+            WorkerState::uninstall();
+        });
+        Ok(Worker {
+            id,
+            thread: Some(thread?),
+        })
+    }
+
+    fn run(id: usize, shared: &Arc<Shared>) {
+        loop {
+            // Only extras above the core size can time out.
+            let timeout = (shared.size.load(Ordering::SeqCst) > shared.core.load(Ordering::SeqCst))
+                .then_some(shared.idle_timeout);
+
+            match shared.queue.pop(timeout) {
+                Pop::Job(job) => {
+                    let overdue = shared.run_job(id, job);
+                    if overdue && shared.recycle_overdue {
+                        shared.size.fetch_sub(1, Ordering::SeqCst);
+                        shared.replace_worker();
+                        break;
+                    }
+                }
+                Pop::Woken => {}
+                Pop::TimedOut => {
+                    if shared.retire_if_extra() {
+                        break;
+                    }
+                    continue;
+                }
+                Pop::Closed => break,
+            }
+
+            if shared.take_retirement() {
+                break;
+            }
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.thread
+            .as_ref()
+            .is_none_or(thread::JoinHandle::is_finished)
+    }
+
+    /// Join the thread if it has already exited, returning whether it had.
+    fn join_if_finished(&mut self) -> bool {
+        match &self.thread {
+            Some(thread) if thread.is_finished() => {
+                if self.thread.take().unwrap().join().is_err() {
+                    eprintln!("Worker {} panicked", self.id);
+                }
+                true
+            }
+            Some(_) => false,
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn handles_return_job_results() {
+        let pool = ThreadPool::new(2);
+        let handles: Vec<_> = (0..4)
+            .map(|i| pool.execute(move || i * 10).unwrap())
+            .collect();
+
+        let results: Vec<i32> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(results, vec![0, 10, 20, 30]);
+    }
+
+    #[test]
+    fn a_panicking_job_is_reported_and_the_worker_survives() {
+        let pool = ThreadPool::new(1);
+
+        let err = pool.execute(|| panic!("boom")).unwrap().join().unwrap_err();
+        assert_eq!(err.downcast_ref::<&str>(), Some(&"boom"));
+
+        assert_eq!(
+            pool.execute(|| "still here").unwrap().join().unwrap(),
+            "still here"
+        );
+    }
+
+    fn wait_for_size(pool: &ThreadPool, size: usize) {
+        for _ in 0..100 {
+            if pool.current_size() == size {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("pool stayed at {} threads", pool.current_size());
+    }
+
+    #[test]
+    fn building_reports_what_went_wrong() {
+        use std::error::Error;
+
+        let failure = |built: Result<ThreadPool, PoolCreationError>| match built {
+            Ok(_) => panic!("built a pool"),
+            Err(err) => err,
+        };
+
+        let err = failure(ThreadPool::build(0));
+        assert!(matches!(err, PoolCreationError::NoThreads));
+        assert_eq!(err.to_string(), "a thread pool needs at least one thread");
+
+        let err = failure(Builder::new().queue_capacity(0).try_build());
+        assert!(matches!(err, PoolCreationError::NoQueueRoom));
+
+        // No system has an address space big enough for this stack.
+        let err = failure(
+            Builder::new()
+                .core_threads(1)
+                .stack_size(usize::MAX / 2)
+                .try_build(),
+        );
+        assert!(matches!(err, PoolCreationError::Spawn(_)));
+        assert!(err.source().unwrap().is::<io::Error>());
+
+        let pool = ThreadPool::build(2).unwrap();
+        assert_eq!(pool.execute(|| 7).unwrap().join().unwrap(), 7);
+    }
+
+    #[test]
+    fn resizing_spawns_and_retires_workers() {
+        let pool = ThreadPool::new(2);
+        assert_eq!(pool.current_size(), 2);
+
+        pool.resize(5);
+        assert_eq!(pool.current_size(), 5);
+
+        pool.resize(1);
+        wait_for_size(&pool, 1);
+        assert_eq!(pool.shared.workers.lock().unwrap().len(), 5);
+
+        pool.resize(3);
+        assert_eq!(pool.current_size(), 3);
+        assert_eq!(pool.shared.workers.lock().unwrap().len(), 3);
+        assert_eq!(pool.execute(|| 7).unwrap().join().unwrap(), 7);
+    }
+
+    #[test]
+    fn shrinking_waits_for_running_jobs() {
+        let pool = ThreadPool::new(2);
+        let (started, wait_started) = mpsc::channel();
+        let (finish, wait_finish) = mpsc::channel::<()>();
+
+        let busy = pool
+            .execute(move || {
+                started.send(()).unwrap();
+                wait_finish.recv().unwrap();
+                "done"
+            })
+            .unwrap();
+        wait_started.recv().unwrap();
+
+        pool.resize(1);
+        wait_for_size(&pool, 1);
+        finish.send(()).unwrap();
+        assert_eq!(busy.join().unwrap(), "done");
+    }
+
+    #[test]
+    fn extra_workers_are_spawned_on_demand_and_retired_when_idle() {
+        let pool = ThreadPool::builder()
+            .core_threads(1)
+            .max_threads(3)
+            .idle_timeout(Duration::from_millis(20))
+            .build();
+        let (release, wait_release) = mpsc::channel::<()>();
+        let wait_release = Arc::new(Mutex::new(wait_release));
+
+        let jobs: Vec<_> = (0..3)
+            .map(|_| {
+                let wait_release = Arc::clone(&wait_release);
+                pool.execute(move || wait_release.lock().unwrap().recv().unwrap())
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(pool.current_size(), 3);
+
+        for _ in 0..3 {
+            release.send(()).unwrap();
+        }
+        for job in jobs {
+            job.join().unwrap();
+        }
+        wait_for_size(&pool, 1);
+        assert_eq!(pool.core_size(), 1);
+    }
+
+    #[test]
+    fn workers_get_the_configured_stack_size() {
+        fn depth(n: u64) -> u64 {
+            let padding = std::hint::black_box([0u8; 1024]);
+            if n == 0 {
+                padding[0] as u64
+            } else {
+                depth(n - 1) + 1
+            }
+        }
+
+        // Deeper than the default 2 MiB stack allows.
+        let pool = ThreadPool::builder()
+            .core_threads(1)
+            .stack_size(64 * 1024 * 1024)
+            .build();
+        assert_eq!(
+            pool.execute(|| depth(10_000)).unwrap().join().unwrap(),
+            10_000
+        );
+    }
+
+    #[test]
+    fn metrics_count_finished_and_panicked_jobs() {
+        let pool = ThreadPool::new(1);
+        pool.execute(|| ()).unwrap().join().unwrap();
+        pool.execute(|| panic!("boom")).unwrap().join().unwrap_err();
+
+        // The worker bumps `completed` just after handing back the result.
+        for _ in 0..100 {
+            if pool.metrics().completed == 2 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let metrics = pool.metrics();
+        assert_eq!(
+            metrics,
+            Metrics {
+                threads: 1,
+                queued: 0,
+                running: 0,
+                completed: 2,
+                panicked: 1,
+                overdue: 0,
+                ..metrics
+            }
+        );
+        assert_eq!(metrics.queue_wait.count(), 2);
+        assert_eq!(metrics.run_time.count(), 2);
+    }
+
+    #[test]
+    fn metrics_write_as_json() {
+        use hello_macro::json::ToJson;
+
+        let mut metrics = ThreadPool::new(1).metrics();
+        metrics.completed = 5;
+        metrics.run_time.buckets[2] = 5;
+        assert_eq!(
+            metrics.to_json(),
+            concat!(
+                r#"{"threads":1,"queued":0,"running":0,"completed":5,"panicked":0,"overdue":0,"#,
+                r#""queue_wait":{"buckets":[0,0,0,0,0,0,0,0]},"#,
+                r#""run_time":{"buckets":[0,0,5,0,0,0,0,0]}}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn slow_jobs_show_up_in_the_tail() {
+        let pool = ThreadPool::new(1);
+        let slow = pool
+            .execute(|| thread::sleep(Duration::from_millis(20)))
+            .unwrap();
+        let quick: Vec<_> = (0..9).map(|_| pool.execute(|| ()).unwrap()).collect();
+        slow.join().unwrap();
+        for handle in quick {
+            handle.join().unwrap();
+        }
+        pool.wait_idle();
+
+        let metrics = pool.metrics();
+        assert!(metrics.run_time.quantile(0.5).unwrap() <= Duration::from_millis(1));
+        assert_eq!(
+            metrics.run_time.quantile(1.0),
+            Some(Duration::from_millis(100))
+        );
+        // The quick jobs all queued up behind the slow one.
+        assert!(metrics.queue_wait.quantile(0.9) >= Some(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn higher_priority_jobs_jump_the_queue() {
+        let pool = ThreadPool::new(1);
+        let (release, wait_release) = mpsc::channel::<()>();
+        let blocker = pool.execute(move || wait_release.recv().unwrap()).unwrap();
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let handles: Vec<_> = [
+            (Priority::Low, "low"),
+            (Priority::Normal, "normal"),
+            (Priority::High, "high 1"),
+            (Priority::High, "high 2"),
+        ]
+        .into_iter()
+        .map(|(priority, name)| {
+            let order = Arc::clone(&order);
+            pool.execute_with_priority(priority, move || order.lock().unwrap().push(name))
+                .unwrap()
+        })
+        .collect();
+
+        release.send(()).unwrap();
+        blocker.join().unwrap();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec!["high 1", "high 2", "normal", "low"]
+        );
+    }
+
+    #[test]
+    fn shutdown_abandons_what_misses_the_deadline() {
+        let pool = ThreadPool::new(1);
+        let (release, wait_release) = mpsc::channel::<()>();
+        let (started, wait_started) = mpsc::channel();
+        pool.execute(move || {
+            started.send(()).unwrap();
+            wait_release.recv().unwrap();
+        })
+        .unwrap();
+        wait_started.recv().unwrap();
+        let queued: Vec<_> = (0..2).map(|i| pool.execute(move || i).unwrap()).collect();
+
+        let report = pool.shutdown(Duration::from_millis(50));
+        assert_eq!(
+            report,
+            ShutdownReport {
+                abandoned_jobs: 2,
+                detached_workers: 1,
+            }
+        );
+        for handle in queued {
+            assert!(handle.join().is_err());
+        }
+        release.send(()).unwrap();
+    }
+
+    #[test]
+    fn shutdown_waits_for_queued_jobs_that_fit() {
+        let pool = ThreadPool::new(2);
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                pool.execute(move || thread::sleep(Duration::from_millis(5 * i)))
+                    .unwrap()
+            })
+            .collect();
+
+        assert_eq!(
+            pool.shutdown(Duration::from_secs(5)),
+            ShutdownReport::default()
+        );
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    /// Poll `future` on this thread, parking between polls.
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        use std::task::{Context, Poll, Wake, Waker};
+
+        struct Unpark(thread::Thread);
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn job_futures_wake_the_awaiting_task() {
+        let pool = ThreadPool::new(2);
+        let slow = pool
+            .execute_future(|| {
+                thread::sleep(Duration::from_millis(20));
+                6
+            })
+            .unwrap();
+        let fast = pool.execute_future(|| 7).unwrap();
+
+        assert_eq!(block_on(async { slow.await * fast.await }), 42);
+    }
+
+    #[test]
+    fn overdue_jobs_are_flagged_and_their_worker_recycled() {
+        let pool = ThreadPool::builder()
+            .core_threads(1)
+            .recycle_overdue_workers(true)
+            .build();
+
+        let slow = pool
+            .execute_with_timeout(Duration::from_millis(5), || {
+                thread::sleep(Duration::from_millis(50));
+                "slow"
+            })
+            .unwrap();
+        // Queued behind the slow job, with no submission after it to spawn
+        // a worker, so only the replacement can run it.
+        let (sender, ran) = mpsc::channel();
+        pool.execute(move || sender.send("next").unwrap()).unwrap();
+        assert_eq!(slow.join().unwrap(), "slow");
+        assert_eq!(pool.metrics().overdue, 1);
+        assert_eq!(ran.recv_timeout(Duration::from_secs(5)), Ok("next"));
+        wait_for_size(&pool, 1);
+        pool.wait_idle();
+
+        let quick = pool
+            .execute_with_timeout(Duration::from_secs(5), || "quick")
+            .unwrap();
+        assert_eq!(quick.join().unwrap(), "quick");
+        assert_eq!(pool.current_size(), 1);
+        assert_eq!(pool.metrics().overdue, 1);
+    }
+
+    #[test]
+    fn hooks_see_every_job_and_panic() {
+        let (events, seen) = mpsc::channel();
+        let (start, end, panicked) = (events.clone(), events.clone(), events);
+        let pool = ThreadPool::builder()
+            .core_threads(1)
+            .on_job_start(move |worker| start.send(format!("start {worker}")).unwrap())
+            .on_job_end(move |worker, _| end.send(format!("end {worker}")).unwrap())
+            .on_worker_panic(move |worker, payload| {
+                let message = payload.downcast_ref::<&str>().unwrap();
+                panicked.send(format!("panic {worker}: {message}")).unwrap();
+            })
+            .build();
+
+        pool.execute(|| ()).unwrap().join().unwrap();
+        pool.execute(|| panic!("boom")).unwrap().join().unwrap_err();
+        drop(pool);
+
+        assert_eq!(
+            seen.iter().collect::<Vec<_>>(),
+            vec!["start 0", "end 0", "start 0", "panic 0: boom", "end 0"]
+        );
+    }
+
+    #[test]
+    fn panicking_hooks_are_reported_and_keep_the_worker() {
+        let (panics, seen) = mpsc::channel();
+        let pool = ThreadPool::builder()
+            .core_threads(1)
+            .on_job_start(|_| panic!("start"))
+            .on_job_end(|_, _| panic!("end"))
+            .on_worker_panic(move |_, payload| {
+                let message = payload.downcast_ref::<&str>().unwrap();
+                panics.send(message.to_string()).unwrap();
+            })
+            .build();
+
+        assert_eq!(pool.execute(|| 1).unwrap().join().unwrap(), 1);
+        assert_eq!(pool.execute(|| 2).unwrap().join().unwrap(), 2);
+        pool.wait_idle();
+
+        let metrics = pool.metrics();
+        assert_eq!((metrics.threads, metrics.running), (1, 0));
+        assert_eq!(metrics.panicked, 4);
+        drop(pool);
+        assert_eq!(
+            seen.iter().collect::<Vec<_>>(),
+            vec!["start", "end", "start", "end"]
+        );
+    }
+
+    /// Start a job on `pool`'s only worker that blocks until the returned
+    /// sender is used, and queue `jobs` more behind it.
+    fn queue_behind_a_blocker(
+        pool: &ThreadPool,
+        jobs: usize,
+    ) -> (mpsc::Sender<()>, Vec<JobHandle<usize>>) {
+        let (release, wait_release) = mpsc::channel::<()>();
+        let (started, wait_started) = mpsc::channel();
+        pool.execute(move || {
+            started.send(()).unwrap();
+            wait_release.recv().unwrap();
+        })
+        .unwrap();
+        wait_started.recv().unwrap();
+        (
+            release,
+            (0..jobs)
+                .map(|i| pool.execute(move || i).unwrap())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn closing_drains_the_queue_by_default() {
+        let pool = ThreadPool::new(1);
+        let (release, queued) = queue_behind_a_blocker(&pool, 3);
+
+        release.send(()).unwrap();
+        assert_eq!(pool.close(), 0);
+        for (i, handle) in queued.into_iter().enumerate() {
+            assert_eq!(handle.join().unwrap(), i);
+        }
+    }
+
+    #[test]
+    fn closing_with_discard_drops_queued_jobs() {
+        let pool = ThreadPool::builder()
+            .core_threads(1)
+            .shutdown_policy(ShutdownPolicy::Discard)
+            .build();
+        let (release, queued) = queue_behind_a_blocker(&pool, 3);
+
+        let closer = thread::spawn(move || pool.close());
+        // Let `close` discard the queue before the running job finishes.
+        thread::sleep(Duration::from_millis(20));
+        release.send(()).unwrap();
+
+        assert_eq!(closer.join().unwrap(), 3);
+        for handle in queued {
+            assert!(handle.join().is_err());
+        }
+    }
+
+    #[test]
+    fn submitting_fails_once_shutdown_has_started() {
+        let pool = ThreadPool::new(1);
+        assert!(!pool.is_shutdown());
+
+        // What `close`, `shutdown` and dropping the pool all do first.
+        pool.shared.queue.close();
+        assert!(pool.is_shutdown());
+        assert_eq!(pool.execute(|| ()).unwrap_err(), ExecuteError::Shutdown);
+        assert!(pool.execute_future(|| ()).is_err());
+        assert_eq!(pool.metrics().queued, 0);
+    }
+
+    #[test]
+    fn a_job_submitting_follow_up_work_during_shutdown_gets_an_error() {
+        let pool = Arc::new(ThreadPool::new(1));
+        let (release, wait_release) = mpsc::channel::<()>();
+        let (started, wait_started) = mpsc::channel();
+
+        let weak = Arc::downgrade(&pool);
+        let outcome = pool
+            .execute(move || {
+                let pool = weak.upgrade().unwrap();
+                started.send(()).unwrap();
+                wait_release.recv().unwrap();
+                pool.execute(|| ()).map(drop)
+            })
+            .unwrap();
+        wait_started.recv().unwrap();
+
+        pool.shared.queue.close();
+        release.send(()).unwrap();
+        assert_eq!(outcome.join().unwrap(), Err(ExecuteError::Shutdown));
+    }
+
+    #[test]
+    fn jobs_share_their_workers_state() {
+        let pool = ThreadPool::builder()
+            .core_threads(2)
+            .worker_state(|worker| (worker, Vec::<usize>::new()))
+            .build();
+
+        let handles: Vec<_> = (0..10)
+            .map(|i| {
+                pool.execute_with_state(move |(worker, seen): &mut (usize, Vec<usize>)| {
+                    seen.push(i);
+                    (*worker, seen.len())
+                })
+                .unwrap()
+            })
+            .collect();
+
+        // Each worker's list grows by one for every job it runs.
+        let mut seen = [Vec::new(), Vec::new()];
+        for handle in handles {
+            let (worker, len) = handle.join().unwrap();
+            seen[worker].push(len);
+        }
+        for lens in &mut seen {
+            lens.sort();
+            assert_eq!(*lens, (1..=lens.len()).collect::<Vec<_>>());
+        }
+        assert_eq!(seen[0].len() + seen[1].len(), 10);
+    }
+
+    #[test]
+    fn jobs_with_state_wait_for_room_instead_of_running_on_the_caller() {
+        let pool = ThreadPool::builder()
+            .core_threads(1)
+            .queue_capacity(2)
+            .rejection_policy(RejectionPolicy::CallerRuns)
+            .worker_state(|worker| worker)
+            .build();
+        let (release, queued) = queue_behind_a_blocker(&pool, 2);
+
+        thread::scope(|s| {
+            let submitted = s.spawn(|| {
+                pool.execute_with_state(|worker: &mut usize| (*worker, thread::current().id()))
+                    .unwrap()
+            });
+            // Still waiting for room, not running on the submitting thread.
+            thread::sleep(Duration::from_millis(20));
+            assert!(!submitted.is_finished());
+
+            release.send(()).unwrap();
+            let submitter = submitted.thread().id();
+            let (worker, ran_on) = submitted.join().unwrap().join().unwrap();
+            assert_eq!(worker, 0);
+            assert_ne!(ran_on, submitter);
+        });
+        for handle in queued {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "worker state is a u32, not a")]
+    fn asking_for_the_wrong_state_type_panics() {
+        let pool = ThreadPool::builder()
+            .core_threads(1)
+            .worker_state(|_| 0u32)
+            .build();
+        let _ = pool.execute_with_state(|_: &mut String| ());
+    }
+
+    #[test]
+    fn the_global_pool_is_shared() {
+        assert!(std::ptr::eq(global(), global()));
+        assert_eq!(
+            global().core_size(),
+            thread::available_parallelism().map_or(1, |n| n.get())
+        );
+        assert_eq!(global().execute(|| 6 * 7).unwrap().join().unwrap(), 42);
+    }
+
+    fn bounded_pool(policy: RejectionPolicy) -> ThreadPool {
+        ThreadPool::builder()
+            .core_threads(1)
+            .queue_capacity(2)
+            .rejection_policy(policy)
+            .build()
+    }
+
+    #[test]
+    fn a_full_queue_can_fail_or_run_on_the_caller() {
+        let pool = bounded_pool(RejectionPolicy::Error);
+        let (release, queued) = queue_behind_a_blocker(&pool, 2);
+        assert_eq!(pool.execute(|| ()).unwrap_err(), ExecuteError::Full);
+        release.send(()).unwrap();
+        for handle in queued {
+            handle.join().unwrap();
+        }
+
+        let pool = bounded_pool(RejectionPolicy::CallerRuns);
+        let (release, _queued) = queue_behind_a_blocker(&pool, 2);
+        let caller = thread::current().id();
+        let ran_on = pool.execute(|| thread::current().id()).unwrap();
+        assert_eq!(ran_on.join().unwrap(), caller);
+        release.send(()).unwrap();
+    }
+
+    #[test]
+    fn jobs_run_on_the_caller_are_counted_and_timed() {
+        let (started, starts) = mpsc::channel();
+        let pool = ThreadPool::builder()
+            .core_threads(1)
+            .queue_capacity(2)
+            .rejection_policy(RejectionPolicy::CallerRuns)
+            .on_job_start(move |worker| started.send(worker).unwrap())
+            .build();
+        let (release, queued) = queue_behind_a_blocker(&pool, 2);
+        assert_eq!(starts.try_iter().collect::<Vec<_>>(), vec![0]);
+
+        let late = pool
+            .execute_with_timeout(Duration::from_millis(5), || {
+                thread::sleep(Duration::from_millis(30));
+            })
+            .unwrap();
+        late.join().unwrap();
+        let metrics = pool.metrics();
+        assert_eq!(
+            (metrics.completed, metrics.running, metrics.queued),
+            (1, 1, 2)
+        );
+        assert_eq!(metrics.overdue, 1);
+        assert_eq!(metrics.run_time.buckets.iter().sum::<u64>(), 1);
+        // Under an id no worker has.
+        assert_eq!(starts.try_iter().collect::<Vec<_>>(), vec![1]);
+
+        release.send(()).unwrap();
+        for handle in queued {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn a_full_queue_can_drop_the_oldest_job() {
+        let pool = bounded_pool(RejectionPolicy::DropOldest);
+        let (release, queued) = queue_behind_a_blocker(&pool, 2);
+        let newest = pool.execute(|| 2).unwrap();
+        release.send(()).unwrap();
+
+        let results: Vec<_> = queued.into_iter().map(|h| h.join().ok()).collect();
+        assert_eq!(results, vec![None, Some(1)]);
+        assert_eq!(newest.join().unwrap(), 2);
+    }
+
+    #[test]
+    fn an_evicted_scoped_job_is_dropped_before_the_scope_returns() {
+        use std::sync::atomic::AtomicBool;
+
+        struct SlowDrop<'a>(&'a AtomicBool);
+
+        impl Drop for SlowDrop<'_> {
+            fn drop(&mut self) {
+                thread::sleep(Duration::from_millis(20));
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let pool = Arc::new(bounded_pool(RejectionPolicy::DropOldest));
+        let (release, _) = queue_behind_a_blocker(&pool, 0);
+        let dropped = AtomicBool::new(false);
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.scope(|s| {
+                let slow = SlowDrop(&dropped);
+                s.execute(move || drop(slow));
+                pool.execute(|| 0).unwrap();
+                // Evicted, and so dropped, on another thread.
+                let evicting = Arc::clone(&pool);
+                thread::spawn(move || evicting.execute(|| 1).unwrap());
+            });
+        }));
+
+        assert!(outcome.is_err());
+        assert!(dropped.load(Ordering::SeqCst));
+        release.send(()).unwrap();
+    }
+
+    #[test]
+    fn a_full_queue_blocks_the_caller_by_default() {
+        let pool = bounded_pool(RejectionPolicy::Block);
+        let (release, _queued) = queue_behind_a_blocker(&pool, 2);
+
+        thread::scope(|s| {
+            let submitter = s.spawn(|| pool.execute(|| "made it").unwrap().join().unwrap());
+            thread::sleep(Duration::from_millis(20));
+            assert!(!submitter.is_finished());
+
+            release.send(()).unwrap();
+            assert_eq!(submitter.join().unwrap(), "made it");
+        });
+    }
+
+    #[test]
+    fn submitted_jobs_send_back_their_value() {
+        let pool = ThreadPool::new(2);
+        let receivers: Vec<_> = (0..4)
+            .map(|i| pool.submit(move || i * 2).unwrap())
+            .collect();
+        let values: Vec<_> = receivers.into_iter().map(|r| r.recv().unwrap()).collect();
+        assert_eq!(values, vec![0, 2, 4, 6]);
+
+        let panicked = pool.submit(|| -> u8 { panic!("boom") }).unwrap();
+        assert_eq!(panicked.recv(), Err(oneshot::RecvError));
+    }
+
+    #[test]
+    fn the_panic_handler_sees_where_a_job_panicked() {
+        let (panics, seen) = mpsc::channel();
+        let pool = ThreadPool::builder()
+            .core_threads(1)
+            .panic_handler(move |info, worker| {
+                let message = info.payload().downcast_ref::<&str>().unwrap();
+                let line = info.location().unwrap().line();
+                panics.send((worker, *message, line)).unwrap();
+            })
+            .build();
+
+        let line = line!() + 1;
+        pool.execute(|| panic!("boom")).unwrap().join().unwrap_err();
+        assert_eq!(seen.recv().unwrap(), (0, "boom", line));
+
+        // Other threads keep the usual hook.
+        thread::spawn(|| panic!("not a worker")).join().unwrap_err();
+        assert!(seen.try_recv().is_err());
+    }
+
+    #[test]
+    fn wait_idle_returns_once_every_job_is_done() {
+        let pool = ThreadPool::new(3);
+        let done = Arc::new(AtomicUsize::new(0));
+        for i in 0..12 {
+            let done = Arc::clone(&done);
+            pool.execute(move || {
+                thread::sleep(Duration::from_millis(i % 4));
+                done.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap();
+        }
+
+        pool.wait_idle();
+        assert_eq!(done.load(Ordering::SeqCst), 12);
+        assert_eq!(pool.metrics().queued, 0);
+
+        // Returns straight away when there's nothing to wait for.
+        pool.wait_idle();
+    }
+}
@@ -0,0 +1,263 @@
+//! Counters and gauges that any thread can update, for the numbers a
+//! program keeps about itself.
+//!
+//! Listing 19-10's `static mut COUNTER` (in `advanced_features`) is the
+//! start of this, and the reason it needs `unsafe`: two threads adding to
+//! it at once is a data race. A [`Counter`] is the same global number as
+//! an `AtomicU64`, so it can be a plain `static` and updated without
+//! `unsafe`. A [`Gauge`] is one that goes down as well as up, like the
+//! number of requests in flight.
+//!
+//! A [`Registry`] names a set of them, so they can all be read at once
+//! with [`snapshot`](Registry::snapshot), or read and zeroed with
+//! [`reset`](Registry::reset).
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// A count that only goes up, until it's reset.
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub const fn new() -> Counter {
+        Counter(AtomicU64::new(0))
+    }
+
+    /// Count one more, returning the new count.
+    pub fn inc(&self) -> u64 {
+        self.add(1)
+    }
+
+    pub fn add(&self, n: u64) -> u64 {
+        self.0.fetch_add(n, Ordering::Relaxed) + n
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Start again from 0, returning the count so far.
+    pub fn reset(&self) -> u64 {
+        self.0.swap(0, Ordering::Relaxed)
+    }
+}
+
+/// A level that goes up and down.
+///
+/// Unlike a [`Counter`], a gauge is often part of how a program decides
+/// what to do next (no more jobs queued, so the pool is idle), so its
+/// updates are `SeqCst`, and return the new level.
+#[derive(Debug, Default)]
+pub struct Gauge(AtomicU64);
+
+impl Gauge {
+    pub const fn new() -> Gauge {
+        Gauge(AtomicU64::new(0))
+    }
+
+    pub fn inc(&self) -> u64 {
+        self.add(1)
+    }
+
+    pub fn dec(&self) -> u64 {
+        self.sub(1)
+    }
+
+    pub fn add(&self, n: u64) -> u64 {
+        self.0.fetch_add(n, Ordering::SeqCst) + n
+    }
+
+    /// Panics if the level would go below 0, which means something was
+    /// taken away twice.
+    pub fn sub(&self, n: u64) -> u64 {
+        let before = self.0.fetch_sub(n, Ordering::SeqCst);
+        assert!(before >= n, "gauge went below 0: {before} - {n}");
+        before - n
+    }
+
+    pub fn set(&self, level: u64) {
+        self.0.store(level, Ordering::SeqCst);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Metric {
+    Counter(Arc<Counter>),
+    Gauge(Arc<Gauge>),
+}
+
+/// Counters and gauges by name.
+#[derive(Debug, Default)]
+pub struct Registry {
+    metrics: Mutex<BTreeMap<&'static str, Metric>>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    /// The counter called `name`, made the first time it's asked for.
+    ///
+    /// Panics if `name` is a gauge.
+    pub fn counter(&self, name: &'static str) -> Arc<Counter> {
+        let mut metrics = self.metrics.lock().unwrap();
+        let metric = metrics
+            .entry(name)
+            .or_insert_with(|| Metric::Counter(Arc::default()));
+        match metric {
+            Metric::Counter(counter) => Arc::clone(counter),
+            Metric::Gauge(_) => panic!("`{name}` is a gauge, not a counter"),
+        }
+    }
+
+    /// The gauge called `name`, made the first time it's asked for.
+    ///
+    /// Panics if `name` is a counter.
+    pub fn gauge(&self, name: &'static str) -> Arc<Gauge> {
+        let mut metrics = self.metrics.lock().unwrap();
+        let metric = metrics
+            .entry(name)
+            .or_insert_with(|| Metric::Gauge(Arc::default()));
+        match metric {
+            Metric::Gauge(gauge) => Arc::clone(gauge),
+            Metric::Counter(_) => panic!("`{name}` is a counter, not a gauge"),
+        }
+    }
+
+    /// Every metric's current value.
+    ///
+    /// Each is read atomically, but not all of them together: one that's
+    /// updated while this runs may or may not be included.
+    pub fn snapshot(&self) -> Snapshot {
+        self.read(|metric| match metric {
+            Metric::Counter(counter) => counter.get(),
+            Metric::Gauge(gauge) => gauge.get(),
+        })
+    }
+
+    /// A snapshot that also zeroes the counters, so the next one counts
+    /// from here. Gauges are levels rather than totals, and keep theirs.
+    pub fn reset(&self) -> Snapshot {
+        self.read(|metric| match metric {
+            Metric::Counter(counter) => counter.reset(),
+            Metric::Gauge(gauge) => gauge.get(),
+        })
+    }
+
+    fn read(&self, value: impl Fn(&Metric) -> u64) -> Snapshot {
+        let metrics = self.metrics.lock().unwrap();
+        Snapshot(
+            metrics
+                .iter()
+                .map(|(&name, metric)| (name, value(metric)))
+                .collect(),
+        )
+    }
+}
+
+/// A [`Registry`]'s values at one point, sorted by name.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Snapshot(BTreeMap<&'static str, u64>);
+
+impl Snapshot {
+    pub fn get(&self, name: &str) -> Option<u64> {
+        self.0.get(name).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, u64)> + '_ {
+        self.0.iter().map(|(&name, &value)| (name, value))
+    }
+}
+
+/// One `name value` line per metric.
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in self.iter() {
+            writeln!(f, "{name} {value}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    static HITS: Counter = Counter::new();
+
+    #[test]
+    fn a_static_counter_needs_no_unsafe() {
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..1000 {
+                        HITS.inc();
+                    }
+                });
+            }
+        });
+        assert_eq!(HITS.get(), 4000);
+        assert_eq!(HITS.reset(), 4000);
+        assert_eq!(HITS.get(), 0);
+    }
+
+    #[test]
+    fn gauges_go_both_ways() {
+        let gauge = Gauge::new();
+        assert_eq!(gauge.add(3), 3);
+        assert_eq!(gauge.dec(), 2);
+        gauge.set(10);
+        assert_eq!(gauge.sub(4), 6);
+    }
+
+    #[test]
+    #[should_panic(expected = "gauge went below 0: 1 - 2")]
+    fn gauges_do_not_go_negative() {
+        let gauge = Gauge::new();
+        gauge.inc();
+        gauge.sub(2);
+    }
+
+    #[test]
+    fn reset_zeroes_counters_but_not_gauges() {
+        let registry = Registry::new();
+        registry.counter("requests_total").add(5);
+        registry.gauge("requests_in_flight").add(2);
+        // Asking again gives the same metric.
+        registry.counter("requests_total").inc();
+
+        let before = registry.reset();
+        assert_eq!(before.get("requests_total"), Some(6));
+        assert_eq!(
+            before.to_string(),
+            "requests_in_flight 2\nrequests_total 6\n"
+        );
+
+        let after = registry.snapshot();
+        assert_eq!(after.get("requests_total"), Some(0));
+        assert_eq!(after.get("requests_in_flight"), Some(2));
+        assert_eq!(after.get("missing"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "`depth` is a gauge, not a counter")]
+    fn a_name_is_one_kind_of_metric() {
+        let registry = Registry::new();
+        registry.gauge("depth");
+        registry.counter("depth");
+    }
+}
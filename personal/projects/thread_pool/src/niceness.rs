@@ -0,0 +1,121 @@
+//! Setting the scheduling priority of the calling thread, so a pool doing
+//! background work can be told to yield to more urgent threads (or the
+//! other way around).
+//!
+//! Priorities are given as Unix nice values, from -20 (most favoured) to 19
+//! (least). Linux schedules each thread with its own nice value; Windows
+//! maps it onto the nearest thread priority level. Elsewhere, nice applies
+//! to the whole process, so setting it for one thread isn't supported.
+
+use std::io;
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::{
+        io,
+        os::raw::{c_int, c_uint},
+    };
+
+    const PRIO_PROCESS: c_int = 0;
+
+    extern "C" {
+        fn gettid() -> c_int;
+        fn setpriority(which: c_int, who: c_uint, prio: c_int) -> c_int;
+    }
+
+    pub(super) fn set_current_thread(nice: i32) -> io::Result<()> {
+        // SAFETY: both calls only read their arguments. On Linux, a thread
+        // id passed as PRIO_PROCESS's `who` names just that thread.
+        let result = unsafe { setpriority(PRIO_PROCESS, gettid() as c_uint, nice) };
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::{
+        ffi::c_void,
+        io,
+        os::raw::{c_int, c_long},
+    };
+
+    const THREAD_PRIORITY_LOWEST: c_int = -2;
+    const THREAD_PRIORITY_BELOW_NORMAL: c_int = -1;
+    const THREAD_PRIORITY_NORMAL: c_int = 0;
+    const THREAD_PRIORITY_ABOVE_NORMAL: c_int = 1;
+    const THREAD_PRIORITY_HIGHEST: c_int = 2;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentThread() -> *mut c_void;
+        fn SetThreadPriority(thread: *mut c_void, priority: c_int) -> c_long;
+    }
+
+    pub(super) fn set_current_thread(nice: i32) -> io::Result<()> {
+        let priority = match nice {
+            ..=-10 => THREAD_PRIORITY_HIGHEST,
+            -9..=-1 => THREAD_PRIORITY_ABOVE_NORMAL,
+            0 => THREAD_PRIORITY_NORMAL,
+            1..=9 => THREAD_PRIORITY_BELOW_NORMAL,
+            10.. => THREAD_PRIORITY_LOWEST,
+        };
+
+        // SAFETY: GetCurrentThread returns a pseudo-handle that's always
+        // valid on the calling thread, and needn't be closed.
+        let ok = unsafe { SetThreadPriority(GetCurrentThread(), priority) };
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod imp {
+    use std::io;
+
+    pub(super) fn set_current_thread(_nice: i32) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "per-thread priorities aren't supported on this platform",
+        ))
+    }
+}
+
+/// Set the calling thread's priority to the nice value `nice`.
+///
+/// Raising a thread's priority (a lower nice value than it has) usually
+/// needs extra privileges, and fails without them.
+pub(crate) fn set_current_thread(nice: i32) -> io::Result<()> {
+    imp::set_current_thread(nice)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::{fs, thread};
+
+    use super::*;
+
+    /// The calling thread's nice value, the 19th field of its `stat`.
+    fn current_nice() -> i32 {
+        let stat = fs::read_to_string("/proc/thread-self/stat").unwrap();
+        // Skip past the command name, which may contain spaces.
+        let fields = &stat[stat.rfind(')').unwrap() + 2..];
+        fields.split(' ').nth(16).unwrap().parse().unwrap()
+    }
+
+    #[test]
+    fn lowering_priority_only_affects_the_calling_thread() {
+        let before = current_nice();
+        let lowered = thread::spawn(move || {
+            set_current_thread(before + 3).unwrap();
+            current_nice()
+        });
+
+        assert_eq!(lowered.join().unwrap(), before + 3);
+        assert_eq!(current_nice(), before);
+    }
+}
@@ -0,0 +1,135 @@
+//! A channel that carries exactly one value, for [`ThreadPool::submit`].
+//!
+//! It's a mutex and condvar around an `Option`, which is all one value
+//! needs, where `mpsc` brings a whole queue along.
+//!
+//! [`ThreadPool::submit`]: crate::ThreadPool::submit
+
+use std::{
+    error::Error,
+    fmt,
+    sync::{Arc, Condvar, Mutex},
+};
+
+struct Slot<T> {
+    value: Mutex<State<T>>,
+    ready: Condvar,
+}
+
+enum State<T> {
+    Waiting,
+    Sent(T),
+    /// The sender was dropped without sending, or the value was taken.
+    Closed,
+}
+
+/// Receives the one value its job returns.
+pub struct Receiver<T> {
+    slot: Arc<Slot<T>>,
+}
+
+pub(crate) struct Sender<T> {
+    slot: Arc<Slot<T>>,
+}
+
+/// The job never produced a value, because it panicked or was dropped
+/// before it ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvError;
+
+pub(crate) fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let slot = Arc::new(Slot {
+        value: Mutex::new(State::Waiting),
+        ready: Condvar::new(),
+    });
+    let sender = Sender {
+        slot: Arc::clone(&slot),
+    };
+    (sender, Receiver { slot })
+}
+
+impl<T> Sender<T> {
+    pub(crate) fn send(self, value: T) {
+        *self.slot.value.lock().unwrap() = State::Sent(value);
+        self.slot.ready.notify_one();
+        // Dropping `self` now finds the value sent, and leaves it be.
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.slot.value.lock().unwrap();
+        if let State::Waiting = *state {
+            *state = State::Closed;
+            self.slot.ready.notify_one();
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Block until the job's value arrives.
+    pub fn recv(self) -> Result<T, RecvError> {
+        let mut state = self.slot.value.lock().unwrap();
+        loop {
+            match std::mem::replace(&mut *state, State::Closed) {
+                State::Waiting => {
+                    *state = State::Waiting;
+                    state = self.slot.ready.wait(state).unwrap();
+                }
+                State::Sent(value) => return Ok(value),
+                State::Closed => return Err(RecvError),
+            }
+        }
+    }
+
+    /// Take the value if it has arrived, without blocking. `Ok(None)` means
+    /// the job hasn't finished yet.
+    pub fn try_recv(&mut self) -> Result<Option<T>, RecvError> {
+        let mut state = self.slot.value.lock().unwrap();
+        match std::mem::replace(&mut *state, State::Closed) {
+            State::Waiting => {
+                *state = State::Waiting;
+                Ok(None)
+            }
+            State::Sent(value) => Ok(Some(value)),
+            State::Closed => Err(RecvError),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver").finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the job finished without sending a value")
+    }
+}
+
+impl Error for RecvError {}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn carries_one_value_across_threads() {
+        let (sender, mut receiver) = channel();
+        assert_eq!(receiver.try_recv(), Ok(None));
+
+        thread::spawn(move || sender.send("hello"));
+        assert_eq!(receiver.recv(), Ok("hello"));
+    }
+
+    #[test]
+    fn a_dropped_sender_closes_the_channel() {
+        let (sender, receiver) = channel::<u8>();
+        thread::spawn(move || drop(sender));
+        assert_eq!(receiver.recv(), Err(RecvError));
+    }
+}
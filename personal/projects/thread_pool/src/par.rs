@@ -0,0 +1,117 @@
+//! Data-parallel helpers: a tiny rayon built on scoped jobs.
+
+use crate::ThreadPool;
+
+/// How many chunks to cut the input into per worker, so a worker that
+/// finishes early can pick up another chunk instead of sitting idle.
+const CHUNKS_PER_WORKER: usize = 4;
+
+impl ThreadPool {
+    /// Apply `f` to every item of `iter` on the pool's workers, returning
+    /// the results in the same order as the items.
+    ///
+    /// The items are collected first and split into chunks, one job per
+    /// chunk, so `f` should do enough work per item to be worth sending to
+    /// another thread. `f` may borrow from the caller, like a job passed to
+    /// [`Scope::execute`](crate::Scope::execute).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` panics for any item, once every chunk has finished.
+    /// Like [`scope`](ThreadPool::scope), calling this from a job on the
+    /// same pool can deadlock.
+    pub fn par_map<I, F, T>(&self, iter: I, f: F) -> Vec<T>
+    where
+        I: IntoIterator,
+        I::Item: Send,
+        F: Fn(I::Item) -> T + Sync,
+        T: Send,
+    {
+        let chunks = self.chunk(iter);
+        let mut results: Vec<Vec<T>> = chunks.iter().map(|_| Vec::new()).collect();
+
+        let f = &f;
+        self.scope(|s| {
+            for (chunk, results) in chunks.into_iter().zip(&mut results) {
+                s.execute(move || results.extend(chunk.into_iter().map(f)));
+            }
+        });
+
+        results.into_iter().flatten().collect()
+    }
+
+    /// Like [`par_map`](ThreadPool::par_map), but only for `f`'s side
+    /// effects.
+    pub fn par_for_each<I, F>(&self, iter: I, f: F)
+    where
+        I: IntoIterator,
+        I::Item: Send,
+        F: Fn(I::Item) + Sync,
+    {
+        let f = &f;
+        self.scope(|s| {
+            for chunk in self.chunk(iter) {
+                s.execute(move || chunk.into_iter().for_each(f));
+            }
+        });
+    }
+
+    /// Split `iter` into a few chunks per worker, in order.
+    fn chunk<I: IntoIterator>(&self, iter: I) -> Vec<Vec<I::Item>> {
+        let mut items: Vec<_> = iter.into_iter().collect();
+        let chunks = self.current_size().max(1) * CHUNKS_PER_WORKER;
+        let chunk_len = items.len().div_ceil(chunks).max(1);
+
+        let mut chunked = Vec::with_capacity(chunks);
+        while items.len() > chunk_len {
+            let rest = items.split_off(chunk_len);
+            chunked.push(items);
+            items = rest;
+        }
+        if !items.is_empty() {
+            chunked.push(items);
+        }
+        chunked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::ThreadPool;
+
+    #[test]
+    fn par_map_keeps_the_input_order() {
+        let pool = ThreadPool::new(3);
+        let offset = 1;
+
+        let squares = pool.par_map(0..100u64, |n| n * n + offset);
+        assert_eq!(squares, (0..100).map(|n| n * n + 1).collect::<Vec<_>>());
+
+        assert_eq!(pool.par_map(Vec::<u8>::new(), |n| n), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn par_for_each_visits_every_item_once() {
+        let pool = ThreadPool::new(2);
+        let total = AtomicUsize::new(0);
+        let mut words = vec![String::from("a"), String::from("bb")];
+        words.extend((0..50).map(|n| n.to_string()));
+
+        pool.par_for_each(&words, |word| {
+            total.fetch_add(word.len(), Ordering::SeqCst);
+        });
+        assert_eq!(
+            total.into_inner(),
+            words.iter().map(String::len).sum::<usize>()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "a scoped job panicked")]
+    fn a_panic_in_f_panics_the_caller() {
+        let pool = ThreadPool::new(2);
+        pool.par_map(0..10, |n| if n == 7 { panic!("seven") } else { n });
+    }
+}
@@ -0,0 +1,178 @@
+//! Scoped jobs: like `std::thread::scope`, but run on the pool's workers.
+
+use std::{
+    marker::PhantomData,
+    mem,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+};
+
+use crate::{Job, Priority, RejectionPolicy, ThreadPool};
+
+/// Lets jobs borrow data that lives for `'env`. Created by
+/// [`ThreadPool::scope`].
+pub struct Scope<'pool, 'env> {
+    pool: &'pool ThreadPool,
+    state: Arc<ScopeState>,
+    /// Invariant in `'env`, so the borrow checker can't shrink it to fit a
+    /// job that borrows something shorter-lived.
+    env: PhantomData<&'env mut &'env ()>,
+}
+
+#[derive(Default)]
+struct ScopeState {
+    pending: Mutex<usize>,
+    finished: Condvar,
+    panicked: AtomicBool,
+    dropped: AtomicBool,
+}
+
+/// Owns a scoped job's closure, and counts the job as finished when dropped,
+/// whether it ran or was dropped from a full queue without running.
+struct Pending<F> {
+    state: Arc<ScopeState>,
+    /// Taken when the job runs.
+    f: Option<F>,
+}
+
+impl<F> Drop for Pending<F> {
+    fn drop(&mut self) {
+        // The closure, and everything it borrowed, has to be gone before the
+        // count can let `scope` return and end those borrows.
+        if let Some(f) = self.f.take() {
+            drop(f);
+            self.state.dropped.store(true, Ordering::SeqCst);
+        }
+
+        let mut pending = self.state.pending.lock().unwrap();
+        *pending -= 1;
+        if *pending == 0 {
+            self.state.finished.notify_all();
+        }
+    }
+}
+
+impl<'pool, 'env> Scope<'pool, 'env> {
+    pub(crate) fn new(pool: &'pool ThreadPool) -> Scope<'pool, 'env> {
+        Scope {
+            pool,
+            state: Arc::default(),
+            env: PhantomData,
+        }
+    }
+
+    /// Run `f` on the pool. It's guaranteed to have finished by the time the
+    /// enclosing `scope` call returns.
+    pub fn execute<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'env,
+    {
+        *self.state.pending.lock().unwrap() += 1;
+
+        let shared = Arc::clone(&self.pool.shared);
+        let mut pending = Pending {
+            state: Arc::clone(&self.state),
+            f: Some(f),
+        };
+        let job: Box<dyn FnOnce() + Send + 'env> = Box::new(move || {
+            let f = pending.f.take().expect("a job only runs once");
+            if shared.catch_panic(f).is_err() {
+                pending.state.panicked.store(true, Ordering::SeqCst);
+            }
+        });
+
+        // SAFETY: the job only borrows data that lives for `'env`, and
+        // `ThreadPool::scope` doesn't return (or finish unwinding) until
+        // `pending` says every job has run or been dropped, so nothing it
+        // borrows can be dropped while it's queued or running. `pending`
+        // owns `f`, and drops it before counting the job as finished, so
+        // that holds even for a job dropped without running.
+        let job = unsafe { mem::transmute::<Box<dyn FnOnce() + Send + 'env>, Job>(job) };
+        // Scoped jobs wait for room rather than fail, since there's no handle
+        // to report the failure to.
+        self.pool
+            .enqueue_with(Priority::Normal, None, job, RejectionPolicy::Block)
+            .expect("a borrowed pool can't be shut down");
+    }
+
+    /// Block until every job has finished, then pass on any panic.
+    pub(crate) fn wait(self) {
+        self.wait_for_jobs();
+        if self.state.panicked.load(Ordering::SeqCst) {
+            panic!("a scoped job panicked");
+        }
+        if self.state.dropped.load(Ordering::SeqCst) {
+            panic!("a scoped job was dropped from a full queue before it ran");
+        }
+    }
+
+    fn wait_for_jobs(&self) {
+        let mut pending = self.state.pending.lock().unwrap();
+        while *pending > 0 {
+            pending = self.state.finished.wait(pending).unwrap();
+        }
+    }
+}
+
+impl Drop for Scope<'_, '_> {
+    /// Also wait if the closure passed to `scope` panicked, so its jobs
+    /// can't outlive the data they borrow while the panic unwinds.
+    fn drop(&mut self) {
+        self.wait_for_jobs();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ThreadPool;
+
+    #[test]
+    fn jobs_can_borrow_from_the_stack() {
+        let pool = ThreadPool::new(3);
+        let mut numbers = vec![1, 2, 3, 4, 5, 6];
+        let offset = 10;
+
+        pool.scope(|s| {
+            for chunk in numbers.chunks_mut(2) {
+                s.execute(|| {
+                    for n in chunk {
+                        *n += offset;
+                    }
+                });
+            }
+        });
+
+        assert_eq!(numbers, vec![11, 12, 13, 14, 15, 16]);
+    }
+
+    #[test]
+    fn scope_returns_the_closures_result() {
+        let pool = ThreadPool::new(2);
+        let total = std::sync::atomic::AtomicUsize::new(0);
+
+        let jobs = pool.scope(|s| {
+            for n in 1..=4 {
+                let total = &total;
+                s.execute(move || {
+                    total.fetch_add(n, std::sync::atomic::Ordering::SeqCst);
+                });
+            }
+            4
+        });
+
+        assert_eq!(jobs, 4);
+        assert_eq!(total.into_inner(), 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "a scoped job panicked")]
+    fn a_panicking_job_panics_the_scope() {
+        let pool = ThreadPool::new(2);
+        pool.scope(|s| {
+            s.execute(|| panic!("boom"));
+            s.execute(|| ());
+        });
+    }
+}
@@ -0,0 +1,167 @@
+//! A counting semaphore: a number of permits that threads take and give
+//! back, blocking while there are none left.
+//!
+//! It's a mutex and condvar around a count. Permits hold on to their
+//! semaphore and give themselves back when dropped, so one can be moved
+//! into a job and released when the job is done with it.
+
+use std::{
+    fmt,
+    sync::{Arc, Condvar, Mutex},
+};
+
+pub struct Semaphore {
+    state: Mutex<State>,
+    released: Condvar,
+}
+
+struct State {
+    permits: usize,
+    closed: bool,
+}
+
+/// One of a [`Semaphore`]'s permits, given back when dropped.
+pub struct Permit {
+    semaphore: Arc<Semaphore>,
+}
+
+impl Semaphore {
+    pub fn new(permits: usize) -> Semaphore {
+        Semaphore {
+            state: Mutex::new(State {
+                permits,
+                closed: false,
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Take a permit, waiting for one to be given back if there are none.
+    /// Returns `None` once the semaphore is closed.
+    pub fn acquire(self: &Arc<Self>) -> Option<Permit> {
+        let mut state = self.state.lock().unwrap();
+        while state.permits == 0 && !state.closed {
+            state = self.released.wait(state).unwrap();
+        }
+        self.take(&mut state)
+    }
+
+    /// Take a permit if one is free, without waiting.
+    pub fn try_acquire(self: &Arc<Self>) -> Option<Permit> {
+        self.take(&mut self.state.lock().unwrap())
+    }
+
+    fn take(self: &Arc<Self>, state: &mut State) -> Option<Permit> {
+        if state.closed || state.permits == 0 {
+            return None;
+        }
+        state.permits -= 1;
+        Some(Permit {
+            semaphore: Arc::clone(self),
+        })
+    }
+
+    pub fn available_permits(&self) -> usize {
+        self.state.lock().unwrap().permits
+    }
+
+    /// Refuse any more permits, waking everyone waiting for one. Permits
+    /// already taken are still given back as usual.
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.released.notify_all();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.semaphore.state.lock().unwrap().permits += 1;
+        self.semaphore.released.notify_one();
+    }
+}
+
+impl fmt::Debug for Semaphore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("Semaphore")
+            .field("permits", &state.permits)
+            .field("closed", &state.closed)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Permit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Permit").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    use super::*;
+
+    #[test]
+    fn permits_are_given_back_when_dropped() {
+        let semaphore = Arc::new(Semaphore::new(2));
+        let first = semaphore.try_acquire().unwrap();
+        let _second = semaphore.acquire().unwrap();
+        assert!(semaphore.try_acquire().is_none());
+
+        drop(first);
+        assert_eq!(semaphore.available_permits(), 1);
+        assert!(semaphore.try_acquire().is_some());
+    }
+
+    #[test]
+    fn never_lets_more_threads_in_than_it_has_permits() {
+        let semaphore = Arc::new(Semaphore::new(3));
+        let inside = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+
+        let threads: Vec<_> = (0..10)
+            .map(|_| {
+                let (semaphore, inside, most) = (
+                    Arc::clone(&semaphore),
+                    Arc::clone(&inside),
+                    Arc::clone(&most),
+                );
+                thread::spawn(move || {
+                    let _permit = semaphore.acquire().unwrap();
+                    let now = inside.fetch_add(1, Ordering::SeqCst) + 1;
+                    most.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(5));
+                    inside.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert!(most.load(Ordering::SeqCst) <= 3);
+        assert_eq!(semaphore.available_permits(), 3);
+    }
+
+    #[test]
+    fn closing_wakes_waiters_empty_handed() {
+        let semaphore = Arc::new(Semaphore::new(0));
+        let waiter = {
+            let semaphore = Arc::clone(&semaphore);
+            thread::spawn(move || semaphore.acquire().is_none())
+        };
+
+        thread::sleep(Duration::from_millis(10));
+        semaphore.close();
+        assert!(waiter.join().unwrap());
+    }
+}
@@ -0,0 +1,86 @@
+//! Per-worker state, made once on each worker thread and lent to the jobs
+//! that ask for it.
+
+use std::{
+    any::{self, Any, TypeId},
+    cell::RefCell,
+    fmt,
+    sync::Arc,
+};
+
+type Init = dyn Fn(usize) -> Box<dyn Any> + Send + Sync;
+
+thread_local! {
+    /// The state of the worker running on this thread, if the pool has any.
+    static STATE: RefCell<Option<Box<dyn Any>>> = const { RefCell::new(None) };
+}
+
+/// How to make each worker's state, set with
+/// [`Builder::worker_state`](crate::Builder::worker_state).
+#[derive(Clone)]
+pub(crate) struct WorkerState {
+    init: Arc<Init>,
+    type_id: TypeId,
+    type_name: &'static str,
+}
+
+impl WorkerState {
+    pub(crate) fn new<S: 'static>(
+        init: impl Fn(usize) -> S + Send + Sync + 'static,
+    ) -> WorkerState {
+        WorkerState {
+            init: Arc::new(move |worker| Box::new(init(worker))),
+            type_id: TypeId::of::<S>(),
+            type_name: any::type_name::<S>(),
+        }
+    }
+
+    /// Make the state for `worker` on the calling thread.
+    pub(crate) fn install(&self, worker: usize) {
+        let state = (self.init)(worker);
+        STATE.with(|slot| *slot.borrow_mut() = Some(state));
+    }
+
+    /// Drop the calling thread's state, so it goes when its worker exits
+    /// rather than whenever the thread's locals are torn down.
+    pub(crate) fn uninstall() {
+        let state = STATE.with(|slot| slot.borrow_mut().take());
+        drop(state);
+    }
+
+    /// Panic unless the state is an `S`.
+    pub(crate) fn check<S: 'static>(this: Option<&WorkerState>) {
+        match this {
+            Some(state) if state.type_id == TypeId::of::<S>() => {}
+            Some(state) => panic!(
+                "the pool's worker state is a {}, not a {}",
+                state.type_name,
+                any::type_name::<S>()
+            ),
+            None => panic!("the pool has no worker state; see Builder::worker_state"),
+        }
+    }
+}
+
+/// Run `f` with the calling worker's state.
+///
+/// Only called from jobs that went through [`WorkerState::check`], so the
+/// state is there and is an `S`.
+pub(crate) fn with<S: 'static, T>(f: impl FnOnce(&mut S) -> T) -> T {
+    STATE.with(|slot| {
+        let mut slot = slot.borrow_mut();
+        let state = slot
+            .as_mut()
+            .and_then(|state| state.downcast_mut::<S>())
+            .expect("worker state is missing");
+        f(state)
+    })
+}
+
+impl fmt::Debug for WorkerState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WorkerState")
+            .field("type", &self.type_name)
+            .finish()
+    }
+}
@@ -0,0 +1,110 @@
+//! Keeps an eye on jobs submitted with a time limit, and flags the ones that
+//! run over it.
+//!
+//! A job can't be stopped from the outside, so all the watchdog can do is
+//! complain, and let the worker know it should be replaced once the job
+//! finally returns.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex, Weak,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{ExecuteError, Shared};
+
+/// How often the watchdog looks at the running jobs.
+const CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+struct RunningJob {
+    started: Instant,
+    limit: Duration,
+    overdue: bool,
+}
+
+impl RunningJob {
+    /// Flag the job if it has run past its limit, logging it the first time.
+    fn check(&mut self, worker: usize, overdue: &AtomicU64) -> bool {
+        let elapsed = self.started.elapsed();
+        if !self.overdue && elapsed > self.limit {
+            self.overdue = true;
+            overdue.fetch_add(1, Ordering::SeqCst);
+            eprintln!(
+                "Worker {worker} has been running a job for {elapsed:?}, over its {:?} limit.",
+                self.limit
+            );
+        }
+        self.overdue
+    }
+}
+
+/// The time-limited jobs running right now, by worker id.
+#[derive(Default)]
+pub(crate) struct Watchdog {
+    running: Mutex<HashMap<usize, RunningJob>>,
+    overdue: AtomicU64,
+    started: AtomicBool,
+}
+
+impl Watchdog {
+    /// Start the watchdog thread for `shared`'s pool, unless it's running
+    /// already. It exits once the pool is gone. If the thread can't be
+    /// spawned, the next call tries again.
+    pub(crate) fn ensure_started(&self, shared: Weak<Shared>) -> Result<(), ExecuteError> {
+        if self.started.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let spawned = thread::Builder::new()
+            .name("pool-watchdog".to_string())
+            .spawn(move || loop {
+                thread::sleep(CHECK_INTERVAL);
+                match shared.upgrade() {
+                    Some(shared) => shared.watchdog.check(),
+                    None => break,
+                }
+            });
+        if let Err(err) = spawned {
+            eprintln!("Couldn't spawn the watchdog: {err}");
+            self.started.store(false, Ordering::SeqCst);
+            return Err(ExecuteError::NoWatchdog);
+        }
+        Ok(())
+    }
+
+    /// Start timing the job `worker` is about to run.
+    pub(crate) fn start(&self, worker: usize, limit: Duration) {
+        self.running.lock().unwrap().insert(
+            worker,
+            RunningJob {
+                started: Instant::now(),
+                limit,
+                overdue: false,
+            },
+        );
+    }
+
+    /// Stop timing `worker`'s job, returning whether it ran over its limit.
+    pub(crate) fn finish(&self, worker: usize) -> bool {
+        self.running
+            .lock()
+            .unwrap()
+            .remove(&worker)
+            .is_some_and(|mut job| job.check(worker, &self.overdue))
+    }
+
+    /// How many jobs have run over their limit so far.
+    pub(crate) fn overdue(&self) -> u64 {
+        self.overdue.load(Ordering::SeqCst)
+    }
+
+    fn check(&self) {
+        for (&worker, job) in self.running.lock().unwrap().iter_mut() {
+            job.check(worker, &self.overdue);
+        }
+    }
+}